@@ -0,0 +1,291 @@
+//! coding-agent-example のツールを定義する `#[tool]` 属性マクロ
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, Attribute, Error, Expr, FnArg, Ident, ItemFn, Lit, LitStr, Meta,
+    MetaNameValue, Pat, Result, Token, Type,
+};
+
+/// async 関数からツールを定義する
+///
+/// 関数の doc コメントがツールの説明、引数の doc コメントが引数の説明（英語）になり、
+/// `ja` で日本語の説明を付けられる（省略時は英語を使う）。
+/// `fn count_lines` から引数の構造体 `CountLinesArgs` と `ToolHandler` を実装した
+/// `CountLinesTool` を生成するので、`registry.register(CountLinesTool)` で登録できる。
+///
+/// `#[state]` を付けた最初の引数（参照）はツールが持つ状態で、`CountLinesTool::new(state)` で渡す。
+/// 引数に付けた `#[serde(...)]` は引数の構造体のフィールドにそのまま付く
+///
+/// ```ignore
+/// /// Counts the lines of a file.
+/// #[tool(name = "countLines", ja = "ファイルの行数を数えます。")]
+/// async fn count_lines(
+///     #[state] config: &CountConfig,
+///     /// Path of the file
+///     #[ja("ファイルのパス")]
+///     path: String,
+///     /// Whether to count blank lines
+///     #[serde(default)]
+///     blank: bool,
+/// ) -> anyhow::Result<ToolResult> {
+///     ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr =
+        parse_macro_input!(attr with Punctuated::<MetaNameValue, Token![,]>::parse_terminated);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(attr, function)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// ツールの状態（`#[state]` を付けた引数）
+struct State {
+    ident: Ident,
+    ty: Type,
+}
+
+/// モデルから受け取る引数
+struct Param {
+    ident: Ident,
+    ty: Type,
+    /// 引数の構造体のフィールドに付ける `#[serde(...)]`
+    serde: Vec<Attribute>,
+    /// スキーマ上の名前（`#[serde(rename = "...")]` を反映）
+    name: String,
+    en: String,
+    ja: Option<String>,
+}
+
+fn expand(
+    attr: Punctuated<MetaNameValue, Token![,]>,
+    mut function: ItemFn,
+) -> Result<TokenStream2> {
+    let mut name = None;
+    let mut ja = None;
+    for meta in &attr {
+        let value = string_value(&meta.value)?;
+        if meta.path.is_ident("name") {
+            name = Some(value);
+        } else if meta.path.is_ident("ja") {
+            ja = Some(value);
+        } else {
+            return Err(Error::new_spanned(&meta.path, "expected `name` or `ja`"));
+        }
+    }
+    let name = name.ok_or_else(|| {
+        Error::new_spanned(&function.sig.ident, "#[tool] needs `name = \"toolName\"`")
+    })?;
+    if function.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(
+            function.sig.fn_token,
+            "#[tool] functions must be async",
+        ));
+    }
+    let en = doc_comment(&function.attrs).ok_or_else(|| {
+        Error::new_spanned(
+            &function.sig.ident,
+            "document the tool with a doc comment; it becomes the tool description",
+        )
+    })?;
+
+    let mut state = None;
+    let mut params = Vec::new();
+    for (index, input) in function.sig.inputs.iter_mut().enumerate() {
+        let FnArg::Typed(input) = input else {
+            return Err(Error::new_spanned(
+                input,
+                "#[tool] functions cannot take self",
+            ));
+        };
+        let Pat::Ident(pat) = &*input.pat else {
+            return Err(Error::new_spanned(&input.pat, "expected an argument name"));
+        };
+        let ident = pat.ident.clone();
+        let attrs = std::mem::take(&mut input.attrs);
+
+        if attrs.iter().any(|attr| attr.path().is_ident("state")) {
+            let Type::Reference(reference) = &*input.ty else {
+                return Err(Error::new_spanned(
+                    &input.ty,
+                    "#[state] must be a reference",
+                ));
+            };
+            if index != 0 {
+                return Err(Error::new_spanned(
+                    &ident,
+                    "#[state] must be the first argument",
+                ));
+            }
+            state = Some(State {
+                ident,
+                ty: (*reference.elem).clone(),
+            });
+            continue;
+        }
+
+        let mut serde = Vec::new();
+        let mut rename = None;
+        let mut param_ja = None;
+        for attr in &attrs {
+            if attr.path().is_ident("doc") {
+                continue;
+            } else if attr.path().is_ident("ja") {
+                param_ja = Some(attr.parse_args::<LitStr>()?.value());
+            } else if attr.path().is_ident("serde") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else if meta.input.peek(Token![=]) {
+                        meta.value()?.parse::<Expr>()?;
+                    }
+                    Ok(())
+                })?;
+                serde.push(attr.clone());
+            } else {
+                return Err(Error::new_spanned(
+                    attr,
+                    "expected #[state], #[ja(\"...\")], #[serde(...)] or a doc comment",
+                ));
+            }
+        }
+        let en = doc_comment(&attrs).ok_or_else(|| {
+            Error::new_spanned(
+                &ident,
+                "document the argument with a doc comment; it becomes the argument description",
+            )
+        })?;
+        params.push(Param {
+            name: rename.unwrap_or_else(|| ident.to_string()),
+            ident,
+            ty: (*input.ty).clone(),
+            serde,
+            en,
+            ja: param_ja,
+        });
+    }
+
+    let vis = &function.vis;
+    let function_ident = &function.sig.ident;
+    let base = pascal_case(&function_ident.to_string());
+    let tool_ident = format_ident!("{}Tool", base);
+    let args_ident = format_ident!("{}Args", base);
+    let description = translated(&en, ja.as_deref());
+
+    let field_idents: Vec<_> = params.iter().map(|param| &param.ident).collect();
+    let fields = params.iter().map(|param| {
+        let Param {
+            ident, ty, serde, ..
+        } = param;
+        quote! { #(#serde)* #ident: #ty }
+    });
+    let field_descriptions = params.iter().map(|param| {
+        let name = &param.name;
+        let description = translated(&param.en, param.ja.as_deref());
+        quote! { (#name, #description) }
+    });
+
+    let (tool_struct, state_arg) = match &state {
+        Some(State { ident, ty }) => (
+            quote! {
+                #vis struct #tool_ident {
+                    #ident: #ty,
+                }
+
+                impl #tool_ident {
+                    #vis fn new(#ident: #ty) -> Self {
+                        Self { #ident }
+                    }
+                }
+            },
+            quote! { &self.#ident, },
+        ),
+        None => (quote! { #vis struct #tool_ident; }, quote! {}),
+    };
+
+    Ok(quote! {
+        #function
+
+        #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+        #vis struct #args_ident {
+            #(#fields,)*
+        }
+
+        #tool_struct
+
+        #[async_trait::async_trait]
+        impl crate::anthropic::ToolHandler for #tool_ident {
+            type Args = #args_ident;
+
+            const NAME: &'static str = #name;
+
+            fn description() -> String {
+                #description
+            }
+
+            fn field_descriptions() -> Vec<(&'static str, String)> {
+                vec![#(#field_descriptions),*]
+            }
+
+            async fn execute(
+                &self,
+                args: #args_ident,
+            ) -> anyhow::Result<crate::anthropic::ToolResult> {
+                let #args_ident { #(#field_idents),* } = args;
+                #function_ident(#state_arg #(#field_idents),*).await
+            }
+        }
+    })
+}
+
+fn string_value(expr: &Expr) -> Result<String> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(value) => Ok(value.value()),
+            _ => Err(Error::new_spanned(expr, "expected a string literal")),
+        },
+        _ => Err(Error::new_spanned(expr, "expected a string literal")),
+    }
+}
+
+/// doc コメントの各行をつなげる（空のコメントは None）
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => string_value(&meta.value).ok(),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect();
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// 表示言語に合わせた説明を返す式（`tr!` の書式として扱われないよう波括弧はエスケープする）
+fn translated(en: &str, ja: Option<&str>) -> TokenStream2 {
+    let escape = |text: &str| text.replace('{', "{{").replace('}', "}}");
+    let en = escape(en);
+    let ja = ja.map(escape).unwrap_or_else(|| en.clone());
+    quote! { crate::i18n::tr!(#en, #ja) }
+}
+
+/// snake_case の関数名を PascalCase にする
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
@@ -0,0 +1,582 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::anthropic::{
+    AgentEvent, AnthropicClient, ConversationResult, EventHandler, Fallback, GenerationParams,
+    Message, Routing, TimeLimits, ToolRegistry,
+};
+use crate::capabilities;
+use crate::checkpoint::Checkpoints;
+use crate::config::{
+    ApprovalPolicy, ColorChoice, Config, Mode, ModelConfig, OutputFormat, Verbosity, VerifyCheck,
+};
+use crate::credentials;
+use crate::error::AgentError;
+use crate::i18n;
+use crate::ledger::{self, LedgerEntry};
+use crate::output::{self, OutputOptions, ProgressReporter, ToolEventPrinter};
+use crate::policy::ApprovalEngine;
+use crate::response_cache::CacheKey;
+use crate::sandbox::Sandbox;
+use crate::session::{self, Session};
+use crate::system_prompt::{load_system_prompt, mode_allows_tool};
+use crate::tools::FileTracker;
+use crate::ui::confirm::PromptHandler;
+use crate::ui::{self, Confirmer};
+use crate::verify::Verifier;
+use crate::webhooks;
+
+/// セッションのタイトルを待つ時間の上限（保存を長く止めない）
+const TITLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options shared by every command that talks to Claude
+#[derive(clap::Args, Debug, Clone)]
+pub struct AgentArgs {
+    /// Anthropic API key (can also be set via ANTHROPIC_API_KEY env var; a key saved with `login` takes precedence)
+    #[arg(long, env = "ANTHROPIC_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// Model to use [default: from profile or config]
+    #[arg(long, short = 'm')]
+    pub model: Option<String>,
+
+    /// Maximum tokens to generate [default: from profile or config]
+    #[arg(long)]
+    pub max_tokens: Option<u32>,
+
+    /// Sampling temperature [default: from config]
+    #[arg(long)]
+    pub temperature: Option<f32>,
+
+    /// Maximum tool use iterations [default: from config]
+    #[arg(long)]
+    pub max_iterations: Option<usize>,
+
+    /// Stop with exit code 5 once the estimated cost exceeds this (USD) [default: from config]
+    #[arg(long, value_name = "USD")]
+    pub max_cost: Option<f64>,
+
+    /// Stop an iteration (API call and tool runs) after this many seconds [default: from config]
+    #[arg(long, value_name = "SECS")]
+    pub max_turn_timeout: Option<u64>,
+
+    /// Stop the whole run after this many seconds, keeping partial results [default: from config]
+    #[arg(long, value_name = "SECS")]
+    pub deadline: Option<u64>,
+
+    /// Built-in prompt and tool policy [default: from config, or code]
+    #[arg(long, value_enum)]
+    pub mode: Option<Mode>,
+
+    /// Run a check after every iteration that changed files and send its failures to the model [default: from config]
+    #[arg(long, value_enum, value_name = "CHECK")]
+    pub verify: Option<VerifyCheck>,
+
+    /// Commit the working tree to refs/agent/checkpoints/<run> after every iteration that changed files
+    #[arg(long)]
+    pub checkpoints: bool,
+
+    /// Add a map of the workspace (file tree and top-level symbols) to the system prompt
+    #[arg(long)]
+    pub repo_map: bool,
+
+    /// Ask without tools or the system prompt (quick questions)
+    #[arg(long, conflicts_with = "tools")]
+    pub no_tools: bool,
+
+    /// Only enable these tools (comma separated; overrides tools.enabled)
+    #[arg(long, value_delimiter = ',', value_name = "TOOLS")]
+    pub tools: Option<Vec<String>>,
+
+    /// Disable a tool (can be repeated)
+    #[arg(long = "disable-tool", value_name = "TOOL")]
+    pub disable_tools: Vec<String>,
+
+    /// Fail on unknown config keys instead of ignoring them
+    #[arg(long)]
+    pub strict_config: bool,
+
+    /// Named profile from the config file
+    #[arg(long, short = 'p')]
+    pub profile: Option<String>,
+
+    /// Output format [default: from config, or text]
+    #[arg(long, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Print only the final answer (no metadata, warnings and errors only in the log)
+    #[arg(long, short = 'q', conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Show tool calls and results inline (-vv: full results and trace logging)
+    #[arg(long, short = 'v', action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Print the response as raw markdown instead of rendering it
+    #[arg(long)]
+    pub no_render: bool,
+
+    /// When to use colors [default: from config, or auto]
+    #[arg(long, value_enum)]
+    pub color: Option<ColorChoice>,
+
+    /// Ring the bell and show a desktop notification when the run finishes or needs confirmation
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Approve confirmations automatically when stdin, stdout or stderr is not a terminal
+    #[arg(long)]
+    pub approve_when_non_interactive: bool,
+}
+
+/// APIキーの解決（OS キーリング > 環境変数 / --api-key）
+pub fn resolve_api_key(api_key: Option<&str>) -> Result<String> {
+    credentials::load_api_key()
+        .or(api_key.filter(|key| !key.is_empty()).map(str::to_string))
+        .context(
+            "ANTHROPIC_API_KEY is required. Run `login`, or set via environment variable or --api-key flag.",
+        )
+}
+
+impl AgentArgs {
+    /// APIキーの解決
+    pub fn api_key(&self) -> Result<String> {
+        resolve_api_key(self.api_key.as_deref())
+    }
+
+    /// 出力レベルの解決（-q / -v / -vv > 設定ファイル）
+    pub fn verbosity(&self, config: &Config) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => config.output.verbosity,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    /// 設定ファイルを読み込み、ツールに関する CLI 引数を反映する
+    pub fn load_config(&self, workspace: &Path) -> Result<Config> {
+        let mut config = Config::load_for_workspace(workspace, self.strict_config)?;
+        if let Some(tools) = &self.tools {
+            config.tools.enabled = Some(tools.clone());
+        }
+        config
+            .tools
+            .disabled
+            .extend(self.disable_tools.iter().cloned());
+        Ok(config)
+    }
+}
+
+/// 設定から組み立てたエージェント（クライアント・モデル・ツール一式）
+pub struct Agent {
+    client: AnthropicClient,
+    pub model: String,
+    params: GenerationParams,
+    /// モデルを切り替えたときに生成パラメータを解決し直すための設定
+    model_config: ModelConfig,
+    max_iterations: usize,
+    /// `--no-tools` と ask モードの場合は None
+    tool_registry: Option<ToolRegistry>,
+    system_prompt: Option<String>,
+    /// 会話が途中で失われた場合に readFile の結果の記録を捨てるため保持する
+    file_tracker: FileTracker,
+    pub output: OutputOptions,
+    /// 使用量の記録に書くワークスペース（`agent.usage_ledger` が無効なら None）
+    ledger_workspace: Option<PathBuf>,
+    /// セッションのタイトルを付けるモデル（`agent.session_titles` が無効なら None）
+    title_model: Option<String>,
+}
+
+impl Agent {
+    /// 設定と CLI 引数からエージェントを構築する
+    ///
+    /// 設定の解決順は CLI 引数 > プロファイル > 設定ファイル > 組み込みデフォルト。
+    /// `file_tracker` は再構築後も readFile の記録を引き継げるよう呼び出し側が保持する
+    pub fn new(
+        args: &AgentArgs,
+        mut config: Config,
+        api_key: String,
+        workspace: &Path,
+        file_tracker: FileTracker,
+        prompt_handler: Option<PromptHandler>,
+    ) -> Result<Self> {
+        // 出力設定の解決
+        let output_format = args.output.unwrap_or(config.output.format);
+        let verbosity = args.verbosity(&config);
+        ui::style::set_color_choice(args.color.unwrap_or(config.output.color));
+        ui::notify::set_enabled(args.notify || config.output.notify);
+        webhooks::configure(&config.webhooks, workspace);
+        i18n::set_language(config.language);
+
+        let profile = config.profile(args.profile.as_deref())?;
+        let model = args
+            .model
+            .clone()
+            .or(profile.model)
+            .unwrap_or_else(|| config.model.default.clone());
+        let params = GenerationParams {
+            max_tokens: args
+                .max_tokens
+                .or(profile.max_tokens)
+                .unwrap_or_else(|| config.model.max_tokens_for(&model)),
+            temperature: args.temperature.or(config.model.temperature_for(&model)),
+            top_p: config.model.top_p_for(&model),
+        };
+        let max_iterations = args.max_iterations.unwrap_or(config.agent.max_iterations);
+
+        if let Some(base_url) = profile.base_url {
+            config.api.base_url = base_url;
+        }
+        let mut client = AnthropicClient::new(api_key, &config.api)?;
+        client.set_max_cost(args.max_cost.or(config.agent.max_cost_usd));
+        if let Some(fallback) = config.model.fallback.as_ref().filter(|m| **m != model) {
+            client.set_fallback(Some(Fallback {
+                model: fallback.clone(),
+                params: model_params(&config.model, fallback),
+            }));
+        }
+        client.set_time_limits(TimeLimits {
+            turn_timeout: args
+                .max_turn_timeout
+                .or(config.agent.max_turn_timeout_secs)
+                .map(Duration::from_secs),
+            deadline: args
+                .deadline
+                .or(config.agent.deadline_secs)
+                .map(Duration::from_secs),
+        });
+        match (output_format, verbosity) {
+            (OutputFormat::StreamJson, _) => {
+                client.set_event_handler(Arc::new(output::emit_event));
+            }
+            (OutputFormat::Text, Verbosity::Quiet) => {}
+            (OutputFormat::Text, _) => {
+                let printer = matches!(verbosity, Verbosity::Verbose | Verbosity::Debug)
+                    .then(|| ToolEventPrinter::new(verbosity));
+                let reporter = ProgressReporter::new();
+                client.set_event_handler(Arc::new(move |event: &AgentEvent| {
+                    reporter.update(event);
+                    if let Some(printer) = &printer {
+                        printer.print(event);
+                    }
+                }));
+            }
+            (OutputFormat::Json, _) => {}
+        }
+
+        // ツールなしの場合は ToolRegistry もシステムプロンプトも使わない
+        // （ask モードはツールなしでモードのプロンプトだけを使う）
+        let mode = args.mode.unwrap_or(config.agent.mode);
+        config.agent.repo_map |= args.repo_map;
+        let no_tools = args.no_tools || config.agent.no_tools;
+        let (tool_registry, system_prompt) = if no_tools {
+            tracing::info!("Tools are disabled; sending the message without tools");
+            (None, None)
+        } else if mode == Mode::Ask {
+            tracing::info!("Ask mode; sending the message without tools");
+            let system_prompt = load_system_prompt(&config, mode, workspace, &[])?;
+            (None, Some(system_prompt))
+        } else {
+            let approval_policy = profile.approval_policy.unwrap_or(config.approvals.policy);
+            let tool_registry = build_tool_registry(
+                &config,
+                mode,
+                approval_policy,
+                args.approve_when_non_interactive || config.approvals.approve_when_non_interactive,
+                workspace,
+                file_tracker.clone(),
+                prompt_handler,
+            )?;
+            let system_prompt =
+                load_system_prompt(&config, mode, workspace, tool_registry.get_schemas())?;
+            if let Some(check) = args.verify.or(config.verify.check) {
+                let sandbox = Sandbox::new(&config.sandbox, workspace);
+                client.set_verifier(Some(Verifier::new(check, &config.verify, sandbox)?));
+            }
+            let routing = &config.model.routing;
+            if let Some(tool_model) = routing.tool_model.as_ref().filter(|m| **m != model) {
+                tracing::info!("Routing tool iterations to {}", tool_model);
+                client.set_routing(Some(Routing {
+                    model: tool_model.clone(),
+                    params: GenerationParams {
+                        max_tokens: config.model.max_tokens_for(tool_model),
+                        temperature: config.model.temperature_for(tool_model),
+                        top_p: config.model.top_p_for(tool_model),
+                    },
+                    final_with_main: routing.final_with_main,
+                }));
+            }
+            if args.checkpoints || config.agent.checkpoints {
+                match Checkpoints::start(workspace) {
+                    Ok(checkpoints) => {
+                        tracing::info!("Saving checkpoints on {}", checkpoints.reference());
+                        if output_format == OutputFormat::Text && verbosity != Verbosity::Quiet {
+                            eprintln!("Saving checkpoints on {}", checkpoints.reference());
+                        }
+                        client.set_checkpoints(Some(checkpoints));
+                    }
+                    // --checkpoints を明示した場合だけエラーにする
+                    Err(e) if args.checkpoints => return Err(e),
+                    Err(e) => tracing::warn!("Checkpoints are disabled: {:#}", e),
+                }
+            }
+            (Some(tool_registry), Some(system_prompt))
+        };
+
+        Ok(Self {
+            client,
+            model,
+            params,
+            model_config: config.model.clone(),
+            max_iterations,
+            tool_registry,
+            system_prompt,
+            file_tracker,
+            output: OutputOptions {
+                format: output_format,
+                verbosity,
+                render_markdown: config.output.render_markdown && !args.no_render,
+            },
+            ledger_workspace: config.agent.usage_ledger.then(|| workspace.to_path_buf()),
+            title_model: config
+                .agent
+                .session_titles
+                .then(|| config.agent.title_model.clone()),
+        })
+    }
+
+    /// 以降の反復で使うモデルを切り替える（会話はそのまま引き継ぐ）
+    ///
+    /// ツールを有効にしている場合や会話にツールの呼び出しがある場合は、
+    /// ツールに対応していないモデルへは切り替えない。
+    /// max_tokens などは切り替え先のモデルの設定（`[model.overrides]`）で解決し直す
+    pub fn switch_model(&mut self, model: &str, conversation: &[Message]) -> Result<()> {
+        let needs_tools = self.tool_registry.is_some() || capabilities::uses_tools(conversation);
+        if needs_tools && !capabilities::supports_tools(model) {
+            bail!(
+                "{} does not support tools, which this {} uses",
+                model,
+                if self.tool_registry.is_some() {
+                    "session"
+                } else {
+                    "conversation"
+                }
+            );
+        }
+        tracing::info!("Switching model from {} to {}", self.model, model);
+        self.model = model.to_string();
+        self.params = model_params(&self.model_config, model);
+        Ok(())
+    }
+
+    /// 登録されているツールの名前（`--no-tools` の場合は空）
+    pub fn tool_names(&self) -> Vec<String> {
+        self.tool_registry
+            .iter()
+            .flat_map(|registry| registry.get_schemas())
+            .map(|tool| tool.name.clone())
+            .collect()
+    }
+
+    /// まだタイトルのないセッションに、最初のやり取りからタイトルを付ける
+    ///
+    /// 失敗しても保存は続けられるよう、最初のメッセージを使う一覧表示のままにする
+    pub async fn name_session(&self, session: &mut Session) {
+        let Some(model) = &self.title_model else {
+            return;
+        };
+        if session.title.is_some() || session.messages.is_empty() {
+            return;
+        }
+        let request = session::generate_title(&self.client, model, &session.messages);
+        match tokio::time::timeout(TITLE_TIMEOUT, request).await {
+            Ok(Ok(title)) => {
+                tracing::debug!("Titled session {}: {}", session.id, title);
+                session.title = Some(title);
+            }
+            Ok(Err(e)) => tracing::debug!("Failed to title session {}: {:#}", session.id, e),
+            Err(_) => tracing::debug!("Timed out titling session {}", session.id),
+        }
+    }
+
+    /// 進行イベントの通知先を差し替える（TUI などの独自の表示用）
+    pub fn set_event_handler(&mut self, handler: EventHandler) {
+        self.client.set_event_handler(handler);
+    }
+
+    /// 1 つのメッセージに対する応答キャッシュのキー
+    pub fn response_cache_key(&self, message: &str) -> CacheKey {
+        CacheKey::new(
+            &self.model,
+            &self.params,
+            self.system_prompt.as_deref(),
+            message,
+        )
+    }
+
+    /// ツールを使った会話を実行する
+    pub async fn send(&self, conversation: Vec<Message>) -> Result<ConversationResult> {
+        tracing::info!("Sending message to Claude API");
+        webhooks::run_started(&self.model, &conversation).await;
+        let started = Instant::now();
+        let run = async {
+            match &self.tool_registry {
+                Some(tool_registry) => {
+                    self.client
+                        .execute_with_tools(
+                            &self.model,
+                            &self.params,
+                            conversation,
+                            tool_registry,
+                            self.max_iterations,
+                            self.system_prompt.clone(),
+                        )
+                        .await
+                }
+                None => {
+                    self.client
+                        .execute_without_tools(
+                            &self.model,
+                            &self.params,
+                            conversation,
+                            self.system_prompt.clone(),
+                        )
+                        .await
+                }
+            }
+        };
+        // Ctrl-C で実行中の会話を中断する
+        let result = tokio::select! {
+            result = run => result,
+            _ = tokio::signal::ctrl_c() => Err(AgentError::Cancelled.into()),
+        };
+        ui::progress::hide();
+        // 失敗や時間切れで打ち切った会話には、この実行中の readFile の結果が残らない
+        if !matches!(&result, Ok(result) if result.timed_out.is_none()) {
+            self.file_tracker.forget_reads();
+        }
+        if let (Ok(result), Some(workspace)) = (&result, &self.ledger_workspace) {
+            let entry = LedgerEntry::new(workspace, result, started.elapsed());
+            if let Err(e) = ledger::append(&entry) {
+                tracing::warn!("Failed to record usage: {:#}", e);
+            }
+        }
+        match &result {
+            Ok(result) => ui::notify::notify(
+                "Run finished",
+                &format!("Completed in {} iterations", result.iterations),
+            ),
+            Err(e) => ui::notify::notify("Run failed", &e.to_string()),
+        }
+        webhooks::run_finished(&self.model, &result).await;
+        result
+    }
+}
+
+/// `[model]` の設定から、あるモデルの生成パラメータを解決する
+fn model_params(config: &ModelConfig, model: &str) -> GenerationParams {
+    GenerationParams {
+        max_tokens: config.max_tokens_for(model),
+        temperature: config.temperature_for(model),
+        top_p: config.top_p_for(model),
+    }
+}
+
+/// ビルトインツールを登録し、設定で無効化されたツールを除外する
+fn build_tool_registry(
+    config: &Config,
+    mode: Mode,
+    approval_policy: ApprovalPolicy,
+    approve_when_non_interactive: bool,
+    workspace: &Path,
+    file_tracker: FileTracker,
+    prompt_handler: Option<PromptHandler>,
+) -> Result<ToolRegistry> {
+    // writeFile と editFile で共有するユーザー確認
+    let mut approval_policy = approval_policy;
+    if approval_policy == ApprovalPolicy::Ask && config.approvals.is_trusted(workspace) {
+        // ワークスペースの外への書き込みは ApprovalEngine が引き続き確認する
+        tracing::info!(
+            "Workspace {:?} is trusted; file writes inside it skip confirmation",
+            workspace
+        );
+        approval_policy = ApprovalPolicy::Allow;
+    }
+    let approval_engine = ApprovalEngine::new(&config.approvals, approval_policy, workspace)?;
+    let confirmer = Arc::new(Confirmer::new(
+        approve_when_non_interactive,
+        approval_engine,
+        prompt_handler,
+    ));
+
+    let mut tool_registry = ToolRegistry::builder(config, workspace)
+        .file_tracker(file_tracker)
+        .confirmer(confirmer)
+        .with_default_read_tools()
+        .with_write_tools()
+        .with_diagnostics_tool()
+        .with_github_tools()
+        .with_custom_tools()
+        .build()?;
+
+    // 設定で無効化されたツールとモードで使えないツールを除外
+    let registered: Vec<String> = tool_registry
+        .get_schemas()
+        .iter()
+        .map(|t| t.name.clone())
+        .collect();
+    for name in config
+        .tools
+        .enabled
+        .iter()
+        .flatten()
+        .chain(&config.tools.disabled)
+    {
+        if !registered.contains(name) {
+            tracing::warn!("Unknown tool in tool settings: {}", name);
+        }
+    }
+    tool_registry.retain(|name| config.tools.is_enabled(name) && mode_allows_tool(mode, name));
+
+    let schemas = tool_registry.get_schemas();
+    let tool_names: Vec<&str> = schemas.iter().map(|t| t.name.as_str()).collect();
+    tracing::info!("Registered tools: {}", tool_names.join(", "));
+    Ok(tool_registry)
+}
+
+/// 設定ファイル（グローバル・プロジェクト）の変更を検出する
+pub struct ConfigWatcher {
+    files: Vec<(PathBuf, Option<std::time::SystemTime>)>,
+}
+
+impl ConfigWatcher {
+    pub fn new(workspace: &Path) -> Self {
+        let paths = Config::config_path()
+            .ok()
+            .into_iter()
+            .chain(Config::project_config_path(workspace));
+        Self {
+            files: paths.map(|path| (path.clone(), modified(&path))).collect(),
+        }
+    }
+
+    /// 前回の確認以降に変更（作成・削除を含む）があれば true を返す
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in &mut self.files {
+            let current = modified(path);
+            if current != *last {
+                *last = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -1,12 +1,202 @@
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tracing::{debug, info};
+use serde_json::value::RawValue;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, field, info, info_span, warn, Instrument};
 
+use crate::checkpoint::Checkpoints;
+use crate::config::{ApiConfig, Config};
+use crate::crash;
+use crate::error::AgentError;
+use crate::i18n::tr;
+use crate::pricing;
+use crate::telemetry;
+use crate::tokens;
+use crate::tools::ToolRegistryBuilder;
+use crate::verify::Verifier;
+
+mod stream;
+
+/// ツールの実装
+///
+/// モデルからの入力は `Args` に変換してから渡し、入力スキーマも `Args` から生成する（schemars）。
+/// 引数の説明は表示言語に合わせるため doc コメントではなく `field_descriptions` で返す
 #[async_trait]
 pub trait ToolHandler: Send + Sync {
-    async fn execute(&self, input: serde_json::Value) -> Result<ToolResult>;
+    type Args: DeserializeOwned + JsonSchema + Send;
+
+    /// モデルに見せるツール名
+    const NAME: &'static str;
+
+    /// 同じ入力なら同じ結果を返す読み込み専用のツールか
+    ///
+    /// true の場合、1 回の実行の中で同じ入力の呼び出しには前の結果を返す
+    const CACHEABLE: bool = false;
+
+    /// ワークスペースのファイルを変更するツールか（変更後に `[verify]` の検査を実行する）
+    const MODIFIES_FILES: bool = false;
+
+    /// ツールの説明
+    fn description() -> String;
+
+    /// 引数のフィールドごとの説明
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    async fn execute(&self, args: Self::Args) -> Result<ToolResult>;
+
+    /// 途中経過を通知しながら実行する
+    ///
+    /// 時間のかかるツールはこちらも実装して `progress` に途中経過を送る（既定は `execute` を呼ぶだけ）
+    async fn execute_with_progress(
+        &self,
+        args: Self::Args,
+        progress: Progress,
+    ) -> Result<ToolResult> {
+        let _ = progress;
+        self.execute(args).await
+    }
+
+    /// API に渡すツールの定義
+    fn schema() -> Tool
+    where
+        Self: Sized,
+    {
+        Tool {
+            name: Self::NAME.to_string(),
+            description: Self::description(),
+            input_schema: input_schema::<Self::Args>(Self::NAME, Self::field_descriptions()),
+        }
+    }
+}
+
+/// `Args` の JSON Schema に引数の説明を入れる
+///
+/// schemars が doc コメントから作るタイトルと説明は使わない
+fn input_schema<T: JsonSchema>(
+    tool: &str,
+    descriptions: Vec<(&'static str, String)>,
+) -> serde_json::Value {
+    let mut schema = schemars::SchemaGenerator::default()
+        .into_root_schema_for::<T>()
+        .to_value();
+    let Some(root) = schema.as_object_mut() else {
+        return schema;
+    };
+    for key in ["$schema", "title", "description"] {
+        root.remove(key);
+    }
+    // 引数のない構造体は properties を持たないが、API は object のスキーマに properties を求める
+    let properties = root
+        .entry("properties")
+        .or_insert_with(|| serde_json::json!({}));
+    if let Some(properties) = properties.as_object_mut() {
+        for property in properties.values_mut().filter_map(|p| p.as_object_mut()) {
+            property.remove("description");
+        }
+        for (field, description) in descriptions {
+            let property = properties
+                .get_mut(field)
+                .and_then(|p| p.as_object_mut())
+                .unwrap_or_else(|| panic!("{} has no argument named {}", tool, field));
+            property.insert("description".to_string(), description.into());
+        }
+    }
+    schema
+}
+
+/// 名前やスキーマが実行時に決まるツール（設定で定義した外部コマンドなど）
+///
+/// 入力は JSON のまま受け取る
+#[async_trait]
+pub trait DynamicTool: Send + Sync {
+    fn schema(&self) -> Tool;
+
+    async fn execute(&self, input: serde_json::Value, progress: Progress) -> Result<ToolResult>;
+}
+
+/// 進捗を通知する最短の間隔（これより頻繁な通知は捨てる）
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// ツールの途中経過の通知先
+///
+/// 通知は `AgentEvent::ToolProgress` として送る。通知先がない場合（`Progress::default()`）は何もしない
+#[derive(Clone, Default)]
+pub struct Progress {
+    inner: Option<Arc<ProgressInner>>,
+}
+
+struct ProgressInner {
+    report: Box<dyn Fn(Option<u8>, String) + Send + Sync>,
+    last: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    /// 通知を `report(percent, message)` で受け取る
+    pub fn new(report: impl Fn(Option<u8>, String) + Send + Sync + 'static) -> Self {
+        Self {
+            inner: Some(Arc::new(ProgressInner {
+                report: Box::new(report),
+                last: Mutex::new(None),
+            })),
+        }
+    }
+
+    /// 途中経過を通知する（`percent` は 0〜100、分からなければ None）
+    pub fn report(&self, percent: Option<u8>, message: impl Into<String>) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        {
+            let mut last = inner.last.lock().unwrap();
+            if last.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        (inner.report)(percent.map(|p| p.min(100)), message.into());
+    }
+}
+
+/// 途中経過の表示用の文字列（例: "42% src/main.rs"）
+pub fn progress_text(percent: Option<u8>, message: &str) -> String {
+    match percent {
+        Some(percent) if message.is_empty() => format!("{}%", percent),
+        Some(percent) => format!("{}% {}", percent, message),
+        None => message.to_string(),
+    }
+}
+
+/// 引数の型によらずレジストリからツールを呼び出すための内部トレイト
+#[async_trait]
+trait DynToolHandler: Send + Sync {
+    async fn call(&self, input: serde_json::Value, progress: Progress) -> Result<ToolResult>;
+}
+
+#[async_trait]
+impl<T: ToolHandler> DynToolHandler for T {
+    async fn call(&self, input: serde_json::Value, progress: Progress) -> Result<ToolResult> {
+        let args = serde_json::from_value(input)
+            .with_context(|| format!("Failed to parse {} arguments", T::NAME))?;
+        self.execute_with_progress(args, progress).await
+    }
+}
+
+/// `DynamicTool` を `DynToolHandler` として扱うためのラッパー
+struct Dynamic<T>(T);
+
+#[async_trait]
+impl<T: DynamicTool> DynToolHandler for Dynamic<T> {
+    async fn call(&self, input: serde_json::Value, progress: Progress) -> Result<ToolResult> {
+        self.0.execute(input, progress).await
+    }
 }
 
 /// メッセージの内容（文字列 or ブロック配列）
@@ -18,15 +208,31 @@ pub enum MessageContent {
 }
 
 /// Request structure for Messages API
+///
+/// 会話履歴は反復ごとに大きくなるため、コピーせず借用したまま直列化する。
+/// ツールのスキーマは反復の間で変わらないため、直列化済みの JSON を使い回す
 #[derive(Debug, Serialize)]
-struct MessageRequest {
-    model: String,
-    max_tokens: u32,
-    messages: Vec<Message>,
+struct MessageRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
     #[serde(skip_serializing_if = "Option::is_none")]
-    tools: Option<Vec<Tool>>,
+    tools: Option<&'a RawValue>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    system: Option<String>,
+    system: Option<&'a str>,
+    #[serde(flatten)]
+    params: &'a GenerationParams,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+/// 生成パラメータ（temperature / top_p は未設定なら API のデフォルトを使う）
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationParams {
+    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,7 +243,6 @@ pub struct Message {
 
 impl Message {
     /// テキストメッセージを作成（便利メソッド）
-    #[allow(dead_code)]
     pub fn user_text(text: impl Into<String>) -> Self {
         Self {
             role: "user".to_string(),
@@ -64,6 +269,18 @@ pub struct MessageResponse {
     pub usage: Usage,
 }
 
+impl MessageResponse {
+    /// 応答を受け取る前に打ち切った場合の空の応答
+    fn empty() -> Self {
+        Self {
+            id: String::new(),
+            content: Vec::new(),
+            stop_reason: None,
+            usage: Usage::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ContentBlock {
@@ -86,10 +303,38 @@ pub enum ContentBlock {
     },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Usage {
     pub input_tokens: u32,
     pub output_tokens: u32,
+    /// プロンプトキャッシュへの書き込み
+    #[serde(default)]
+    pub cache_creation_input_tokens: u32,
+    /// プロンプトキャッシュからの読み込み
+    #[serde(default)]
+    pub cache_read_input_tokens: u32,
+}
+
+impl Usage {
+    /// 別のレスポンスの使用量を加算する
+    pub fn add(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+/// Model information returned by the Models API
+#[derive(Debug, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelInfo>,
 }
 
 /// Tool definition for the API
@@ -109,179 +354,586 @@ pub struct ToolResult {
     pub error: Option<String>,
 }
 
+/// エージェントループの進行イベント（`--output stream-json` などで利用）
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    IterationStart {
+        iteration: usize,
+        max_iterations: usize,
+    },
+    TextDelta {
+        text: String,
+    },
+    ToolCall {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// 実行中のツールの途中経過
+    ToolProgress {
+        id: String,
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percent: Option<u8>,
+        message: String,
+    },
+    ToolResult {
+        id: String,
+        name: String,
+        is_error: bool,
+        content: String,
+        duration_ms: u64,
+        /// 結果をモデルに送るときの推定トークン数
+        tokens: usize,
+        /// 同じ実行の中の同じ呼び出しの結果を再利用した
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        cached: bool,
+    },
+    Usage {
+        iteration: usize,
+        #[serde(flatten)]
+        usage: Usage,
+    },
+}
+
+/// イベントを受け取るハンドラ
+pub type EventHandler = Arc<dyn Fn(&AgentEvent) + Send + Sync>;
+
 /// Anthropic API client
 pub struct AnthropicClient {
     api_key: String,
     base_url: String,
+    anthropic_version: String,
+    max_retries: u32,
     client: reqwest::Client,
+    /// 設定されている場合はストリーミング API を使い、進行状況を通知する
+    events: Option<EventHandler>,
+    /// 推定コストの上限（USD）
+    max_cost: Option<f64>,
+    time_limits: TimeLimits,
+    /// ファイルを変更した反復の後に実行する検査
+    verifier: Option<Verifier>,
+    /// ファイルを変更した反復ごとに作業ツリーを記録する
+    checkpoints: Option<Checkpoints>,
+    /// ツールを呼ぶだけの反復で使う安いモデル
+    routing: Option<Routing>,
+    /// 過負荷が続いた場合に切り替えるモデル
+    fallback: Option<Fallback>,
 }
 
 impl AnthropicClient {
-    /// Create new Anthropic API client
-    pub fn new(api_key: String) -> Self {
-        Self {
+    /// Create new Anthropic API client from the `[api]` settings
+    pub fn new(api_key: String, api: &ApiConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(api.timeout_secs))
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
             api_key,
-            base_url: "https://api.anthropic.com/v1".to_string(),
-            client: reqwest::Client::new(),
+            base_url: api.base_url.trim_end_matches('/').to_string(),
+            anthropic_version: api.anthropic_version.clone(),
+            max_retries: api.max_retries,
+            client,
+            events: None,
+            max_cost: None,
+            time_limits: TimeLimits::default(),
+            verifier: None,
+            checkpoints: None,
+            routing: None,
+            fallback: None,
+        })
+    }
+
+    /// 進行イベントのハンドラを設定する（以降のリクエストはストリーミングになる）
+    pub fn set_event_handler(&mut self, handler: EventHandler) {
+        self.events = Some(handler);
+    }
+
+    /// 推定コストの上限を設定する（料金表にないモデルでは判定しない）
+    pub fn set_max_cost(&mut self, max_cost: Option<f64>) {
+        self.max_cost = max_cost;
+    }
+
+    /// 反復ごとと実行全体の時間制限を設定する
+    pub fn set_time_limits(&mut self, time_limits: TimeLimits) {
+        self.time_limits = time_limits;
+    }
+
+    /// ファイルを変更した反復の後に実行する検査を設定する
+    pub fn set_verifier(&mut self, verifier: Option<Verifier>) {
+        self.verifier = verifier;
+    }
+
+    /// ツールを呼ぶだけの反復を安いモデルに振り分ける
+    pub fn set_routing(&mut self, routing: Option<Routing>) {
+        self.routing = routing;
+    }
+
+    /// 過負荷（529）が再試行の後も続いた場合に切り替えるモデルを設定する
+    pub fn set_fallback(&mut self, fallback: Option<Fallback>) {
+        self.fallback = fallback;
+    }
+
+    /// `error` が `model` の過負荷なら切り替え先のモデル
+    fn fallback_for(&self, error: &anyhow::Error, model: &str) -> Option<&Fallback> {
+        let fallback = self.fallback.as_ref().filter(|f| f.model != model)?;
+        error.downcast_ref::<Overloaded>()?;
+        warn!(
+            "{} is overloaded; switching to {} for the rest of the run",
+            model, fallback.model
+        );
+        Some(fallback)
+    }
+
+    /// ファイルを変更した反復ごとにチェックポイントを記録する
+    pub fn set_checkpoints(&mut self, checkpoints: Option<Checkpoints>) {
+        self.checkpoints = checkpoints;
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        if let Some(handler) = &self.events {
+            handler(&event);
         }
     }
-    /// Send a message to Claude (non-streaming)
-    #[allow(dead_code)]
-    pub async fn create_message(
+
+    /// Messages API にリクエストを送信（一時的なエラーは指数バックオフで再試行）
+    async fn send_with_retry(&self, request: &MessageRequest<'_>) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+
+        loop {
+            let result = self
+                .client
+                .post(format!("{}/messages", self.base_url))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", &self.anthropic_version)
+                .header("content-type", "application/json")
+                .json(request)
+                .send()
+                .await;
+
+            let retryable = match &result {
+                Ok(response) => is_retryable_status(response.status()),
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            if retryable && attempt < self.max_retries {
+                let delay = Duration::from_millis(500 * 2u64.pow(attempt));
+                match &result {
+                    Ok(response) => warn!(
+                        "API request failed with status {}, retrying in {:?} ({}/{})",
+                        response.status(),
+                        delay,
+                        attempt + 1,
+                        self.max_retries
+                    ),
+                    Err(e) => warn!(
+                        "API request failed: {}, retrying in {:?} ({}/{})",
+                        e,
+                        delay,
+                        attempt + 1,
+                        self.max_retries
+                    ),
+                }
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            let response = result.context("Failed to send request to Anthropic API")?;
+
+            let status = response.status();
+            debug!(?status, "Received response from Anthropic API");
+
+            if !status.is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                let message = format!("API request failed with status {}: {}", status, error_text);
+                if status.as_u16() == 529 {
+                    return Err(anyhow::Error::new(Overloaded).context(message));
+                }
+                bail!(message);
+            }
+
+            return Ok(response);
+        }
+    }
+
+    /// リクエストを送信する（API 呼び出し 1 回分をスパンとして記録）
+    async fn send(&self, request: &MessageRequest<'_>) -> Result<MessageResponse> {
+        let span = info_span!(
+            "chat",
+            otel.name = format!("chat {}", request.model),
+            otel.kind = "client",
+            otel.status_code = field::Empty,
+            otel.status_description = field::Empty,
+            gen_ai.operation.name = "chat",
+            gen_ai.system = "anthropic",
+            gen_ai.request.model = request.model,
+            gen_ai.request.max_tokens = request.params.max_tokens,
+            gen_ai.response.id = field::Empty,
+            gen_ai.response.finish_reasons = field::Empty,
+            gen_ai.usage.input_tokens = field::Empty,
+            gen_ai.usage.output_tokens = field::Empty,
+            gen_ai.usage.cache_read_input_tokens = field::Empty,
+            gen_ai.usage.cache_creation_input_tokens = field::Empty,
+            duration_ms = field::Empty,
+        );
+        crash::record_request(request);
+        let started = Instant::now();
+        let result = if request.stream {
+            self.send_streaming_request(request)
+                .instrument(span.clone())
+                .await
+        } else {
+            self.send_request(request).instrument(span.clone()).await
+        };
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        match &result {
+            Ok(response) => {
+                span.record("gen_ai.response.id", response.id.as_str());
+                if let Some(stop_reason) = &response.stop_reason {
+                    span.record("gen_ai.response.finish_reasons", stop_reason.as_str());
+                }
+                telemetry::record_usage(&span, &response.usage);
+            }
+            Err(e) => telemetry::record_error(&span, format!("{:#}", e)),
+        }
+        result
+    }
+
+    /// リクエストを送信してレスポンス全体を受け取る
+    async fn send_request(&self, request: &MessageRequest<'_>) -> Result<MessageResponse> {
+        let message_response = self
+            .send_with_retry(request)
+            .await?
+            .json::<MessageResponse>()
+            .await
+            .context("Failed to parse API response")?;
+
+        info!("Successfully received response from Claude");
+
+        Ok(message_response)
+    }
+
+    /// ストリーミングで送信し、テキストの差分をイベントとして通知しながらレスポンスを組み立てる
+    async fn send_streaming_request(
         &self,
-        model: &str,
-        max_tokens: u32,
-        user_message: &str,
-        system: Option<String>,
+        request: &MessageRequest<'_>,
     ) -> Result<MessageResponse> {
-        debug!("Preparing request to Anthropic API");
-        debug!(?model, ?max_tokens, "Request parameters");
+        let mut response = self.send_with_retry(request).await?;
+        let mut builder = stream::ResponseBuilder::default();
+        let mut buffer = Vec::new();
 
-        let request = MessageRequest {
-            model: model.to_string(),
-            max_tokens,
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: MessageContent::Text(user_message.to_string()),
-            }],
-            tools: None,
-            system,
-        };
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("Failed to read streaming response")?
+        {
+            buffer.extend_from_slice(&chunk);
+            while let Some(event) = stream::next_event(&mut buffer) {
+                if let Some(text) = builder.handle_event(&event)? {
+                    self.emit(AgentEvent::TextDelta { text });
+                }
+            }
+        }
 
+        let message_response = builder.finish()?;
+        info!("Successfully received response from Claude");
+        Ok(message_response)
+    }
+
+    /// 利用可能なモデルの一覧を取得
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
         let response = self
             .client
-            .post(format!("{}/messages", self.base_url))
+            .get(format!("{}/models", self.base_url))
+            .query(&[("limit", "1000")])
             .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
+            .header("anthropic-version", &self.anthropic_version)
             .send()
             .await
             .context("Failed to send request to Anthropic API")?;
 
         let status = response.status();
-        debug!(?status, "Received response from Anthropic API");
-
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
             bail!("API request failed with status {}: {}", status, error_text);
         }
 
-        let message_response = response
-            .json::<MessageResponse>()
+        let models = response
+            .json::<ModelList>()
             .await
             .context("Failed to parse API response")?;
+        Ok(models.data)
+    }
 
-        info!("Successfully received response from Claude");
+    /// Send a message to Claude without tools
+    pub async fn create_message(
+        &self,
+        model: &str,
+        params: &GenerationParams,
+        messages: &[Message],
+        system: Option<&str>,
+    ) -> Result<MessageResponse> {
+        debug!("Preparing request to Anthropic API");
+        debug!(?model, ?params, "Request parameters");
 
-        Ok(message_response)
+        let request = MessageRequest {
+            model,
+            messages,
+            tools: None,
+            system,
+            params,
+            stream: self.events.is_some(),
+        };
+
+        self.send(&request).await
+    }
+
+    /// 進行イベントを出さずにメッセージを作成する（セッションのタイトルなどの補助的な呼び出し用）
+    pub async fn create_message_quietly(
+        &self,
+        model: &str,
+        params: &GenerationParams,
+        messages: &[Message],
+    ) -> Result<MessageResponse> {
+        let request = MessageRequest {
+            model,
+            messages,
+            tools: None,
+            system: None,
+            params,
+            stream: false,
+        };
+        self.send(&request).await
     }
 
     /// ツールをサポートしたメッセージ作成
     pub async fn create_message_with_tools(
         &self,
         model: &str,
-        max_tokens: u32,
-        messages: Vec<Message>,
-        tools: Option<Vec<Tool>>,
-        system: Option<String>,
+        params: &GenerationParams,
+        messages: &[Message],
+        tools: Option<&RawValue>,
+        system: Option<&str>,
     ) -> Result<MessageResponse> {
         debug!("Preparing request to Anthropic API with tools");
         debug!(
             ?model,
-            ?max_tokens,
+            ?params,
             messages_count = messages.len(),
             "Request parameters"
         );
 
         let request = MessageRequest {
-            model: model.to_string(),
-            max_tokens,
+            model,
             messages,
             tools,
             system,
+            params,
+            stream: self.events.is_some(),
         };
 
-        let response = self
-            .client
-            .post(format!("{}/messages", self.base_url))
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send request to Anthropic API")?;
-
-        let status = response.status();
-        debug!(?status, "Received response from Anthropic API");
-
-        if !status.is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            bail!("API request failed with status {}: {}", status, error_text);
-        }
-
-        let message_response = response
-            .json::<MessageResponse>()
-            .await
-            .context("Failed to parse API response")?;
-
-        info!("Successfully received response from Claude");
-
-        Ok(message_response)
+        self.send(&request).await
     }
 
     /// ツールを使った会話（Agentic Loop）
+    ///
+    /// `conversation` はユーザーの新しいメッセージで終わる会話履歴
     pub async fn execute_with_tools(
         &self,
         model: &str,
-        max_tokens: u32,
-        user_message: &str,
+        params: &GenerationParams,
+        conversation: Vec<Message>,
         tool_registry: &ToolRegistry,
         max_iterations: usize,
         system: Option<String>,
     ) -> Result<ConversationResult> {
-        // 会話履歴を初期化
-        let mut conversation = vec![Message {
-            role: "user".to_string(),
-            content: MessageContent::Text(user_message.to_string()),
-        }];
+        let span = agent_span(model);
+        let result = self
+            .agent_loop(
+                model,
+                params,
+                conversation,
+                tool_registry,
+                max_iterations,
+                system,
+            )
+            .instrument(span.clone())
+            .await;
+        record_run(&span, &result);
+        result
+    }
+
+    async fn agent_loop(
+        &self,
+        model: &str,
+        params: &GenerationParams,
+        mut conversation: Vec<Message>,
+        tool_registry: &ToolRegistry,
+        max_iterations: usize,
+        system: Option<String>,
+    ) -> Result<ConversationResult> {
+        // ツールごとの統計と反復ごとの記録
+        let mut tool_stats = BTreeMap::new();
+        let mut tool_cache = ToolCache::default();
+        let mut steps = Vec::new();
+        let mut usage = Usage::default();
+
+        let tools = serde_json::value::to_raw_value(tool_registry.get_schemas())
+            .context("Failed to serialize tool schemas")?;
+
+        let run_started = Instant::now();
+        let mut last_response = None;
+        let mut timed_out = None;
+        // 最初の反復と、ツールの失敗や検査の失敗の後の反復は元のモデルで考え直す
+        let mut hard = true;
+        // 過負荷で切り替えた後は切り替え先のモデルだけを使う
+        let (mut model, mut params, mut routing) = (model, params, self.routing.as_ref());
+        let mut fallback_from = None;
 
         // 最大反復回数までループ
         for iteration in 0..max_iterations {
             info!("Iteration {}/{}", iteration + 1, max_iterations);
+            let started = Instant::now();
+            let limit = self.time_limits.next(run_started, started);
+            let checkpoint = conversation.len();
+            self.emit(AgentEvent::IterationStart {
+                iteration: iteration + 1,
+                max_iterations,
+            });
 
-            // APIを呼び出す
-            let response = self
-                .create_message_with_tools(
-                    model,
-                    max_tokens,
-                    conversation.clone(),
-                    Some(tool_registry.get_schemas()),
-                    system.clone(),
-                )
-                .await?;
+            // APIを呼び出す（安いモデルが最終応答を書いた場合は元のモデルで書き直す）
+            let mut routed = routing.filter(|_| !hard);
+            let (response, step_model) = loop {
+                let (step_model, step_params) = match routed {
+                    Some(routing) => (routing.model.as_str(), &routing.params),
+                    None => (model, params),
+                };
+                let request = self.create_message_with_tools(
+                    step_model,
+                    step_params,
+                    &conversation,
+                    Some(&tools),
+                    system.as_deref(),
+                );
+                let response = match with_limit(limit, request).await {
+                    Ok(response) => response,
+                    Err(e) => match self.fallback_for(&e, step_model) {
+                        Some(fallback) => {
+                            fallback_from.get_or_insert_with(|| model.to_string());
+                            (model, params, routing, routed) =
+                                (&fallback.model, &fallback.params, None, None);
+                            continue;
+                        }
+                        None => match e.downcast::<AgentError>() {
+                            Ok(e) => return Err(e.into()),
+                            Err(e) => return Err(AgentError::Api(format!("{:#}", e)).into()),
+                        },
+                    },
+                };
+                let Some(response) = response else {
+                    break (None, step_model);
+                };
+                usage.add(&response.usage);
+                self.emit(AgentEvent::Usage {
+                    iteration: iteration + 1,
+                    usage: response.usage,
+                });
+                let redo = routed.is_some_and(|routing| routing.final_with_main)
+                    && response.stop_reason.as_deref() != Some("tool_use");
+                if !redo {
+                    break (Some(response), step_model);
+                }
+                info!(
+                    "{} wrote the final answer; asking {} instead",
+                    step_model, model
+                );
+                steps.push(IterationRecord {
+                    model: step_model.to_string(),
+                    stop_reason: response.stop_reason,
+                    usage: response.usage,
+                    tool_calls: Vec::new(),
+                    duration: started.elapsed(),
+                });
+                self.check_budget(steps_cost(&steps))?;
+                routed = None;
+            };
+            let Some(response) = response else {
+                timed_out = limit.map(|(_, kind)| kind);
+                break;
+            };
 
             // アシスタントのメッセージを会話履歴に追加
             conversation.push(Message {
                 role: "assistant".to_string(),
                 content: MessageContent::Blocks(response.content.clone()),
             });
+            crash::record_conversation(&conversation);
+            let mut step = IterationRecord {
+                model: step_model.to_string(),
+                stop_reason: response.stop_reason.clone(),
+                usage: response.usage,
+                tool_calls: Vec::new(),
+                duration: Duration::ZERO,
+            };
+            let spent = steps_cost(&steps)
+                .zip(pricing::estimate_cost(step_model, &response.usage))
+                .map(|(before, now)| before + now);
+            self.check_budget(spent)?;
 
             // stop_reason をチェック
             if response.stop_reason.as_deref() != Some("tool_use") {
                 // ツール使用がない → 最終応答
                 info!("Conversation completed in {} iterations", iteration + 1);
+                step.duration = started.elapsed();
+                steps.push(step);
                 return Ok(ConversationResult {
+                    model: model.to_string(),
                     response,
                     conversation,
                     iterations: iteration + 1,
+                    usage,
+                    steps,
+                    tool_stats,
+                    timed_out: None,
+                    fallback_from,
                 });
             }
 
             // ツールを実行
             info!("Executing tools...");
-            let tool_results = self.execute_tools(&response.content, tool_registry).await?;
+            let execution = self.execute_tools(
+                &response.content,
+                tool_registry,
+                &mut tool_stats,
+                &mut step.tool_calls,
+                &mut tool_cache,
+            );
+            let tool_results = with_limit(limit, execution).await?;
+            // 読み込み専用のツールだけの反復ではファイルは変わらない
+            if step
+                .tool_calls
+                .iter()
+                .any(|call| !tool_registry.is_cacheable(&call.name))
+            {
+                self.save_checkpoint(iteration + 1, &step.tool_calls).await;
+            }
+            step.duration = started.elapsed();
+            hard = step.tool_calls.iter().any(|call| call.is_error);
+            steps.push(step);
+            last_response = Some(response);
+
+            let Some(tool_results) = tool_results else {
+                // ツール結果のない tool_use は送れないので、この反復の会話を取り消す
+                conversation.truncate(checkpoint);
+                timed_out = limit.map(|(_, kind)| kind);
+                break;
+            };
+            // ツールの結果の後のテキストは `[verify]` の検査の失敗
+            hard |= tool_results
+                .iter()
+                .any(|block| matches!(block, ContentBlock::Text { .. }));
 
             // ツール結果を会話履歴に追加
             conversation.push(Message {
@@ -290,11 +942,130 @@ impl AnthropicClient {
             });
         }
 
+        // 時間制限で打ち切った場合はそこまでの結果を返す
+        if let Some(limit) = timed_out {
+            warn!("Stopped early: {}", limit);
+            return Ok(ConversationResult {
+                model: model.to_string(),
+                response: last_response.unwrap_or_else(MessageResponse::empty),
+                conversation,
+                iterations: steps.len(),
+                usage,
+                steps,
+                tool_stats,
+                timed_out: Some(limit),
+                fallback_from,
+            });
+        }
+
         // 最大反復回数に到達
-        bail!(
-            "Max iterations ({}) reached without final response",
-            max_iterations
-        );
+        Err(AgentError::MaxIterations(max_iterations).into())
+    }
+
+    /// ツールを使わずに 1 回だけ問い合わせる（`--no-tools`）
+    pub async fn execute_without_tools(
+        &self,
+        model: &str,
+        params: &GenerationParams,
+        conversation: Vec<Message>,
+        system: Option<String>,
+    ) -> Result<ConversationResult> {
+        let span = agent_span(model);
+        let result = self
+            .single_turn(model, params, conversation, system)
+            .instrument(span.clone())
+            .await;
+        record_run(&span, &result);
+        result
+    }
+
+    async fn single_turn(
+        &self,
+        model: &str,
+        params: &GenerationParams,
+        mut conversation: Vec<Message>,
+        system: Option<String>,
+    ) -> Result<ConversationResult> {
+        let started = Instant::now();
+        self.emit(AgentEvent::IterationStart {
+            iteration: 1,
+            max_iterations: 1,
+        });
+
+        let limit = self.time_limits.next(started, started);
+        let request = self.create_message(model, params, &conversation, system.as_deref());
+        let mut model = model;
+        let mut fallback_from = None;
+        let response = match with_limit(limit, request).await {
+            Err(e) => match self.fallback_for(&e, model) {
+                Some(fallback) => {
+                    fallback_from = Some(model.to_string());
+                    model = &fallback.model;
+                    let request = self.create_message(
+                        model,
+                        &fallback.params,
+                        &conversation,
+                        system.as_deref(),
+                    );
+                    with_limit(limit, request).await
+                }
+                None => Err(e),
+            },
+            response => response,
+        };
+        let Some(response) = response.map_err(|e| AgentError::Api(format!("{:#}", e)))? else {
+            let limit = limit.map(|(_, kind)| kind);
+            warn!("Stopped early: {}", limit.unwrap_or(TimeLimit::Deadline));
+            return Ok(ConversationResult {
+                model: model.to_string(),
+                response: MessageResponse::empty(),
+                conversation,
+                iterations: 0,
+                usage: Usage::default(),
+                steps: Vec::new(),
+                tool_stats: BTreeMap::new(),
+                timed_out: limit,
+                fallback_from,
+            });
+        };
+
+        conversation.push(Message {
+            role: "assistant".to_string(),
+            content: MessageContent::Blocks(response.content.clone()),
+        });
+        self.emit(AgentEvent::Usage {
+            iteration: 1,
+            usage: response.usage,
+        });
+        self.check_budget(pricing::estimate_cost(model, &response.usage))?;
+
+        Ok(ConversationResult {
+            model: model.to_string(),
+            conversation,
+            iterations: 1,
+            usage: response.usage,
+            steps: vec![IterationRecord {
+                model: model.to_string(),
+                stop_reason: response.stop_reason.clone(),
+                usage: response.usage,
+                tool_calls: Vec::new(),
+                duration: started.elapsed(),
+            }],
+            tool_stats: BTreeMap::new(),
+            response,
+            timed_out: None,
+            fallback_from,
+        })
+    }
+
+    /// 推定コストが上限を超えていればエラーにする
+    fn check_budget(&self, spent: Option<f64>) -> Result<()> {
+        if let (Some(limit), Some(spent)) = (self.max_cost, spent) {
+            if spent > limit {
+                return Err(AgentError::BudgetExceeded { limit, spent }.into());
+            }
+        }
+        Ok(())
     }
 
     /// content blocks からツールを抽出して実行
@@ -302,19 +1073,146 @@ impl AnthropicClient {
         &self,
         content_blocks: &[ContentBlock],
         tool_registry: &ToolRegistry,
+        tool_stats: &mut BTreeMap<String, ToolStats>,
+        tool_calls: &mut Vec<ToolCallRecord>,
+        cache: &mut ToolCache,
     ) -> Result<Vec<ContentBlock>> {
         let mut results = Vec::new();
+        let mut modified_by = None;
 
         for block in content_blocks {
             if let ContentBlock::ToolUse { id, name, input } = block {
                 info!("Executing tool: {}", name);
+                self.emit(AgentEvent::ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    input: input.clone(),
+                });
 
-                // ツールを実行
-                let result = tool_registry.execute(name, input.clone()).await?;
+                // ツールを実行（所要時間とエラーを記録）
+                let span = info_span!(
+                    "execute_tool",
+                    otel.name = format!("execute_tool {}", name),
+                    otel.status_code = field::Empty,
+                    otel.status_description = field::Empty,
+                    gen_ai.operation.name = "execute_tool",
+                    gen_ai.tool.name = name.as_str(),
+                    gen_ai.tool.call.id = id.as_str(),
+                    tool.cached = field::Empty,
+                    tool.result_tokens = field::Empty,
+                    duration_ms = field::Empty,
+                );
+                let progress = match &self.events {
+                    Some(handler) => {
+                        let (handler, id, name) = (handler.clone(), id.clone(), name.clone());
+                        Progress::new(move |percent, message| {
+                            handler(&AgentEvent::ToolProgress {
+                                id: id.clone(),
+                                name: name.clone(),
+                                percent,
+                                message,
+                            })
+                        })
+                    }
+                    None => Progress::default(),
+                };
+                let cache_key = tool_registry
+                    .is_cacheable(name)
+                    .then(|| ToolCache::key(name, input));
+                let cached = cache_key
+                    .as_ref()
+                    .and_then(|key| cache.results.get(key))
+                    .cloned();
+                let started = Instant::now();
+                let result = match &cached {
+                    Some(content) => {
+                        debug!("Reusing the cached result of {}", name);
+                        Ok(ToolResult {
+                            content: format!(
+                                "{}\n\n{}",
+                                content,
+                                tr!(
+                                    "[Same result as an identical earlier {} call in this run]",
+                                    "[この実行の中で同じ入力で呼び出した {} と同じ結果です]",
+                                    name
+                                )
+                            ),
+                            error: None,
+                        })
+                    }
+                    None => {
+                        tool_registry
+                            .execute(name, input.clone(), progress)
+                            .instrument(span.clone())
+                            .await
+                    }
+                };
+                let duration = started.elapsed();
+                let cached = cached.is_some();
+                span.record("duration_ms", duration.as_millis() as u64);
+                span.record("tool.cached", cached);
+                match (&cache_key, &result) {
+                    (
+                        Some(key),
+                        Ok(ToolResult {
+                            content,
+                            error: None,
+                        }),
+                    ) if !cached => {
+                        cache.results.insert(key.clone(), content.clone());
+                    }
+                    (Some(_), _) => {}
+                    // ファイルなどが変わったかもしれないので、それまでの結果は使わない
+                    (None, _) => cache.results.clear(),
+                }
+                let is_error = !matches!(&result, Ok(r) if r.error.is_none());
+                match &result {
+                    Ok(ToolResult {
+                        error: Some(error), ..
+                    }) => telemetry::record_error(&span, error),
+                    Err(e) => telemetry::record_error(&span, format!("{:#}", e)),
+                    Ok(_) => {}
+                }
+                let stats = tool_stats.entry(name.clone()).or_default();
+                stats.calls += 1;
+                stats.total_duration += duration;
+                if is_error {
+                    stats.errors += 1;
+                }
+                if cached {
+                    stats.cached += 1;
+                }
+                tool_calls.push(ToolCallRecord {
+                    name: name.clone(),
+                    input: input.clone(),
+                    is_error,
+                    duration,
+                    cached,
+                });
+                // ツールの失敗で実行全体を止めず、エラーとして返してモデルに対処させる
+                let result = result.unwrap_or_else(|e| {
+                    warn!("Tool '{}' failed: {:#}", name, e);
+                    ToolResult {
+                        content: String::new(),
+                        error: Some(format!("{:#}", e)),
+                    }
+                });
 
                 // 結果を JSON にシリアライズ
                 let content =
                     serde_json::to_string(&result).context("Failed to serialize tool result")?;
+                let tokens = tokens::estimate(&content);
+                span.record("tool.result_tokens", tokens);
+                debug!("Tool '{}' result: ~{} tokens", name, tokens);
+                self.emit(AgentEvent::ToolResult {
+                    id: id.clone(),
+                    name: name.clone(),
+                    is_error,
+                    content: content.clone(),
+                    duration_ms: duration.as_millis() as u64,
+                    tokens,
+                    cached,
+                });
 
                 // tool_result block を作成
                 results.push(ContentBlock::ToolResult {
@@ -323,20 +1221,153 @@ impl AnthropicClient {
                     is_error: result.error.as_ref().map(|_| true),
                 });
 
-                info!("Tool '{}' executed successfully", name);
+                if !is_error {
+                    info!("Tool '{}' executed successfully", name);
+                    if tool_registry.modifies_files(name) {
+                        modified_by = Some((id, name));
+                    }
+                }
+            }
+        }
+
+        // 変更後の検査が失敗した場合は、その出力をツールの結果の後に添える
+        if let (Some(verifier), Some((id, name))) = (&self.verifier, modified_by) {
+            if let Some(report) = self.verify(verifier, id, name).await? {
+                results.push(ContentBlock::Text { text: report });
             }
         }
 
         Ok(results)
     }
+
+    /// 作業ツリーが変わっていればチェックポイントを記録する（失敗しても実行は続ける）
+    async fn save_checkpoint(&self, iteration: usize, tool_calls: &[ToolCallRecord]) {
+        let Some(checkpoints) = &self.checkpoints else {
+            return;
+        };
+        let mut tools: Vec<&str> = tool_calls.iter().map(|call| call.name.as_str()).collect();
+        tools.dedup();
+        let message = format!(
+            "Checkpoint {} after iteration {}\n\nTools: {}",
+            checkpoints.next_number().await,
+            iteration,
+            tools.join(", ")
+        );
+        match checkpoints.save(&message).await {
+            Ok(Some(commit)) => info!(
+                "Saved checkpoint {} on {}",
+                &commit[..commit.len().min(12)],
+                checkpoints.reference()
+            ),
+            Ok(None) => debug!("No changes since the last checkpoint"),
+            Err(e) => warn!("Failed to save a checkpoint: {:#}", e),
+        }
+    }
+
+    /// `[verify]` の検査を実行する（失敗した場合はモデルに返す報告）
+    async fn verify(&self, verifier: &Verifier, id: &str, name: &str) -> Result<Option<String>> {
+        info!("Running {}", verifier.command_line());
+        self.emit(AgentEvent::ToolProgress {
+            id: id.to_string(),
+            name: name.to_string(),
+            percent: None,
+            message: format!("verifying with {}", verifier.command_line()),
+        });
+        let span = info_span!(
+            "verify",
+            otel.status_code = field::Empty,
+            otel.status_description = field::Empty,
+            verify.command = verifier.command_line(),
+        );
+        let report = verifier.run().instrument(span.clone()).await?;
+        match &report {
+            Some(_) => {
+                warn!("{} failed", verifier.command_line());
+                telemetry::record_error(&span, format!("{} failed", verifier.command_line()));
+            }
+            None => info!("{} passed", verifier.command_line()),
+        }
+        Ok(report)
+    }
+}
+
+/// エージェントの実行 1 回分のスパン（API 呼び出しとツール実行はこの子になる）
+fn agent_span(model: &str) -> tracing::Span {
+    info_span!(
+        "invoke_agent",
+        otel.status_code = field::Empty,
+        otel.status_description = field::Empty,
+        gen_ai.operation.name = "invoke_agent",
+        gen_ai.request.model = model,
+        gen_ai.usage.input_tokens = field::Empty,
+        gen_ai.usage.output_tokens = field::Empty,
+        gen_ai.usage.cache_read_input_tokens = field::Empty,
+        gen_ai.usage.cache_creation_input_tokens = field::Empty,
+        agent.iterations = field::Empty,
+        agent.tool_calls = field::Empty,
+        agent.timed_out = field::Empty,
+    )
 }
 
+/// 実行結果（反復回数・合計トークン数・失敗）をスパンに記録する
+fn record_run(span: &tracing::Span, result: &Result<ConversationResult>) {
+    match result {
+        Ok(result) => {
+            telemetry::record_usage(span, &result.usage);
+            span.record("agent.iterations", result.iterations);
+            span.record(
+                "agent.tool_calls",
+                result.tool_stats.values().map(|s| s.calls).sum::<usize>(),
+            );
+            if let Some(limit) = result.timed_out {
+                span.record("agent.timed_out", limit.to_string());
+            }
+        }
+        Err(e) => telemetry::record_error(span, format!("{:#}", e)),
+    }
+}
+
+/// 再試行すべきステータス（レート制限・過負荷・サーバーエラー）
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status.as_u16() == 529
+        || status.is_server_error()
+}
+
+/// 入力の検証エラーとして返す問題の上限
+const MAX_VALIDATION_ERRORS: usize = 10;
+
 /// ツールのレジストリ（登録・管理・実行）
 pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn ToolHandler>>,
+    tools: HashMap<String, RegisteredTool>,
     schemas: Vec<Tool>,
 }
 
+/// 登録済みのツールと入力スキーマの検証器
+struct RegisteredTool {
+    handler: Box<dyn DynToolHandler>,
+    validator: jsonschema::Validator,
+    cacheable: bool,
+    modifies_files: bool,
+}
+
+/// 1 回の実行の中での読み込み専用ツールの結果（ツール名と入力のハッシュ → 結果）
+///
+/// 読み込み専用でないツールはファイルなどを変更しうるので、実行されたら全体を捨てる
+#[derive(Default)]
+struct ToolCache {
+    results: HashMap<(String, u64), String>,
+}
+
+impl ToolCache {
+    fn key(name: &str, input: &serde_json::Value) -> (String, u64) {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        input.to_string().hash(&mut hasher);
+        (name.to_string(), hasher.finish())
+    }
+}
+
 impl ToolRegistry {
     /// 新しいレジストリを作成
     pub fn new() -> Self {
@@ -346,33 +1377,778 @@ impl ToolRegistry {
         }
     }
 
-    /// ツールを登録
-    pub fn register<T: ToolHandler + 'static>(&mut self, schema: Tool, handler: T) {
-        let name = schema.name.clone();
+    /// 標準のツールをまとめて登録するビルダー
+    pub fn builder<'a>(config: &'a Config, workspace: &'a Path) -> ToolRegistryBuilder<'a> {
+        ToolRegistryBuilder::new(config, workspace)
+    }
+
+    /// ツールを登録（同じ名前のツールがあればエラー）
+    pub fn register<T: ToolHandler + 'static>(&mut self, handler: T) -> Result<()> {
+        self.insert(
+            T::schema(),
+            Box::new(handler),
+            T::CACHEABLE,
+            T::MODIFIES_FILES,
+        )
+    }
+
+    /// 外部から取り込んだツールを `<namespace>__<ツール名>` の名前で登録する
+    ///
+    /// 例: MCP サーバー github の listIssues は `mcp__github__listIssues`
+    #[allow(dead_code)] // 外部のツールはまだ取り込んでいない
+    pub fn register_namespaced<T: ToolHandler + 'static>(
+        &mut self,
+        namespace: &str,
+        handler: T,
+    ) -> Result<()> {
+        let mut schema = T::schema();
+        schema.name = namespaced_tool_name(namespace, &schema.name);
+        self.insert(schema, Box::new(handler), T::CACHEABLE, T::MODIFIES_FILES)
+    }
+
+    /// 実行時にスキーマが決まるツールを登録（同じ名前のツールがあればエラー）
+    pub fn register_dynamic<T: DynamicTool + 'static>(&mut self, tool: T) -> Result<()> {
+        self.insert(tool.schema(), Box::new(Dynamic(tool)), false, false)
+    }
+
+    fn insert(
+        &mut self,
+        schema: Tool,
+        handler: Box<dyn DynToolHandler>,
+        cacheable: bool,
+        modifies_files: bool,
+    ) -> Result<()> {
+        validate_tool_name(&schema.name)?;
+        if self.tools.contains_key(&schema.name) {
+            bail!("Tool {} is already registered", schema.name);
+        }
+        let validator = jsonschema::validator_for(&schema.input_schema)
+            .map_err(|e| anyhow::anyhow!("Invalid input schema of {}: {}", schema.name, e))?;
+        self.tools.insert(
+            schema.name.clone(),
+            RegisteredTool {
+                handler,
+                validator,
+                cacheable,
+                modifies_files,
+            },
+        );
         self.schemas.push(schema);
-        self.tools.insert(name, Box::new(handler));
+        Ok(())
+    }
+
+    /// 条件を満たすツールだけを残す
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.schemas.retain(|schema| keep(&schema.name));
+        let names: Vec<String> = self.schemas.iter().map(|s| s.name.clone()).collect();
+        self.tools.retain(|name, _| names.contains(name));
+    }
+
+    /// 結果を再利用できるツールか（`ToolHandler::CACHEABLE`）
+    fn is_cacheable(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|tool| tool.cacheable)
+    }
+
+    /// ファイルを変更するツールか（`ToolHandler::MODIFIES_FILES`）
+    fn modifies_files(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|tool| tool.modifies_files)
     }
 
     /// 登録されているツールのスキーマ一覧を取得
-    pub fn get_schemas(&self) -> Vec<Tool> {
-        self.schemas.clone()
+    pub fn get_schemas(&self) -> &[Tool] {
+        &self.schemas
     }
 
     /// ツールを実行
-    pub async fn execute(&self, name: &str, input: serde_json::Value) -> Result<ToolResult> {
-        let handler = self
-            .tools
-            .get(name)
-            .ok_or_else(|| anyhow::anyhow!("Tool not found: {}", name))?;
+    ///
+    /// 入力がスキーマに合わない場合はツールを呼ばず、問題の箇所を列挙したエラーを結果として返す。
+    /// 登録されていないツールの場合も、使えるツールの一覧をエラーとして返す
+    pub async fn execute(
+        &self,
+        name: &str,
+        input: serde_json::Value,
+        progress: Progress,
+    ) -> Result<ToolResult> {
+        let Some(tool) = self.tools.get(name) else {
+            warn!("Tool not found: {}", name);
+            let available: Vec<&str> = self.schemas.iter().map(|s| s.name.as_str()).collect();
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(tr!(
+                    "Unknown tool: {}. Available tools: {}",
+                    "{} というツールはありません。使えるツール: {}",
+                    name,
+                    available.join(", ")
+                )),
+            });
+        };
 
-        handler.execute(input).await
+        if let Some(error) = validation_error(name, &tool.validator, &input) {
+            debug!("Invalid input for {}: {}", name, error);
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(error),
+            });
+        }
+        tool.handler.call(input, progress).await
     }
 }
 
+/// 入力がスキーマに合わない場合のエラー（モデルが直せるよう、問題の場所ごとに並べる）
+fn validation_error(
+    name: &str,
+    validator: &jsonschema::Validator,
+    input: &serde_json::Value,
+) -> Option<String> {
+    let problems: Vec<String> = validator
+        .iter_errors(input)
+        .take(MAX_VALIDATION_ERRORS)
+        .map(|e| {
+            let path = e.instance_path().as_str();
+            format!("- {}: {}", if path.is_empty() { "/" } else { path }, e)
+        })
+        .collect();
+    if problems.is_empty() {
+        return None;
+    }
+    Some(tr!(
+        "The input does not match the schema of {}:\n{}",
+        "入力が {} のスキーマに合いません:\n{}",
+        name,
+        problems.join("\n")
+    ))
+}
+
+/// 名前空間付きのツール名（名前空間の区切りは `__`）
+pub fn namespaced_tool_name(namespace: &str, name: &str) -> String {
+    format!("{}__{}", namespace, name)
+}
+
+/// API が受け付けるツール名か（英数字・`_`・`-` の 1〜64 文字）
+fn validate_tool_name(name: &str) -> Result<()> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if name.is_empty() || name.len() > 64 || !valid_chars {
+        bail!(
+            "Invalid tool name {:?}: use 1-64 ASCII letters, digits, '_' or '-'",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// ツールごとの実行統計
+#[derive(Debug, Clone, Default)]
+pub struct ToolStats {
+    /// 呼び出し回数
+    pub calls: usize,
+    /// エラーになった回数
+    pub errors: usize,
+    /// 前の結果を再利用した回数
+    pub cached: usize,
+    /// 合計実行時間
+    pub total_duration: Duration,
+}
+
+/// ツール呼び出し 1 回分の記録
+#[derive(Debug, Clone)]
+pub struct ToolCallRecord {
+    pub name: String,
+    pub input: serde_json::Value,
+    pub is_error: bool,
+    pub duration: Duration,
+    /// 前の結果を再利用した
+    pub cached: bool,
+}
+
+/// 反復（API 呼び出し）1 回分の記録
+#[derive(Debug, Clone)]
+pub struct IterationRecord {
+    /// この反復で使ったモデル（`[model.routing]` では反復ごとに変わる）
+    pub model: String,
+    pub stop_reason: Option<String>,
+    pub usage: Usage,
+    pub tool_calls: Vec<ToolCallRecord>,
+    /// API 呼び出しとツール実行にかかった時間
+    pub duration: Duration,
+}
+
 /// 会話の結果（ツール実行を含む）
 pub struct ConversationResult {
+    pub model: String,
     pub response: MessageResponse,
-    #[allow(dead_code)]
     pub conversation: Vec<Message>,
     pub iterations: usize,
+    /// 全反復の合計使用量
+    pub usage: Usage,
+    /// 反復ごとの記録
+    pub steps: Vec<IterationRecord>,
+    /// ツール名ごとの実行統計（名前順）
+    pub tool_stats: BTreeMap<String, ToolStats>,
+    /// 時間制限で打ち切った場合はその種類（`response` は最後に受け取った応答）
+    pub timed_out: Option<TimeLimit>,
+    /// 過負荷で `model` に切り替えた場合は元のモデル
+    pub fallback_from: Option<String>,
+}
+
+/// 打ち切りの原因になった時間制限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeLimit {
+    /// 1 回の反復（API 呼び出しとツール実行）の制限
+    TurnTimeout,
+    /// 実行全体の制限
+    Deadline,
+}
+
+impl std::fmt::Display for TimeLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeLimit::TurnTimeout => write!(f, "turn timeout reached"),
+            TimeLimit::Deadline => write!(f, "deadline reached"),
+        }
+    }
+}
+
+/// 反復ごとの推定料金の合計（料金が不明なモデルを含む場合は None）
+fn steps_cost(steps: &[IterationRecord]) -> Option<f64> {
+    steps
+        .iter()
+        .map(|step| pricing::estimate_cost(&step.model, &step.usage))
+        .sum()
+}
+
+impl ConversationResult {
+    /// 実行全体の推定料金（反復ごとのモデルの料金で計算する）
+    pub fn cost_usd(&self) -> Option<f64> {
+        if self.steps.is_empty() {
+            return pricing::estimate_cost(&self.model, &self.usage);
+        }
+        steps_cost(&self.steps)
+    }
+}
+
+/// `model.fallback` の設定（元のモデルの過負荷が続いた場合に使うモデル）
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    pub model: String,
+    pub params: GenerationParams,
+}
+
+/// 再試行の後も API が過負荷（529）を返した
+#[derive(Debug)]
+struct Overloaded;
+
+impl std::fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the model is overloaded")
+    }
+}
+
+impl std::error::Error for Overloaded {}
+
+/// `[model.routing]` の設定（ツールを呼ぶだけの反復で使うモデル）
+#[derive(Debug, Clone)]
+pub struct Routing {
+    pub model: String,
+    pub params: GenerationParams,
+    /// 安いモデルが書いた最終応答を捨てて元のモデルに書き直させる
+    pub final_with_main: bool,
+}
+
+/// `--max-turn-timeout` と `--deadline` の設定
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeLimits {
+    pub turn_timeout: Option<Duration>,
+    pub deadline: Option<Duration>,
+}
+
+impl TimeLimits {
+    /// この反復の打ち切り時刻（早いほうの制限）
+    fn next(&self, run_started: Instant, turn_started: Instant) -> Option<(Instant, TimeLimit)> {
+        let turn = self
+            .turn_timeout
+            .map(|timeout| (turn_started + timeout, TimeLimit::TurnTimeout));
+        let deadline = self
+            .deadline
+            .map(|deadline| (run_started + deadline, TimeLimit::Deadline));
+        match (turn, deadline) {
+            (Some(turn), Some(deadline)) => {
+                Some(if deadline.0 <= turn.0 { deadline } else { turn })
+            }
+            (turn, deadline) => turn.or(deadline),
+        }
+    }
+}
+
+/// 時間制限付きで実行する（制限を超えた場合は `Ok(None)`）
+async fn with_limit<T>(
+    limit: Option<(Instant, TimeLimit)>,
+    future: impl std::future::Future<Output = Result<T>>,
+) -> Result<Option<T>> {
+    match limit {
+        Some((at, _)) => match tokio::time::timeout_at(at.into(), future).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        },
+        None => future.await.map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ListFilesConfig, ReadFileConfig, SymlinkPolicy};
+    use crate::test_support::{FakeAnthropic, Reply, TempWorkspace};
+    use crate::tools::{FileTracker, IgnoreMatcher, ListFilesTool, ReadFileTool, SymlinkGuard};
+    use coding_agent_example_macros::tool;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    const PARAMS: GenerationParams = GenerationParams {
+        max_tokens: 1024,
+        temperature: None,
+        top_p: None,
+    };
+
+    fn registry() -> ToolRegistry {
+        let mut registry = ToolRegistry::new();
+        registry
+            .register(ReadFileTool::new(
+                FileTracker::new(),
+                ReadFileConfig::default(),
+                SymlinkGuard::new(SymlinkPolicy::Follow, Path::new(".")),
+            ))
+            .unwrap();
+        registry
+    }
+
+    async fn run(client: &AnthropicClient, max_iterations: usize) -> Result<ConversationResult> {
+        client
+            .execute_with_tools(
+                "claude-sonnet-4-5",
+                &PARAMS,
+                vec![Message::user_text("Summarize the notes")],
+                &registry(),
+                max_iterations,
+                None,
+            )
+            .await
+    }
+
+    /// Adds two numbers to the offset.
+    #[tool(name = "addNumbers", ja = "オフセットに 2 つの数を足します。")]
+    async fn add_numbers(
+        #[state] offset: &i64,
+        /// First number
+        a: i64,
+        /// Second number {defaults to 0}
+        #[ja("2 つ目の数（省略時は 0）")]
+        #[serde(default, rename = "second")]
+        b: i64,
+    ) -> Result<ToolResult> {
+        Ok(ToolResult {
+            content: (offset + a + b).to_string(),
+            error: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tool_macro_generates_schema_and_parsing() {
+        let schema = AddNumbersTool::schema();
+        assert_eq!(schema.name, "addNumbers");
+        assert_eq!(schema.description, "Adds two numbers to the offset.");
+        assert_eq!(schema.input_schema["required"], json!(["a"]));
+        assert_eq!(
+            schema.input_schema["properties"]["second"]["description"],
+            "Second number {defaults to 0}"
+        );
+
+        let mut registry = ToolRegistry::new();
+        registry.register(AddNumbersTool::new(10)).unwrap();
+        let result = registry
+            .execute(
+                "addNumbers",
+                json!({ "a": 1, "second": 2 }),
+                Progress::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.content, "13");
+        assert!(registry
+            .execute("addNumbers", json!({ "b": 2 }), Progress::default())
+            .await
+            .unwrap()
+            .error
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_registry_rejects_duplicates_and_namespaces_tools() {
+        let mut registry = ToolRegistry::new();
+        registry.register(AddNumbersTool::new(0)).unwrap();
+        assert!(registry.register(AddNumbersTool::new(1)).is_err());
+        assert!(registry
+            .register_namespaced("bad name", AddNumbersTool::new(1))
+            .is_err());
+
+        registry
+            .register_namespaced("mcp__math", AddNumbersTool::new(100))
+            .unwrap();
+        let names: Vec<&str> = registry
+            .get_schemas()
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect();
+        assert_eq!(names, ["addNumbers", "mcp__math__addNumbers"]);
+        let result = registry
+            .execute(
+                "mcp__math__addNumbers",
+                json!({ "a": 1 }),
+                Progress::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.content, "101");
+    }
+
+    #[tokio::test]
+    async fn test_registry_validates_input_against_schema() {
+        let mut registry = ToolRegistry::new();
+        registry.register(AddNumbersTool::new(0)).unwrap();
+        let result = registry
+            .execute(
+                "addNumbers",
+                json!({ "second": "two" }),
+                Progress::default(),
+            )
+            .await
+            .unwrap();
+        let error = result.error.unwrap();
+        assert!(
+            error.contains("- /: \"a\" is a required property"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("- /second: \"two\" is not of type"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_sends_tool_results_back() {
+        let workspace = TempWorkspace::new().file("notes.txt", "remember the milk\n");
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_use(
+                "readFile",
+                json!({ "path": workspace.path_str("notes.txt") }),
+            ),
+            Reply::text("You need milk."),
+        ])
+        .await;
+
+        let result = run(&fake.client(), 5).await.unwrap();
+        assert_eq!(result.iterations, 2);
+        assert_eq!(result.tool_stats["readFile"].calls, 1);
+        assert_eq!(result.tool_stats["readFile"].errors, 0);
+        assert_eq!(result.usage.input_tokens, 20);
+
+        let requests = fake.requests().await;
+        assert_eq!(requests.len(), 2);
+        let tool_result = &requests[1]["messages"][2]["content"][0];
+        assert_eq!(tool_result["type"], "tool_result");
+        assert!(tool_result["content"]
+            .as_str()
+            .unwrap()
+            .contains("remember the milk"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tool_is_reported_to_the_model() {
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_use("deleteEverything", json!({})),
+            Reply::text("Sorry, I will use readFile."),
+        ])
+        .await;
+
+        let result = run(&fake.client(), 5).await.unwrap();
+        assert_eq!(result.iterations, 2);
+        assert_eq!(result.tool_stats["deleteEverything"].errors, 1);
+
+        let requests = fake.requests().await;
+        let tool_result = &requests[1]["messages"][2]["content"][0];
+        assert_eq!(tool_result["is_error"], true);
+        let content = tool_result["content"].as_str().unwrap();
+        assert!(
+            content.contains("Unknown tool: deleteEverything"),
+            "{}",
+            content
+        );
+        assert!(content.contains("Available tools: readFile"), "{}", content);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_results_are_reused_until_a_write() {
+        let workspace = TempWorkspace::new().file("a.txt", "a\n");
+        let list = json!({ "path": workspace.path_str("") });
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_use("listFiles", list.clone()),
+            Reply::tool_use("listFiles", list.clone()),
+            Reply::tool_use("addNumbers", json!({ "a": 1 })),
+            Reply::tool_use("listFiles", list),
+            Reply::text("Done."),
+        ])
+        .await;
+        let mut registry = ToolRegistry::new();
+        registry
+            .register(ListFilesTool::new(
+                ListFilesConfig::default(),
+                IgnoreMatcher::new(&[], workspace.root()).unwrap(),
+                SymlinkGuard::new(SymlinkPolicy::Follow, workspace.root()),
+            ))
+            .unwrap();
+        registry.register(AddNumbersTool::new(0)).unwrap();
+
+        let result = fake
+            .client()
+            .execute_with_tools(
+                "claude-sonnet-4-5",
+                &PARAMS,
+                vec![Message::user_text("List the files")],
+                &registry,
+                10,
+                None,
+            )
+            .await
+            .unwrap();
+        let cached: Vec<bool> = result
+            .steps
+            .iter()
+            .flat_map(|step| &step.tool_calls)
+            .map(|call| call.cached)
+            .collect();
+        assert_eq!(cached, [false, true, false, false]);
+        assert_eq!(result.tool_stats["listFiles"].cached, 1);
+
+        let requests = fake.requests().await;
+        let second = requests[2]["messages"][4]["content"][0]["content"]
+            .as_str()
+            .unwrap();
+        assert!(second.contains("a.txt"), "{}", second);
+        assert!(
+            second.contains("identical earlier listFiles call"),
+            "{}",
+            second
+        );
+    }
+
+    /// ファイルを変更したことにするだけのツール
+    struct TouchTool;
+
+    #[async_trait]
+    impl ToolHandler for TouchTool {
+        type Args = serde_json::Value;
+
+        const NAME: &'static str = "touch";
+
+        const MODIFIES_FILES: bool = true;
+
+        fn description() -> String {
+            "Touches a file.".to_string()
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult {
+                content: "touched".to_string(),
+                error: None,
+            })
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_verification_is_sent_with_tool_results() {
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_uses(&[("touch", json!({})), ("addNumbers", json!({ "a": 1 }))]),
+            Reply::text("Fixed."),
+        ])
+        .await;
+        let mut client = fake.client();
+        let config = crate::config::VerifyConfig {
+            command: ["sh", "-c", "echo 'error: missing semicolon' >&2; exit 1"]
+                .map(String::from)
+                .to_vec(),
+            ..Default::default()
+        };
+        let sandbox = crate::sandbox::Sandbox::new(&Default::default(), &std::env::temp_dir());
+        client.set_verifier(Some(
+            Verifier::new(crate::config::VerifyCheck::Command, &config, sandbox).unwrap(),
+        ));
+        let mut registry = ToolRegistry::new();
+        registry.register(TouchTool).unwrap();
+        registry.register(AddNumbersTool::new(0)).unwrap();
+
+        client
+            .execute_with_tools(
+                "claude-sonnet-4-5",
+                &PARAMS,
+                vec![Message::user_text("Edit the file")],
+                &registry,
+                5,
+                None,
+            )
+            .await
+            .unwrap();
+        let requests = fake.requests().await;
+        let blocks = requests[1]["messages"][2]["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[2]["type"], "text");
+        let report = blocks[2]["text"].as_str().unwrap();
+        assert!(report.contains("error: missing semicolon"), "{}", report);
+    }
+
+    #[tokio::test]
+    async fn test_routing_uses_the_cheaper_model_between_tool_calls() {
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_use("addNumbers", json!({ "a": 1 })),
+            Reply::tool_use("addNumbers", json!({ "a": 2 })),
+            Reply::text("Cheap answer"),
+            Reply::text("Done."),
+        ])
+        .await;
+        let mut client = fake.client();
+        client.set_routing(Some(Routing {
+            model: "claude-haiku-4-5".to_string(),
+            params: PARAMS,
+            final_with_main: true,
+        }));
+        let mut registry = ToolRegistry::new();
+        registry.register(AddNumbersTool::new(0)).unwrap();
+
+        let result = client
+            .execute_with_tools(
+                "claude-sonnet-4-5",
+                &PARAMS,
+                vec![Message::user_text("Add")],
+                &registry,
+                5,
+                None,
+            )
+            .await
+            .unwrap();
+        let models: Vec<serde_json::Value> = fake
+            .requests()
+            .await
+            .iter()
+            .map(|request| request["model"].clone())
+            .collect();
+        assert_eq!(
+            models,
+            [
+                "claude-sonnet-4-5",
+                "claude-haiku-4-5",
+                "claude-haiku-4-5",
+                "claude-sonnet-4-5"
+            ]
+        );
+        // 安いモデルの最終応答は会話に残さない
+        assert_eq!(result.iterations, 3);
+        assert_eq!(result.steps.len(), 4);
+        assert_eq!(result.conversation.len(), 6);
+        assert_eq!(crate::output::final_text(&result), "Done.");
+        // 料金は反復ごとのモデルで見積もる（sonnet 2 回 + haiku 2 回）
+        let expected = (2.0 * (10.0 * 3.0 + 5.0 * 15.0) + 2.0 * (10.0 * 1.0 + 5.0 * 5.0)) / 1e6;
+        assert!((result.cost_usd().unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_when_the_model_is_overloaded() {
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_use("addNumbers", json!({ "a": 1 })),
+            Reply::error(529, "Overloaded"),
+            Reply::text("Done."),
+        ])
+        .await;
+        let mut client = fake.client();
+        client.set_fallback(Some(Fallback {
+            model: "claude-haiku-4-5".to_string(),
+            params: PARAMS,
+        }));
+        let mut registry = ToolRegistry::new();
+        registry.register(AddNumbersTool::new(0)).unwrap();
+
+        let result = client
+            .execute_with_tools(
+                "claude-sonnet-4-5",
+                &PARAMS,
+                vec![Message::user_text("Add")],
+                &registry,
+                5,
+                None,
+            )
+            .await
+            .unwrap();
+        let models: Vec<serde_json::Value> = fake
+            .requests()
+            .await
+            .iter()
+            .map(|request| request["model"].clone())
+            .collect();
+        assert_eq!(
+            models,
+            ["claude-sonnet-4-5", "claude-sonnet-4-5", "claude-haiku-4-5"]
+        );
+        assert_eq!(result.model, "claude-haiku-4-5");
+        assert_eq!(result.fallback_from.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(result.steps[1].model, "claude-haiku-4-5");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_emits_text_deltas() {
+        let fake = FakeAnthropic::start(vec![Reply::text("Streamed answer")]).await;
+        let deltas = Arc::new(Mutex::new(String::new()));
+        let mut client = fake.client();
+        let sink = deltas.clone();
+        client.set_event_handler(Arc::new(move |event| {
+            if let AgentEvent::TextDelta { text } = event {
+                sink.lock().unwrap().push_str(text);
+            }
+        }));
+
+        let result = run(&client, 5).await.unwrap();
+        assert_eq!(result.iterations, 1);
+        assert_eq!(*deltas.lock().unwrap(), "Streamed answer");
+    }
+
+    #[tokio::test]
+    async fn test_api_errors_and_limits() {
+        let fake = FakeAnthropic::start(vec![Reply::error(400, "bad request")]).await;
+        let Err(error) = run(&fake.client(), 5).await else {
+            panic!("expected an API error");
+        };
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(AgentError::Api(message)) if message.contains("bad request")
+        ));
+
+        let fake = FakeAnthropic::start(vec![Reply::truncated("Partial ans")]).await;
+        let result = run(&fake.client(), 5).await.unwrap();
+        assert_eq!(result.steps[0].stop_reason.as_deref(), Some("max_tokens"));
+
+        let fake = FakeAnthropic::start(vec![
+            Reply::tool_use("readFile", json!({ "path": "missing.txt" })),
+            Reply::tool_use("readFile", json!({ "path": "missing.txt" })),
+        ])
+        .await;
+        let Err(error) = run(&fake.client(), 2).await else {
+            panic!("expected to hit the iteration limit");
+        };
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(AgentError::MaxIterations(2))
+        ));
+    }
 }
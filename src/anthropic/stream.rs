@@ -0,0 +1,255 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ContentBlock, MessageResponse, Usage};
+
+/// ストリーミング API のイベント
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart {
+        message: StartMessage,
+    },
+    ContentBlockStart {
+        index: usize,
+        content_block: Value,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: Delta,
+    },
+    ContentBlockStop {
+        index: usize,
+    },
+    MessageDelta {
+        delta: MessageDeltaBody,
+        usage: DeltaUsage,
+    },
+    MessageStop,
+    Ping,
+    Error {
+        error: Value,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct StartMessage {
+    id: String,
+    usage: Usage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Delta {
+    #[serde(rename = "text_delta")]
+    Text { text: String },
+    #[serde(rename = "input_json_delta")]
+    InputJson { partial_json: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeltaBody {
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaUsage {
+    output_tokens: u32,
+}
+
+/// 組み立て中のコンテンツブロック
+#[derive(Debug)]
+enum PartialBlock {
+    Text(String),
+    ToolUse {
+        id: String,
+        name: String,
+        input_json: String,
+    },
+    /// このクライアントが扱わない種類のブロック（読み捨てる）
+    Unsupported,
+}
+
+/// SSE イベントから `MessageResponse` を組み立てる
+#[derive(Debug, Default)]
+pub(super) struct ResponseBuilder {
+    id: String,
+    usage: Usage,
+    stop_reason: Option<String>,
+    blocks: Vec<(usize, PartialBlock)>,
+    finished: Vec<(usize, ContentBlock)>,
+}
+
+impl ResponseBuilder {
+    /// SSE イベント 1 つを処理し、テキストの差分があれば返す
+    pub(super) fn handle_event(&mut self, raw: &str) -> Result<Option<String>> {
+        let data: String = raw
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(str::trim_start)
+            .collect();
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        let event: StreamEvent =
+            serde_json::from_str(&data).context("Failed to parse streaming event")?;
+        match event {
+            StreamEvent::MessageStart { message } => {
+                self.id = message.id;
+                self.usage = message.usage;
+            }
+            StreamEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                let block = match content_block["type"].as_str() {
+                    Some("text") => {
+                        PartialBlock::Text(content_block["text"].as_str().unwrap_or("").to_string())
+                    }
+                    Some("tool_use") => PartialBlock::ToolUse {
+                        id: content_block["id"].as_str().unwrap_or("").to_string(),
+                        name: content_block["name"].as_str().unwrap_or("").to_string(),
+                        input_json: String::new(),
+                    },
+                    _ => PartialBlock::Unsupported,
+                };
+                self.blocks.push((index, block));
+            }
+            StreamEvent::ContentBlockDelta { index, delta } => {
+                let block = self.blocks.iter_mut().find(|(i, _)| *i == index);
+                match (block, delta) {
+                    (Some((_, PartialBlock::Text(text))), Delta::Text { text: delta }) => {
+                        text.push_str(&delta);
+                        return Ok(Some(delta));
+                    }
+                    (
+                        Some((_, PartialBlock::ToolUse { input_json, .. })),
+                        Delta::InputJson { partial_json },
+                    ) => input_json.push_str(&partial_json),
+                    _ => {}
+                }
+            }
+            StreamEvent::ContentBlockStop { index } => {
+                if let Some(pos) = self.blocks.iter().position(|(i, _)| *i == index) {
+                    let (index, block) = self.blocks.remove(pos);
+                    if let Some(block) = finish_block(block)? {
+                        self.finished.push((index, block));
+                    }
+                }
+            }
+            StreamEvent::MessageDelta { delta, usage } => {
+                self.stop_reason = delta.stop_reason;
+                self.usage.output_tokens = usage.output_tokens;
+            }
+            StreamEvent::MessageStop | StreamEvent::Ping => {}
+            StreamEvent::Error { error } => bail!("Streaming API error: {}", error),
+        }
+        Ok(None)
+    }
+
+    /// 受信したブロックからレスポンスを作成する
+    pub(super) fn finish(mut self) -> Result<MessageResponse> {
+        for (index, block) in std::mem::take(&mut self.blocks) {
+            if let Some(block) = finish_block(block)? {
+                self.finished.push((index, block));
+            }
+        }
+        self.finished.sort_by_key(|(index, _)| *index);
+
+        Ok(MessageResponse {
+            id: self.id,
+            content: self.finished.into_iter().map(|(_, block)| block).collect(),
+            stop_reason: self.stop_reason,
+            usage: self.usage,
+        })
+    }
+}
+
+/// 受信したバイト列から完結した SSE イベント（空行区切り）を 1 つ取り出す
+///
+/// 受信の区切りで複数バイトの文字が分かれることがあるため、バイトのまま溜めて
+/// イベント全体が揃ってから文字列にする
+pub(super) fn next_event(buffer: &mut Vec<u8>) -> Option<String> {
+    let end = buffer.windows(2).position(|window| window == b"\n\n")?;
+    let event: Vec<u8> = buffer.drain(..end + 2).collect();
+    Some(String::from_utf8_lossy(&event).into_owned())
+}
+
+fn finish_block(block: PartialBlock) -> Result<Option<ContentBlock>> {
+    Ok(match block {
+        PartialBlock::Text(text) => Some(ContentBlock::Text { text }),
+        PartialBlock::ToolUse {
+            id,
+            name,
+            input_json,
+        } => {
+            let input = if input_json.trim().is_empty() {
+                Value::Object(Default::default())
+            } else {
+                serde_json::from_str(&input_json).context("Failed to parse tool input JSON")?
+            };
+            Some(ContentBlock::ToolUse { id, name, input })
+        }
+        PartialBlock::Unsupported => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_response_from_events() {
+        let events = [
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":12,"output_tokens":1}}}"#,
+            r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#,
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"lo"}}"#,
+            r#"data: {"type":"content_block_stop","index":0}"#,
+            r#"data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"tu_1","name":"readFile","input":{}}}"#,
+            r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\":"}}"#,
+            r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"a.rs\"}"}}"#,
+            r#"data: {"type":"content_block_stop","index":1}"#,
+            r#"data: {"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":30}}"#,
+            r#"data: {"type":"message_stop"}"#,
+        ];
+
+        let mut builder = ResponseBuilder::default();
+        let mut deltas = Vec::new();
+        for event in events {
+            deltas.extend(builder.handle_event(event).unwrap());
+        }
+        assert_eq!(deltas, ["Hel", "lo"]);
+
+        let response = builder.finish().unwrap();
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(response.usage.input_tokens, 12);
+        assert_eq!(response.usage.output_tokens, 30);
+        assert!(matches!(&response.content[0], ContentBlock::Text { text } if text == "Hello"));
+        assert!(
+            matches!(&response.content[1], ContentBlock::ToolUse { input, .. } if input["path"] == "a.rs")
+        );
+    }
+
+    #[test]
+    fn test_splits_events_without_breaking_characters() {
+        let stream = "data: {\"text\":\"日本語\"}\n\ndata: {}\n\n".as_bytes();
+        // 「日」の途中で受信が分かれる
+        let split = stream.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        let mut buffer = Vec::new();
+        let mut events = Vec::new();
+        for chunk in [&stream[..split], &stream[split..]] {
+            buffer.extend_from_slice(chunk);
+            while let Some(event) = next_event(&mut buffer) {
+                events.push(event);
+            }
+        }
+        assert_eq!(events, ["data: {\"text\":\"日本語\"}\n\n", "data: {}\n\n"]);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::relevance::{select, Relevance};
+use crate::tokens;
+use crate::tools::FileTracker;
+
+/// `--file` で指定されたファイルの内容をメッセージに埋め込む
+///
+/// 埋め込んだファイルは readFile 済みとして記録し、そのまま editFile できるようにする。
+/// 合計が `max_tokens` を超える場合はメッセージとの関連度が高いものだけを載せ、
+/// 省いたファイルはメッセージの末尾に書く
+pub fn attach_files(
+    message: &str,
+    files: &[PathBuf],
+    tracker: &FileTracker,
+    max_bytes: u64,
+    max_tokens: usize,
+) -> Result<String> {
+    if files.is_empty() {
+        return Ok(message.to_string());
+    }
+
+    let mut contents = Vec::new();
+    for path in files {
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read attached file {:?}", path))?;
+        if metadata.len() > max_bytes {
+            bail!(
+                "Attached file {:?} is {} bytes, larger than tools.readFile.max_bytes ({})",
+                path,
+                metadata.len(),
+                max_bytes
+            );
+        }
+
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read attached file {:?}", path))?;
+        let content = String::from_utf8(bytes)
+            .with_context(|| format!("Attached file {:?} is not valid UTF-8", path))?;
+        contents.push((content, metadata.modified().ok()));
+    }
+
+    let blocks: Vec<String> = files
+        .iter()
+        .zip(&contents)
+        .map(|(path, (content, _))| fenced(path, content))
+        .collect();
+    let costs: Vec<usize> = blocks.iter().map(|block| tokens::estimate(block)).collect();
+    let selected = if costs.iter().sum::<usize>() <= max_tokens {
+        (0..files.len()).collect()
+    } else {
+        let relevance = Relevance::new(message);
+        let scores: Vec<f64> = files
+            .iter()
+            .zip(&contents)
+            .map(|(path, (content, modified))| relevance.score(path, content, *modified))
+            .collect();
+        select(&scores, &costs, max_tokens)
+    };
+
+    let mut attached = String::from(message);
+    attached.push_str("\n\n## Attached files\n");
+    for &i in &selected {
+        tracing::info!(
+            "Attaching {:?} (~{} tokens)",
+            files[i],
+            tokens::format(costs[i])
+        );
+        tracker.record(&files[i], contents[i].0.as_bytes());
+        attached.push('\n');
+        attached.push_str(&blocks[i]);
+    }
+
+    let omitted: Vec<String> = (0..files.len())
+        .filter(|i| !selected.contains(i))
+        .map(|i| files[i].display().to_string())
+        .collect();
+    if !omitted.is_empty() {
+        tracing::warn!(
+            "Attached files exceed agent.attachments_max_tokens ({}); omitted the least relevant: {}",
+            max_tokens,
+            omitted.join(", ")
+        );
+        attached.push_str(&format!(
+            "\n(Omitted to stay within {} tokens; read them with readFile if needed: {})\n",
+            max_tokens,
+            omitted.join(", ")
+        ));
+    }
+    Ok(attached)
+}
+
+/// パイプされた標準入力の内容をメッセージに添付する（空なら何もしない）
+pub fn attach_stdin(message: &str, content: &str) -> String {
+    if content.trim().is_empty() {
+        return message.to_string();
+    }
+    format!(
+        "{}\n\n## Context from stdin\n\n{}",
+        message,
+        code_block("", content)
+    )
+}
+
+/// エディタから渡された内容（`name` はパスまたは URI）をメッセージに添付する
+pub fn attach_resource(message: &str, name: &str, content: &str) -> String {
+    format!("{}\n\n{}", message, fenced(Path::new(name), content))
+}
+
+/// パス付きのコードブロックに整形する
+fn fenced(path: &Path, content: &str) -> String {
+    let lang = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    format!("`{}`:\n{}", path.display(), code_block(lang, content))
+}
+
+/// コードブロックに整形する（内容に含まれるより長いフェンスを使う）
+fn code_block(lang: &str, content: &str) -> String {
+    let longest = content
+        .lines()
+        .map(|line| line.trim_start().chars().take_while(|&c| c == '`').count())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+
+    let mut block = format!("{}{}\n{}", fence, lang, content);
+    if !content.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(&fence);
+    block.push('\n');
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempWorkspace;
+    use crate::tools::file_tracker::Freshness;
+
+    #[test]
+    fn test_omits_the_least_relevant_files_over_the_budget() {
+        let workspace = TempWorkspace::new()
+            .file("parser.rs", "fn parse_header() {}\n".repeat(5))
+            .file("notes.md", "x ".repeat(200))
+            .file("lexer.rs", "fn next_token() {}\n");
+        let files = ["notes.md", "parser.rs", "lexer.rs"].map(|name| workspace.path(name));
+        let tracker = FileTracker::new();
+
+        let message = attach_files("Fix parse_header", &files, &tracker, 1024, 150).unwrap();
+        assert!(message.contains("parser.rs`:"));
+        assert!(message.contains("lexer.rs`:"));
+        assert!(!message.contains("notes.md`:"));
+        assert!(message.contains(&format!(
+            "read them with readFile if needed: {})",
+            files[0].display()
+        )));
+        assert_eq!(tracker.check(&files[0]), Freshness::NotRead);
+        assert_eq!(tracker.check(&files[1]), Freshness::Fresh);
+
+        let message = attach_files("Fix parse_header", &files, &tracker, 1024, 1000).unwrap();
+        assert!(message.contains("notes.md`:"));
+    }
+
+    #[test]
+    fn test_fence_is_longer_than_content_fences() {
+        let block = fenced(Path::new("README.md"), "```rust\nfn main() {}\n```");
+        assert_eq!(
+            block,
+            "`README.md`:\n````md\n```rust\nfn main() {}\n```\n````\n"
+        );
+    }
+}
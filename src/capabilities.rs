@@ -0,0 +1,41 @@
+//! モデルごとに使える機能（会話の途中でモデルを切り替えるときの確認用）
+//!
+//! このクライアントは extended thinking を有効にせず、画像のブロックも送らない
+//! （`ContentBlock` はテキストとツールだけ）ので、確かめる機能はツールだけ
+
+use crate::anthropic::{ContentBlock, Message, MessageContent};
+
+/// ツールを使えないモデル（日付付きのモデル ID にも一致するよう前方一致で比較する）
+const WITHOUT_TOOLS: &[&str] = &["claude-2", "claude-instant"];
+
+/// ツールの呼び出しに対応しているか（一覧にないモデルは対応しているものとする）
+pub fn supports_tools(model: &str) -> bool {
+    !WITHOUT_TOOLS.iter().any(|prefix| model.starts_with(prefix))
+}
+
+/// 会話にツールの呼び出しか結果が含まれるか
+pub fn uses_tools(conversation: &[Message]) -> bool {
+    conversation.iter().any(|message| match &message.content {
+        MessageContent::Text(_) => false,
+        MessageContent::Blocks(blocks) => blocks.iter().any(|block| {
+            matches!(
+                block,
+                ContentBlock::ToolUse { .. } | ContentBlock::ToolResult { .. }
+            )
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports_tools() {
+        assert!(supports_tools("claude-haiku-4-5"));
+        assert!(supports_tools("claude-3-5-sonnet-20241022"));
+        assert!(!supports_tools("claude-2.1"));
+        assert!(!supports_tools("claude-instant-1.2"));
+        assert!(!uses_tools(&[Message::user_text("hi")]));
+    }
+}
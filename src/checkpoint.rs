@@ -0,0 +1,188 @@
+//! ファイルを変更した反復ごとに作業ツリーを記録するチェックポイント（`agent.checkpoints` / `--checkpoints`）
+//!
+//! 専用の ref（`refs/agent/checkpoints/<実行>`）にコミットを積むだけで、HEAD・インデックス・
+//! ブランチには触れない。長い実行の途中の状態を `git log -p` や `git diff` で確認し、
+//! `git checkout <チェックポイント> -- <パス>` で戻せるようにする
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+/// 1 回の実行のチェックポイントを積む ref
+pub struct Checkpoints {
+    /// 作業ツリーのルート
+    repo_root: PathBuf,
+    /// 利用者のインデックスを汚さないための一時的なインデックス（`.git` の中）
+    index: PathBuf,
+    reference: String,
+    state: Mutex<State>,
+}
+
+/// 最後に記録したコミットとツリー
+struct State {
+    /// 最初は HEAD（コミットがなければ None）
+    parent: Option<String>,
+    tree: Option<String>,
+    count: usize,
+}
+
+impl Checkpoints {
+    /// `workspace` を含むリポジトリに今回の実行の ref を用意する
+    pub fn start(workspace: &Path) -> Result<Self> {
+        let repo_root = PathBuf::from(
+            git(workspace, &["rev-parse", "--show-toplevel"])
+                .context("Checkpoints need a git repository")?,
+        );
+        let git_dir = PathBuf::from(git(
+            &repo_root,
+            &["rev-parse", "--path-format=absolute", "--git-dir"],
+        )?);
+        // コミットのないリポジトリでは最初のチェックポイントが親のないコミットになる
+        let parent = git(&repo_root, &["rev-parse", "--verify", "--quiet", "HEAD"]).ok();
+        let tree = match &parent {
+            Some(parent) => Some(git(
+                &repo_root,
+                &["rev-parse", &format!("{}^{{tree}}", parent)],
+            )?),
+            None => None,
+        };
+        let name = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        Ok(Self {
+            index: git_dir.join(format!("agent-checkpoint-{}.index", name)),
+            reference: format!("refs/agent/checkpoints/{}", name),
+            repo_root,
+            state: Mutex::new(State {
+                parent,
+                tree,
+                count: 0,
+            }),
+        })
+    }
+
+    /// チェックポイントを積む ref（例: "refs/agent/checkpoints/20250101-120000"）
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    /// 作業ツリー（.gitignore の対象を除く）をコミットする
+    ///
+    /// 前のチェックポイントから変わっていなければ何もしない。作ったコミットを返す
+    pub async fn save(&self, message: &str) -> Result<Option<String>> {
+        let mut state = self.state.lock().await;
+        let result = self.commit(&state, message).await;
+        let _ = tokio::fs::remove_file(&self.index).await;
+        let Some((commit, tree)) = result? else {
+            return Ok(None);
+        };
+        state.parent = Some(commit.clone());
+        state.tree = Some(tree);
+        state.count += 1;
+        Ok(Some(commit))
+    }
+
+    /// 何番目のチェックポイントか（次に作るものの番号）
+    pub async fn next_number(&self) -> usize {
+        self.state.lock().await.count + 1
+    }
+
+    async fn commit(&self, state: &State, message: &str) -> Result<Option<(String, String)>> {
+        match &state.parent {
+            Some(parent) => self.git(&["read-tree", parent]).await?,
+            None => self.git(&["read-tree", "--empty"]).await?,
+        };
+        self.git(&["add", "--all"]).await?;
+        let tree = self.git(&["write-tree"]).await?;
+        if state.tree.as_ref() == Some(&tree) {
+            return Ok(None);
+        }
+
+        let mut args = vec!["commit-tree", &tree, "-m", message];
+        if let Some(parent) = &state.parent {
+            args.extend(["-p", parent]);
+        }
+        let commit = self.git(&args).await?;
+        self.git(&["update-ref", &self.reference, &commit]).await?;
+        Ok(Some((commit, tree)))
+    }
+
+    /// 一時的なインデックスを使って git を実行する
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        let output = tokio::process::Command::new("git")
+            .args(args)
+            .current_dir(&self.repo_root)
+            .env("GIT_INDEX_FILE", &self.index)
+            .output()
+            .await
+            .context("Failed to run git")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("git {} failed: {}", args.join(" "), stderr.trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// git を実行して標準出力（前後の空白を除く）を返す
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempWorkspace;
+
+    #[tokio::test]
+    async fn test_saves_changes_without_touching_head_or_index() {
+        let workspace = TempWorkspace::new()
+            .file("a.txt", "one\n")
+            .file(".gitignore", "target/\n");
+        let root = workspace.root();
+        git(root, &["init", "--quiet"]).unwrap();
+        git(root, &["config", "user.name", "Test"]).unwrap();
+        git(root, &["config", "user.email", "test@example.com"]).unwrap();
+        git(root, &["add", "--all"]).unwrap();
+        git(root, &["commit", "--quiet", "-m", "initial"]).unwrap();
+        let head = git(root, &["rev-parse", "HEAD"]).unwrap();
+
+        let checkpoints = Checkpoints::start(root).unwrap();
+        assert_eq!(checkpoints.save("unchanged").await.unwrap(), None);
+
+        std::fs::write(workspace.path("a.txt"), "two\n").unwrap();
+        std::fs::write(workspace.path("b.txt"), "new\n").unwrap();
+        std::fs::create_dir(workspace.path("target")).unwrap();
+        std::fs::write(workspace.path("target/out"), "ignored\n").unwrap();
+        let first = checkpoints.save("first").await.unwrap().unwrap();
+        assert_eq!(checkpoints.save("again").await.unwrap(), None);
+        assert_eq!(checkpoints.next_number().await, 2);
+
+        let reference = checkpoints.reference();
+        assert_eq!(git(root, &["rev-parse", reference]).unwrap(), first);
+        assert_eq!(
+            git(root, &["rev-parse", &format!("{}^", first)]).unwrap(),
+            head
+        );
+        assert_eq!(
+            git(root, &["show", &format!("{}:b.txt", reference)]).unwrap(),
+            "new"
+        );
+        assert!(git(root, &["show", &format!("{}:target/out", reference)]).is_err());
+
+        // HEAD とインデックスはそのまま
+        assert_eq!(git(root, &["rev-parse", "HEAD"]).unwrap(), head);
+        assert_eq!(
+            git(root, &["status", "--porcelain"]).unwrap(),
+            "M a.txt\n?? b.txt"
+        );
+    }
+}
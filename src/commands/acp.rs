@@ -0,0 +1,652 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+
+use crate::agent::{Agent, AgentArgs};
+use crate::anthropic::{progress_text, AgentEvent, Message};
+use crate::attachments::attach_resource;
+use crate::config::{ColorChoice, Mode};
+use crate::error::AgentError;
+use crate::tools::FileTracker;
+use crate::ui::confirm::{Answer, ConfirmRequest, PromptHandler};
+use crate::ui::style;
+
+/// Speak the Agent Client Protocol (JSON-RPC over stdin/stdout) for editor integrations
+#[derive(clap::Args, Debug)]
+pub struct AcpArgs {
+    #[command(flatten)]
+    pub agent: AgentArgs,
+}
+
+/// 対応する ACP のバージョン
+const PROTOCOL_VERSION: u64 = 1;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// session/request_permission で提示する選択肢（optionId と確認への回答の対応）
+const PERMISSION_OPTIONS: [(&str, &str, Answer); 4] = [
+    ("allow_once", "Allow", Answer::Yes),
+    ("allow_always", "Always allow this tool", Answer::Always),
+    ("reject_once", "Reject", Answer::No),
+    ("reject_always", "Always reject this tool", Answer::DenyAll),
+];
+
+/// JSON-RPC のエラー応答
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+
+    fn internal(error: anyhow::Error) -> Self {
+        Self {
+            code: INTERNAL_ERROR,
+            message: format!("{:#}", error),
+        }
+    }
+}
+
+/// クライアント（エディタ）へのメッセージの送信
+#[derive(Clone)]
+struct Client {
+    outgoing: mpsc::UnboundedSender<Value>,
+    /// 応答を待っている要求（id ごと）
+    pending: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Client {
+    fn notify(&self, method: &str, params: Value) {
+        let _ = self
+            .outgoing
+            .send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn respond(&self, id: Value, result: Result<Value, RpcError>) {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": error.code, "message": error.message },
+            }),
+        };
+        let _ = self.outgoing.send(message);
+    }
+
+    /// クライアントへ要求を送り、応答の result を待つ
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+
+        let response = rx.await.context("The client closed the connection")?;
+        if let Some(error) = response.get("error") {
+            bail!("{} failed: {}", method, error);
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// クライアントからの応答を待っている要求へ渡す
+    fn handle_response(&self, message: Value) {
+        let Some(id) = message["id"].as_u64() else {
+            return;
+        };
+        if let Some(tx) = self.pending.lock().unwrap().remove(&id) {
+            let _ = tx.send(message);
+        }
+    }
+}
+
+/// ACP のセッション（1 つの会話）
+struct Session {
+    id: String,
+    workspace: PathBuf,
+    agent: Mutex<Arc<Agent>>,
+    conversation: Mutex<Vec<Message>>,
+    /// 実行中のプロンプトと、その session/prompt 要求の id（session/cancel で中断する）
+    running: Mutex<Option<(AbortHandle, Value)>>,
+    /// ツール名ごとの直近のツール呼び出しの id（確認をツール呼び出しに結び付ける）
+    tool_calls: Arc<Mutex<HashMap<String, String>>>,
+}
+
+/// ACP サーバーの状態
+struct Acp {
+    args: AgentArgs,
+    api_key: String,
+    client: Client,
+    sessions: HashMap<String, Arc<Session>>,
+    next_session: usize,
+}
+
+/// `acp`: 標準入出力で Agent Client Protocol を話す（エディタへの組み込み用）
+///
+/// 標準出力は JSON-RPC のメッセージ専用で、ログは標準エラーへ出す
+pub async fn run(args: AcpArgs) -> Result<()> {
+    let api_key = args.agent.api_key()?;
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = outgoing_rx.recv().await {
+            let line = format!("{}\n", message);
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut acp = Acp {
+        args: args.agent,
+        api_key,
+        client: Client {
+            outgoing,
+            pending: Arc::default(),
+            next_id: Arc::new(AtomicU64::new(1)),
+        },
+        sessions: HashMap::new(),
+        next_session: 1,
+    };
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .context("Failed to read from stdin")?
+    {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(&line) {
+            Ok(message) => acp.dispatch(message),
+            Err(e) => tracing::warn!("Ignoring a message that is not JSON: {}", e),
+        }
+    }
+
+    // 入力が閉じたら実行中のプロンプトを止めて終了する
+    for session in acp.sessions.values() {
+        if let Some((handle, _)) = session.running.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+    drop(acp);
+    let _ = writer.await;
+    Ok(())
+}
+
+impl Acp {
+    fn dispatch(&mut self, message: Value) {
+        let Some(method) = message["method"].as_str().map(str::to_string) else {
+            // method のないメッセージはこちらの要求への応答
+            self.client.handle_response(message);
+            return;
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let Some(id) = message.get("id").cloned() else {
+            self.handle_notification(&method, &params);
+            return;
+        };
+
+        let result = match method.as_str() {
+            "initialize" => Ok(initialize_result()),
+            "authenticate" => Ok(json!({})),
+            "session/new" => self.new_session(&params),
+            "session/set_mode" => self.set_mode(&params),
+            "session/prompt" => {
+                // 応答は実行が終わってから返す
+                if let Err(error) = self.prompt(id.clone(), &params) {
+                    self.client.respond(id, Err(error));
+                }
+                return;
+            }
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not found: {}", method),
+            }),
+        };
+        self.client.respond(id, result);
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) {
+        match method {
+            "session/cancel" => {
+                if let Ok(session) = self.session(params) {
+                    cancel(&self.client, &session);
+                }
+            }
+            _ => tracing::debug!("Ignoring notification {}", method),
+        }
+    }
+
+    fn session(&self, params: &Value) -> Result<Arc<Session>, RpcError> {
+        let id = params["sessionId"]
+            .as_str()
+            .ok_or_else(|| RpcError::invalid_params("sessionId is required"))?;
+        self.sessions
+            .get(id)
+            .cloned()
+            .ok_or_else(|| RpcError::invalid_params(format!("Unknown session: {}", id)))
+    }
+
+    fn new_session(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let workspace = match params["cwd"].as_str() {
+            Some(cwd) => PathBuf::from(cwd),
+            None => std::env::current_dir().map_err(|e| RpcError::internal(e.into()))?,
+        };
+        // ツールの相対パスはプロセスの作業ディレクトリを基準に解決される
+        std::env::set_current_dir(&workspace)
+            .with_context(|| format!("Failed to change directory to {:?}", workspace))
+            .map_err(RpcError::internal)?;
+
+        let id = format!("session-{}", self.next_session);
+        self.next_session += 1;
+        let mut args = self.args.clone();
+        let mode = args.mode.unwrap_or_else(|| {
+            args.load_config(&workspace)
+                .map(|c| c.agent.mode)
+                .unwrap_or_default()
+        });
+        args.mode = Some(mode);
+        let tool_calls = Arc::default();
+        let agent = self
+            .build_agent(&args, &id, &workspace, &tool_calls)
+            .map_err(RpcError::internal)?;
+        tracing::info!("Started {} in {:?}", id, workspace);
+
+        self.sessions.insert(
+            id.clone(),
+            Arc::new(Session {
+                id: id.clone(),
+                workspace,
+                agent: Mutex::new(Arc::new(agent)),
+                conversation: Mutex::new(Vec::new()),
+                running: Mutex::new(None),
+                tool_calls,
+            }),
+        );
+        Ok(json!({ "sessionId": id, "modes": modes_json(mode) }))
+    }
+
+    /// セッションのモードを切り替える（会話は引き継ぐ）
+    fn set_mode(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let session = self.session(params)?;
+        let mode_id = params["modeId"].as_str().unwrap_or_default();
+        let mode = <Mode as clap::ValueEnum>::from_str(mode_id, true)
+            .map_err(|_| RpcError::invalid_params(format!("Unknown mode: {}", mode_id)))?;
+        let mut args = self.args.clone();
+        args.mode = Some(mode);
+        let agent = self
+            .build_agent(&args, &session.id, &session.workspace, &session.tool_calls)
+            .map_err(RpcError::internal)?;
+        *session.agent.lock().unwrap() = Arc::new(agent);
+        self.client.notify(
+            "session/update",
+            json!({
+                "sessionId": session.id,
+                "update": { "sessionUpdate": "current_mode_update", "currentModeId": mode_id },
+            }),
+        );
+        Ok(json!({}))
+    }
+
+    /// 確認とイベントをクライアントへ中継するエージェントを作る
+    fn build_agent(
+        &self,
+        args: &AgentArgs,
+        session_id: &str,
+        workspace: &Path,
+        tool_calls: &Arc<Mutex<HashMap<String, String>>>,
+    ) -> Result<Agent> {
+        let config = args.load_config(workspace)?;
+
+        let client = self.client.clone();
+        let id = session_id.to_string();
+        let calls = Arc::clone(tool_calls);
+        let prompt_handler: PromptHandler = Arc::new(move |request| {
+            let (reply, answer) = oneshot::channel();
+            let tool_call_id = calls.lock().unwrap().get(&request.tool).cloned();
+            let client = client.clone();
+            let params = permission_params(&id, tool_call_id.as_deref(), &request);
+            tokio::spawn(async move {
+                let answer = match client.request("session/request_permission", params).await {
+                    Ok(result) => permission_answer(&result),
+                    Err(e) => {
+                        tracing::warn!("Permission request failed; rejecting: {:#}", e);
+                        Answer::No
+                    }
+                };
+                let _ = reply.send(answer);
+            });
+            answer
+        });
+
+        let mut agent = Agent::new(
+            args,
+            config,
+            self.api_key.clone(),
+            workspace,
+            FileTracker::with_search_index(workspace),
+            Some(prompt_handler),
+        )?;
+        // 差分などはエディタが表示するので ANSI エスケープを含めない
+        style::set_color_choice(ColorChoice::Never);
+
+        let client = self.client.clone();
+        let id = session_id.to_string();
+        let calls = Arc::clone(tool_calls);
+        let workspace = workspace.to_path_buf();
+        agent.set_event_handler(Arc::new(move |event| {
+            if let AgentEvent::ToolCall { id, name, .. } = event {
+                calls.lock().unwrap().insert(name.clone(), id.clone());
+            }
+            if let Some(update) = session_update(event, &workspace) {
+                client.notify(
+                    "session/update",
+                    json!({ "sessionId": id, "update": update }),
+                );
+            }
+        }));
+        Ok(agent)
+    }
+
+    /// プロンプトをバックグラウンドで実行し、終わったら session/prompt に応答する
+    fn prompt(&mut self, request_id: Value, params: &Value) -> Result<(), RpcError> {
+        let session = self.session(params)?;
+        let message = prompt_text(&params["prompt"])?;
+        let mut running = session.running.lock().unwrap();
+        if running.is_some() {
+            return Err(RpcError::invalid_params(
+                "A prompt is already running in this session",
+            ));
+        }
+
+        let messages = {
+            let mut conversation = session.conversation.lock().unwrap();
+            conversation.push(Message::user_text(message));
+            conversation.clone()
+        };
+        let agent = Arc::clone(&session.agent.lock().unwrap());
+        let client = self.client.clone();
+        let task_session = Arc::clone(&session);
+        let task = tokio::spawn(async move {
+            let result = agent.send(messages).await;
+            let session = task_session;
+            // 先に session/cancel で応答済みなら何もしない
+            let Some((_, request_id)) = session.running.lock().unwrap().take() else {
+                return;
+            };
+
+            let mut conversation = session.conversation.lock().unwrap();
+            let response = match result {
+                Ok(result) => {
+                    let stop_reason =
+                        match (result.timed_out, result.response.stop_reason.as_deref()) {
+                            (Some(_), _) => "max_turn_requests",
+                            (None, Some("max_tokens")) => "max_tokens",
+                            (None, Some("refusal")) => "refusal",
+                            _ => "end_turn",
+                        };
+                    *conversation = result.conversation;
+                    Ok(json!({ "stopReason": stop_reason }))
+                }
+                Err(e) => {
+                    // 失敗したメッセージは履歴に残さない
+                    conversation.pop();
+                    match e.downcast_ref::<AgentError>() {
+                        Some(AgentError::MaxIterations(_)) => {
+                            Ok(json!({ "stopReason": "max_turn_requests" }))
+                        }
+                        Some(AgentError::Cancelled) => Ok(json!({ "stopReason": "cancelled" })),
+                        _ => Err(RpcError::internal(e)),
+                    }
+                }
+            };
+            client.respond(request_id, response);
+        });
+        *running = Some((task.abort_handle(), request_id));
+        Ok(())
+    }
+}
+
+/// 実行中のプロンプトを中断し、session/prompt に cancelled で応答する
+fn cancel(client: &Client, session: &Session) {
+    let Some((handle, request_id)) = session.running.lock().unwrap().take() else {
+        return;
+    };
+    handle.abort();
+    session.conversation.lock().unwrap().pop();
+    tracing::info!("Cancelled the prompt in {}", session.id);
+    client.respond(request_id, Ok(json!({ "stopReason": "cancelled" })));
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "agentCapabilities": {
+            "loadSession": false,
+            "promptCapabilities": { "image": false, "audio": false, "embeddedContext": true },
+        },
+        "authMethods": [],
+        "agentInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn modes_json(current: Mode) -> Value {
+    let modes = [
+        (Mode::Code, "Code", "All tools"),
+        (
+            Mode::Plan,
+            "Plan",
+            "Read-only tools; ends in an implementation plan",
+        ),
+        (
+            Mode::Review,
+            "Review",
+            "Read-only tools; reviews the uncommitted changes",
+        ),
+        (Mode::Ask, "Ask", "No tools; answers questions"),
+    ];
+    let available: Vec<Value> = modes
+        .iter()
+        .map(|(mode, name, description)| {
+            json!({ "id": mode_id(*mode), "name": name, "description": description })
+        })
+        .collect();
+    json!({ "currentModeId": mode_id(current), "availableModes": available })
+}
+
+fn mode_id(mode: Mode) -> String {
+    clap::ValueEnum::to_possible_value(&mode)
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// プロンプトの内容ブロックを 1 つのメッセージにまとめる（埋め込まれたファイルは添付する）
+fn prompt_text(prompt: &Value) -> Result<String, RpcError> {
+    let blocks = prompt
+        .as_array()
+        .ok_or_else(|| RpcError::invalid_params("prompt must be an array of content blocks"))?;
+    let mut message = String::new();
+    let mut resources = Vec::new();
+    for block in blocks {
+        match block["type"].as_str() {
+            Some("text") => {
+                if !message.is_empty() {
+                    message.push('\n');
+                }
+                message.push_str(block["text"].as_str().unwrap_or_default());
+            }
+            Some("resource_link") => {
+                let uri = block["uri"].as_str().unwrap_or_default();
+                message.push_str(&format!(" {} ", display_uri(uri)));
+            }
+            Some("resource") => {
+                let resource = &block["resource"];
+                if let Some(text) = resource["text"].as_str() {
+                    let uri = resource["uri"].as_str().unwrap_or_default();
+                    resources.push((display_uri(uri).to_string(), text.to_string()));
+                }
+            }
+            other => tracing::debug!("Ignoring prompt content of type {:?}", other),
+        }
+    }
+    for (name, text) in resources {
+        message = attach_resource(&message, &name, &text);
+    }
+    if message.trim().is_empty() {
+        return Err(RpcError::invalid_params("prompt has no text"));
+    }
+    Ok(message)
+}
+
+fn display_uri(uri: &str) -> &str {
+    uri.strip_prefix("file://").unwrap_or(uri)
+}
+
+/// エージェントのイベントを session/update の内容に変換する
+fn session_update(event: &AgentEvent, workspace: &Path) -> Option<Value> {
+    match event {
+        AgentEvent::TextDelta { text } => Some(json!({
+            "sessionUpdate": "agent_message_chunk",
+            "content": { "type": "text", "text": text },
+        })),
+        AgentEvent::ToolCall { id, name, input } => {
+            let path = input["path"].as_str();
+            let locations: Vec<Value> = path
+                .map(|path| json!({ "path": workspace.join(path) }))
+                .into_iter()
+                .collect();
+            Some(json!({
+                "sessionUpdate": "tool_call",
+                "toolCallId": id,
+                "title": match path {
+                    Some(path) => format!("{} {}", name, path),
+                    None => name.clone(),
+                },
+                "kind": tool_kind(name),
+                "status": "in_progress",
+                "rawInput": input,
+                "locations": locations,
+            }))
+        }
+        AgentEvent::ToolProgress {
+            id,
+            percent,
+            message,
+            ..
+        } => Some(json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": id,
+            "status": "in_progress",
+            "content": [{ "type": "content", "content": { "type": "text", "text": progress_text(*percent, message) } }],
+        })),
+        AgentEvent::ToolResult {
+            id,
+            is_error,
+            content,
+            ..
+        } => Some(json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": id,
+            "status": if *is_error { "failed" } else { "completed" },
+            "content": [{ "type": "content", "content": { "type": "text", "text": content } }],
+        })),
+        AgentEvent::IterationStart { .. } | AgentEvent::Usage { .. } => None,
+    }
+}
+
+fn tool_kind(name: &str) -> &'static str {
+    match name {
+        "readFile" => "read",
+        "listFiles" | "searchInDirectory" | "searchInFile" => "search",
+        "writeFile" | "editFile" => "edit",
+        _ => "other",
+    }
+}
+
+fn permission_params(
+    session_id: &str,
+    tool_call_id: Option<&str>,
+    request: &ConfirmRequest,
+) -> Value {
+    let options: Vec<Value> = PERMISSION_OPTIONS
+        .iter()
+        .map(|(id, name, _)| json!({ "optionId": id, "name": name, "kind": id }))
+        .collect();
+    json!({
+        "sessionId": session_id,
+        "toolCall": {
+            "toolCallId": tool_call_id.unwrap_or_default(),
+            "title": request.message,
+            "content": [{
+                "type": "content",
+                "content": { "type": "text", "text": format!("```diff\n{}```", request.preview) },
+            }],
+        },
+        "options": options,
+    })
+}
+
+/// session/request_permission の結果を確認への回答にする（取り消しは拒否）
+fn permission_answer(result: &Value) -> Answer {
+    let outcome = &result["outcome"];
+    if outcome["outcome"].as_str() != Some("selected") {
+        return Answer::No;
+    }
+    let selected = outcome["optionId"].as_str();
+    PERMISSION_OPTIONS
+        .iter()
+        .find(|(id, _, _)| Some(*id) == selected)
+        .map_or(Answer::No, |(_, _, answer)| *answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_and_permissions_map_to_acp() {
+        let update = session_update(
+            &AgentEvent::ToolCall {
+                id: "tu_1".to_string(),
+                name: "editFile".to_string(),
+                input: json!({ "path": "src/main.rs" }),
+            },
+            Path::new("/work"),
+        )
+        .unwrap();
+        assert_eq!(update["kind"], "edit");
+        assert_eq!(update["locations"][0]["path"], "/work/src/main.rs");
+
+        let selected = json!({ "outcome": { "outcome": "selected", "optionId": "allow_always" } });
+        assert_eq!(permission_answer(&selected), Answer::Always);
+        let cancelled = json!({ "outcome": { "outcome": "cancelled" } });
+        assert_eq!(permission_answer(&cancelled), Answer::No);
+
+        let prompt = json!([
+            { "type": "text", "text": "Explain" },
+            { "type": "resource", "resource": { "uri": "file:///work/a.rs", "text": "fn a() {}" } },
+        ]);
+        let message = prompt_text(&prompt).ok().unwrap();
+        assert!(message.starts_with("Explain\n\n`/work/a.rs`:\n```rs\nfn a() {}"));
+    }
+}
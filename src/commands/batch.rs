@@ -0,0 +1,231 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::agent::{Agent, AgentArgs};
+use crate::anthropic::{Message, Usage};
+use crate::config::{Config, Verbosity};
+use crate::error::AgentError;
+use crate::output;
+use crate::session::Session;
+use crate::tools::FileTracker;
+
+/// Run each prompt in a file as its own conversation
+#[derive(clap::Args, Debug)]
+pub struct BatchArgs {
+    /// Tasks file: one prompt per line (blank lines and `#` comments are skipped),
+    /// or a YAML list of prompts (.yaml / .yml)
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Directory for per-task transcripts and summary.json [default: batch-<timestamp>]
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub agent: AgentArgs,
+}
+
+/// タスク 1 件の結果（summary.json の要素）
+#[derive(Debug, Serialize)]
+struct TaskReport {
+    index: usize,
+    prompt: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    iterations: usize,
+    usage: Usage,
+    cost_usd: Option<f64>,
+    duration_ms: u64,
+    transcript: PathBuf,
+}
+
+/// `batch`: タスクを順番に実行し、タスクごとの会話記録と集計を書き出す
+pub async fn run(args: BatchArgs, config: Config, workspace: &Path) -> Result<()> {
+    let tasks = load_tasks(&args.file)?;
+    if tasks.is_empty() {
+        bail!("No tasks found in {:?}", args.file);
+    }
+    let api_key = args.agent.api_key()?;
+    let out_dir = args.out_dir.unwrap_or_else(|| {
+        PathBuf::from(format!("batch-{}", Local::now().format("%Y%m%d-%H%M%S")))
+    });
+    std::fs::create_dir_all(&out_dir)
+        .with_context(|| format!("Failed to create output directory {:?}", out_dir))?;
+
+    let mut reports = Vec::new();
+    for (i, prompt) in tasks.iter().enumerate() {
+        let index = i + 1;
+        eprintln!("[{}/{}] {}", index, tasks.len(), first_line(prompt));
+
+        // タスクごとに会話とファイルの読み取り記録を分ける
+        let agent = Agent::new(
+            &args.agent,
+            config.clone(),
+            api_key.clone(),
+            workspace,
+            FileTracker::new(),
+            None,
+        )?;
+        let started = Instant::now();
+        let result = agent.send(vec![Message::user_text(prompt)]).await;
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        let mut session = Session::new(workspace, &agent.model);
+        let transcript = out_dir.join(format!("task-{:03}.json", index));
+        // Ctrl-C で中断した場合は残りのタスクも実行しない
+        let cancelled = matches!(
+            &result,
+            Err(e) if matches!(e.downcast_ref::<AgentError>(), Some(AgentError::Cancelled))
+        );
+        let report = match result {
+            Ok(result) => {
+                let cost_usd = result.cost_usd();
+                session.messages = result.conversation.clone();
+                let error = result
+                    .timed_out
+                    .map(|limit| format!("Stopped early: {}", limit));
+                eprintln!(
+                    "  {} ({} iterations, {})",
+                    if error.is_none() { "ok" } else { "stopped" },
+                    result.iterations,
+                    format_cost(cost_usd)
+                );
+                if agent.output.verbosity != Verbosity::Quiet {
+                    eprintln!("  {}", first_line(&output::final_text(&result)));
+                }
+                TaskReport {
+                    index,
+                    prompt: prompt.clone(),
+                    success: error.is_none(),
+                    error,
+                    iterations: result.iterations,
+                    usage: result.usage,
+                    cost_usd,
+                    duration_ms,
+                    transcript: transcript.clone(),
+                }
+            }
+            Err(e) => {
+                eprintln!("  failed: {:#}", e);
+                session.messages = vec![Message::user_text(prompt)];
+                TaskReport {
+                    index,
+                    prompt: prompt.clone(),
+                    success: false,
+                    error: Some(format!("{:#}", e)),
+                    iterations: 0,
+                    usage: Usage::default(),
+                    cost_usd: None,
+                    duration_ms,
+                    transcript: transcript.clone(),
+                }
+            }
+        };
+
+        let content =
+            serde_json::to_string_pretty(&session).context("Failed to serialize transcript")?;
+        std::fs::write(&transcript, content)
+            .with_context(|| format!("Failed to write transcript {:?}", transcript))?;
+        reports.push(report);
+        if cancelled {
+            eprintln!("Batch cancelled; skipping the remaining tasks");
+            break;
+        }
+    }
+
+    // 集計の書き出しと表示
+    let summary = out_dir.join("summary.json");
+    let content = serde_json::to_string_pretty(&reports).context("Failed to serialize summary")?;
+    std::fs::write(&summary, content)
+        .with_context(|| format!("Failed to write summary {:?}", summary))?;
+    print_summary(&reports);
+    println!("Transcripts and summary written to {}", out_dir.display());
+
+    let failed = reports.iter().filter(|report| !report.success).count();
+    if failed > 0 {
+        bail!("{} of {} tasks failed", failed, reports.len());
+    }
+    Ok(())
+}
+
+/// タスクファイルを読み込む（.yaml / .yml は文字列のリスト、それ以外は 1 行 1 タスク）
+fn load_tasks(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read tasks file {:?}", path))?;
+    let is_yaml = path
+        .extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml");
+    if is_yaml {
+        let tasks: Vec<String> = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse {:?} as a YAML list of prompts", path))?;
+        return Ok(tasks
+            .into_iter()
+            .filter(|task| !task.trim().is_empty())
+            .collect());
+    }
+    Ok(parse_lines(&content))
+}
+
+fn parse_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn print_summary(reports: &[TaskReport]) {
+    println!(
+        "{:>4}  {:<7} {:>5} {:>9} {:>9}  Prompt",
+        "#", "Status", "Iter", "Time", "Cost"
+    );
+    for report in reports {
+        println!(
+            "{:>4}  {:<7} {:>5} {:>8.1}s {:>9}  {}",
+            report.index,
+            if report.success { "ok" } else { "failed" },
+            report.iterations,
+            report.duration_ms as f64 / 1000.0,
+            format_cost(report.cost_usd),
+            first_line(&report.prompt)
+        );
+    }
+    let succeeded = reports.iter().filter(|report| report.success).count();
+    let cost: f64 = reports.iter().filter_map(|report| report.cost_usd).sum();
+    println!(
+        "{} succeeded, {} failed, estimated cost ${:.4}",
+        succeeded,
+        reports.len() - succeeded,
+        cost
+    );
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    cost.map_or_else(|| "-".to_string(), |cost| format!("${:.4}", cost))
+}
+
+/// 表示用に先頭行を 60 文字までに切り詰める
+fn first_line(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("");
+    if line.chars().count() > 60 {
+        format!("{}…", line.chars().take(60).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines_skips_blanks_and_comments() {
+        let tasks = parse_lines("# tasks\nFix the typo\n\n  Add tests  \n");
+        assert_eq!(tasks, ["Fix the typo", "Add tests"]);
+    }
+}
@@ -0,0 +1,479 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::agent::{Agent, AgentArgs, ConfigWatcher};
+use crate::anthropic::{ContentBlock, Message, MessageContent, Usage};
+use crate::config::Config;
+use crate::i18n::{self, tr};
+use crate::output;
+use crate::session::Session;
+use crate::tools::FileTracker;
+use crate::ui::input::{Input, LineEditor};
+use crate::ui::style;
+
+const HELP_EN: &str = "\
+/clear          Clear the conversation and start a new session
+/model [NAME]   Show the current model (switch to NAME when given)
+/tools          Show the enabled tools
+/cost           Show the tokens and estimated cost of this session
+/save           Save the session and show its ID
+/diff           Show the changes in the working tree (git diff)
+/compact        Shrink the conversation by removing tool calls and results
+/retry          Resend the last message, discarding the previous answer
+/edit [MESSAGE] Amend the last message (edit it in place when MESSAGE is omitted) and resend it
+/fork [TURN]    Branch off after turn TURN (default: the latest) and switch to the new branch
+/checkout [N]   Switch to branch N (list the branches when N is omitted)
+/reload         Reload the config files (model, approval policy and tool settings)
+/exit           Quit
+/help           Show this help
+
+Enclose a multi-line message in \"\"\" or insert line breaks with Esc+Enter.";
+
+const HELP_JA: &str = "\
+/clear          会話履歴を消去して新しいセッションを始める
+/model [NAME]   使用中のモデルを表示する（NAME を指定すると切り替える）
+/tools          有効なツールを表示する
+/cost           このセッションのトークン数と推定料金を表示する
+/save           セッションを保存して ID を表示する
+/diff           作業ツリーの変更を表示する（git diff）
+/compact        ツールの呼び出しと結果を除いて会話履歴を縮める
+/retry          最後のメッセージを送り直す（前の応答は捨てる）
+/edit [MESSAGE] 最後のメッセージを書き換えて送り直す（MESSAGE を省略するとその場で編集する）
+/fork [TURN]    TURN ターン目（省略時は最新）の後から会話を分岐させ、新しい分岐に切り替える
+/checkout [N]   分岐 N に切り替える（N を省略すると分岐の一覧を表示する）
+/reload         設定ファイルを読み直す（モデル・承認ポリシー・ツール設定を反映）
+/exit           終了する
+/help           このヘルプを表示する
+
+複数行のメッセージは \"\"\" で囲むか、Esc+Enter で改行して入力します。";
+
+/// Start an interactive chat session (supports /reload)
+#[derive(clap::Args, Debug)]
+pub struct ChatArgs {
+    /// Continue a saved session (see `sessions list`) with its model, or with --model
+    #[arg(long, value_name = "SESSION_ID")]
+    pub resume: Option<String>,
+
+    /// Work in a new git worktree and branch; at the end, show the diff and
+    /// merge, keep or discard the changes
+    #[arg(long)]
+    pub isolated: bool,
+
+    #[command(flatten)]
+    pub agent: AgentArgs,
+}
+
+/// `chat`: 対話モード（会話履歴を保持したまま複数のメッセージをやり取りする）
+pub async fn run(chat_args: ChatArgs, config: Config, workspace: &Path) -> Result<()> {
+    let args = chat_args.agent;
+    let api_key = args.api_key()?;
+    // 設定の再読み込み後も readFile の記録を引き継ぐ
+    let file_tracker = FileTracker::with_search_index(workspace);
+    let mut agent = Agent::new(
+        &args,
+        config,
+        api_key.clone(),
+        workspace,
+        file_tracker.clone(),
+        None,
+    )?;
+    let mut watcher = ConfigWatcher::new(workspace);
+    let mut session = match &chat_args.resume {
+        Some(id) => Session::load(id)?,
+        None => Session::new(workspace, &agent.model),
+    };
+    let mut conversation = std::mem::take(&mut session.messages);
+    // 再開したセッションは --model を指定しなければ保存時のモデルで続ける
+    if chat_args.resume.is_some() {
+        let model = args.model.clone().unwrap_or_else(|| session.model.clone());
+        agent.switch_model(&model, &conversation)?;
+    }
+    // セッション中の使用量と推定料金（料金が不明なモデルの分は含まない）
+    let mut usage = Usage::default();
+    let mut cost = 0.0;
+
+    eprintln!(
+        "{}",
+        tr!(
+            "Chat mode (model: {}, session: {}). Type /help for commands.",
+            "Chat mode (model: {}, session: {}). /help でコマンド一覧を表示します。",
+            agent.model,
+            session.id
+        )
+    );
+
+    let mut editor = LineEditor::new()?;
+    loop {
+        let line = match editor.read_line("> ").await? {
+            Input::Line(line) => line,
+            // Ctrl+C は入力中の行だけを取り消す
+            Input::Interrupted => continue,
+            Input::Eof => break,
+        };
+        let line = line.trim();
+
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+        // /retry と /edit で送り直すメッセージ
+        let mut resend = None;
+        match command {
+            "" => continue,
+            "/exit" | "/quit" => break,
+            "/help" => eprintln!("{}", i18n::pick(HELP_EN, HELP_JA)),
+            "/reload" => {
+                watcher.changed();
+                reload(&args, &api_key, workspace, &file_tracker, &mut agent);
+            }
+            "/clear" => {
+                conversation.clear();
+                file_tracker.forget_reads();
+                session = Session::new(workspace, &agent.model);
+                eprintln!("Conversation cleared (new session: {})", session.id);
+            }
+            "/model" if argument.is_empty() => eprintln!("Model: {}", agent.model),
+            "/model" => match agent.switch_model(argument, &conversation) {
+                Ok(()) => eprintln!("Switched model to {}", agent.model),
+                Err(e) => eprintln!("Cannot switch model: {:#}", e),
+            },
+            "/tools" => {
+                let names = agent.tool_names();
+                if names.is_empty() {
+                    eprintln!("Tools are disabled");
+                }
+                for name in names {
+                    eprintln!("  {}", name);
+                }
+            }
+            "/cost" => {
+                eprintln!(
+                    "Input tokens: {}, output tokens: {}, cache write: {}, cache read: {}",
+                    usage.input_tokens,
+                    usage.output_tokens,
+                    usage.cache_creation_input_tokens,
+                    usage.cache_read_input_tokens
+                );
+                eprintln!("Estimated cost: ${:.4}", cost);
+            }
+            "/save" => {
+                session.model = agent.model.clone();
+                session.messages = conversation.clone();
+                agent.name_session(&mut session).await;
+                match session.save() {
+                    Ok(()) => eprintln!(
+                        "Saved session {} (resume with `chat --resume {}`)",
+                        session.id, session.id
+                    ),
+                    Err(e) => eprintln!("Failed to save session: {:#}", e),
+                }
+            }
+            "/diff" => {
+                if let Err(e) = show_diff(workspace) {
+                    eprintln!("Failed to show diff: {:#}", e);
+                }
+            }
+            "/compact" => {
+                let before = conversation.len();
+                conversation = compact(conversation);
+                file_tracker.forget_reads();
+                eprintln!(
+                    "Compacted conversation: {} → {} messages",
+                    before,
+                    conversation.len()
+                );
+            }
+            "/retry" | "/edit" => {
+                let Some((index, previous)) = Session::last_turn(&conversation) else {
+                    eprintln!("No message to {}", &command[1..]);
+                    continue;
+                };
+                let message = match (command, argument) {
+                    ("/retry", _) => previous,
+                    ("/edit", "") => match editor.edit_line("edit> ", &previous).await? {
+                        Input::Line(line) if !line.trim().is_empty() => line.trim().to_string(),
+                        _ => continue,
+                    },
+                    (_, argument) => argument.to_string(),
+                };
+                resend = Some((index, message));
+            }
+            "/fork" => {
+                let turn = match argument {
+                    "" => None,
+                    turn => match turn.parse() {
+                        Ok(turn) => Some(turn),
+                        Err(_) => {
+                            eprintln!("Usage: /fork [TURN]");
+                            continue;
+                        }
+                    },
+                };
+                let previous = session.branch;
+                session.messages = std::mem::take(&mut conversation);
+                let forked = session.fork(turn);
+                conversation = session.messages.clone();
+                match forked {
+                    Ok(branch) => {
+                        file_tracker.forget_reads();
+                        save_session(&mut session, &agent, &conversation).await;
+                        eprintln!(
+                            "Switched to branch {} ({} turns; back with /checkout {})",
+                            branch + 1,
+                            Session::turns(&conversation),
+                            previous + 1
+                        );
+                    }
+                    Err(e) => eprintln!("{:#}", e),
+                }
+            }
+            "/checkout" if argument.is_empty() => {
+                session.messages = conversation.clone();
+                list_branches(&session);
+            }
+            "/checkout" => {
+                let Some(branch) = argument
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                else {
+                    eprintln!("Usage: /checkout [N]");
+                    continue;
+                };
+                session.messages = std::mem::take(&mut conversation);
+                let switched = session.checkout(branch);
+                conversation = session.messages.clone();
+                match switched {
+                    Ok(()) => {
+                        file_tracker.forget_reads();
+                        save_session(&mut session, &agent, &conversation).await;
+                        eprintln!(
+                            "Switched to branch {} ({} turns)",
+                            branch + 1,
+                            Session::turns(&conversation)
+                        );
+                    }
+                    Err(e) => eprintln!("{:#}", e),
+                }
+            }
+            _ if command.starts_with('/') => {
+                eprintln!(
+                    "{}",
+                    tr!(
+                        "Unknown command: {} (type /help for commands)",
+                        "Unknown command: {} (/help でコマンド一覧を表示します)",
+                        command
+                    )
+                )
+            }
+            _ => {}
+        }
+        // 送り直す場合は最後のメッセージ以降を取り除く（失敗したら元に戻す）
+        let (message, discarded) = match resend {
+            Some((index, message)) => {
+                let discarded = conversation.split_off(index);
+                file_tracker.forget_reads();
+                (message, discarded)
+            }
+            None if command.is_empty() || command.starts_with('/') => continue,
+            None => (line.to_string(), Vec::new()),
+        };
+
+        // 設定ファイルが変更されていれば自動で反映する
+        if watcher.changed() {
+            tracing::info!("Config file changed; reloading");
+            reload(&args, &api_key, workspace, &file_tracker, &mut agent);
+        }
+
+        conversation.push(Message::user_text(&message));
+        match agent.send(conversation.clone()).await {
+            Ok(result) => {
+                output::print_result(&result, &agent.output)?;
+                usage.add(&result.usage);
+                cost += result.cost_usd().unwrap_or(0.0);
+                session.record_usage(&result);
+                conversation = result.conversation;
+
+                // ターンごとに会話を保存
+                save_session(&mut session, &agent, &conversation).await;
+            }
+            Err(e) => {
+                // 失敗したメッセージは履歴に残さない
+                conversation.pop();
+                conversation.extend(discarded);
+                eprintln!("Error: {:#}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 現在の会話を保存する（失敗しても会話は続ける）
+async fn save_session(session: &mut Session, agent: &Agent, conversation: &[Message]) {
+    session.model = agent.model.clone();
+    session.messages = conversation.to_vec();
+    agent.name_session(session).await;
+    if let Err(e) = session.save() {
+        tracing::warn!("Failed to save session: {:#}", e);
+    }
+}
+
+/// 会話の分岐を一覧表示する（現在の分岐に * を付ける）
+fn list_branches(session: &Session) {
+    if session.branches.is_empty() {
+        eprintln!("No branches yet (create one with /fork [TURN])");
+        return;
+    }
+    for (index, branch) in session.branches.iter().enumerate() {
+        let current = index == session.branch;
+        let messages = if current {
+            &session.messages
+        } else {
+            &branch.messages
+        };
+        let origin = match branch.parent {
+            Some(parent) => format!("from {} at turn {}", parent + 1, branch.forked_at_turn),
+            None => "original".to_string(),
+        };
+        eprintln!(
+            "{} {:>2}  {:<22} {:>3} turns  {}",
+            if current { "*" } else { " " },
+            index + 1,
+            origin,
+            Session::turns(messages),
+            Session::latest_prompt(messages)
+        );
+    }
+}
+
+/// 設定を読み直してエージェントを再構築する（失敗時は現在の設定を維持）
+fn reload(
+    args: &AgentArgs,
+    api_key: &str,
+    workspace: &Path,
+    file_tracker: &FileTracker,
+    agent: &mut Agent,
+) {
+    let reloaded = args.load_config(workspace).and_then(|config| {
+        Agent::new(
+            args,
+            config,
+            api_key.to_string(),
+            workspace,
+            file_tracker.clone(),
+            None,
+        )
+    });
+    match reloaded {
+        Ok(new_agent) => {
+            *agent = new_agent;
+            // 除外パターンなどが変わり得るので検索索引は次の検索で作り直す
+            if let Some(index) = file_tracker.search_index() {
+                index.clear();
+            }
+            eprintln!("Config reloaded (model: {})", agent.model);
+        }
+        Err(e) => eprintln!("Failed to reload config; keeping current settings: {:#}", e),
+    }
+}
+
+/// 作業ツリーの変更を git diff で表示する
+fn show_diff(workspace: &Path) -> Result<()> {
+    let color = if style::color_enabled() {
+        "--color=always"
+    } else {
+        "--color=never"
+    };
+    let output = std::process::Command::new("git")
+        .args(["diff", "--exit-code", color])
+        .current_dir(workspace)
+        .output()
+        .context("Failed to run git")?;
+    // --exit-code: 0 は変更なし、1 は変更あり、それ以外は git リポジトリでない場合など
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{}", stderr.lines().next().unwrap_or("git diff failed"));
+    }
+    if output.stdout.is_empty() {
+        eprintln!("No changes");
+    } else {
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+    }
+    Ok(())
+}
+
+/// ツールの呼び出しと結果を取り除き、テキストだけの会話に縮める
+///
+/// 取り除いた結果、同じ役割のメッセージが続く場合は 1 つにまとめる
+fn compact(conversation: Vec<Message>) -> Vec<Message> {
+    let mut compacted: Vec<Message> = Vec::new();
+    for message in conversation {
+        let text = match message.content {
+            MessageContent::Text(text) => text,
+            MessageContent::Blocks(blocks) => blocks
+                .into_iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        match compacted.last_mut() {
+            Some(Message {
+                role,
+                content: MessageContent::Text(previous),
+            }) if *role == message.role => {
+                previous.push_str("\n\n");
+                previous.push_str(&text);
+            }
+            _ => compacted.push(Message {
+                role: message.role,
+                content: MessageContent::Text(text),
+            }),
+        }
+    }
+    compacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_drops_tool_blocks_and_merges() {
+        let conversation = vec![
+            Message::user_text("Fix the bug"),
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![
+                    ContentBlock::Text {
+                        text: "Let me look.".to_string(),
+                    },
+                    ContentBlock::ToolUse {
+                        id: "tu_1".to_string(),
+                        name: "readFile".to_string(),
+                        input: serde_json::json!({"path": "a.rs"}),
+                    },
+                ]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Blocks(vec![ContentBlock::ToolResult {
+                    tool_use_id: "tu_1".to_string(),
+                    content: "fn main() {}".to_string(),
+                    is_error: None,
+                }]),
+            },
+            Message::assistant_text("Fixed."),
+        ];
+
+        let compacted = compact(conversation);
+        assert_eq!(compacted.len(), 2);
+        assert!(
+            matches!(&compacted[1].content, MessageContent::Text(text) if text == "Let me look.\n\nFixed.")
+        );
+    }
+}
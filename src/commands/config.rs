@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+
+use crate::config::{validate_file, Config, DEFAULT_CONFIG_TEMPLATE};
+
+/// `config` サブコマンド
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Write a commented default config file
+    Init {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print a config value (e.g. `model.default`)
+    Get {
+        /// Dotted key path
+        key: String,
+    },
+    /// Set a config value (e.g. `agent.max_iterations 20`)
+    Set {
+        /// Dotted key path
+        key: String,
+        /// New value (parsed as TOML, falling back to a string)
+        value: String,
+    },
+    /// Print the config file path
+    Path,
+    /// Check the global and project config files for mistakes
+    Validate,
+}
+
+/// `config` サブコマンドを実行
+pub fn run(command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Init { force } => init(force),
+        ConfigCommand::Get { key } => get(&key),
+        ConfigCommand::Set { key, value } => set(&key, &value),
+        ConfigCommand::Validate => validate(),
+        ConfigCommand::Path => {
+            println!("{}", Config::config_path()?.display());
+            Ok(())
+        }
+    }
+}
+
+fn init(force: bool) -> Result<()> {
+    let path = Config::config_path()?;
+    if path.exists() && !force {
+        bail!(
+            "Config file already exists at {}. Use --force to overwrite.",
+            path.display()
+        );
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create config directory")?;
+    }
+    std::fs::write(&path, DEFAULT_CONFIG_TEMPLATE).context("Failed to write config file")?;
+
+    println!("Wrote default config to {}", path.display());
+    Ok(())
+}
+
+fn validate() -> Result<()> {
+    let mut files = vec![Config::config_path()?];
+    files.extend(Config::project_config_path(&std::env::current_dir()?));
+
+    let mut issue_count = 0;
+    for path in files.iter().filter(|p| p.exists()) {
+        let issues = validate_file(path)?;
+        if issues.is_empty() {
+            println!("{}: OK", path.display());
+        }
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        issue_count += issues.len();
+    }
+
+    if issue_count > 0 {
+        bail!("Found {} problem(s) in config", issue_count);
+    }
+    Ok(())
+}
+
+fn get(key: &str) -> Result<()> {
+    let config = toml::Value::try_from(Config::load()?).context("Failed to serialize config")?;
+
+    let mut current = &config;
+    for part in key.split('.') {
+        current = current
+            .get(part)
+            .with_context(|| format!("Unknown config key: {}", key))?;
+    }
+
+    match current {
+        toml::Value::String(s) => println!("{}", s),
+        toml::Value::Table(_) => print!("{}", toml::to_string_pretty(current)?),
+        other => println!("{}", other),
+    }
+    Ok(())
+}
+
+fn set(key: &str, value: &str) -> Result<()> {
+    let mut config =
+        toml::Value::try_from(Config::load()?).context("Failed to serialize config")?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, parents) = parts.split_last().context("Empty config key")?;
+
+    let mut table = config
+        .as_table_mut()
+        .context("Config root is not a table")?;
+    for part in parents {
+        table = table
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .with_context(|| format!("{} is not a table", part))?;
+    }
+    table.insert(last.to_string(), parse_value(value));
+
+    // 型が合っているかを Config へのデシリアライズで検証する
+    let updated: Config = config
+        .try_into()
+        .with_context(|| format!("Invalid value for {}: {}", key, value))?;
+    updated.save()?;
+
+    println!("Set {} = {}", key, value);
+    Ok(())
+}
+
+/// 値を TOML として解釈し、失敗した場合は文字列として扱う
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("20"), toml::Value::Integer(20));
+        assert_eq!(parse_value("true"), toml::Value::Boolean(true));
+        assert_eq!(
+            parse_value("claude-haiku-4-5"),
+            toml::Value::String("claude-haiku-4-5".to_string())
+        );
+        assert_eq!(
+            parse_value(r#"["/tmp/a"]"#),
+            toml::Value::Array(vec![toml::Value::String("/tmp/a".to_string())])
+        );
+    }
+}
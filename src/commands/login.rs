@@ -0,0 +1,58 @@
+use anyhow::{bail, Context, Result};
+use std::io::{self, IsTerminal, Write};
+
+use crate::credentials;
+
+/// Arguments for `login` and `logout`
+#[derive(clap::Args, Debug)]
+pub struct LoginArgs {
+    /// Manage the GitHub token used by the GitHub tools instead of the API key
+    #[arg(long)]
+    pub github: bool,
+}
+
+/// `login`: API キー（--github の場合は GitHub のトークン）を OS キーリングに保存
+pub fn login(args: LoginArgs) -> Result<()> {
+    let (prompt, what) = if args.github {
+        ("GitHub token: ", "GitHub token")
+    } else {
+        ("Anthropic API key: ", "API key")
+    };
+    // 端末では入力を表示しない（スクロールバックや画面の録画に残さない）
+    let secret = if io::stdin().is_terminal() {
+        rpassword::prompt_password(prompt).with_context(|| format!("Failed to read {}", what))?
+    } else {
+        print!("{}", prompt);
+        io::stdout().flush().context("Failed to flush stdout")?;
+        let mut secret = String::new();
+        io::stdin()
+            .read_line(&mut secret)
+            .with_context(|| format!("Failed to read {}", what))?;
+        secret
+    };
+    let secret = secret.trim();
+
+    if secret.is_empty() {
+        bail!("No {} entered", what);
+    }
+
+    if args.github {
+        credentials::store_github_token(secret)?;
+    } else {
+        credentials::store_api_key(secret)?;
+    }
+    println!("{} saved to the OS keyring", what);
+    Ok(())
+}
+
+/// `logout`: OS キーリングから API キー（--github の場合は GitHub のトークン）を削除
+pub fn logout(args: LoginArgs) -> Result<()> {
+    if args.github {
+        credentials::delete_github_token()?;
+        println!("GitHub token removed from the OS keyring");
+    } else {
+        credentials::delete_api_key()?;
+        println!("API key removed from the OS keyring");
+    }
+    Ok(())
+}
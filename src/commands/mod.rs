@@ -0,0 +1,14 @@
+pub mod acp;
+pub mod batch;
+pub mod chat;
+pub mod config;
+pub mod login;
+pub mod models;
+pub mod run;
+pub mod serve;
+pub mod sessions;
+pub mod tokens;
+pub mod tools;
+pub mod tui;
+pub mod usage;
+pub mod watch;
@@ -0,0 +1,29 @@
+use anyhow::Result;
+
+use crate::agent::resolve_api_key;
+use crate::anthropic::AnthropicClient;
+use crate::config::Config;
+
+/// List the models available to the API key
+#[derive(clap::Args, Debug)]
+pub struct ModelsArgs {
+    /// Anthropic API key (can also be set via ANTHROPIC_API_KEY env var; a key saved with `login` takes precedence)
+    #[arg(long, env = "ANTHROPIC_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+}
+
+/// `models`: 利用可能なモデルを一覧表示（既定のモデルに * を付ける）
+pub async fn run(args: ModelsArgs, config: &Config) -> Result<()> {
+    let api_key = resolve_api_key(args.api_key.as_deref())?;
+    let client = AnthropicClient::new(api_key, &config.api)?;
+
+    for model in client.list_models().await? {
+        let marker = if model.id == config.model.default {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {:<32} {}", marker, model.id, model.display_name);
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+use crate::agent::{Agent, AgentArgs};
+use crate::anthropic::Message;
+use crate::attachments::{attach_files, attach_stdin};
+use crate::config::{Config, Verbosity};
+use crate::error::AgentError;
+use crate::output;
+use crate::response_cache;
+use crate::session::Session;
+use crate::templates;
+use crate::tools::FileTracker;
+
+/// Send a single message and print the final response
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// User message/prompt to send to Claude (`-` or omitted with piped stdin: read from stdin;
+    /// with a MESSAGE, piped stdin is attached as context)
+    #[arg(value_name = "MESSAGE")]
+    pub message: Option<String>,
+
+    /// Don't attach piped stdin to the MESSAGE as context
+    #[arg(long)]
+    pub no_stdin: bool,
+
+    /// Use the prompt template ~/.codex/templates/NAME.md (a MESSAGE is appended to it)
+    #[arg(long, value_name = "NAME")]
+    pub template: Option<String>,
+
+    /// Fill a `{{KEY}}` placeholder in the template (can be repeated)
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = templates::parse_var, requires = "template")]
+    pub vars: Vec<(String, String)>,
+
+    /// Attach a file's contents to the prompt (can be repeated)
+    #[arg(long = "file", short = 'f', value_name = "PATH")]
+    pub files: Vec<PathBuf>,
+
+    /// Reuse the answer of an earlier identical run that finished without tools
+    /// (same model, settings, system prompt and message)
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Work in a new git worktree and branch; at the end, show the diff and
+    /// merge, keep or discard the changes
+    #[arg(long)]
+    pub isolated: bool,
+
+    #[command(flatten)]
+    pub agent: AgentArgs,
+}
+
+/// `run`: メッセージを 1 回送信して最終応答を表示する
+pub async fn run(args: RunArgs, mut config: Config, workspace: &Path) -> Result<()> {
+    let template = args
+        .template
+        .as_deref()
+        .map(|name| templates::load(name, &args.vars))
+        .transpose()?;
+    let message = resolve_message(args.message, template, args.no_stdin)?;
+    // リポジトリマップは添付前のメッセージとの関連度で絞る
+    config.agent.task = Some(message.clone());
+    let api_key = args.agent.api_key()?;
+    // readFile と editFile で共有するファイル状態の記録
+    let file_tracker = FileTracker::new();
+    let message = attach_files(
+        &message,
+        &args.files,
+        &file_tracker,
+        config.tools.read_file.max_bytes,
+        config.agent.attachments_max_tokens,
+    )?;
+    let use_cache = args.cache || config.agent.cache_responses;
+    let agent = Agent::new(&args.agent, config, api_key, workspace, file_tracker, None)?;
+
+    // ツールを使った会話を実行（キャッシュがあれば API を呼ばない）
+    let cache_key = use_cache.then(|| agent.response_cache_key(&message));
+    let result = match cache_key.as_ref().and_then(response_cache::lookup) {
+        Some((result, created_at)) => {
+            if agent.output.verbosity != Verbosity::Quiet {
+                eprintln!(
+                    "Using the cached response from {}",
+                    created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                );
+            }
+            result
+        }
+        None => {
+            let result = agent.send(vec![Message::user_text(message)]).await?;
+            if let Some(key) = &cache_key {
+                if let Err(e) = response_cache::store(key, &result) {
+                    tracing::warn!("Failed to cache the response: {:#}", e);
+                }
+            }
+            result
+        }
+    };
+
+    // 結果の表示（時間制限で打ち切った場合は部分的な結果を表示してから失敗にする）
+    let printed = output::print_result(&result, &agent.output);
+
+    // 会話を保存（タイトルを付けるのは結果を表示してから。失敗しても結果は返す）
+    let mut session = Session::new(workspace, &agent.model);
+    session.messages = result.conversation.clone();
+    session.record_usage(&result);
+    agent.name_session(&mut session).await;
+    if let Err(e) = session.save() {
+        tracing::warn!("Failed to save session: {:#}", e);
+    }
+
+    printed?;
+    match result.timed_out {
+        Some(limit) => Err(AgentError::TimedOut(limit).into()),
+        None => Ok(()),
+    }
+}
+
+/// メッセージの解決
+///
+/// `-` または省略時に標準入力がパイプならプロンプトとして全体を読み込み、
+/// メッセージがある場合はパイプされた内容をコンテキストとして添付する
+fn resolve_message(
+    message: Option<String>,
+    template: Option<String>,
+    no_stdin: bool,
+) -> Result<String> {
+    // テンプレートを使う場合は MESSAGE を追加の指示として後ろに付ける
+    let message = match (template, message) {
+        (Some(template), Some(message)) if message != "-" => {
+            Some(format!("{}\n\n{}", template.trim_end(), message))
+        }
+        (Some(template), _) => Some(template),
+        (None, message) => message,
+    };
+    let piped = !std::io::stdin().is_terminal();
+    match message {
+        Some(message) if message != "-" => {
+            if !piped || no_stdin {
+                return Ok(message);
+            }
+            let context = read_stdin().context("Failed to read context from stdin")?;
+            return Ok(attach_stdin(&message, &context));
+        }
+        Some(_) => {}
+        None if !piped => {
+            bail!("MESSAGE is required (or pipe the prompt via stdin)")
+        }
+        None => {}
+    }
+
+    let input = read_stdin().context("Failed to read the prompt from stdin")?;
+    if input.trim().is_empty() {
+        bail!("The prompt read from stdin is empty");
+    }
+    Ok(input)
+}
+
+fn read_stdin() -> std::io::Result<String> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    Ok(input)
+}
@@ -0,0 +1,661 @@
+use anyhow::{anyhow, Context, Result};
+use axum::extract::{Path as UrlPath, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use subtle::ConstantTimeEq;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::agent::{Agent, AgentArgs};
+use crate::anthropic::{ConversationResult, Message};
+use crate::config::{ColorChoice, Config};
+use crate::output;
+use crate::session::Session;
+use crate::tools::FileTracker;
+use crate::ui::confirm::{Answer, ConfirmRequest, PromptHandler};
+use crate::ui::style;
+
+mod metrics;
+
+use metrics::Metrics;
+
+/// Serve a REST/JSON API for submitting tasks, streaming their events and answering confirmations
+/// (with Prometheus metrics on /metrics). Every request needs the bearer token; on localhost,
+/// requests whose Host is not localhost are refused
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
+    /// Port to listen on
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+
+    /// Address to bind (only bind other addresses than localhost on trusted networks)
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+
+    /// Require `Authorization: Bearer <TOKEN>` on every request
+    /// [default: a random token printed at startup]
+    #[arg(long, env = "AGENT_SERVE_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    #[command(flatten)]
+    pub agent: AgentArgs,
+}
+
+/// 記録しておく終了したタスクの数（超えた分は古いものから捨てる）
+const MAX_FINISHED_TASKS: usize = 100;
+
+/// タスクごとに記録しておくイベントの数（後から購読したクライアントには残っている分を送る）
+const MAX_TASK_EVENTS: usize = 10_000;
+
+/// タスクの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TaskStatus {
+    Running,
+    /// 確認への回答を待っている
+    AwaitingConfirmation,
+    Completed,
+    Failed,
+}
+
+/// 回答を待っている確認
+struct PendingConfirmation {
+    request: ConfirmRequest,
+    reply: oneshot::Sender<Answer>,
+}
+
+struct TaskState {
+    status: TaskStatus,
+    /// これまでのイベント（後から購読したクライアントにも送る。
+    /// `MAX_TASK_EVENTS` を超えた分は古いものから捨てる）
+    events: VecDeque<Value>,
+    subscribers: Vec<mpsc::UnboundedSender<Value>>,
+    confirmations: BTreeMap<usize, PendingConfirmation>,
+    next_confirmation: usize,
+    conversation: Vec<Message>,
+    /// 完了時の結果（`--output json` と同じ形式）または失敗の理由
+    result: Option<Value>,
+    error: Option<String>,
+}
+
+/// サーバーで実行する 1 つのタスク（1 つのプロンプトに対する会話）
+struct Task {
+    id: String,
+    prompt: String,
+    created_at: DateTime<Utc>,
+    state: Mutex<TaskState>,
+}
+
+impl Task {
+    fn new(id: String, prompt: String) -> Self {
+        let conversation = vec![Message::user_text(&prompt)];
+        Self {
+            id,
+            prompt,
+            created_at: Utc::now(),
+            state: Mutex::new(TaskState {
+                status: TaskStatus::Running,
+                events: VecDeque::new(),
+                subscribers: Vec::new(),
+                confirmations: BTreeMap::new(),
+                next_confirmation: 1,
+                conversation,
+                result: None,
+                error: None,
+            }),
+        }
+    }
+
+    /// イベントを記録して購読中のクライアントへ送る
+    fn publish(&self, event: Value) {
+        let mut state = self.state.lock().unwrap();
+        publish_locked(&mut state, event);
+    }
+
+    /// 確認を保留し、回答を待っていることを通知する
+    fn add_confirmation(&self, request: ConfirmRequest, reply: oneshot::Sender<Answer>) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_confirmation;
+        state.next_confirmation += 1;
+        state.status = TaskStatus::AwaitingConfirmation;
+        let event = json!({
+            "type": "confirmation_required",
+            "confirmation": confirmation_json(id, &request),
+        });
+        state
+            .confirmations
+            .insert(id, PendingConfirmation { request, reply });
+        publish_locked(&mut state, event);
+    }
+
+    /// 確認に回答する（該当する確認がなければ false）
+    fn answer(&self, id: usize, answer: Answer) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(pending) = state.confirmations.remove(&id) else {
+            return false;
+        };
+        // タスクが中断されていれば受け取り側はもうないので送信の失敗は無視する
+        let _ = pending.reply.send(answer);
+        if state.confirmations.is_empty() && state.status == TaskStatus::AwaitingConfirmation {
+            state.status = TaskStatus::Running;
+        }
+        publish_locked(
+            &mut state,
+            json!({
+                "type": "confirmation_answered",
+                "id": id,
+                "answer": answer_name(answer),
+            }),
+        );
+        true
+    }
+
+    /// 実行結果を記録し、イベントの配信を終える
+    fn finish(&self, result: Result<ConversationResult>) {
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Ok(result) => {
+                state.status = TaskStatus::Completed;
+                state.result = Some(output::json_document(&result));
+                state.conversation = result.conversation;
+            }
+            Err(e) => {
+                state.status = TaskStatus::Failed;
+                state.error = Some(format!("{:#}", e));
+            }
+        }
+        // 実行が終われば残った確認は意味がない
+        state.confirmations.clear();
+        let event = json!({
+            "type": "finished",
+            "status": state.status,
+            "result": state.result,
+            "error": state.error,
+        });
+        publish_locked(&mut state, event);
+        // 送信側を破棄するとイベントのストリームが閉じる
+        state.subscribers.clear();
+    }
+
+    /// これまでのイベントを送り、実行中なら以降のイベントも送るチャネルを返す
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<Value> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut state = self.state.lock().unwrap();
+        for event in &state.events {
+            let _ = tx.send(event.clone());
+        }
+        if matches!(
+            state.status,
+            TaskStatus::Running | TaskStatus::AwaitingConfirmation
+        ) {
+            state.subscribers.push(tx);
+        }
+        rx
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(
+            self.state.lock().unwrap().status,
+            TaskStatus::Completed | TaskStatus::Failed
+        )
+    }
+
+    fn summary(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        json!({
+            "id": self.id,
+            "prompt": self.prompt,
+            "created_at": self.created_at,
+            "status": state.status,
+        })
+    }
+
+    fn detail(&self) -> Value {
+        let mut detail = self.summary();
+        let state = self.state.lock().unwrap();
+        let confirmations: Vec<Value> = state
+            .confirmations
+            .iter()
+            .map(|(id, pending)| confirmation_json(*id, &pending.request))
+            .collect();
+        detail["confirmations"] = json!(confirmations);
+        detail["result"] = json!(state.result);
+        detail["error"] = json!(state.error);
+        detail
+    }
+}
+
+fn publish_locked(state: &mut TaskState, event: Value) {
+    state
+        .subscribers
+        .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    state.events.push_back(event);
+    if state.events.len() > MAX_TASK_EVENTS {
+        state.events.pop_front();
+    }
+}
+
+fn confirmation_json(id: usize, request: &ConfirmRequest) -> Value {
+    json!({
+        "id": id,
+        "tool": request.tool,
+        "path": request.path,
+        "message": request.message,
+        "preview": request.preview,
+    })
+}
+
+/// サーバー全体の状態
+struct ServerState {
+    args: AgentArgs,
+    config: Config,
+    api_key: String,
+    workspace: PathBuf,
+    token: String,
+    /// ループバックで待ち受けている（localhost 以外の Host ヘッダーを拒否する）
+    loopback: bool,
+    tasks: Mutex<BTreeMap<usize, Arc<Task>>>,
+    next_task: AtomicUsize,
+    metrics: Metrics,
+}
+
+impl ServerState {
+    fn task(&self, id: &str) -> Option<Arc<Task>> {
+        let number: usize = id.parse().ok()?;
+        self.tasks.lock().unwrap().get(&number).cloned()
+    }
+
+    /// タスクを登録し、上限を超えた終了済みのタスクを古いものから捨てる
+    fn insert_task(&self, number: usize, task: Arc<Task>) {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.insert(number, task);
+        prune_finished(&mut tasks);
+    }
+}
+
+fn prune_finished(tasks: &mut BTreeMap<usize, Arc<Task>>) {
+    let finished: Vec<usize> = tasks
+        .iter()
+        .filter(|(_, task)| task.is_finished())
+        .map(|(number, _)| *number)
+        .collect();
+    let excess = finished.len().saturating_sub(MAX_FINISHED_TASKS);
+    for number in &finished[..excess] {
+        tasks.remove(number);
+    }
+}
+
+/// `serve`: REST/JSON API でタスクを受け付け、進行をイベントとして配信する
+///
+/// どのリクエストにも Bearer トークンを求め、ループバックでは Host も確かめる。
+/// 終了したタスクは `MAX_FINISHED_TASKS` 件、イベントはタスクごとに `MAX_TASK_EVENTS` 件まで残す
+pub async fn run(args: ServeArgs, config: Config, workspace: &Path) -> Result<()> {
+    let api_key = args.agent.api_key()?;
+    // 設定の誤りは最初のタスクではなく起動時に知らせる
+    Agent::new(
+        &args.agent,
+        config.clone(),
+        api_key.clone(),
+        workspace,
+        FileTracker::new(),
+        None,
+    )?;
+    let listener = tokio::net::TcpListener::bind((args.host.as_str(), args.port))
+        .await
+        .with_context(|| format!("Failed to listen on {}:{}", args.host, args.port))?;
+    let address = listener.local_addr()?;
+    // トークンなしでは、ブラウザで開いたページからでもタスクを実行できてしまう
+    let token = match args.token {
+        Some(token) => token,
+        None => {
+            let token = random_token()?;
+            eprintln!("Bearer token: {}", token);
+            token
+        }
+    };
+    let state = Arc::new(ServerState {
+        args: args.agent,
+        config,
+        api_key,
+        workspace: workspace.to_path_buf(),
+        token,
+        loopback: address.ip().is_loopback(),
+        tasks: Mutex::new(BTreeMap::new()),
+        next_task: AtomicUsize::new(1),
+        metrics: Metrics::new(),
+    });
+
+    let app = Router::new()
+        .route("/tasks", get(list_tasks).post(create_task))
+        .route("/tasks/{id}", get(get_task))
+        .route("/tasks/{id}/events", get(task_events))
+        .route("/tasks/{id}/transcript", get(task_transcript))
+        .route(
+            "/tasks/{id}/confirmations/{confirmation}",
+            post(answer_confirmation),
+        )
+        .route("/metrics", get(metrics))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state.clone());
+
+    eprintln!("Listening on http://{} (Ctrl-C to stop)", address);
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .context("Server failed")
+}
+
+/// Host ヘッダーと Bearer トークンを確認する
+///
+/// ループバックで待ち受けている場合は DNS rebinding で別のサイトから呼ばれないよう、
+/// Host が localhost 以外のリクエストを拒否する
+async fn authorize(
+    State(state): State<Arc<ServerState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    if state.loopback {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok());
+        if !host.is_some_and(is_local_host) {
+            return error(StatusCode::FORBIDDEN, "Host must be localhost");
+        }
+    }
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // 比較にかかる時間からトークンを推測されないよう定数時間で比べる
+    let valid = provided
+        .is_some_and(|provided| bool::from(provided.as_bytes().ct_eq(state.token.as_bytes())));
+    if !valid {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token");
+    }
+    next.run(request).await
+}
+
+/// Host ヘッダーがこのマシンを指す名前か（ポートは問わない）
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1" || name == "[::1]"
+}
+
+/// 起動ごとのランダムなトークン
+fn random_token() -> Result<String> {
+    let mut bytes = [0u8; 24];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("Failed to generate a token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn task_not_found() -> Response {
+    error(StatusCode::NOT_FOUND, "Task not found")
+}
+
+async fn list_tasks(State(state): State<Arc<ServerState>>) -> Json<Value> {
+    let tasks: Vec<Value> = state
+        .tasks
+        .lock()
+        .unwrap()
+        .values()
+        .map(|task| task.summary())
+        .collect();
+    Json(json!({ "tasks": tasks }))
+}
+
+#[derive(Deserialize)]
+struct CreateTask {
+    prompt: String,
+}
+
+/// タスクを作成してバックグラウンドで実行する
+async fn create_task(
+    State(state): State<Arc<ServerState>>,
+    Json(body): Json<CreateTask>,
+) -> Response {
+    if body.prompt.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "prompt must not be empty");
+    }
+    let number = state.next_task.fetch_add(1, Ordering::Relaxed);
+    let task = Arc::new(Task::new(number.to_string(), body.prompt));
+
+    // 確認はタスクに保留し、API 経由の回答を待つ
+    let confirm_task = Arc::clone(&task);
+    let prompt_handler: PromptHandler = Arc::new(move |request| {
+        let (reply, answer) = oneshot::channel();
+        confirm_task.add_confirmation(request, reply);
+        answer
+    });
+    let mut agent = match Agent::new(
+        &state.args,
+        state.config.clone(),
+        state.api_key.clone(),
+        &state.workspace,
+        FileTracker::new(),
+        Some(prompt_handler),
+    ) {
+        Ok(agent) => agent,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+    };
+    // 差分のプレビューはクライアントが表示するので ANSI エスケープを含めない
+    style::set_color_choice(ColorChoice::Never);
+    let event_task = Arc::clone(&task);
+    let event_state = Arc::clone(&state);
+    agent.set_event_handler(Arc::new(move |event| {
+        event_state.metrics.observe(event);
+        event_task.publish(serde_json::to_value(event).unwrap_or_default());
+    }));
+
+    state.insert_task(number, Arc::clone(&task));
+    tracing::info!("Started task {}", task.id);
+
+    let run_state = Arc::clone(&state);
+    let run_task = Arc::clone(&task);
+    state.metrics.run_started();
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let result = agent.send(vec![Message::user_text(&run_task.prompt)]).await;
+        run_state.metrics.run_finished(&result, started.elapsed());
+        let workspace = &run_state.workspace;
+        if let Ok(result) = &result {
+            let mut session = Session::new(workspace, &agent.model);
+            session.messages = result.conversation.clone();
+            session.record_usage(result);
+            agent.name_session(&mut session).await;
+            if let Err(e) = session.save() {
+                tracing::warn!("Failed to save session: {:#}", e);
+            }
+        }
+        tracing::info!("Task {} finished", run_task.id);
+        run_task.finish(result);
+    });
+
+    (StatusCode::ACCEPTED, Json(task.summary())).into_response()
+}
+
+async fn get_task(State(state): State<Arc<ServerState>>, UrlPath(id): UrlPath<String>) -> Response {
+    match state.task(&id) {
+        Some(task) => Json(task.detail()).into_response(),
+        None => task_not_found(),
+    }
+}
+
+/// Prometheus のテキスト形式の集計
+async fn metrics(State(state): State<Arc<ServerState>>) -> Response {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+/// 会話の記録（実行中は最初のメッセージだけ）
+async fn task_transcript(
+    State(state): State<Arc<ServerState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Response {
+    let Some(task) = state.task(&id) else {
+        return task_not_found();
+    };
+    let messages = task.state.lock().unwrap().conversation.clone();
+    Json(json!({ "id": task.id, "messages": messages })).into_response()
+}
+
+/// タスクのイベントを Server-Sent Events で送る（`finished` の後に閉じる）
+async fn task_events(
+    State(state): State<Arc<ServerState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, Response> {
+    let task = state.task(&id).ok_or_else(task_not_found)?;
+    let stream = UnboundedReceiverStream::new(task.subscribe()).map(|event| {
+        let name = event["type"].as_str().unwrap_or("message").to_string();
+        Ok(Event::default().event(name).data(event.to_string()))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct AnswerBody {
+    answer: String,
+}
+
+async fn answer_confirmation(
+    State(state): State<Arc<ServerState>>,
+    UrlPath((id, confirmation)): UrlPath<(String, usize)>,
+    Json(body): Json<AnswerBody>,
+) -> Response {
+    let Some(task) = state.task(&id) else {
+        return task_not_found();
+    };
+    let Some(answer) = parse_answer(&body.answer) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "answer must be one of yes, no, always, deny",
+        );
+    };
+    if !task.answer(confirmation, answer) {
+        return error(
+            StatusCode::NOT_FOUND,
+            "No pending confirmation with this id",
+        );
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// API での回答（端末と違い、不明な値は「いいえ」にせず拒否する）
+fn parse_answer(answer: &str) -> Option<Answer> {
+    match answer {
+        "yes" => Some(Answer::Yes),
+        "no" => Some(Answer::No),
+        "always" => Some(Answer::Always),
+        "deny" => Some(Answer::DenyAll),
+        _ => None,
+    }
+}
+
+fn answer_name(answer: Answer) -> &'static str {
+    match answer {
+        Answer::Yes => "yes",
+        Answer::No => "no",
+        Answer::Always => "always",
+        Answer::DenyAll => "deny",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_late_subscribers_get_earlier_events() {
+        let task = Task::new("1".to_string(), "hi".to_string());
+        task.publish(json!({ "type": "text_delta", "text": "a" }));
+        let mut early = task.subscribe();
+        let (reply, _answer) = oneshot::channel();
+        task.add_confirmation(
+            ConfirmRequest {
+                tool: "writeFile".to_string(),
+                path: "a.txt".to_string(),
+                message: "Create a.txt?".to_string(),
+                preview: String::new(),
+            },
+            reply,
+        );
+        assert!(task.answer(1, Answer::Yes));
+        assert!(!task.answer(1, Answer::Yes));
+
+        let mut late = task.subscribe();
+        for rx in [&mut early, &mut late] {
+            let types: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok())
+                .map(|event| event["type"].as_str().unwrap().to_string())
+                .collect();
+            assert_eq!(
+                types,
+                [
+                    "text_delta",
+                    "confirmation_required",
+                    "confirmation_answered"
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_host_check_and_task_limits() {
+        assert!(is_local_host("localhost:8080"));
+        assert!(is_local_host("127.0.0.1"));
+        assert!(is_local_host("[::1]:8080"));
+        assert!(!is_local_host("evil.example:8080"));
+        assert!(!is_local_host("localhost.evil.example"));
+
+        let task = Task::new("1".to_string(), "hi".to_string());
+        for i in 0..MAX_TASK_EVENTS + 5 {
+            task.publish(json!({ "type": "text_delta", "text": i.to_string() }));
+        }
+        let events = &task.state.lock().unwrap().events;
+        assert_eq!(events.len(), MAX_TASK_EVENTS);
+        assert_eq!(events[0]["text"], "5");
+
+        let mut tasks = BTreeMap::new();
+        for number in 1..=MAX_FINISHED_TASKS + 2 {
+            let task = Task::new(number.to_string(), "hi".to_string());
+            if number != 1 {
+                task.finish(Err(anyhow!("failed")));
+            }
+            tasks.insert(number, Arc::new(task));
+        }
+        prune_finished(&mut tasks);
+        // 実行中のタスクは残し、終了したタスクは古いものから捨てる
+        assert_eq!(tasks.len(), MAX_FINISHED_TASKS + 1);
+        assert!(tasks.contains_key(&1) && !tasks.contains_key(&2));
+        assert!(tasks.contains_key(&3));
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::anthropic::{AgentEvent, ConversationResult};
+use crate::error::AgentError;
+
+/// 実行時間のヒストグラムの境界（秒）
+const RUN_BUCKETS: &[f64] = &[1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+/// ツールの実行時間のヒストグラムの境界（秒）
+const TOOL_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+/// 累積ヒストグラム
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let prefix = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{},", labels)
+        };
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}le=\"{}\"}} {}",
+                name, prefix, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}le=\"+Inf\"}} {}",
+            name, prefix, self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+struct Inner {
+    /// 結果（completed / timed_out / failed）ごとの実行回数
+    runs: BTreeMap<&'static str, u64>,
+    runs_in_progress: u64,
+    run_duration: Histogram,
+    iterations: u64,
+    /// (ツール名, エラーかどうか) ごとの呼び出し回数
+    tool_calls: BTreeMap<(String, bool), u64>,
+    tool_duration: BTreeMap<String, Histogram>,
+    /// 種類（input / output / cache_read / cache_creation）ごとのトークン数
+    tokens: BTreeMap<&'static str, u64>,
+    api_errors: u64,
+}
+
+/// `serve` の `/metrics` で公開する集計（Prometheus のテキスト形式）
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                runs: BTreeMap::new(),
+                runs_in_progress: 0,
+                run_duration: Histogram::new(RUN_BUCKETS),
+                iterations: 0,
+                tool_calls: BTreeMap::new(),
+                tool_duration: BTreeMap::new(),
+                tokens: BTreeMap::new(),
+                api_errors: 0,
+            }),
+        }
+    }
+
+    pub fn run_started(&self) {
+        self.inner.lock().unwrap().runs_in_progress += 1;
+    }
+
+    /// 実行中のイベントから反復・ツール呼び出し・トークン数を集計する
+    pub fn observe(&self, event: &AgentEvent) {
+        let mut inner = self.inner.lock().unwrap();
+        match event {
+            AgentEvent::IterationStart { .. } => inner.iterations += 1,
+            AgentEvent::ToolResult {
+                name,
+                is_error,
+                duration_ms,
+                ..
+            } => {
+                *inner
+                    .tool_calls
+                    .entry((name.clone(), *is_error))
+                    .or_default() += 1;
+                inner
+                    .tool_duration
+                    .entry(name.clone())
+                    .or_insert_with(|| Histogram::new(TOOL_BUCKETS))
+                    .observe(*duration_ms as f64 / 1000.0);
+            }
+            AgentEvent::Usage { usage, .. } => {
+                for (kind, tokens) in [
+                    ("input", usage.input_tokens),
+                    ("output", usage.output_tokens),
+                    ("cache_read", usage.cache_read_input_tokens),
+                    ("cache_creation", usage.cache_creation_input_tokens),
+                ] {
+                    *inner.tokens.entry(kind).or_default() += u64::from(tokens);
+                }
+            }
+            AgentEvent::TextDelta { .. }
+            | AgentEvent::ToolCall { .. }
+            | AgentEvent::ToolProgress { .. } => {}
+        }
+    }
+
+    pub fn run_finished(&self, result: &Result<ConversationResult>, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.runs_in_progress = inner.runs_in_progress.saturating_sub(1);
+        inner.run_duration.observe(duration.as_secs_f64());
+        let outcome = match result {
+            Ok(result) if result.timed_out.is_some() => "timed_out",
+            Ok(_) => "completed",
+            Err(e) => {
+                let api_error = e
+                    .chain()
+                    .any(|cause| matches!(cause.downcast_ref(), Some(AgentError::Api(_))));
+                if api_error {
+                    inner.api_errors += 1;
+                }
+                "failed"
+            }
+        };
+        *inner.runs.entry(outcome).or_default() += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "agent_runs_total",
+            "counter",
+            "Finished agent runs by outcome",
+        );
+        for outcome in ["completed", "timed_out", "failed"] {
+            let count = inner.runs.get(outcome).copied().unwrap_or_default();
+            let _ = writeln!(out, "agent_runs_total{{outcome=\"{}\"}} {}", outcome, count);
+        }
+        header(
+            &mut out,
+            "agent_runs_in_progress",
+            "gauge",
+            "Agent runs currently in progress",
+        );
+        let _ = writeln!(out, "agent_runs_in_progress {}", inner.runs_in_progress);
+        header(
+            &mut out,
+            "agent_run_duration_seconds",
+            "histogram",
+            "Duration of finished agent runs",
+        );
+        inner
+            .run_duration
+            .render(&mut out, "agent_run_duration_seconds", "");
+
+        header(
+            &mut out,
+            "agent_iterations_total",
+            "counter",
+            "Agent loop iterations (API calls)",
+        );
+        let _ = writeln!(out, "agent_iterations_total {}", inner.iterations);
+
+        header(
+            &mut out,
+            "agent_tool_calls_total",
+            "counter",
+            "Tool calls by tool and result",
+        );
+        for ((tool, is_error), count) in &inner.tool_calls {
+            let _ = writeln!(
+                out,
+                "agent_tool_calls_total{{tool=\"{}\",result=\"{}\"}} {}",
+                escape(tool),
+                if *is_error { "error" } else { "ok" },
+                count
+            );
+        }
+        header(
+            &mut out,
+            "agent_tool_duration_seconds",
+            "histogram",
+            "Duration of tool calls by tool",
+        );
+        for (tool, histogram) in &inner.tool_duration {
+            let labels = format!("tool=\"{}\"", escape(tool));
+            histogram.render(&mut out, "agent_tool_duration_seconds", &labels);
+        }
+
+        header(
+            &mut out,
+            "agent_tokens_total",
+            "counter",
+            "Tokens used by type",
+        );
+        for kind in ["input", "output", "cache_read", "cache_creation"] {
+            let count = inner.tokens.get(kind).copied().unwrap_or_default();
+            let _ = writeln!(out, "agent_tokens_total{{type=\"{}\"}} {}", kind, count);
+        }
+
+        header(
+            &mut out,
+            "agent_api_errors_total",
+            "counter",
+            "Agent runs that failed because the API call failed after retries",
+        );
+        let _ = writeln!(out, "agent_api_errors_total {}", inner.api_errors);
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// ラベルの値のエスケープ
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counts_tools_and_failures() {
+        let metrics = Metrics::new();
+        metrics.run_started();
+        metrics.observe(&AgentEvent::ToolResult {
+            id: "t1".to_string(),
+            name: "readFile".to_string(),
+            is_error: false,
+            content: String::new(),
+            duration_ms: 20,
+            tokens: 0,
+            cached: false,
+        });
+        metrics.run_finished(
+            &Err(AgentError::Api("overloaded".to_string()).into()),
+            Duration::from_secs(3),
+        );
+
+        let text = metrics.render();
+        assert!(text.contains("agent_runs_total{outcome=\"failed\"} 1\n"));
+        assert!(text.contains("agent_runs_in_progress 0\n"));
+        assert!(text.contains("agent_run_duration_seconds_bucket{le=\"1\"} 0\n"));
+        assert!(text.contains("agent_run_duration_seconds_bucket{le=\"5\"} 1\n"));
+        assert!(text.contains("agent_tool_calls_total{tool=\"readFile\",result=\"ok\"} 1\n"));
+        assert!(
+            text.contains("agent_tool_duration_seconds_bucket{tool=\"readFile\",le=\"0.05\"} 1\n")
+        );
+        assert!(text.contains("agent_api_errors_total 1\n"));
+    }
+}
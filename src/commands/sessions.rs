@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use clap::Subcommand;
+use std::path::PathBuf;
+
+use crate::anthropic::{ContentBlock, MessageContent};
+use crate::export;
+use crate::session::Session;
+
+/// `sessions show --export` の形式
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    Html,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SessionsCommand {
+    /// List saved sessions, newest first
+    List,
+    /// Print the transcript of a saved session
+    Show {
+        /// Session id (from `sessions list`)
+        id: String,
+        /// Export the transcript instead, as a standalone page with collapsible tool
+        /// calls, highlighted diffs and usage
+        #[arg(long, value_name = "FORMAT")]
+        export: Option<ExportFormat>,
+        /// Write the export to this file instead of stdout
+        #[arg(long, short = 'o', value_name = "PATH", requires = "export")]
+        output: Option<PathBuf>,
+    },
+    /// Delete a saved session
+    Delete {
+        /// Session id (from `sessions list`)
+        id: String,
+    },
+}
+
+/// `sessions` サブコマンドの実行
+pub fn run(command: SessionsCommand) -> Result<()> {
+    match command {
+        SessionsCommand::List => {
+            let sessions = Session::list()?;
+            if sessions.is_empty() {
+                println!("No saved sessions in {:?}", Session::sessions_dir()?);
+            }
+            for session in sessions {
+                println!(
+                    "{}  {}  {:<20} {}",
+                    session.id,
+                    session
+                        .updated_at
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    session.model,
+                    session.title()
+                );
+            }
+        }
+        SessionsCommand::Show {
+            id,
+            export: Some(ExportFormat::Html),
+            output,
+        } => {
+            let html = export::session_html(&Session::load(&id)?);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, html)
+                        .with_context(|| format!("Failed to write {:?}", path))?;
+                    eprintln!("Exported session {} to {}", id, path.display());
+                }
+                None => print!("{}", html),
+            }
+        }
+        SessionsCommand::Show { id, .. } => {
+            let session = Session::load(&id)?;
+            println!("Session:   {}", session.id);
+            println!("Workspace: {}", session.workspace.display());
+            println!("Model:     {}", session.model);
+            if let Some(title) = &session.title {
+                println!("Title:     {}", title);
+            }
+            if !session.branches.is_empty() {
+                println!(
+                    "Branch:    {} of {}",
+                    session.branch + 1,
+                    session.branches.len()
+                );
+            }
+            for message in &session.messages {
+                print_message(&message.role, &message.content);
+            }
+        }
+        SessionsCommand::Delete { id } => {
+            Session::delete(&id)?;
+            println!("Deleted session {}", id);
+        }
+    }
+    Ok(())
+}
+
+fn print_message(role: &str, content: &MessageContent) {
+    match content {
+        MessageContent::Text(text) => println!("\n[{}]\n{}", role, text),
+        MessageContent::Blocks(blocks) => {
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text } => println!("\n[{}]\n{}", role, text),
+                    ContentBlock::ToolUse { name, input, .. } => {
+                        println!("\n[{} → {}] {}", role, name, input)
+                    }
+                    ContentBlock::ToolResult {
+                        content, is_error, ..
+                    } => {
+                        let label = if is_error.unwrap_or(false) {
+                            "tool error"
+                        } else {
+                            "tool result"
+                        };
+                        println!("\n[{}] {}", label, content)
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::tokens;
+
+/// Estimate the token count of files or stdin without calling the API
+#[derive(clap::Args, Debug)]
+pub struct TokensArgs {
+    /// Files to count (`-` reads stdin)
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+/// `tokens`: ファイルごとの推定トークン数（複数なら合計も）を表示する
+pub fn run(args: TokensArgs) -> Result<()> {
+    let mut total = 0;
+    for path in &args.paths {
+        let bytes = if path.as_os_str() == "-" {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .context("Failed to read stdin")?;
+            bytes
+        } else {
+            std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?
+        };
+        let count = tokens::estimate(&String::from_utf8_lossy(&bytes));
+        total += count;
+        println!("{:>10} {}", tokens::format(count), path.display());
+    }
+    if args.paths.len() > 1 {
+        println!("{:>10} total", tokens::format(total));
+    }
+    Ok(())
+}
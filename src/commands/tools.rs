@@ -0,0 +1,26 @@
+use crate::anthropic::Tool;
+use crate::config::Config;
+use crate::i18n;
+use crate::tools::builtin_schemas;
+use anyhow::Result;
+
+/// `tools`: 組み込みツールと `[[tools.custom]]` のツール、設定上の有効・無効を表示
+pub fn run(config: &Config) -> Result<()> {
+    i18n::set_language(config.language);
+    let custom = config.tools.custom.iter().map(|tool| Tool {
+        name: tool.name.clone(),
+        description: tool.description.clone(),
+        input_schema: tool.schema.clone(),
+    });
+    for tool in builtin_schemas().into_iter().chain(custom) {
+        let state = if config.tools.is_enabled(&tool.name) {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        // 説明文は 1 行目のみ表示
+        let summary = tool.description.lines().next().unwrap_or("");
+        println!("{:<20} {:<9} {}", tool.name, state, summary);
+    }
+    Ok(())
+}
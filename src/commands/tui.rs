@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+use super::chat::ChatArgs;
+use crate::agent::Agent;
+use crate::anthropic::{AgentEvent, ConversationResult, Message};
+use crate::config::{ColorChoice, Config};
+use crate::output;
+use crate::session::Session;
+use crate::tools::FileTracker;
+use crate::ui::confirm::{Answer, ConfirmRequest, PromptHandler};
+use crate::ui::style;
+use crate::ui::tui::App;
+
+/// TUI のイベントループに届くイベント
+enum UiEvent {
+    Input(Event),
+    Agent(AgentEvent),
+    Confirm(ConfirmRequest, oneshot::Sender<Answer>),
+    Finished(Result<ConversationResult>),
+}
+
+/// `tui`: 全画面の対話モード（会話・ツールの動作・承認待ちの差分・使用量を表示）
+pub async fn run(chat_args: ChatArgs, config: Config, workspace: &Path) -> Result<()> {
+    let args = chat_args.agent;
+    let api_key = args.api_key()?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    // 確認は標準入力ではなく承認ペインで受け付ける
+    let confirm_tx = tx.clone();
+    let prompt_handler: PromptHandler = Arc::new(move |request| {
+        let (reply, answer) = oneshot::channel();
+        let _ = confirm_tx.send(UiEvent::Confirm(request, reply));
+        answer
+    });
+    let mut agent = Agent::new(
+        &args,
+        config,
+        api_key,
+        workspace,
+        FileTracker::with_search_index(workspace),
+        Some(prompt_handler),
+    )?;
+    // 差分などは ratatui で色付けするので ANSI エスケープを含めない
+    style::set_color_choice(ColorChoice::Never);
+    let event_tx = tx.clone();
+    agent.set_event_handler(Arc::new(move |event| {
+        let _ = event_tx.send(UiEvent::Agent(event.clone()));
+    }));
+
+    let mut session = match &chat_args.resume {
+        Some(id) => Session::load(id)?,
+        None => Session::new(workspace, &agent.model),
+    };
+    let mut conversation = std::mem::take(&mut session.messages);
+    // 再開したセッションは --model を指定しなければ保存時のモデルで続ける
+    if chat_args.resume.is_some() {
+        let model = args.model.clone().unwrap_or_else(|| session.model.clone());
+        agent.switch_model(&model, &conversation)?;
+    }
+    let agent = Arc::new(agent);
+
+    // キー入力は別スレッドで読み取る（終了時はプロセスごと破棄される）
+    let input_tx = tx.clone();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if input_tx.send(UiEvent::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let mut app = App::new(&agent.model);
+    let result = async {
+        loop {
+            terminal
+                .draw(|frame| app.draw(frame))
+                .context("Failed to draw the terminal UI")?;
+            let Some(event) = rx.recv().await else {
+                break;
+            };
+            match event {
+                UiEvent::Agent(event) => app.handle_event(event),
+                UiEvent::Confirm(request, reply) => app.request_approval(request, reply),
+                UiEvent::Finished(Ok(result)) => {
+                    app.finish(output::final_text(&result));
+                    session.record_usage(&result);
+                    conversation = result.conversation;
+
+                    // ターンごとに会話を保存
+                    session.messages = conversation.clone();
+                    agent.name_session(&mut session).await;
+                    if let Err(e) = session.save() {
+                        app.fail(format!("Failed to save session: {:#}", e));
+                    }
+                }
+                UiEvent::Finished(Err(e)) => {
+                    // 失敗したメッセージは履歴に残さない
+                    conversation.pop();
+                    app.fail(format!("{:#}", e));
+                }
+                UiEvent::Input(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c');
+                    if ctrl_c || key.code == KeyCode::Esc {
+                        break;
+                    }
+                    if app.awaiting_approval() {
+                        if let KeyCode::Char(c) = key.code {
+                            if matches!(c, 'y' | 'n' | 'a' | 'd') {
+                                app.answer(Answer::parse(&c.to_string()));
+                            }
+                        }
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::PageUp => app.scroll_up(),
+                        KeyCode::PageDown => app.scroll_down(),
+                        KeyCode::Backspace => {
+                            app.input.pop();
+                        }
+                        KeyCode::Char(c) => app.input.push(c),
+                        KeyCode::Enter if !app.busy && !app.input.trim().is_empty() => {
+                            let line = std::mem::take(&mut app.input);
+                            app.push_user(line.trim());
+                            conversation.push(Message::user_text(line.trim()));
+
+                            let agent = Arc::clone(&agent);
+                            let messages = conversation.clone();
+                            let finished_tx = tx.clone();
+                            tokio::spawn(async move {
+                                let result = agent.send(messages).await;
+                                let _ = finished_tx.send(UiEvent::Finished(result));
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                UiEvent::Input(_) => {}
+            }
+        }
+        Ok(())
+    }
+    .await;
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use chrono::{Local, Utc};
+use std::collections::BTreeMap;
+
+use crate::ledger::{self, Total};
+
+/// Summarize the usage and cost recorded in ~/.codex/usage.jsonl per project and model
+#[derive(clap::Args, Debug)]
+pub struct UsageArgs {
+    /// Only count runs in this period, e.g. 7d, 12h, 30m or 2w [default: all runs]
+    #[arg(long, value_name = "PERIOD", value_parser = ledger::parse_period)]
+    pub since: Option<chrono::Duration>,
+}
+
+/// `usage`: 記録した使用量をプロジェクトごととモデルごとに集計して表示する
+pub fn run(args: UsageArgs) -> Result<()> {
+    let since = args.since.map(|period| Utc::now() - period);
+    let entries = ledger::load(since)?;
+    if entries.is_empty() {
+        println!("No runs recorded in {:?}", ledger::ledger_path()?);
+        return Ok(());
+    }
+
+    let runs = match entries.len() {
+        1 => "1 run".to_string(),
+        n => format!("{} runs", n),
+    };
+    match since {
+        Some(since) => println!(
+            "{} since {}",
+            runs,
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        None => println!("{}", runs),
+    }
+    let projects: BTreeMap<String, Total> = ledger::by_project(&entries)
+        .into_iter()
+        .map(|(workspace, total)| (workspace.display().to_string(), total))
+        .collect();
+    print_table("Project", &projects);
+    print_table("Model", &ledger::by_model(&entries));
+
+    let mut total = Total::default();
+    for (_, project) in projects {
+        total.input_tokens += project.input_tokens;
+        total.output_tokens += project.output_tokens;
+        total.cost_usd += project.cost_usd;
+        total.unpriced |= project.unpriced;
+    }
+    println!(
+        "\nTotal: {} input and {} output tokens, {}",
+        total.input_tokens,
+        total.output_tokens,
+        cost_text(&total)
+    );
+    Ok(())
+}
+
+/// 料金の高い順に表示する
+fn print_table(label: &str, totals: &BTreeMap<String, Total>) {
+    let mut rows: Vec<_> = totals.iter().collect();
+    rows.sort_by(|a, b| b.1.cost_usd.total_cmp(&a.1.cost_usd));
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .chain([label.len()])
+        .max()
+        .unwrap_or(0);
+    println!(
+        "\n{:<width$}  {:>5}  {:>12}  {:>12}  {:>10}",
+        label, "Runs", "Input", "Output", "Cost"
+    );
+    for (name, total) in rows {
+        println!(
+            "{:<width$}  {:>5}  {:>12}  {:>12}  {:>10}",
+            name,
+            total.runs,
+            total.input_tokens,
+            total.output_tokens,
+            cost_text(total)
+        );
+    }
+}
+
+/// 料金が不明なものを含む場合は "+" を付ける（下限であることを示す）
+fn cost_text(total: &Total) -> String {
+    let unpriced = if total.unpriced { "+" } else { "" };
+    format!("${:.4}{}", total.cost_usd, unpriced)
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::agent::{Agent, AgentArgs};
+use crate::anthropic::Message;
+use crate::config::Config;
+use crate::error::AgentError;
+use crate::output;
+use crate::session::Session;
+use crate::templates;
+use crate::tools::{FileTracker, IgnoreMatcher};
+
+/// Re-run a task whenever watched files change
+#[derive(clap::Args, Debug)]
+pub struct WatchArgs {
+    /// Task to run on every change; `{{files}}` is replaced with the changed files
+    /// (otherwise they are listed after the task)
+    #[arg(value_name = "TASK")]
+    pub task: String,
+
+    /// Files to watch, as a glob relative to the workspace (can be repeated)
+    #[arg(long = "on-change", value_name = "GLOB", required = true)]
+    pub patterns: Vec<String>,
+
+    /// Wait until no file has changed for this long before running the task
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    pub debounce_ms: u64,
+
+    #[command(flatten)]
+    pub agent: AgentArgs,
+}
+
+/// 監視するファイル（`--on-change` に一致し、`ignore` と .git に含まれないもの）
+struct WatchFilter {
+    patterns: GlobSet,
+    ignore: IgnoreMatcher,
+    workspace: PathBuf,
+}
+
+impl WatchFilter {
+    fn new(patterns: &[String], ignore: &[String], workspace: &Path) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(
+                Glob::new(pattern)
+                    .with_context(|| format!("Invalid --on-change glob: {}", pattern))?,
+            );
+        }
+        let workspace = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.to_path_buf());
+        Ok(Self {
+            patterns: builder
+                .build()
+                .context("Failed to build --on-change globs")?,
+            ignore: IgnoreMatcher::new(ignore, &workspace)?,
+            workspace,
+        })
+    }
+
+    /// 監視対象ならワークスペースからの相対パスを返す
+    fn matches(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.workspace).ok()?;
+        if relative.starts_with(".git") || self.ignore.is_ignored(relative) {
+            return None;
+        }
+        self.patterns
+            .is_match(relative)
+            .then(|| relative.to_string_lossy().into_owned())
+    }
+}
+
+/// `watch`: ファイルの変更を待ち、変更のたびにタスクを新しい会話で実行する
+///
+/// タスク中のエージェント自身の編集で再び実行しないよう、実行中の変更は捨てる
+pub async fn run(args: WatchArgs, config: Config, workspace: &Path) -> Result<()> {
+    let filter = WatchFilter::new(&args.patterns, &config.ignore, workspace)?;
+    let api_key = args.agent.api_key()?;
+    let debounce = Duration::from_millis(args.debounce_ms);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .context("Failed to start the file watcher")?;
+    watcher
+        .watch(&filter.workspace, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {:?}", filter.workspace))?;
+    eprintln!(
+        "Watching {} for changes (Ctrl-C to stop)",
+        args.patterns.join(", ")
+    );
+
+    loop {
+        // 最初の変更を待ち、変更が落ち着くまで集める
+        let mut changed = BTreeSet::new();
+        while changed.is_empty() {
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            };
+            let Some(event) = event else {
+                return Ok(());
+            };
+            collect(&filter, event, &mut changed);
+        }
+        while let Ok(Some(event)) = tokio::time::timeout(debounce, rx.recv()).await {
+            collect(&filter, event, &mut changed);
+        }
+
+        let files: Vec<String> = changed.into_iter().collect();
+        eprintln!("Changed: {}", files.join(", "));
+        let message = task_message(&args.task, &files)?;
+        let mut config = config.clone();
+        config.agent.task = Some(message.clone());
+        let agent = Agent::new(
+            &args.agent,
+            config,
+            api_key.clone(),
+            workspace,
+            FileTracker::new(),
+            None,
+        )?;
+        match agent.send(vec![Message::user_text(&message)]).await {
+            Ok(result) => {
+                let mut session = Session::new(workspace, &agent.model);
+                session.messages = result.conversation.clone();
+                session.record_usage(&result);
+                agent.name_session(&mut session).await;
+                if let Err(e) = session.save() {
+                    tracing::warn!("Failed to save session: {:#}", e);
+                }
+                output::print_result(&result, &agent.output)?;
+            }
+            // Ctrl-C で中断した場合は監視もやめる
+            Err(e) if matches!(e.downcast_ref::<AgentError>(), Some(AgentError::Cancelled)) => {
+                return Err(e)
+            }
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+
+        // 実行中（とその直後に届く）変更はエージェント自身の編集なので捨てる
+        tokio::time::sleep(debounce).await;
+        while rx.try_recv().is_ok() {}
+        eprintln!("Watching for changes…");
+    }
+}
+
+/// 変更のイベントから監視対象のファイルを集める（読み取りなどのイベントは除く）
+fn collect(
+    filter: &WatchFilter,
+    event: notify::Result<notify::Event>,
+    changed: &mut BTreeSet<String>,
+) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!("File watcher error: {}", e);
+            return;
+        }
+    };
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        return;
+    }
+    changed.extend(event.paths.iter().filter_map(|path| filter.matches(path)));
+}
+
+/// タスクに変更されたファイルを埋め込む（`{{files}}` がなければ後ろに並べる）
+fn task_message(task: &str, files: &[String]) -> Result<String> {
+    let list = files
+        .iter()
+        .map(|file| format!("- {}", file))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if task.contains("{{") {
+        return templates::render(task, &[("files".to_string(), list)]);
+    }
+    Ok(format!("{}\n\nChanged files:\n{}", task.trim_end(), list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempWorkspace;
+
+    #[test]
+    fn test_filters_changes_and_fills_the_task() {
+        let workspace = TempWorkspace::new();
+        let filter = WatchFilter::new(
+            &["src/**".to_string(), "*.toml".to_string()],
+            &["src/generated/**".to_string()],
+            workspace.root(),
+        )
+        .unwrap();
+        assert_eq!(
+            filter.matches(&workspace.path("src/main.rs")).as_deref(),
+            Some("src/main.rs")
+        );
+        assert_eq!(
+            filter.matches(&workspace.path("Cargo.toml")).as_deref(),
+            Some("Cargo.toml")
+        );
+        assert_eq!(filter.matches(&workspace.path("README.md")), None);
+        assert_eq!(filter.matches(&workspace.path("src/generated/a.rs")), None);
+        assert_eq!(filter.matches(Path::new("/elsewhere/src/main.rs")), None);
+
+        let files = ["src/a.rs".to_string(), "src/b.rs".to_string()];
+        assert_eq!(
+            task_message("Fix the errors in:\n{{files}}", &files).unwrap(),
+            "Fix the errors in:\n- src/a.rs\n- src/b.rs"
+        );
+        assert_eq!(
+            task_message("Fix any new compile errors", &files).unwrap(),
+            "Fix any new compile errors\n\nChanged files:\n- src/a.rs\n- src/b.rs"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::de::{DeTable, DeValue};
+
+use super::Config;
+use crate::capabilities;
+
+/// A problem found in a config file
+#[derive(Debug)]
+pub struct Issue {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: ", self.file.display(), line)?,
+            None => write!(f, "{}: ", self.file.display())?,
+        }
+        if self.key.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.key, self.message)
+        }
+    }
+}
+
+/// Deserialize a config value, collecting the dotted paths of unknown keys
+pub fn deserialize_with_unknown_keys(value: toml::Value) -> Result<(Config, Vec<String>)> {
+    let mut unknown = Vec::new();
+    let config = serde_ignored::deserialize(value, |path| unknown.push(path.to_string()))
+        .context("Failed to parse config")?;
+    Ok((config, unknown))
+}
+
+/// Validate a single config file, reporting unknown keys, type errors,
+/// unexpected model names and out-of-range values
+pub fn validate_file(path: &Path) -> Result<Vec<Issue>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {:?}", path))?;
+    let issue = |key: &str, line: Option<usize>, message: String| Issue {
+        file: path.to_path_buf(),
+        line,
+        key: key.to_string(),
+        message,
+    };
+
+    // 構文エラーは位置情報付きでそのまま報告する
+    let spanned = match DeTable::parse(&content) {
+        Ok(table) => table,
+        Err(e) => {
+            let line = e.span().map(|span| line_of(&content, span.start));
+            return Ok(vec![issue("", line, e.message().to_string())]);
+        }
+    };
+    let table = spanned.get_ref();
+    let raw: toml::Value = toml::from_str(&content).context("Failed to parse config file")?;
+
+    let (config, unknown) = match deserialize_with_unknown_keys(raw) {
+        Ok(result) => result,
+        Err(_) => {
+            // 型エラーは toml のエラーメッセージに行番号が含まれる
+            let e = toml::from_str::<Config>(&content).unwrap_err();
+            let line = e.span().map(|span| line_of(&content, span.start));
+            return Ok(vec![issue("", line, e.message().to_string())]);
+        }
+    };
+
+    let mut issues = Vec::new();
+    for key in unknown {
+        let line = find_key_line(&content, table, &key);
+        issues.push(issue(&key, line, "unknown key".to_string()));
+    }
+
+    // デフォルト値は常に妥当なので、ファイルに明示されたキーだけを検査する
+    let mut check = |key: &str, ok: bool, message: &str| {
+        if ok {
+            return;
+        }
+        if let Some(line) = find_key_line(&content, table, key) {
+            issues.push(issue(key, Some(line), message.to_string()));
+        }
+    };
+
+    check(
+        "model.default",
+        is_known_model(&config.model.default),
+        "model name should start with \"claude-\"",
+    );
+    check(
+        "model.max_tokens",
+        config.model.max_tokens > 0,
+        "must be greater than 0",
+    );
+    check(
+        "model.temperature",
+        config
+            .model
+            .temperature
+            .is_none_or(|t| (0.0..=1.0).contains(&t)),
+        "must be between 0.0 and 1.0",
+    );
+    check(
+        "model.top_p",
+        config.model.top_p.is_none_or(|p| (0.0..=1.0).contains(&p)),
+        "must be between 0.0 and 1.0",
+    );
+    check(
+        "model.fallback",
+        config.model.fallback.as_deref().is_none_or(is_known_model),
+        "model name should start with \"claude-\"",
+    );
+    check(
+        "model.routing.tool_model",
+        config
+            .model
+            .routing
+            .tool_model
+            .as_deref()
+            .is_none_or(|model| is_known_model(model) && capabilities::supports_tools(model)),
+        "must be a Claude model that supports tools",
+    );
+    check(
+        "agent.max_iterations",
+        (1..=200).contains(&config.agent.max_iterations),
+        "must be between 1 and 200",
+    );
+    check(
+        "agent.max_cost_usd",
+        config.agent.max_cost_usd.is_none_or(|cost| cost > 0.0),
+        "must be greater than 0",
+    );
+    check(
+        "agent.max_turn_timeout_secs",
+        config.agent.max_turn_timeout_secs != Some(0),
+        "must be greater than 0",
+    );
+    check(
+        "agent.deadline_secs",
+        config.agent.deadline_secs != Some(0),
+        "must be greater than 0",
+    );
+    check(
+        "api.timeout_secs",
+        config.api.timeout_secs > 0,
+        "must be greater than 0",
+    );
+    check(
+        "api.max_retries",
+        config.api.max_retries <= 10,
+        "must be 10 or less",
+    );
+    check(
+        "api.base_url",
+        config.api.base_url.starts_with("http://") || config.api.base_url.starts_with("https://"),
+        "must start with http:// or https://",
+    );
+    check(
+        "github.api_url",
+        config.github.api_url.starts_with("http://")
+            || config.github.api_url.starts_with("https://"),
+        "must start with http:// or https://",
+    );
+    check(
+        "telemetry.otlp_endpoint",
+        config
+            .telemetry
+            .otlp_endpoint
+            .as_ref()
+            .is_none_or(|endpoint| {
+                endpoint.starts_with("http://") || endpoint.starts_with("https://")
+            }),
+        "must start with http:// or https://",
+    );
+    for webhook in &config.webhooks {
+        check(
+            "webhooks.url",
+            webhook.url.starts_with("http://") || webhook.url.starts_with("https://"),
+            "must start with http:// or https://",
+        );
+        check(
+            "webhooks.events",
+            !webhook.events.is_empty(),
+            "must not be empty",
+        );
+    }
+    check(
+        "sandbox.image",
+        !config.sandbox.image.trim().is_empty(),
+        "must not be empty",
+    );
+    check(
+        "agent.repo_map_max_bytes",
+        config.agent.repo_map_max_bytes > 0,
+        "must be greater than 0",
+    );
+    check(
+        "agent.title_model",
+        !config.agent.title_model.trim().is_empty(),
+        "must not be empty",
+    );
+    check(
+        "agent.attachments_max_tokens",
+        config.agent.attachments_max_tokens > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.readFile.max_bytes",
+        config.tools.read_file.max_bytes > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.listFiles.max_entries",
+        config.tools.list_files.max_entries > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.searchInDirectory.max_matches",
+        config.tools.search_in_directory.max_matches > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.searchInDirectory.max_file_bytes",
+        config.tools.search_in_directory.max_file_bytes > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.listFiles.concurrency",
+        config.tools.list_files.concurrency > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.searchInDirectory.concurrency",
+        config.tools.search_in_directory.concurrency > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.getDiagnostics.command",
+        !config.tools.get_diagnostics.command.is_empty(),
+        "must not be empty",
+    );
+    check(
+        "tools.getDiagnostics.timeout_secs",
+        config.tools.get_diagnostics.timeout_secs > 0,
+        "must be greater than 0",
+    );
+    check(
+        "tools.getDiagnostics.max_diagnostics",
+        config.tools.get_diagnostics.max_diagnostics > 0,
+        "must be greater than 0",
+    );
+    check(
+        "verify.command",
+        config.verify.check != Some(super::VerifyCheck::Command)
+            || !config.verify.command.is_empty(),
+        "must not be empty when verify.check is \"command\"",
+    );
+    check(
+        "verify.timeout_secs",
+        config.verify.timeout_secs > 0,
+        "must be greater than 0",
+    );
+    for tool in &config.tools.custom {
+        check(
+            "tools.custom",
+            !tool.command.is_empty(),
+            &format!("{}: command must not be empty", tool.name),
+        );
+        check(
+            "tools.custom",
+            tool.timeout_secs > 0,
+            &format!("{}: timeout_secs must be greater than 0", tool.name),
+        );
+        check(
+            "tools.custom",
+            tool.schema["type"] == "object",
+            &format!("{}: schema must be an object schema", tool.name),
+        );
+    }
+    for (name, profile) in &config.profiles {
+        if let Some(model) = &profile.model {
+            check(
+                &format!("profiles.{}.model", name),
+                is_known_model(model),
+                "model name should start with \"claude-\"",
+            );
+        }
+        if let Some(max_tokens) = profile.max_tokens {
+            check(
+                &format!("profiles.{}.max_tokens", name),
+                max_tokens > 0,
+                "must be greater than 0",
+            );
+        }
+    }
+
+    Ok(issues)
+}
+
+fn is_known_model(model: &str) -> bool {
+    model.starts_with("claude-")
+}
+
+/// 1-based line number of a byte offset
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+/// Find the line where a dotted key is defined
+fn find_key_line(content: &str, table: &DeTable<'_>, key: &str) -> Option<usize> {
+    let mut current = table;
+    let mut parts = key.split('.').peekable();
+
+    while let Some(part) = parts.next() {
+        let (k, v) = current.iter().find(|(k, _)| k.get_ref() == part)?;
+        if parts.peek().is_none() {
+            return Some(line_of(content, k.span().start));
+        }
+        match v.get_ref() {
+            DeValue::Table(next) => current = next,
+            _ => return Some(line_of(content, k.span().start)),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_unknown_and_out_of_range_keys() {
+        let dir = std::env::temp_dir().join(format!("config_validate_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            "[model]\ndefault = \"gpt-4\"\n\n[agent]\nmax_iteratons = 5\nmax_iterations = 0\n",
+        )
+        .unwrap();
+
+        let issues = validate_file(&path).unwrap();
+        let summary: Vec<(String, Option<usize>)> =
+            issues.iter().map(|i| (i.key.clone(), i.line)).collect();
+        assert!(summary.contains(&("agent.max_iteratons".to_string(), Some(5))));
+        assert!(summary.contains(&("agent.max_iterations".to_string(), Some(6))));
+        assert!(summary.contains(&("model.default".to_string(), Some(2))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
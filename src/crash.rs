@@ -0,0 +1,160 @@
+//! パニック時のクラッシュレポート（~/.codex/crash/<時刻>/）
+//!
+//! 長い実行の途中で落ちても作業を追えるよう、進行中の会話・最後の API リクエスト・
+//! バックトレースを書き出してパスを表示する。会話とリクエストは API キーなどを伏せて書く
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::anthropic::Message;
+use crate::config::Config;
+
+/// 伏せる秘密の値の接頭辞（この後に続く英数字と `-` `_` を伏せる）
+const SECRET_PREFIXES: [&str; 7] = [
+    "sk-ant-",
+    "ghp_",
+    "gho_",
+    "ghs_",
+    "github_pat_",
+    "xoxb-",
+    "AKIA",
+];
+
+/// 値そのものを伏せる環境変数
+const SECRET_VARS: [&str; 3] = ["ANTHROPIC_API_KEY", "GITHUB_TOKEN", "GH_TOKEN"];
+
+/// パニックの時点で書き出す内容（実行中に更新する）
+struct State {
+    conversation: Option<Vec<Message>>,
+    /// 最後に送ったリクエストの JSON
+    last_request: Option<String>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    conversation: None,
+    last_request: None,
+});
+
+/// パニックしたスレッドが持っていたロックでも読めるようにする
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// パニックフックを入れる（既定のフックでメッセージを表示した後にレポートを書く）
+pub fn install() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+        match write_report(info) {
+            Ok(dir) => eprintln!("Crash report written to {}", dir.display()),
+            Err(e) => eprintln!("Failed to write a crash report: {:#}", e),
+        }
+    }));
+}
+
+/// 進行中の会話を記録する
+pub fn record_conversation(messages: &[Message]) {
+    state().conversation = Some(messages.to_vec());
+}
+
+/// 送るリクエストを記録する
+pub fn record_request(request: &impl Serialize) {
+    if let Ok(json) = serde_json::to_string(request) {
+        state().last_request = Some(json);
+    }
+}
+
+fn write_report(info: &PanicHookInfo) -> Result<PathBuf> {
+    let dir = Config::codex_home()?.join("crash").join(format!(
+        "{}-{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+
+    let thread = std::thread::current();
+    let backtrace = format!(
+        "thread '{}' {}\n\n{}\n",
+        thread.name().unwrap_or("<unnamed>"),
+        info,
+        Backtrace::force_capture()
+    );
+    write(&dir, "backtrace.txt", &backtrace)?;
+
+    // パニックしたのが記録中のスレッドでもロックを待たない
+    let state = match STATE.try_lock() {
+        Ok(state) => state,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => return Ok(dir),
+    };
+    if let Some(conversation) = &state.conversation {
+        let json = serde_json::to_string_pretty(conversation)
+            .context("Failed to serialize the conversation")?;
+        write(&dir, "conversation.json", &redact(&json))?;
+    }
+    if let Some(request) = &state.last_request {
+        let json = serde_json::from_str::<serde_json::Value>(request)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .unwrap_or_else(|_| request.clone());
+        write(&dir, "request.json", &redact(&json))?;
+    }
+    Ok(dir)
+}
+
+fn write(dir: &std::path::Path, name: &str, content: &str) -> Result<()> {
+    let path = dir.join(name);
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// API キーやトークンらしい値を伏せる
+fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for var in SECRET_VARS {
+        if let Ok(value) = std::env::var(var) {
+            if value.len() >= 8 {
+                text = text.replace(&value, "[REDACTED]");
+            }
+        }
+    }
+    for prefix in SECRET_PREFIXES {
+        let mut redacted = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find(prefix) {
+            let after = &rest[start + prefix.len()..];
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(after.len());
+            redacted.push_str(&rest[..start]);
+            if len >= 8 {
+                redacted.push_str(prefix);
+                redacted.push_str("[REDACTED]");
+            } else {
+                redacted.push_str(&rest[start..start + prefix.len() + len]);
+            }
+            rest = &after[len..];
+        }
+        redacted.push_str(rest);
+        text = redacted;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_tokens() {
+        let text =
+            r#"{"content":"key=sk-ant-REDACTED\nAKIA (see ghp_0123456789abcdef)"}"#;
+        assert_eq!(
+            redact(text),
+            r#"{"content":"key=sk-ant-[REDACTED]\nAKIA (see ghp_[REDACTED])"}"#
+        );
+    }
+}
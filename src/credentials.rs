@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use keyring::Entry;
+
+/// OS キーリングに保存する際のサービス名とユーザー名
+const SERVICE: &str = "coding-agent-example";
+const USER: &str = "anthropic_api_key";
+const GITHUB_USER: &str = "github_token";
+
+fn entry(user: &str) -> Result<Entry> {
+    Entry::new(SERVICE, user).context("Failed to access the OS keyring")
+}
+
+/// API キーを OS キーリングに保存
+pub fn store_api_key(api_key: &str) -> Result<()> {
+    entry(USER)?
+        .set_password(api_key)
+        .context("Failed to store API key in the OS keyring")
+}
+
+/// OS キーリングから API キーを取得（未保存やキーリングが使えない場合は None）
+pub fn load_api_key() -> Option<String> {
+    load(USER, "API key")
+}
+
+/// OS キーリングから API キーを削除
+pub fn delete_api_key() -> Result<()> {
+    delete(USER).context("Failed to delete API key from the OS keyring")
+}
+
+/// GitHub のトークンを OS キーリングに保存
+pub fn store_github_token(token: &str) -> Result<()> {
+    entry(GITHUB_USER)?
+        .set_password(token)
+        .context("Failed to store GitHub token in the OS keyring")
+}
+
+/// OS キーリングから GitHub のトークンを取得（未保存やキーリングが使えない場合は None）
+pub fn load_github_token() -> Option<String> {
+    load(GITHUB_USER, "GitHub token")
+}
+
+/// OS キーリングから GitHub のトークンを削除
+pub fn delete_github_token() -> Result<()> {
+    delete(GITHUB_USER).context("Failed to delete GitHub token from the OS keyring")
+}
+
+fn load(user: &str, what: &str) -> Option<String> {
+    match entry(user).and_then(|e| e.get_password().map_err(Into::into)) {
+        Ok(secret) if !secret.is_empty() => {
+            tracing::debug!("Using {} from the OS keyring", what);
+            Some(secret)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            tracing::debug!("No {} in the OS keyring: {}", what, e);
+            None
+        }
+    }
+}
+
+fn delete(user: &str) -> Result<()> {
+    match entry(user)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
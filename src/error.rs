@@ -0,0 +1,78 @@
+use std::fmt;
+use std::process::ExitCode;
+
+use crate::anthropic::TimeLimit;
+
+/// 終了コードで区別するエラー
+///
+/// それ以外のエラーは終了コード 1 になる
+#[derive(Debug)]
+pub enum AgentError {
+    /// 最大反復回数までに最終応答が得られなかった
+    MaxIterations(usize),
+    /// ユーザーが中断した（Ctrl-C）
+    Cancelled,
+    /// API の呼び出しに失敗した（再試行後も失敗した場合を含む）
+    Api(String),
+    /// 推定コストが上限を超えた
+    BudgetExceeded { limit: f64, spent: f64 },
+    /// 時間制限で打ち切った（部分的な結果は表示済み）
+    TimedOut(TimeLimit),
+}
+
+impl AgentError {
+    /// スクリプトから判別するための終了コード
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AgentError::MaxIterations(_) => 2,
+            AgentError::Cancelled => 3,
+            AgentError::Api(_) => 4,
+            AgentError::BudgetExceeded { .. } => 5,
+            AgentError::TimedOut(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::MaxIterations(max) => {
+                write!(f, "Max iterations ({}) reached without final response", max)
+            }
+            AgentError::Cancelled => write!(f, "Cancelled by user"),
+            AgentError::Api(message) => write!(f, "{}", message),
+            AgentError::BudgetExceeded { limit, spent } => write!(
+                f,
+                "Estimated cost ${:.4} exceeded the budget of ${:.4}",
+                spent, limit
+            ),
+            AgentError::TimedOut(limit) => write!(f, "Stopped early: {}", limit),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+/// エラーに対応する終了コード（エラーの原因をたどって `AgentError` を探す）
+pub fn exit_code(error: &anyhow::Error) -> ExitCode {
+    let code = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<AgentError>())
+        .map_or(1, AgentError::exit_code);
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code_through_context() {
+        let error = Err::<(), _>(AgentError::Cancelled)
+            .context("Run failed")
+            .unwrap_err();
+        assert_eq!(exit_code(&error), ExitCode::from(3));
+        assert_eq!(exit_code(&anyhow::anyhow!("other")), ExitCode::from(1));
+    }
+}
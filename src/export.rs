@@ -0,0 +1,308 @@
+//! セッションの書き出し（`sessions show --export html`）
+//!
+//! 外部のファイルを参照しない 1 枚の HTML にする。ツールの呼び出しは折りたたみ、
+//! ファイルの書き込みは会話の中で直前に分かっている内容との差分として色付けする
+
+use serde_json::Value;
+use similar::TextDiff;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::anthropic::{ContentBlock, MessageContent};
+use crate::output::tool_output_text;
+use crate::session::Session;
+use crate::ui::highlight::{escape_html, highlight_html, split_code_blocks, Segment};
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', sans-serif; max-width: 960px; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { font-size: 1.5em; margin-bottom: 0.2em; }
+.meta { color: #666; font-size: 0.9em; }
+table { border-collapse: collapse; margin: 1em 0; font-size: 0.9em; }
+th, td { border: 1px solid #ddd; padding: 0.3em 0.8em; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+.message { margin: 1em 0; padding: 0.6em 1em; border-radius: 6px; }
+.user { background: #eef4ff; }
+.assistant { background: #f6f6f6; }
+.role { font-weight: bold; font-size: 0.8em; color: #555; text-transform: uppercase; }
+.text { white-space: pre-wrap; }
+pre { padding: 0.6em; overflow-x: auto; border-radius: 4px; }
+details.tool { margin: 0.5em 0; border: 1px solid #ddd; border-radius: 4px; padding: 0.3em 0.6em; background: #fff; }
+details.tool summary { cursor: pointer; font-family: monospace; }
+details.error summary { color: #b00; }
+.label { font-size: 0.8em; color: #666; margin-top: 0.5em; }
+";
+
+/// セッションを 1 枚の HTML にする（現在の分岐の会話）
+pub fn session_html(session: &Session) -> String {
+    // ツールの結果は呼び出しと一緒に表示する
+    let mut results: HashMap<&str, (&str, bool)> = HashMap::new();
+    for message in &session.messages {
+        if let MessageContent::Blocks(blocks) = &message.content {
+            for block in blocks {
+                if let ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                } = block
+                {
+                    results.insert(tool_use_id, (content, is_error.unwrap_or(false)));
+                }
+            }
+        }
+    }
+
+    let mut files = HashMap::new();
+    let mut tools: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    let mut body = String::new();
+    for message in &session.messages {
+        let mut content = String::new();
+        match &message.content {
+            MessageContent::Text(text) => push_text(&mut content, text),
+            MessageContent::Blocks(blocks) => {
+                for block in blocks {
+                    match block {
+                        ContentBlock::Text { text } => push_text(&mut content, text),
+                        ContentBlock::ToolUse { id, name, input } => {
+                            let (result, is_error) =
+                                results.get(id.as_str()).copied().unwrap_or_default();
+                            let count = tools.entry(name).or_default();
+                            count.0 += 1;
+                            count.1 += usize::from(is_error);
+                            push_tool(&mut content, name, input, result, is_error, &mut files);
+                        }
+                        ContentBlock::ToolResult { .. } => {}
+                    }
+                }
+            }
+        }
+        if content.is_empty() {
+            continue;
+        }
+        let _ = write!(
+            body,
+            "<div class=\"message {0}\"><div class=\"role\">{0}</div>\n{1}</div>\n",
+            escape_html(&message.role),
+            content
+        );
+    }
+
+    let title = escape_html(&session.title());
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, STYLE, title
+    );
+    let _ = writeln!(
+        html,
+        "<div class=\"meta\">Session {} · {} · {} · {} – {}</div>",
+        escape_html(&session.id),
+        escape_html(&session.workspace.display().to_string()),
+        escape_html(&session.model),
+        session.created_at.format("%Y-%m-%d %H:%M UTC"),
+        session.updated_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+    push_usage(&mut html, session, &tools);
+    html.push_str(&body);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// 使用量とツールごとの呼び出し回数の表
+fn push_usage(html: &mut String, session: &Session, tools: &BTreeMap<&str, (usize, usize)>) {
+    let usage = &session.usage;
+    let _ = writeln!(
+        html,
+        "<table>\n<tr><th>Turns</th><th>Input</th><th>Output</th><th>Cache write</th><th>Cache read</th><th>Estimated cost</th></tr>\n<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>${:.4}</td></tr>\n</table>",
+        Session::turns(&session.messages),
+        usage.input_tokens,
+        usage.output_tokens,
+        usage.cache_creation_input_tokens,
+        usage.cache_read_input_tokens,
+        session.cost_usd
+    );
+    if tools.is_empty() {
+        return;
+    }
+    html.push_str("<table>\n<tr><th>Tool</th><th>Calls</th><th>Errors</th></tr>\n");
+    for (name, (calls, errors)) in tools {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(name),
+            calls,
+            errors
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// テキストを段落とハイライトしたコードブロックにする
+fn push_text(html: &mut String, text: &str) {
+    for segment in split_code_blocks(text) {
+        match segment {
+            Segment::Text(text) if text.trim().is_empty() => {}
+            Segment::Text(text) => {
+                let _ = writeln!(
+                    html,
+                    "<div class=\"text\">{}</div>",
+                    escape_html(text.trim())
+                );
+            }
+            Segment::Code { lang, code } => html.push_str(&highlight_html(&code, lang)),
+        }
+    }
+}
+
+/// ツールの呼び出しを折りたたみにする（書き込みは差分、それ以外は入力と結果）
+///
+/// `files` は readFile の結果と書き込んだ内容から分かっている各ファイルの内容
+fn push_tool(
+    html: &mut String,
+    name: &str,
+    input: &Value,
+    result: &str,
+    is_error: bool,
+    files: &mut HashMap<String, String>,
+) {
+    let path = input.get("path").and_then(Value::as_str);
+    let class = if is_error { "tool error" } else { "tool" };
+    let status = if is_error { " (error)" } else { "" };
+    let _ = writeln!(
+        html,
+        "<details class=\"{}\"><summary>{} {}{}</summary>",
+        class,
+        escape_html(name),
+        escape_html(path.unwrap_or("")),
+        status
+    );
+
+    let written = match name {
+        "writeFile" => input.get("content"),
+        "editFile" => input.get("new_content"),
+        _ => None,
+    }
+    .and_then(Value::as_str);
+    match (path, written) {
+        (Some(path), Some(new)) => {
+            html.push_str(&file_change(path, files.get(path).map(String::as_str), new));
+            if !is_error {
+                files.insert(path.to_string(), new.to_string());
+            }
+        }
+        _ => {
+            html.push_str("<div class=\"label\">Input</div>\n");
+            let input = serde_json::to_string_pretty(input).unwrap_or_default();
+            html.push_str(&highlight_html(&input, "json"));
+        }
+    }
+
+    let output = tool_output_text(result);
+    if name == "readFile" && !is_error && input.get("start_line").is_none_or(Value::is_null) {
+        if let Some(path) = path {
+            files.insert(path.to_string(), output.clone());
+        }
+    }
+    if !output.is_empty() {
+        html.push_str("<div class=\"label\">Result</div>\n");
+        let lang = match name {
+            "readFile" => path.and_then(extension).unwrap_or(""),
+            _ => "",
+        };
+        html.push_str(&highlight_html(&output, lang));
+    }
+    html.push_str("</details>\n");
+}
+
+/// 書き込みを直前の内容との統一差分にする（内容が分からなければ新しい内容をそのまま）
+fn file_change(path: &str, old: Option<&str>, new: &str) -> String {
+    match old {
+        Some(old) => {
+            let diff = TextDiff::from_lines(old, new)
+                .unified_diff()
+                .context_radius(3)
+                .header(&format!("a/{}", path), &format!("b/{}", path))
+                .to_string();
+            highlight_html(&diff, "diff")
+        }
+        None => highlight_html(new, extension(path).unwrap_or("")),
+    }
+}
+
+fn extension(path: &str) -> Option<&str> {
+    Path::new(path).extension().and_then(|ext| ext.to_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::Message;
+    use serde_json::json;
+
+    #[test]
+    fn test_exports_tool_calls_with_diffs() {
+        let mut session = Session::new(Path::new("/work"), "claude-sonnet-4-5");
+        let blocks = |blocks: Vec<ContentBlock>| MessageContent::Blocks(blocks);
+        let call = |id: &str, name: &str, input: Value| ContentBlock::ToolUse {
+            id: id.to_string(),
+            name: name.to_string(),
+            input,
+        };
+        let result = |id: &str, content: &str| ContentBlock::ToolResult {
+            tool_use_id: id.to_string(),
+            content: json!({ "content": content }).to_string(),
+            is_error: None,
+        };
+        session.messages = vec![
+            Message::user_text("Rename <old> to new"),
+            Message {
+                role: "assistant".to_string(),
+                content: blocks(vec![call("t1", "readFile", json!({ "path": "a.txt" }))]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: blocks(vec![result("t1", "old line\nsame\n")]),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: blocks(vec![call(
+                    "t2",
+                    "editFile",
+                    json!({ "path": "a.txt", "new_content": "new line\nsame\n" }),
+                )]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: blocks(vec![result("t2", "Edited a.txt")]),
+            },
+            Message::assistant_text("Done:\n```rust\nfn main() {}\n```"),
+        ];
+        session.usage.input_tokens = 1200;
+        session.cost_usd = 0.0123;
+
+        let html = session_html(&session);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Rename &lt;old&gt; to new</title>"));
+        assert!(html.contains("<td>1200</td>") && html.contains("$0.0123"));
+        assert!(html.contains("<tr><td>editFile</td><td>1</td><td>0</td></tr>"));
+        assert!(html.contains("<details class=\"tool\"><summary>editFile a.txt</summary>"));
+        // 差分はハイライトされ、HTML の中で行ごとに分かれる
+        let text = strip_tags(&html);
+        assert!(text
+            .contains("--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-old line\n+new line\n same\n"));
+        assert!(text.contains("fn main() {}"));
+    }
+
+    fn strip_tags(html: &str) -> String {
+        let mut text = String::new();
+        let mut in_tag = false;
+        for c in html.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                c if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        text
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::GitHubConfig;
+
+/// GitHub API へのリクエストのタイムアウト
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// GitHub のリポジトリ（owner/name）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    pub owner: String,
+    pub name: String,
+}
+
+impl std::fmt::Display for Repo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+/// origin リモートが `host` 上にあればそのリポジトリを返す
+pub fn origin_repo(workspace: &Path, host: &str) -> Option<Repo> {
+    let output = std::process::Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .current_dir(workspace)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_remote(String::from_utf8_lossy(&output.stdout).trim(), host)
+}
+
+/// `https://host/owner/name(.git)`、`git@host:owner/name(.git)`、
+/// `ssh://git@host/owner/name(.git)` の形のリモート URL を解釈する
+fn parse_remote(url: &str, host: &str) -> Option<Repo> {
+    let path = ["https://", "http://", "ssh://"]
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+        .and_then(|rest| {
+            // user@ とポートを除く
+            let rest = rest.split_once('@').map_or(rest, |(_, rest)| rest);
+            let (authority, path) = rest.split_once('/')?;
+            (authority.split(':').next() == Some(host)).then_some(path)
+        })
+        .or_else(|| {
+            let (user_host, path) = url.split_once(':')?;
+            let remote_host = user_host.split_once('@').map_or(user_host, |(_, h)| h);
+            (remote_host == host).then_some(path)
+        })?;
+
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, name) = path.split_once('/')?;
+    if owner.is_empty() || name.is_empty() || name.contains('/') {
+        return None;
+    }
+    Some(Repo {
+        owner: owner.to_string(),
+        name: name.to_string(),
+    })
+}
+
+/// GitHub REST API のクライアント（1 つのリポジトリ用）
+#[derive(Debug, Clone)]
+pub struct GitHubClient {
+    http: reqwest::Client,
+    api_url: String,
+    token: Option<String>,
+    pub repo: Repo,
+}
+
+impl GitHubClient {
+    pub fn new(config: &GitHubConfig, repo: Repo) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            http,
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            token: config.resolve_token(),
+            repo,
+        })
+    }
+
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// リポジトリ配下の API（`/repos/{owner}/{name}{path}`）を呼び出す
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        let url = format!("{}/repos/{}{}", self.api_url, self.repo, path);
+        let mut request = self
+            .http
+            .request(method, &url)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to {}", url))?;
+        let status = response.status();
+        let value: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let message = value["message"].as_str().unwrap_or("no details");
+            // 検証エラーは errors に理由が入っている
+            let details: Vec<&str> = value["errors"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e["message"].as_str())
+                .collect();
+            if details.is_empty() {
+                bail!("GitHub API returned {}: {}", status, message);
+            }
+            bail!(
+                "GitHub API returned {}: {} ({})",
+                status,
+                message,
+                details.join("; ")
+            );
+        }
+        Ok(value)
+    }
+
+    async fn get(&self, path: &str) -> Result<Value> {
+        self.send(reqwest::Method::GET, path, None).await
+    }
+
+    /// issue（プルリクエストも含む）
+    pub async fn issue(&self, number: u64) -> Result<Value> {
+        self.get(&format!("/issues/{}", number)).await
+    }
+
+    /// issue のコメント（古い順に最大 `limit` 件）
+    pub async fn issue_comments(&self, number: u64, limit: usize) -> Result<Vec<Value>> {
+        let comments = self
+            .get(&format!("/issues/{}/comments?per_page={}", number, limit))
+            .await?;
+        Ok(comments.as_array().cloned().unwrap_or_default())
+    }
+
+    pub async fn pull_request(&self, number: u64) -> Result<Value> {
+        self.get(&format!("/pulls/{}", number)).await
+    }
+
+    pub async fn default_branch(&self) -> Result<String> {
+        let repo = self.get("").await?;
+        repo["default_branch"]
+            .as_str()
+            .map(str::to_string)
+            .context("GitHub did not return the default branch")
+    }
+
+    pub async fn create_pull_request(&self, body: &Value) -> Result<Value> {
+        self.send(reqwest::Method::POST, "/pulls", Some(body)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote() {
+        let repo = Some(Repo {
+            owner: "octo".to_string(),
+            name: "agent".to_string(),
+        });
+        assert_eq!(
+            parse_remote("https://github.com/octo/agent.git", "github.com"),
+            repo
+        );
+        assert_eq!(
+            parse_remote("git@github.com:octo/agent.git", "github.com"),
+            repo
+        );
+        assert_eq!(
+            parse_remote("ssh://git@github.com:22/octo/agent", "github.com"),
+            repo
+        );
+        assert_eq!(
+            parse_remote("https://token@github.com/octo/agent/", "github.com"),
+            repo
+        );
+        assert_eq!(
+            parse_remote("https://gitlab.com/octo/agent.git", "github.com"),
+            None
+        );
+        assert_eq!(parse_remote("/srv/git/agent.git", "github.com"), None);
+    }
+}
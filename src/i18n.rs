@@ -0,0 +1,43 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::config::Language;
+
+/// `language` の設定値
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+/// ツールの説明・結果、確認プロンプト、システムプロンプトの言語を設定する
+pub fn set_language(language: Language) {
+    let value = match language {
+        Language::En => 0,
+        Language::Ja => 1,
+    };
+    LANGUAGE.store(value, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::Ja,
+        _ => Language::En,
+    }
+}
+
+/// 設定された言語の方を選ぶ（プロンプトやヘルプなどの長い定数用）
+pub fn pick<'a>(en: &'a str, ja: &'a str) -> &'a str {
+    match language() {
+        Language::En => en,
+        Language::Ja => ja,
+    }
+}
+
+/// 設定された言語の文字列を返す（英語と日本語を並べて書く）
+///
+/// `tr!("Saved {}", "{} を保存しました", path)` のように format! と同じ引数を取る
+macro_rules! tr {
+    ($en:literal, $ja:literal $(, $arg:expr)* $(,)?) => {
+        match $crate::i18n::language() {
+            $crate::config::Language::En => format!($en $(, $arg)*),
+            $crate::config::Language::Ja => format!($ja $(, $arg)*),
+        }
+    };
+}
+pub(crate) use tr;
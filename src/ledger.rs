@@ -0,0 +1,270 @@
+//! 実行ごとの使用量の記録（~/.codex/usage.jsonl）
+//!
+//! 1 回の実行（`Agent::send`）ごとに 1 行を追記し、`usage` コマンドでプロジェクトやモデルごとの
+//! 料金を集計する。個人やチームの予算管理のための記録で、API の請求額そのものではない
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::anthropic::{ConversationResult, Usage};
+use crate::config::Config;
+use crate::pricing;
+
+/// 記録の 1 行（1 回の実行）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub timestamp: DateTime<Utc>,
+    pub workspace: PathBuf,
+    /// 実行のモデル（反復ごとのモデルは `models`）
+    pub model: String,
+    pub duration_ms: u64,
+    pub iterations: usize,
+    #[serde(flatten)]
+    pub usage: Usage,
+    /// 料金が不明なモデルを含む場合は None
+    pub cost_usd: Option<f64>,
+    /// モデルごとの内訳（ルーティングや過負荷での切り替えがあると複数になる）
+    pub models: BTreeMap<String, ModelUsage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelUsage {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub cost_usd: Option<f64>,
+}
+
+impl LedgerEntry {
+    pub fn new(
+        workspace: &Path,
+        result: &ConversationResult,
+        duration: std::time::Duration,
+    ) -> Self {
+        let mut models: BTreeMap<String, ModelUsage> = BTreeMap::new();
+        for step in &result.steps {
+            let entry = models.entry(step.model.clone()).or_default();
+            entry.usage.add(&step.usage);
+        }
+        if models.is_empty() {
+            models.insert(
+                result.model.clone(),
+                ModelUsage {
+                    usage: result.usage,
+                    cost_usd: None,
+                },
+            );
+        }
+        for (model, entry) in models.iter_mut() {
+            entry.cost_usd = pricing::estimate_cost(model, &entry.usage);
+        }
+
+        Self {
+            timestamp: Utc::now(),
+            workspace: workspace.to_path_buf(),
+            model: result.model.clone(),
+            duration_ms: duration.as_millis() as u64,
+            iterations: result.iterations,
+            usage: result.usage,
+            cost_usd: result.cost_usd(),
+            models,
+        }
+    }
+}
+
+/// Get the usage ledger path (~/.codex/usage.jsonl)
+pub fn ledger_path() -> Result<PathBuf> {
+    Ok(Config::codex_home()?.join("usage.jsonl"))
+}
+
+/// 記録に 1 行を追記する
+pub fn append(entry: &LedgerEntry) -> Result<()> {
+    let path = ledger_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create ~/.codex")?;
+    }
+    let mut line = serde_json::to_string(entry).context("Failed to serialize usage entry")?;
+    line.push('\n');
+    // 1 回の write で書き、並行する実行の行が混ざらないようにする
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Failed to write usage ledger {:?}", path))
+}
+
+/// `since` 以降の記録を読み込む（読めない行は飛ばす）
+pub fn load(since: Option<DateTime<Utc>>) -> Result<Vec<LedgerEntry>> {
+    let path = ledger_path()?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<LedgerEntry>(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                tracing::debug!("Skipping unreadable usage entry: {}", e);
+                None
+            }
+        })
+        .filter(|entry| since.is_none_or(|since| entry.timestamp >= since))
+        .collect())
+}
+
+/// "7d" / "12h" / "30m" / "2w" の形の期間
+pub fn parse_period(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let split = text.len() - text.chars().last().map_or(0, char::len_utf8);
+    let (number, unit) = text.split_at(split);
+    let Ok(number) = number.parse::<i64>() else {
+        bail!(
+            "Invalid period {:?} (expected e.g. 7d, 12h, 30m or 2w)",
+            text
+        );
+    };
+    match unit {
+        "m" => Ok(Duration::minutes(number)),
+        "h" => Ok(Duration::hours(number)),
+        "d" => Ok(Duration::days(number)),
+        "w" => Ok(Duration::weeks(number)),
+        _ => bail!(
+            "Invalid period {:?} (expected e.g. 7d, 12h, 30m or 2w)",
+            text
+        ),
+    }
+}
+
+/// 集計の 1 行
+#[derive(Debug, Default, PartialEq)]
+pub struct Total {
+    pub runs: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    /// 料金が不明な実行（またはモデル）があった
+    pub unpriced: bool,
+}
+
+impl Total {
+    fn add(&mut self, usage: &Usage, cost_usd: Option<f64>) {
+        self.runs += 1;
+        self.input_tokens += u64::from(usage.input_tokens)
+            + u64::from(usage.cache_creation_input_tokens)
+            + u64::from(usage.cache_read_input_tokens);
+        self.output_tokens += u64::from(usage.output_tokens);
+        match cost_usd {
+            Some(cost) => self.cost_usd += cost,
+            None => self.unpriced = true,
+        }
+    }
+}
+
+/// プロジェクト（ワークスペース）ごとの集計
+pub fn by_project(entries: &[LedgerEntry]) -> BTreeMap<PathBuf, Total> {
+    let mut totals: BTreeMap<PathBuf, Total> = BTreeMap::new();
+    for entry in entries {
+        totals
+            .entry(entry.workspace.clone())
+            .or_default()
+            .add(&entry.usage, entry.cost_usd);
+    }
+    totals
+}
+
+/// モデルごとの集計（`runs` はそのモデルを使った実行の数）
+pub fn by_model(entries: &[LedgerEntry]) -> BTreeMap<String, Total> {
+    let mut totals: BTreeMap<String, Total> = BTreeMap::new();
+    for entry in entries {
+        for (model, usage) in &entry.models {
+            totals
+                .entry(model.clone())
+                .or_default()
+                .add(&usage.usage, usage.cost_usd);
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(workspace: &str, models: &[(&str, u32, Option<f64>)]) -> LedgerEntry {
+        let mut usage = Usage::default();
+        let mut cost = Some(0.0);
+        let models: BTreeMap<String, ModelUsage> = models
+            .iter()
+            .map(|&(model, tokens, cost_usd)| {
+                let model_usage = Usage {
+                    input_tokens: tokens,
+                    output_tokens: tokens / 10,
+                    ..Usage::default()
+                };
+                usage.add(&model_usage);
+                cost = cost.zip(cost_usd).map(|(a, b)| a + b);
+                (
+                    model.to_string(),
+                    ModelUsage {
+                        usage: model_usage,
+                        cost_usd,
+                    },
+                )
+            })
+            .collect();
+        LedgerEntry {
+            timestamp: Utc::now(),
+            workspace: PathBuf::from(workspace),
+            model: models.keys().next().unwrap().clone(),
+            duration_ms: 1000,
+            iterations: 1,
+            usage,
+            cost_usd: cost,
+            models,
+        }
+    }
+
+    #[test]
+    fn test_summarizes_by_project_and_model() {
+        let entries = vec![
+            entry("/work/api", &[("claude-sonnet-4-5", 1000, Some(0.5))]),
+            entry(
+                "/work/api",
+                &[
+                    ("claude-sonnet-4-5", 2000, Some(1.0)),
+                    ("claude-haiku-4-5", 500, Some(0.1)),
+                ],
+            ),
+            entry("/work/web", &[("custom-model", 100, None)]),
+        ];
+
+        let projects = by_project(&entries);
+        let api = &projects[Path::new("/work/api")];
+        assert_eq!(
+            (api.runs, api.input_tokens, api.output_tokens),
+            (2, 3500, 350)
+        );
+        assert!((api.cost_usd - 1.6).abs() < 1e-9 && !api.unpriced);
+        assert!(projects[Path::new("/work/web")].unpriced);
+
+        let models = by_model(&entries);
+        assert_eq!(models["claude-sonnet-4-5"].runs, 2);
+        assert_eq!(models["claude-haiku-4-5"].input_tokens, 500);
+
+        let line = serde_json::to_string(&entries[1]).unwrap();
+        let parsed: LedgerEntry = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed.models.len(), 2);
+
+        assert_eq!(parse_period("7d").unwrap(), Duration::days(7));
+        assert_eq!(parse_period("12h").unwrap(), Duration::hours(12));
+        assert!(parse_period("7").is_err());
+        assert!(parse_period("d").is_err());
+    }
+}
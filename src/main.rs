@@ -1,106 +1,370 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
+mod agent;
 mod anthropic;
+mod attachments;
+mod capabilities;
+mod checkpoint;
+mod commands;
 mod config;
+mod crash;
+mod credentials;
+mod error;
+mod export;
+mod github;
+mod i18n;
+mod ledger;
+mod outline;
+mod output;
+mod platform;
+mod policy;
+mod pricing;
+mod relevance;
+mod response_cache;
+mod sandbox;
+mod session;
 mod system_prompt;
+mod telemetry;
+mod templates;
+#[cfg(any(test, feature = "test-support"))]
+// 結合テスト用の部品なので、このクレートのテストで使わないものもある
+#[allow(dead_code)]
+mod test_support;
+mod tokens;
 mod tools;
-use anthropic::{AnthropicClient, ContentBlock, ToolRegistry};
-use system_prompt::build_system_prompt;
-use tools::{EditFileTool, ListFilesTool, ReadFileTool, SearchInDirectoryTool, WriteFileTool};
+mod ui;
+mod verify;
+mod webhooks;
+mod worktree;
+use commands::run::RunArgs;
+use config::{Config, TelemetryConfig, Verbosity};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Mutex;
+use tracing_subscriber::filter::{filter_fn, FilterExt, LevelFilter, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
 
 /// Anthropic Claude CLI Agent
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Anthropic Claude CLI Agent")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// User message/prompt to send to Claude
-    #[arg(value_name = "MESSAGE")]
-    message: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Anthropic API key (can also be set via ANTHROPIC_API_KEY env var)
-    #[arg(long, env = "ANTHROPIC_API_KEY")]
-    api_key: String,
+    /// Run as if started in this directory (the workspace root for tools and project config)
+    #[arg(long, short = 'C', global = true, value_name = "DIR")]
+    cwd: Option<PathBuf>,
 
-    /// Model to use
-    #[arg(long, short = 'm', default_value = "claude-sonnet-4-5")]
-    model: String,
+    /// Also write logs and spans (API calls, tool executions) as JSON lines to this file,
+    /// at debug level unless RUST_LOG says otherwise
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
 
-    /// Maximum tokens to generate
-    #[arg(long, default_value = "1024")]
-    max_tokens: u32,
+    #[command(flatten)]
+    run: RunArgs,
+}
 
-    /// Maximum tool use iterations
-    #[arg(long, default_value = "5")]
-    max_iterations: usize,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a single message (same as `agent "<msg>"`)
+    Run(commands::run::RunArgs),
+    /// Start an interactive chat session (supports /reload)
+    Chat(commands::chat::ChatArgs),
+    /// Start the full-screen terminal UI (conversation, tool activity, approvals, usage)
+    Tui(commands::chat::ChatArgs),
+    /// Run each prompt in a file as its own conversation and write a summary
+    Batch(commands::batch::BatchArgs),
+    /// Re-run a task whenever files matching --on-change globs change
+    Watch(commands::watch::WatchArgs),
+    /// Serve a REST/JSON API for submitting tasks, streaming events and answering confirmations
+    Serve(commands::serve::ServeArgs),
+    /// Speak the Agent Client Protocol on stdin/stdout so editors can embed the agent
+    Acp(commands::acp::AcpArgs),
+    /// List the built-in and custom tools and whether they are enabled
+    Tools,
+    /// Manage the config file (~/.codex/config.toml)
+    #[command(subcommand)]
+    Config(commands::config::ConfigCommand),
+    /// Manage saved sessions (~/.codex/sessions)
+    #[command(subcommand)]
+    Sessions(commands::sessions::SessionsCommand),
+    /// Estimate the token count of files or stdin without calling the API
+    Tokens(commands::tokens::TokensArgs),
+    /// Summarize the usage and cost recorded in ~/.codex/usage.jsonl per project and model
+    Usage(commands::usage::UsageArgs),
+    /// List the models available to the API key
+    Models(commands::models::ModelsArgs),
+    /// Store the API key (or a GitHub token with --github) in the OS keyring
+    Login(commands::login::LoginArgs),
+    /// Remove the API key (or the GitHub token with --github) from the OS keyring
+    Logout(commands::login::LoginArgs),
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // ロギング初期化
-    tracing_subscriber::fmt()
-        .with_env_filter("coding_agent_example=debug")
+/// ロギング初期化（ログは標準出力の結果と混ざらないよう標準エラーへ出力）
+///
+/// `RUST_LOG` が設定されていれば出力レベルより優先する。`verbosity` が None ならログは出さない。
+/// `telemetry` のエンドポイントが設定されていればスパンを OTLP へ送り、返した値の drop で送り終える。
+/// `log_file`（`--log-file`）には出力レベルに関係なくログと閉じたスパンを JSON で 1 行ずつ書く
+fn init_tracing(
+    verbosity: Option<Verbosity>,
+    telemetry: Option<&TelemetryConfig>,
+    log_file: Option<File>,
+) -> Option<telemetry::Telemetry> {
+    let fmt = verbosity.map(|verbosity| {
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+            EnvFilter::new(match verbosity {
+                Verbosity::Quiet => "coding_agent_example=warn",
+                Verbosity::Normal => "coding_agent_example=info",
+                Verbosity::Verbose => "coding_agent_example=debug",
+                Verbosity::Debug => "coding_agent_example=trace,reqwest=debug",
+            })
+        });
+        // トレース用のスパンはログの各行に付けない
+        tracing_subscriber::fmt::layer()
+            .with_writer(|| ui::progress::LogWriter)
+            .with_ansi(platform::ansi_supported())
+            .with_filter(filter.and(filter_fn(|metadata| !metadata.is_span())))
+    });
+
+    // スパンは閉じたときに所要時間（time.busy）と記録したフィールドを付けて 1 行にする
+    let json = log_file.map(|file| {
+        let filter = EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new("coding_agent_example=debug"));
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(Mutex::new(file))
+            .with_span_events(FmtSpan::CLOSE)
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_filter(filter)
+    });
+
+    // スパンは出力レベルに関係なく info 以上を送る
+    let (otel, guard, error) = match telemetry.map(telemetry::layer).transpose() {
+        Ok(Some(Some((layer, guard)))) => (Some(layer), Some(guard), None),
+        Ok(_) => (None, None, None),
+        Err(e) => (None, None, Some(e)),
+    };
+    let otel = otel.map(|layer| {
+        layer.with_filter(Targets::new().with_target("coding_agent_example", LevelFilter::INFO))
+    });
+    tracing_subscriber::registry()
+        .with(fmt)
+        .with(json)
+        .with(otel)
         .init();
 
+    if let Some(e) = error {
+        tracing::warn!("Traces are not exported: {:#}", e);
+    }
+    guard
+}
+
+fn main() -> ExitCode {
     // load environment variables from .env file
     dotenv().ok();
 
     // CLI引数のパース
     let args = Args::parse();
 
-    // APIキーの検証
-    if args.api_key.is_empty() {
-        anyhow::bail!(
-            "ANTHROPIC_API_KEY is required. Set via environment variable or --api-key flag."
-        );
-    }
+    // 予期しない失敗でも会話を失わないよう、パニック時にクラッシュレポートを書く
+    crash::install();
 
-    tracing::info!("Sending message to Claude API");
-
-    let client = AnthropicClient::new(args.api_key);
-
-    // ToolRegistry の作成
-    let mut tool_registry = ToolRegistry::new();
-    tool_registry.register(ReadFileTool::schema(), ReadFileTool::new());
-    tool_registry.register(ListFilesTool::schema(), ListFilesTool::new());
-    tool_registry.register(
-        SearchInDirectoryTool::schema(),
-        SearchInDirectoryTool::new(),
-    );
-    tool_registry.register(WriteFileTool::schema(), WriteFileTool::new());
-    tool_registry.register(EditFileTool::schema(), EditFileTool::new());
-
-    let schemas = tool_registry.get_schemas();
-    let tool_names: Vec<&str> = schemas.iter().map(|t| t.name.as_str()).collect();
-    tracing::info!("Registered tools: {}", tool_names.join(", "));
-
-    // システムプロンプトの構築
-    let system_prompt = build_system_prompt();
-
-    // ツールを使った会話を実行
-    let result = client
-        .execute_with_tools(
-            &args.model,
-            args.max_tokens,
-            &args.message,
-            &tool_registry,
-            args.max_iterations,
-            Some(system_prompt),
-        )
-        .await?;
-
-    // レスポンスの表示
-    println!("\n--- Claude's Response ---");
-    for block in &result.response.content {
-        if let ContentBlock::Text { text } = block {
-            println!("{}", text);
+    // 失敗の種類をスクリプトから判別できるよう終了コードを分ける
+    // (0: 成功, 1: その他, 2: 最大反復回数, 3: ユーザーによる中断, 4: API エラー, 5: 予算超過,
+    //  6: 時間制限)
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            ui::progress::hide();
+            eprintln!("Error: {:?}", e);
+            error::exit_code(&e)
         }
     }
+}
 
-    // メタデータの表示
-    println!("\n--- Metadata ---");
-    println!("Iterations: {}", result.iterations);
-    println!("Input tokens: {}", result.response.usage.input_tokens);
-    println!("Output tokens: {}", result.response.usage.output_tokens);
+/// 非同期の処理を実行する
+///
+/// Landlock の制限は有効にしたスレッドとその後に作られたスレッドにしか及ばないので、
+/// ランタイム（ワーカースレッド）はサンドボックスを有効にしてから作る
+fn block_on<F: Future>(future: F) -> Result<F::Output> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the async runtime")?;
+    Ok(runtime.block_on(future))
+}
+
+fn run(args: Args) -> Result<()> {
+    // 作業ディレクトリの変更（ツールの相対パスもここを基準に解決される）
+    if let Some(cwd) = &args.cwd {
+        std::env::set_current_dir(cwd)
+            .with_context(|| format!("Failed to change directory to {:?}", cwd))?;
+    }
+
+    let log_file = match &args.log_file {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {:?}", path))?,
+        ),
+        None => None,
+    };
 
-    Ok(())
+    // 引数がメッセージだけの場合は `run` として扱う（互換性のため）
+    let command = match args.command {
+        Some(command) => command,
+        None => Command::Run(args.run),
+    };
+
+    // サブコマンドの実行（設定ファイルを使うコマンドは設定の出力レベルでロギングを初期化）
+    let workspace = std::env::current_dir()?;
+    match command {
+        Command::Run(run_args) => {
+            let config = run_args.agent.load_config(&workspace)?;
+            let restriction = sandbox::restrict_process(&config.sandbox, &workspace)?;
+            let _telemetry = init_tracing(
+                Some(run_args.agent.verbosity(&config)),
+                Some(&config.telemetry),
+                log_file,
+            );
+            restriction.log();
+            block_on(async {
+                if run_args.isolated {
+                    worktree::run_isolated(&workspace, |workspace| async move {
+                        commands::run::run(run_args, config, &workspace).await
+                    })
+                    .await
+                } else {
+                    commands::run::run(run_args, config, &workspace).await
+                }
+            })?
+        }
+        Command::Chat(chat_args) => {
+            let config = chat_args.agent.load_config(&workspace)?;
+            let restriction = sandbox::restrict_process(&config.sandbox, &workspace)?;
+            let _telemetry = init_tracing(
+                Some(chat_args.agent.verbosity(&config)),
+                Some(&config.telemetry),
+                log_file,
+            );
+            restriction.log();
+            block_on(async {
+                if chat_args.isolated {
+                    worktree::run_isolated(&workspace, |workspace| async move {
+                        commands::chat::run(chat_args, config, &workspace).await
+                    })
+                    .await
+                } else {
+                    commands::chat::run(chat_args, config, &workspace).await
+                }
+            })?
+        }
+        Command::Tui(chat_args) => {
+            // ログが画面を崩さないよう TUI ではログを出さない（トレースは送る）
+            let config = chat_args.agent.load_config(&workspace)?;
+            let restriction = sandbox::restrict_process(&config.sandbox, &workspace)?;
+            let _telemetry = init_tracing(None, Some(&config.telemetry), log_file);
+            restriction.log();
+            block_on(async {
+                if chat_args.isolated {
+                    worktree::run_isolated(&workspace, |workspace| async move {
+                        commands::tui::run(chat_args, config, &workspace).await
+                    })
+                    .await
+                } else {
+                    commands::tui::run(chat_args, config, &workspace).await
+                }
+            })?
+        }
+        Command::Batch(batch_args) => {
+            let config = batch_args.agent.load_config(&workspace)?;
+            let restriction = sandbox::restrict_process(&config.sandbox, &workspace)?;
+            let _telemetry = init_tracing(
+                Some(batch_args.agent.verbosity(&config)),
+                Some(&config.telemetry),
+                log_file,
+            );
+            restriction.log();
+            block_on(commands::batch::run(batch_args, config, &workspace))?
+        }
+        Command::Watch(watch_args) => {
+            let config = watch_args.agent.load_config(&workspace)?;
+            let restriction = sandbox::restrict_process(&config.sandbox, &workspace)?;
+            let _telemetry = init_tracing(
+                Some(watch_args.agent.verbosity(&config)),
+                Some(&config.telemetry),
+                log_file,
+            );
+            restriction.log();
+            block_on(commands::watch::run(watch_args, config, &workspace))?
+        }
+        Command::Serve(serve_args) => {
+            let config = serve_args.agent.load_config(&workspace)?;
+            let restriction = sandbox::restrict_process(&config.sandbox, &workspace)?;
+            let _telemetry = init_tracing(
+                Some(serve_args.agent.verbosity(&config)),
+                Some(&config.telemetry),
+                log_file,
+            );
+            restriction.log();
+            block_on(commands::serve::run(serve_args, config, &workspace))?
+        }
+        Command::Acp(acp_args) => {
+            // 標準出力はプロトコル専用なので、ログは設定の出力レベルで標準エラーへ出す
+            let config = acp_args.agent.load_config(&workspace)?;
+            let _telemetry = init_tracing(
+                Some(acp_args.agent.verbosity(&config)),
+                Some(&config.telemetry),
+                log_file,
+            );
+            // セッションごとにクライアントが作業ディレクトリを選ぶので、起動時の場所には制限しない
+            if config.sandbox.landlock {
+                tracing::warn!("sandbox.landlock is not applied to acp sessions");
+            }
+            block_on(commands::acp::run(acp_args))?
+        }
+        Command::Tools => {
+            let config = Config::load_for_workspace(&workspace, false)?;
+            init_tracing(Some(config.output.verbosity), None, log_file);
+            commands::tools::run(&config)
+        }
+        Command::Tokens(tokens_args) => {
+            init_tracing(Some(Verbosity::Normal), None, log_file);
+            commands::tokens::run(tokens_args)
+        }
+        Command::Usage(usage_args) => {
+            init_tracing(Some(Verbosity::Normal), None, log_file);
+            commands::usage::run(usage_args)
+        }
+        Command::Models(models_args) => {
+            let config = Config::load_for_workspace(&workspace, false)?;
+            init_tracing(Some(config.output.verbosity), None, log_file);
+            block_on(commands::models::run(models_args, &config))?
+        }
+        Command::Config(command) => {
+            init_tracing(Some(Verbosity::Normal), None, log_file);
+            commands::config::run(command)
+        }
+        Command::Sessions(command) => {
+            init_tracing(Some(Verbosity::Normal), None, log_file);
+            commands::sessions::run(command)
+        }
+        Command::Login(login_args) => {
+            init_tracing(Some(Verbosity::Normal), None, log_file);
+            commands::login::login(login_args)
+        }
+        Command::Logout(login_args) => {
+            init_tracing(Some(Verbosity::Normal), None, log_file);
+            commands::login::logout(login_args)
+        }
+    }
 }
@@ -0,0 +1,134 @@
+//! ソースファイルのトップレベルのシンボルの抽出（リポジトリマップと大きなファイルの readFile で使う）
+//!
+//! 構文解析はせず、インデントのない行を言語ごとのキーワードで見分ける
+
+use std::path::Path;
+
+/// シンボルを抽出できる言語のファイルか
+pub fn is_supported(path: &Path) -> bool {
+    extractor(path).is_some()
+}
+
+/// トップレベルのシンボルと行番号（1 から）。対応していない言語は空
+pub fn top_level_symbols(path: &Path, content: &str) -> Vec<(usize, String)> {
+    let Some(extract) = extractor(path) else {
+        return Vec::new();
+    };
+    // インデントのない行だけをトップレベルとみなす
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with(char::is_whitespace))
+        .filter_map(|(i, line)| extract(line).map(|symbol| (i + 1, symbol)))
+        .collect()
+}
+
+fn extractor(path: &Path) -> Option<fn(&str) -> Option<String>> {
+    let ext = path.extension().and_then(|ext| ext.to_str())?;
+    let extract: fn(&str) -> Option<String> = match ext {
+        "rs" => rust_symbol,
+        "py" => python_symbol,
+        "js" | "jsx" | "ts" | "tsx" | "mjs" => js_symbol,
+        "go" => go_symbol,
+        _ => return None,
+    };
+    Some(extract)
+}
+
+fn rust_symbol(line: &str) -> Option<String> {
+    let line = ["pub(crate) ", "pub(super) ", "pub "]
+        .iter()
+        .find_map(|vis| line.strip_prefix(vis))
+        .unwrap_or(line);
+    let line = ["async ", "unsafe ", "const "]
+        .iter()
+        .fold(line, |line, prefix| {
+            // `const NAME` は定数なので `const fn` の場合だけ外す
+            match line.strip_prefix(prefix) {
+                Some(rest) if *prefix != "const " || rest.starts_with("fn ") => rest,
+                _ => line,
+            }
+        });
+    if line.starts_with("impl") {
+        let head = line.split(['{', ';']).next()?;
+        let head = head.split(" where").next()?;
+        return Some(head.trim().to_string());
+    }
+    let symbol = keyword_symbol(
+        line,
+        &[
+            "fn",
+            "struct",
+            "enum",
+            "trait",
+            "type",
+            "mod",
+            "macro_rules!",
+        ],
+    )?;
+    (symbol != "mod tests").then_some(symbol)
+}
+
+fn python_symbol(line: &str) -> Option<String> {
+    let line = line.strip_prefix("async ").unwrap_or(line);
+    keyword_symbol(line, &["def", "class"])
+}
+
+fn js_symbol(line: &str) -> Option<String> {
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let line = line.strip_prefix("default ").unwrap_or(line);
+    let line = line.strip_prefix("async ").unwrap_or(line);
+    keyword_symbol(line, &["function", "class", "interface", "type", "enum"])
+}
+
+fn go_symbol(line: &str) -> Option<String> {
+    // メソッドはレシーバを外して名前だけにする
+    let line = match line.strip_prefix("func (") {
+        Some(rest) => format!("func {}", rest.split_once(") ")?.1),
+        None => line.to_string(),
+    };
+    keyword_symbol(&line, &["func", "type"])
+}
+
+/// `keyword name` の形の行を `keyword name` として返す
+fn keyword_symbol(line: &str, keywords: &[&str]) -> Option<String> {
+    keywords.iter().find_map(|keyword| {
+        let rest = line.strip_prefix(keyword)?.strip_prefix(' ')?;
+        let name: String = rest
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+            .collect();
+        (!name.is_empty()).then(|| format!("{} {}", keyword, name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols_by_language() {
+        assert_eq!(
+            rust_symbol("pub(crate) async fn run(args: Args)"),
+            Some("fn run".to_string())
+        );
+        assert_eq!(
+            rust_symbol("impl<T: Clone> Trait for Foo<T> where T: Send {"),
+            Some("impl<T: Clone> Trait for Foo<T>".to_string())
+        );
+        assert_eq!(rust_symbol("const MAX: usize = 3;"), None);
+        assert_eq!(rust_symbol("mod tests {"), None);
+        assert_eq!(
+            python_symbol("class Agent(Base):"),
+            Some("class Agent".to_string())
+        );
+        assert_eq!(
+            js_symbol("export default async function handler(req) {"),
+            Some("function handler".to_string())
+        );
+        assert_eq!(
+            go_symbol("func (s *Server) Start(ctx context.Context) error {"),
+            Some("func Start".to_string())
+        );
+    }
+}
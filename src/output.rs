@@ -0,0 +1,386 @@
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use termimad::MadSkin;
+
+use crate::anthropic::{progress_text, AgentEvent, ContentBlock, ConversationResult, Usage};
+use crate::config::{OutputFormat, Verbosity};
+use crate::pricing;
+use crate::tokens;
+use crate::ui::highlight::{self, Segment};
+use crate::ui::{progress, style};
+
+/// CLI 引数と設定ファイルから解決した出力設定
+#[derive(Debug, Clone, Copy)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub verbosity: Verbosity,
+    /// 最終応答の Markdown を端末向けに整形する（色が無効な場合は常にそのまま表示）
+    pub render_markdown: bool,
+}
+
+/// 会話の結果を出力形式に応じて表示する
+pub fn print_result(result: &ConversationResult, options: &OutputOptions) -> Result<()> {
+    match options.format {
+        OutputFormat::Text => {
+            print_text(result, options);
+            Ok(())
+        }
+        OutputFormat::Json => {
+            let document = json_document(result);
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&document).context("Failed to serialize result")?
+            );
+            Ok(())
+        }
+        OutputFormat::StreamJson => {
+            let mut document = json_document(result);
+            document["type"] = json!("result");
+            println!(
+                "{}",
+                serde_json::to_string(&document).context("Failed to serialize result")?
+            );
+            Ok(())
+        }
+    }
+}
+
+/// イベントを 1 行の JSON として標準出力へ書き出す（`--output stream-json`）
+pub fn emit_event(event: &AgentEvent) {
+    let Ok(line) = serde_json::to_string(event) else {
+        return;
+    };
+    let mut stdout = std::io::stdout().lock();
+    // 受け取り側が逐次処理できるよう行ごとにフラッシュする
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+/// 最終応答のテキスト部分を連結する
+pub fn final_text(result: &ConversationResult) -> String {
+    result
+        .response
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// ツールの呼び出しと結果を標準エラーへ表示する（-v / -vv）
+///
+/// -v では結果を先頭の数行に省略し、-vv では全文を表示する。
+/// readFile の結果は呼び出し時のパスの拡張子でハイライトする
+pub struct ToolEventPrinter {
+    verbosity: Verbosity,
+    /// tool_use id → readFile のパス
+    read_paths: Mutex<HashMap<String, String>>,
+}
+
+impl ToolEventPrinter {
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self {
+            verbosity,
+            read_paths: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn print(&self, event: &AgentEvent) {
+        progress::suspend(|| self.print_event(event));
+    }
+
+    fn print_event(&self, event: &AgentEvent) {
+        match event {
+            AgentEvent::ToolCall { id, name, input } => {
+                if name == "readFile" {
+                    if let Some(path) = input.get("path").and_then(|p| p.as_str()) {
+                        self.read_paths
+                            .lock()
+                            .unwrap()
+                            .insert(id.clone(), path.to_string());
+                    }
+                }
+                eprintln!("{} {} {}", style::cyan("▶"), style::bold(name), input);
+            }
+            AgentEvent::ToolResult {
+                id,
+                name,
+                is_error,
+                content,
+                duration_ms,
+                tokens,
+                cached,
+            } => {
+                let status = if *is_error {
+                    style::red("error")
+                } else if *cached {
+                    style::green("ok, cached")
+                } else {
+                    style::green("ok")
+                };
+                eprintln!(
+                    "{} {} ({}, {} ms, ~{} tokens)",
+                    style::cyan("◀"),
+                    style::bold(name),
+                    status,
+                    duration_ms,
+                    tokens::format(*tokens)
+                );
+                let text = tool_output_text(content);
+                let body = if self.verbosity == Verbosity::Debug {
+                    text
+                } else {
+                    abbreviate(&text, 5)
+                };
+                let body = match self.read_paths.lock().unwrap().remove(id) {
+                    Some(path) if !*is_error => {
+                        let ext = Path::new(&path)
+                            .extension()
+                            .and_then(|ext| ext.to_str())
+                            .unwrap_or("");
+                        highlight::highlight(&body, ext)
+                    }
+                    _ => style::dim(&body),
+                };
+                for line in body.lines() {
+                    eprintln!("  {}", line);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 待ち時間の状態をスピナーで表示する
+///
+/// 例: "Iteration 2/5 — running searchInDirectory…"、
+/// ツールが途中経過を通知した場合は "Iteration 2/5 — running searchInDirectory: 40% src/lib.rs"
+#[derive(Default)]
+pub struct ProgressReporter {
+    status: Mutex<String>,
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&self, event: &AgentEvent) {
+        let mut status = self.status.lock().unwrap();
+        match event {
+            AgentEvent::IterationStart {
+                iteration,
+                max_iterations,
+            } => {
+                *status = format!("Iteration {}/{}", iteration, max_iterations);
+                progress::show(format!("{} — waiting for Claude…", status));
+            }
+            AgentEvent::ToolCall { name, .. } => {
+                progress::show(format!("{} — running {}…", status, name));
+            }
+            AgentEvent::ToolProgress {
+                name,
+                percent,
+                message,
+                ..
+            } => {
+                progress::show(format!(
+                    "{} — running {}: {}",
+                    status,
+                    name,
+                    progress_text(*percent, message)
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// ツール結果（JSON 文字列）から本文を取り出す（エスケープされた改行を戻して読みやすくする）
+pub fn tool_output_text(content: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(content)
+        .ok()
+        .and_then(|value| {
+            let body = value.get("error").or(value.get("content"))?;
+            body.as_str().map(str::to_string)
+        })
+        .unwrap_or_else(|| content.to_string())
+}
+
+/// 先頭 `max_lines` 行だけを残し、省略した行数を付記する
+fn abbreviate(text: &str, max_lines: usize) -> String {
+    let total = text.lines().count();
+    let mut shown = text.lines().take(max_lines).collect::<Vec<_>>().join("\n");
+    if total > max_lines {
+        shown.push_str(&format!("\n… ({} more lines)", total - max_lines));
+    }
+    shown
+}
+
+/// Markdown を端末向けに整形する（見出し・リスト・表、コードブロックはシンタックスハイライト）
+fn render_markdown(text: &str) -> String {
+    let skin = MadSkin::default();
+    let mut rendered = String::new();
+    for segment in highlight::split_code_blocks(text) {
+        match segment {
+            Segment::Text(text) => rendered.push_str(&skin.term_text(&text).to_string()),
+            Segment::Code { lang, code } => {
+                rendered.push_str(&style::dim(&format!("─── {}", lang)));
+                rendered.push('\n');
+                rendered.push_str(&highlight::highlight(&code, lang));
+                rendered.push_str(&style::dim("───"));
+                rendered.push('\n');
+            }
+        }
+    }
+    rendered
+}
+
+fn print_text(result: &ConversationResult, options: &OutputOptions) {
+    let text = final_text(result);
+    let text = if options.render_markdown && style::color_enabled() {
+        render_markdown(&text)
+    } else {
+        text
+    };
+
+    if options.verbosity == Verbosity::Quiet {
+        println!("{}", text);
+        return;
+    }
+
+    // レスポンスの表示
+    println!("\n--- Claude's Response ---");
+    println!("{}", text);
+    if let Some(limit) = result.timed_out {
+        println!("\n(Stopped early: {}; showing the partial result)", limit);
+    }
+    if let Some(original) = &result.fallback_from {
+        println!(
+            "\n({} was overloaded; answered with {})",
+            original, result.model
+        );
+    }
+
+    // 使用量の表示
+    println!("\n--- Usage ---");
+    print!("{}", usage_table(result));
+    if !result.tool_stats.is_empty() {
+        println!("Tool calls:");
+        for (name, stats) in &result.tool_stats {
+            print!(
+                "  {:<20} calls: {:>3}  errors: {:>3}  time: {:>8.2?}",
+                name, stats.calls, stats.errors, stats.total_duration
+            );
+            if stats.cached > 0 {
+                print!("  cached: {}", stats.cached);
+            }
+            println!();
+        }
+    }
+}
+
+/// 反復ごとのトークン数・ツール呼び出し数・所要時間・推定料金の表
+fn usage_table(result: &ConversationResult) -> String {
+    let cost =
+        |cost: Option<f64>| cost.map_or_else(|| "-".to_string(), |cost| format!("${:.4}", cost));
+    let row = |label: &str, usage: &Usage, tools: usize, time: Duration, spent: Option<f64>| {
+        format!(
+            "{:>5} {:>9} {:>9} {:>9} {:>9} {:>5} {:>9.2?} {:>9}\n",
+            label,
+            usage.input_tokens,
+            usage.output_tokens,
+            usage.cache_creation_input_tokens,
+            usage.cache_read_input_tokens,
+            tools,
+            time,
+            cost(spent)
+        )
+    };
+
+    let mut table = format!(
+        "{:>5} {:>9} {:>9} {:>9} {:>9} {:>5} {:>9} {:>9}\n",
+        "Iter", "Input", "Output", "Cache W", "Cache R", "Tools", "Time", "Cost"
+    );
+    for (i, step) in result.steps.iter().enumerate() {
+        table.push_str(&row(
+            &(i + 1).to_string(),
+            &step.usage,
+            step.tool_calls.len(),
+            step.duration,
+            pricing::estimate_cost(&step.model, &step.usage),
+        ));
+    }
+    let tools = result.steps.iter().map(|step| step.tool_calls.len()).sum();
+    let time = result.steps.iter().map(|step| step.duration).sum();
+    table.push_str(&row("Total", &result.usage, tools, time, result.cost_usd()));
+    table
+}
+
+pub fn json_document(result: &ConversationResult) -> serde_json::Value {
+    let tool_stats: serde_json::Map<String, serde_json::Value> = result
+        .tool_stats
+        .iter()
+        .map(|(name, stats)| {
+            (
+                name.clone(),
+                json!({
+                    "calls": stats.calls,
+                    "errors": stats.errors,
+                    "cached": stats.cached,
+                    "duration_ms": stats.total_duration.as_millis() as u64,
+                }),
+            )
+        })
+        .collect();
+
+    let steps: Vec<serde_json::Value> = result
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let tool_calls: Vec<serde_json::Value> = step
+                .tool_calls
+                .iter()
+                .map(|call| {
+                    json!({
+                        "name": call.name,
+                        "input": call.input,
+                        "is_error": call.is_error,
+                        "duration_ms": call.duration.as_millis() as u64,
+                        "cached": call.cached,
+                    })
+                })
+                .collect();
+            json!({
+                "iteration": i + 1,
+                "model": step.model,
+                "stop_reason": step.stop_reason,
+                "usage": step.usage,
+                "duration_ms": step.duration.as_millis() as u64,
+                "tool_calls": tool_calls,
+            })
+        })
+        .collect();
+
+    json!({
+        "text": final_text(result),
+        "model": result.model,
+        "stop_reason": result.response.stop_reason,
+        "iterations": result.iterations,
+        "steps": steps,
+        "usage": result.usage,
+        "cost_usd": result.cost_usd(),
+        "tool_stats": tool_stats,
+        "timed_out": result.timed_out,
+        "fallback_from": result.fallback_from,
+    })
+}
@@ -0,0 +1,130 @@
+//! OS ごとの違いを吸収する処理（主に Windows 向け）
+//!
+//! 判定は引数で受け取れる形にして、どの OS の CI でもテストできるようにする
+
+use anyhow::{Context, Result};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 改行コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// 既存の内容で多い方の改行コードを返す（改行を含まなければ LF）
+    pub fn detect(content: &str) -> Self {
+        let crlf = content.matches("\r\n").count();
+        let lf = content.matches('\n').count() - crlf;
+        if crlf > lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// `content` の改行をこの改行コードに揃える
+    pub fn apply(self, content: &str) -> String {
+        let normalized = content.replace("\r\n", "\n");
+        match self {
+            LineEnding::Lf => normalized,
+            LineEnding::CrLf => normalized.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// ツールの出力に使うパスの表記（区切りは OS によらず `/`）
+///
+/// Windows の `\` 区切りのままだとモデルが次のツール呼び出しでエスケープを誤りやすい
+pub fn display_path(path: &Path) -> String {
+    to_slash(&path.to_string_lossy(), std::path::MAIN_SEPARATOR)
+}
+
+fn to_slash(path: &str, separator: char) -> String {
+    if separator == '/' {
+        path.to_string()
+    } else {
+        path.replace(separator, "/")
+    }
+}
+
+/// ホームディレクトリ
+///
+/// OS の API で決まらない場合（サービスアカウントや最小構成のコンテナなど）は
+/// `HOME`、Windows では `USERPROFILE` も見る
+pub fn home_dir() -> Option<PathBuf> {
+    dirs::home_dir().or_else(|| home_from_env(|name| std::env::var_os(name)))
+}
+
+fn home_from_env(var: impl Fn(&str) -> Option<std::ffi::OsString>) -> Option<PathBuf> {
+    let names: &[&str] = if cfg!(windows) {
+        &["USERPROFILE", "HOME"]
+    } else {
+        &["HOME"]
+    };
+    names
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// 端末が ANSI エスケープシーケンスを解釈できるか
+///
+/// Windows ではコンソールの仮想端末処理を有効にし、できなかった場合（古いコンソール）は false
+pub fn ansi_supported() -> bool {
+    #[cfg(windows)]
+    {
+        use std::sync::OnceLock;
+        static SUPPORTED: OnceLock<bool> = OnceLock::new();
+        *SUPPORTED.get_or_init(ratatui::crossterm::ansi_support::supports_ansi)
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+/// プロンプトを表示して標準入力から 1 行読む（改行は除く）
+///
+/// PowerShell やコマンドプロンプトでは行末が `\r\n` になるので両方取り除く
+pub fn prompt_line(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush().context("Failed to flush stderr")?;
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .context("Failed to read user input")?;
+    Ok(trim_newline(&input).to_string())
+}
+
+fn trim_newline(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_ending_detect_and_apply() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\nb"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a"), LineEnding::Lf);
+        assert_eq!(LineEnding::CrLf.apply("a\nb\r\n"), "a\r\nb\r\n");
+        assert_eq!(LineEnding::Lf.apply("a\r\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn test_to_slash_and_home_from_env() {
+        assert_eq!(to_slash(r"src\tools\mod.rs", '\\'), "src/tools/mod.rs");
+        assert_eq!(to_slash(r"odd\name.rs", '/'), r"odd\name.rs");
+        assert_eq!(trim_newline("y\r\n"), "y");
+
+        let home = home_from_env(|name| (name == "HOME").then(|| "/home/me".into()));
+        assert_eq!(home, Some(PathBuf::from("/home/me")));
+        assert_eq!(home_from_env(|_| Some("".into())), None);
+    }
+}
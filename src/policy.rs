@@ -0,0 +1,228 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::{ApprovalConfig, ApprovalPolicy};
+
+/// `approvals.paths` のコンパイル済みルール
+struct PathRule {
+    matcher: GlobMatcher,
+    tools: Option<Vec<String>>,
+    decision: ApprovalPolicy,
+}
+
+/// ツール名とパスから承認ポリシーを決定する
+///
+/// 優先順位: パスルール（最初に一致したもの）> ツールごとの設定 > デフォルト
+pub struct ApprovalEngine {
+    default: ApprovalPolicy,
+    tools: BTreeMap<String, ApprovalPolicy>,
+    paths: Vec<PathRule>,
+    workspace: PathBuf,
+}
+
+impl ApprovalEngine {
+    /// `[approvals]` の設定からエンジンを構築する
+    ///
+    /// `default` はプロファイルや信頼済みワークスペースを反映したデフォルトポリシー
+    pub fn new(config: &ApprovalConfig, default: ApprovalPolicy, workspace: &Path) -> Result<Self> {
+        let paths = config
+            .paths
+            .iter()
+            .map(|rule| {
+                let matcher = Glob::new(&rule.glob)
+                    .with_context(|| format!("Invalid glob in approvals.paths: {}", rule.glob))?
+                    .compile_matcher();
+                Ok(PathRule {
+                    matcher,
+                    tools: rule.tools.clone(),
+                    decision: rule.decision,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            default,
+            tools: config.tools.clone(),
+            paths,
+            workspace: workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+        })
+    }
+
+    /// ツールの実行に対するポリシーを決定する
+    ///
+    /// ワークスペースの外に出るパスは、パスルール・ツールごとの設定・デフォルトの
+    /// どれで allow になっても確認する
+    pub fn decide(&self, tool: &str, path: &str) -> ApprovalPolicy {
+        let (path, in_workspace) = self.relative_path(Path::new(path));
+
+        for rule in &self.paths {
+            let applies = rule
+                .tools
+                .as_ref()
+                .is_none_or(|tools| tools.iter().any(|t| t == tool));
+            if !applies || (!in_workspace && rule.decision == ApprovalPolicy::Allow) {
+                continue;
+            }
+            if rule.matcher.is_match(&path) {
+                return rule.decision;
+            }
+        }
+
+        match self.tools.get(tool).copied().unwrap_or(self.default) {
+            ApprovalPolicy::Allow if !in_workspace => ApprovalPolicy::Ask,
+            policy => policy,
+        }
+    }
+
+    /// `.` と `..` を除いたワークスペースからの相対パスにしてから照合する
+    ///
+    /// ワークスペースの外のパスは絶対パスのままで、false を返す
+    fn relative_path(&self, path: &Path) -> (PathBuf, bool) {
+        let path = normalize(&self.workspace.join(path));
+        match path.strip_prefix(&self.workspace) {
+            Ok(relative) => (relative.to_path_buf(), true),
+            Err(_) => (path, false),
+        }
+    }
+}
+
+/// パスの `.` と `..` をファイルシステムを見ずに解決する
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PathApprovalRule;
+
+    #[test]
+    fn test_decide_precedence() {
+        let config = ApprovalConfig {
+            tools: BTreeMap::from([("editFile".to_string(), ApprovalPolicy::Allow)]),
+            paths: vec![
+                PathApprovalRule {
+                    glob: "secrets/**".to_string(),
+                    decision: ApprovalPolicy::Deny,
+                    tools: None,
+                },
+                PathApprovalRule {
+                    glob: "docs/**".to_string(),
+                    decision: ApprovalPolicy::Allow,
+                    tools: Some(vec!["writeFile".to_string()]),
+                },
+            ],
+            ..ApprovalConfig::default()
+        };
+        let engine = ApprovalEngine::new(&config, ApprovalPolicy::Ask, Path::new("/work")).unwrap();
+
+        assert_eq!(
+            engine.decide("editFile", "secrets/key"),
+            ApprovalPolicy::Deny
+        );
+        assert_eq!(
+            engine.decide("editFile", "src/main.rs"),
+            ApprovalPolicy::Allow
+        );
+        assert_eq!(
+            engine.decide("writeFile", "./docs/a.md"),
+            ApprovalPolicy::Allow
+        );
+        assert_eq!(
+            engine.decide("writeFile", "/work/docs/a.md"),
+            ApprovalPolicy::Allow
+        );
+        assert_eq!(
+            engine.decide("writeFile", "src/main.rs"),
+            ApprovalPolicy::Ask
+        );
+
+        // `..` でルールを避けたり、ルールに当てはめたりできない
+        assert_eq!(
+            engine.decide("editFile", "src/../secrets/key"),
+            ApprovalPolicy::Deny
+        );
+        assert_eq!(
+            engine.decide("writeFile", "docs/../src/main.rs"),
+            ApprovalPolicy::Ask
+        );
+        assert_eq!(
+            engine.decide("writeFile", "/work/docs/./../secrets/./key"),
+            ApprovalPolicy::Deny
+        );
+        // ワークスペースの外には allow のルールを適用しない
+        assert_eq!(
+            engine.decide("writeFile", "docs/../../etc/passwd"),
+            ApprovalPolicy::Ask
+        );
+        let outside = ApprovalEngine::new(
+            &ApprovalConfig {
+                paths: vec![PathApprovalRule {
+                    glob: "**".to_string(),
+                    decision: ApprovalPolicy::Allow,
+                    tools: None,
+                }],
+                ..ApprovalConfig::default()
+            },
+            ApprovalPolicy::Ask,
+            Path::new("/work"),
+        )
+        .unwrap();
+        assert_eq!(outside.decide("writeFile", "a.txt"), ApprovalPolicy::Allow);
+        assert_eq!(
+            outside.decide("writeFile", "../other/a.txt"),
+            ApprovalPolicy::Ask
+        );
+        assert_eq!(
+            outside.decide("writeFile", "/etc/passwd"),
+            ApprovalPolicy::Ask
+        );
+        // ツールごとの allow も同じ
+        assert_eq!(
+            engine.decide("editFile", "/etc/passwd"),
+            ApprovalPolicy::Ask
+        );
+        assert_eq!(
+            engine.decide("editFile", "../../.bashrc"),
+            ApprovalPolicy::Ask
+        );
+    }
+
+    #[test]
+    fn test_trusted_workspace_only_allows_writes_inside() {
+        // 信頼済みのワークスペースではデフォルトが allow になる（agent::build_tool_registry）
+        let engine = ApprovalEngine::new(
+            &ApprovalConfig {
+                trusted_paths: vec![PathBuf::from("/work")],
+                ..ApprovalConfig::default()
+            },
+            ApprovalPolicy::Allow,
+            Path::new("/work"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            engine.decide("writeFile", "src/main.rs"),
+            ApprovalPolicy::Allow
+        );
+        assert_eq!(
+            engine.decide("writeFile", "../../.bashrc"),
+            ApprovalPolicy::Ask
+        );
+        assert_eq!(engine.decide("editFile", "/etc/hosts"), ApprovalPolicy::Ask);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::anthropic::Usage;
+
+/// モデルごとの料金（USD / 100 万トークン）
+///
+/// 日付付きのモデル ID にも一致するよう前方一致で比較する（長いものを先に並べる）
+const PRICES: &[(&str, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-haiku", 0.25, 1.25),
+];
+
+/// キャッシュへの書き込みと読み込みの入力料金に対する倍率
+const CACHE_WRITE_MULTIPLIER: f64 = 1.25;
+const CACHE_READ_MULTIPLIER: f64 = 0.1;
+
+/// 使用量から料金を見積もる（料金が不明なモデルは None）
+pub fn estimate_cost(model: &str, usage: &Usage) -> Option<f64> {
+    let (_, input, output) = PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))?;
+    let input_tokens = usage.input_tokens as f64
+        + usage.cache_creation_input_tokens as f64 * CACHE_WRITE_MULTIPLIER
+        + usage.cache_read_input_tokens as f64 * CACHE_READ_MULTIPLIER;
+    Some((input_tokens * input + usage.output_tokens as f64 * output) / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let usage = Usage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            ..Default::default()
+        };
+        assert_eq!(estimate_cost("claude-sonnet-4-5", &usage), Some(4.5));
+        assert_eq!(
+            estimate_cost("claude-opus-4-1-20250805", &usage),
+            Some(22.5)
+        );
+        assert_eq!(estimate_cost("claude-opus-4-5", &usage), Some(7.5));
+        assert_eq!(estimate_cost("unknown-model", &usage), None);
+
+        let cached = Usage {
+            cache_creation_input_tokens: 1_000_000,
+            cache_read_input_tokens: 1_000_000,
+            ..Default::default()
+        };
+        assert_eq!(estimate_cost("claude-haiku-4-5", &cached), Some(1.35));
+    }
+}
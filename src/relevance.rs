@@ -0,0 +1,134 @@
+//! タスクとの関連度によるファイルの順位付け
+//!
+//! `--file` の添付やリポジトリマップが予算に収まらないとき、パスの一致・シンボルの重なり・
+//! 最近の変更からファイルに点を付け、上位のものだけを載せるために使う
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// 一致とみなさないありふれた単語
+const STOP_WORDS: [&str; 24] = [
+    "the", "and", "for", "with", "this", "that", "from", "into", "file", "files", "code", "add",
+    "fix", "use", "make", "should", "when", "then", "them", "all", "are", "not", "can", "please",
+];
+
+/// パスの単語がタスクに現れたときの点
+const PATH_WORD_SCORE: f64 = 5.0;
+/// ファイル名そのものがタスクに現れたときの点
+const FILE_NAME_SCORE: f64 = 20.0;
+/// ファイル中の識別子がタスクの単語と重なったときの 1 語あたりの点
+const SYMBOL_SCORE: f64 = 1.0;
+/// 直前に変更されたファイルの点（1 日ごとに半分以下に減る）
+const RECENCY_SCORE: f64 = 2.0;
+
+/// タスクの文章から作った関連度の物差し
+pub struct Relevance {
+    text: String,
+    words: HashSet<String>,
+}
+
+impl Relevance {
+    pub fn new(task: &str) -> Self {
+        Self {
+            text: task.to_lowercase(),
+            words: words(task)
+                .into_iter()
+                .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+                .collect(),
+        }
+    }
+
+    /// `path`（ワークスペースからの相対パス）の点。`content` は識別子を比べる本文
+    pub fn score(&self, path: &Path, content: &str, modified: Option<SystemTime>) -> f64 {
+        let mut score = 0.0;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase());
+        if name.is_some_and(|name| name.contains('.') && self.text.contains(&name)) {
+            score += FILE_NAME_SCORE;
+        }
+        let path_words = words(&path.to_string_lossy());
+        score += PATH_WORD_SCORE * self.words.intersection(&path_words).count() as f64;
+        score += SYMBOL_SCORE * self.words.intersection(&words(content)).count() as f64;
+
+        if let Some(age) = modified.and_then(|time| time.elapsed().ok()) {
+            let days = age.as_secs_f64() / 86400.0;
+            score += RECENCY_SCORE / (1.0 + days);
+        }
+        score
+    }
+}
+
+/// 予算（`cost` の合計）に収まるよう点の高いものから選ぶ
+///
+/// 選んだものの添字を元の順で返す。収まらないものは飛ばして次を試す
+pub fn select(scores: &[f64], costs: &[usize], budget: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let mut used = 0;
+    let mut selected: Vec<usize> = order
+        .into_iter()
+        .filter(|&i| {
+            let fits = used + costs[i] <= budget;
+            if fits {
+                used += costs[i];
+            }
+            fits
+        })
+        .collect();
+    selected.sort_unstable();
+    selected
+}
+
+/// 識別子とパスを小文字の単語に分ける（snake_case・camelCase も分け、3 文字未満は除く）
+fn words(text: &str) -> HashSet<String> {
+    let mut words = HashSet::new();
+    for token in text.split(|c: char| !c.is_alphanumeric()) {
+        let mut word = String::new();
+        let mut prev_lower = false;
+        for c in token.chars() {
+            if c.is_uppercase() && prev_lower {
+                insert_word(&mut words, &word);
+                word.clear();
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            word.extend(c.to_lowercase());
+        }
+        insert_word(&mut words, &word);
+    }
+    words
+}
+
+fn insert_word(words: &mut HashSet<String>, word: &str) {
+    if word.chars().count() >= 3 {
+        words.insert(word.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ranks_by_path_symbols_and_recency() {
+        let relevance = Relevance::new("Fix the retry logic in send_with_retry (see client.rs)");
+        let client = relevance.score(Path::new("src/client.rs"), "fn send_with_retry()", None);
+        let retry = relevance.score(Path::new("src/retry/mod.rs"), "", None);
+        let other = relevance.score(Path::new("src/config.rs"), "fn load()", None);
+        assert!(client > retry && retry > other, "{client} {retry} {other}");
+        assert_eq!(other, 0.0);
+
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(30 * 86400);
+        assert!(
+            relevance.score(Path::new("a.md"), "", Some(now))
+                > relevance.score(Path::new("b.md"), "", Some(old))
+        );
+
+        assert_eq!(select(&[1.0, 5.0, 3.0], &[10, 60, 50], 100), vec![0, 1]);
+        assert_eq!(select(&[1.0, 5.0, 3.0], &[10, 60, 50], 200), vec![0, 1, 2]);
+    }
+}
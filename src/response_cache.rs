@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use crate::anthropic::{
+    ContentBlock, ConversationResult, GenerationParams, Message, MessageContent, MessageResponse,
+    Usage,
+};
+use crate::config::Config;
+
+/// 応答キャッシュのキー（モデル・生成パラメータ・システムプロンプトのハッシュ・メッセージ）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheKey {
+    model: String,
+    params: String,
+    system_hash: String,
+    message: String,
+}
+
+impl CacheKey {
+    pub fn new(
+        model: &str,
+        params: &GenerationParams,
+        system_prompt: Option<&str>,
+        message: &str,
+    ) -> Self {
+        Self {
+            model: model.to_string(),
+            params: serde_json::to_string(params).unwrap_or_default(),
+            system_hash: system_prompt.map(hash_hex).unwrap_or_default(),
+            message: message.to_string(),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        let key = format!(
+            "{}\0{}\0{}\0{}",
+            self.model, self.params, self.system_hash, self.message
+        );
+        format!("{}.json", hash_hex(&key))
+    }
+}
+
+/// キャッシュした最終応答（~/.codex/cache/responses/<hash>.json）
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    created_at: DateTime<Utc>,
+    /// ハッシュの衝突を避けるためキー全体も保存して照合する
+    key: CacheKey,
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+}
+
+/// Get the response cache directory (~/.codex/cache/responses)
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(Config::codex_home()?.join("cache").join("responses"))
+}
+
+/// 同じキーで保存した最終応答があれば、API を呼ばずに結果として返す
+///
+/// 読めないエントリはキャッシュがないものとして扱う
+pub fn lookup(key: &CacheKey) -> Option<(ConversationResult, DateTime<Utc>)> {
+    let path = cache_dir().ok()?.join(key.file_name());
+    let content = std::fs::read_to_string(&path).ok()?;
+    let entry: CacheEntry = match serde_json::from_str(&content) {
+        Ok(entry) => entry,
+        Err(e) => {
+            tracing::debug!("Ignoring unreadable cache entry {:?}: {}", path, e);
+            return None;
+        }
+    };
+    if entry.key != *key {
+        return None;
+    }
+
+    let conversation = vec![
+        Message::user_text(&key.message),
+        Message {
+            role: "assistant".to_string(),
+            content: MessageContent::Blocks(entry.content.clone()),
+        },
+    ];
+    let result = ConversationResult {
+        model: key.model.clone(),
+        response: MessageResponse {
+            id: String::new(),
+            content: entry.content,
+            stop_reason: entry.stop_reason,
+            usage: Usage::default(),
+        },
+        conversation,
+        // API は呼んでいない
+        iterations: 0,
+        usage: Usage::default(),
+        steps: Vec::new(),
+        tool_stats: BTreeMap::new(),
+        timed_out: None,
+        fallback_from: None,
+    };
+    Some((result, entry.created_at))
+}
+
+/// ツールを使わずに 1 回で最後まで答えた結果だけを保存する
+pub fn store(key: &CacheKey, result: &ConversationResult) -> Result<()> {
+    let used_tools = result
+        .response
+        .content
+        .iter()
+        .any(|block| matches!(block, ContentBlock::ToolUse { .. }));
+    let complete = result.response.stop_reason.as_deref() == Some("end_turn");
+    if result.iterations != 1 || result.timed_out.is_some() || used_tools || !complete {
+        return Ok(());
+    }
+
+    let dir = cache_dir()?;
+    std::fs::create_dir_all(&dir).context("Failed to create response cache directory")?;
+    let entry = CacheEntry {
+        created_at: Utc::now(),
+        key: key.clone(),
+        content: result.response.content.clone(),
+        stop_reason: result.response.stop_reason.clone(),
+    };
+    let path = dir.join(key.file_name());
+    let content =
+        serde_json::to_string_pretty(&entry).context("Failed to serialize cache entry")?;
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write cache entry {:?}", path))?;
+    tracing::debug!("Cached the response in {:?}", path);
+    Ok(())
+}
+
+fn hash_hex(text: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_covers_every_part() {
+        let params = GenerationParams {
+            max_tokens: 1024,
+            temperature: None,
+            top_p: None,
+        };
+        let key = CacheKey::new("claude-sonnet-4-5", &params, Some("system"), "hi");
+        assert_eq!(
+            key.file_name(),
+            CacheKey::new("claude-sonnet-4-5", &params, Some("system"), "hi").file_name()
+        );
+        assert_ne!(
+            key.file_name(),
+            CacheKey::new("claude-sonnet-4-5", &params, Some("other"), "hi").file_name()
+        );
+        assert_ne!(
+            key.file_name(),
+            CacheKey::new("claude-haiku-4-5", &params, Some("system"), "hi").file_name()
+        );
+    }
+}
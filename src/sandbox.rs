@@ -0,0 +1,165 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::config::{SandboxBackend, SandboxConfig};
+
+#[cfg(target_os = "linux")]
+mod landlock;
+
+/// `sandbox.landlock`: このプロセスと以降に起動するすべての子プロセスが触れるファイルを
+/// 作業ディレクトリとシステムのディレクトリに制限する（Linux のみ）
+///
+/// 有効にした後に作られたスレッドにしか及ばないので、ロギング（OTel の送信スレッド）と
+/// 非同期ランタイムより先に呼ぶ。結果のログは初期化後に `Restriction::log` で出す
+pub fn restrict_process(config: &SandboxConfig, workspace: &Path) -> Result<Restriction> {
+    if !config.landlock {
+        return Ok(Restriction::Disabled);
+    }
+    #[cfg(target_os = "linux")]
+    {
+        landlock::restrict(config, workspace)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = workspace;
+        Ok(Restriction::Unsupported)
+    }
+}
+
+/// `restrict_process` で制限した結果
+#[derive(Debug)]
+pub enum Restriction {
+    /// `sandbox.landlock` が無効
+    Disabled,
+    /// 制限が有効になった（書き込みを許可したパス）
+    Enforced(Vec<PathBuf>),
+    /// カーネルが Landlock の一部にしか対応していない
+    Partial,
+    /// OS やカーネルが Landlock に対応していない
+    Unsupported,
+}
+
+impl Restriction {
+    pub fn log(&self) {
+        match self {
+            Restriction::Disabled => {}
+            Restriction::Enforced(writable) => {
+                tracing::info!("Landlock sandbox enabled; writable: {:?}", writable)
+            }
+            Restriction::Partial => tracing::warn!(
+                "The kernel supports only part of Landlock; some file accesses are not restricted"
+            ),
+            Restriction::Unsupported if cfg!(target_os = "linux") => {
+                tracing::warn!("The kernel does not support Landlock; running without the sandbox")
+            }
+            Restriction::Unsupported => {
+                tracing::warn!("sandbox.landlock is only supported on Linux; running without it")
+            }
+        }
+    }
+}
+
+/// ツールが実行するコマンドの実行環境（`[sandbox]`）
+///
+/// docker / podman の場合は使い捨てのコンテナで実行し、ホストからは作業ディレクトリだけを
+/// 同じパスにマウントする
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    config: SandboxConfig,
+    workspace: PathBuf,
+}
+
+impl Sandbox {
+    pub fn new(config: &SandboxConfig, workspace: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            workspace: workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+        }
+    }
+
+    /// `program args` を実行するコマンドを作る（サンドボックスが有効ならコンテナ内で実行する）
+    pub fn command(&self, program: &str, args: &[String]) -> Command {
+        let runtime = match self.config.backend {
+            SandboxBackend::None => {
+                let mut command = Command::new(program);
+                command.args(args);
+                return command;
+            }
+            SandboxBackend::Docker => "docker",
+            SandboxBackend::Podman => "podman",
+        };
+        // 作業ディレクトリの外で起動した場合はコンテナ内ではワークスペースのルートで実行する
+        let cwd = std::env::current_dir()
+            .ok()
+            .and_then(|dir| dir.canonicalize().ok())
+            .filter(|dir| dir.starts_with(&self.workspace))
+            .unwrap_or_else(|| self.workspace.clone());
+        let mut command = Command::new(runtime);
+        command.args(self.run_args(&cwd, program, args));
+        command
+    }
+
+    /// `docker run` / `podman run` の引数
+    fn run_args(&self, cwd: &Path, program: &str, args: &[String]) -> Vec<String> {
+        let workspace = self.workspace.to_string_lossy();
+        let mut run_args: Vec<String> = [
+            "run",
+            "--rm",
+            "--interactive",
+            "--init",
+            "--cap-drop",
+            "ALL",
+            "--security-opt",
+            "no-new-privileges",
+        ]
+        .map(String::from)
+        .to_vec();
+        if !self.config.network {
+            run_args.extend(["--network".to_string(), "none".to_string()]);
+        }
+        run_args.push("--volume".to_string());
+        run_args.push(format!(
+            "{}:{}{}",
+            workspace,
+            workspace,
+            if self.config.read_only { ":ro" } else { "" }
+        ));
+        run_args.push("--workdir".to_string());
+        run_args.push(cwd.to_string_lossy().into_owned());
+        run_args.extend(self.config.extra_args.iter().cloned());
+        run_args.push(self.config.image.clone());
+        run_args.push(program.to_string());
+        run_args.extend(args.iter().cloned());
+        run_args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_args_isolate_network_and_mount_workspace() {
+        let config = SandboxConfig {
+            backend: SandboxBackend::Docker,
+            image: "alpine:3".to_string(),
+            read_only: true,
+            extra_args: vec!["--memory".to_string(), "1g".to_string()],
+            ..SandboxConfig::default()
+        };
+        let sandbox = Sandbox::new(&config, Path::new("/nonexistent/ws"));
+        let args = sandbox.run_args(
+            Path::new("/nonexistent/ws/src"),
+            "sh",
+            &["-c".to_string(), "ls".to_string()],
+        );
+        let args = args.join(" ");
+        assert!(args.starts_with("run --rm --interactive"));
+        assert!(args.contains("--network none"));
+        assert!(args.contains("--volume /nonexistent/ws:/nonexistent/ws:ro"));
+        assert!(args.ends_with("--workdir /nonexistent/ws/src --memory 1g alpine:3 sh -c ls"));
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use landlock::{
+    path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus,
+    ABI,
+};
+use std::path::{Path, PathBuf};
+
+use super::Restriction;
+use crate::config::{expand_tilde, Config, SandboxConfig};
+
+/// 読み取り（と実行）を許可するシステムのディレクトリ
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/bin", "/sbin", "/usr", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/proc", "/sys", "/run",
+    "/nix", "/snap",
+];
+
+/// 書き込みも許可するシステムのディレクトリ（/dev/null や端末）
+const SYSTEM_WRITE_PATHS: &[&str] = &["/dev"];
+
+/// 呼び出したスレッド（とその後に作られるスレッド・子プロセス）のファイルアクセスを制限する
+pub fn restrict(config: &SandboxConfig, workspace: &Path) -> Result<Restriction> {
+    let abi = ABI::V5;
+
+    let mut writable = vec![workspace.to_path_buf(), std::env::temp_dir()];
+    writable.extend(Config::codex_home().ok());
+    writable.extend(git_common_dir(workspace));
+    writable.extend(config.write_paths.iter().map(|path| expand_tilde(path)));
+    writable.extend(SYSTEM_WRITE_PATHS.iter().map(PathBuf::from));
+
+    let mut readable: Vec<PathBuf> = SYSTEM_READ_PATHS.iter().map(PathBuf::from).collect();
+    // git は利用者の設定を読めないと失敗する
+    if let Some(home) = crate::platform::home_dir() {
+        readable.push(home.join(".gitconfig"));
+        readable.push(home.join(".config").join("git"));
+    }
+    // `--verify cargo-check` や getDiagnostics はツールチェーンを読めないと失敗する
+    readable.extend(toolchain_dirs());
+    readable.extend(config.read_paths.iter().map(|path| expand_tilde(path)));
+
+    // 存在しないパスは path_beneath_rules が飛ばす
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .create()?
+        .add_rules(path_beneath_rules(&readable, AccessFs::from_read(abi)))?
+        .add_rules(path_beneath_rules(&writable, AccessFs::from_all(abi)))?
+        .restrict_self()
+        .context("Failed to enable the Landlock sandbox")?;
+    Ok(match status.ruleset {
+        RulesetStatus::FullyEnforced => Restriction::Enforced(writable),
+        RulesetStatus::PartiallyEnforced => Restriction::Partial,
+        RulesetStatus::NotEnforced => Restriction::Unsupported,
+    })
+}
+
+/// Rust のツールチェーンのディレクトリ（`CARGO_HOME` と `RUSTUP_HOME`、既定は ~/.cargo と ~/.rustup）
+///
+/// 依存クレートの取得などで書き込みが必要な場合は `sandbox.write_paths` に追加する
+fn toolchain_dirs() -> Vec<PathBuf> {
+    let home = crate::platform::home_dir();
+    [("CARGO_HOME", ".cargo"), ("RUSTUP_HOME", ".rustup")]
+        .into_iter()
+        .filter_map(|(var, default)| {
+            std::env::var_os(var)
+                .map(PathBuf::from)
+                .or_else(|| home.as_ref().map(|home| home.join(default)))
+        })
+        .collect()
+}
+
+/// 作業ディレクトリを含む git リポジトリの .git（worktree の作成やコミットに必要）
+fn git_common_dir(workspace: &Path) -> Option<PathBuf> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--path-format=absolute", "--git-common-dir"])
+        .current_dir(workspace)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
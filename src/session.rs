@@ -0,0 +1,393 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::anthropic::{
+    AnthropicClient, ContentBlock, ConversationResult, GenerationParams, Message, MessageContent,
+    Usage,
+};
+use crate::config::Config;
+use crate::i18n::tr;
+
+/// タイトルを付けるときにモデルへ渡す、1 つのメッセージあたりの文字数
+const MAX_TITLE_CONTEXT_CHARS: usize = 2000;
+
+/// 保存された会話（~/.codex/sessions/<id>.json）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub workspace: PathBuf,
+    pub model: String,
+    /// モデルに付けさせた短いタイトル（`agent.session_titles`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// 現在の分岐の会話
+    pub messages: Vec<Message>,
+    /// `/fork` で分けた会話の木（分岐していなければ空）
+    ///
+    /// `branch` 番目の分岐の最新の会話は `messages` にあり、切り替えるときに書き戻す
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<Branch>,
+    #[serde(default)]
+    pub branch: usize,
+    /// このセッションの実行の合計使用量
+    #[serde(default)]
+    pub usage: Usage,
+    /// このセッションの実行の推定料金の合計（料金が不明なモデルの分は含まない）
+    #[serde(default)]
+    pub cost_usd: f64,
+}
+
+/// 会話の分岐（分岐元の番号と、分岐元から引き継いだターン数）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Branch {
+    /// 分岐元（最初の会話は None）
+    pub parent: Option<usize>,
+    pub forked_at_turn: usize,
+    pub messages: Vec<Message>,
+}
+
+impl Session {
+    /// 新しいセッションを作成（保存は `save` で行う）
+    pub fn new(workspace: &Path, model: &str) -> Self {
+        // 同じプロセスで続けて作成しても ID が重ならないよう連番を付ける
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+        let now = Utc::now();
+        let mut id = format!("{}-{}", now.format("%Y%m%d-%H%M%S"), std::process::id());
+        let count = CREATED.fetch_add(1, Ordering::Relaxed);
+        if count > 0 {
+            id.push_str(&format!("-{}", count));
+        }
+        Self {
+            id,
+            created_at: now,
+            updated_at: now,
+            workspace: workspace.to_path_buf(),
+            model: model.to_string(),
+            title: None,
+            messages: Vec::new(),
+            branches: Vec::new(),
+            branch: 0,
+            usage: Usage::default(),
+            cost_usd: 0.0,
+        }
+    }
+
+    /// 実行の使用量と推定料金を加える
+    pub fn record_usage(&mut self, result: &ConversationResult) {
+        self.usage.add(&result.usage);
+        self.cost_usd += result.cost_usd().unwrap_or(0.0);
+    }
+
+    /// 現在の会話の `turn` ターン目まで（省略時はすべて）を引き継ぐ分岐を作り、そこへ切り替える
+    ///
+    /// 元の会話は分岐としてそのまま残る。新しい分岐の番号を返す
+    pub fn fork(&mut self, turn: Option<usize>) -> Result<usize> {
+        let starts = turn_starts(&self.messages);
+        let turn = turn.unwrap_or(starts.len());
+        if turn > starts.len() {
+            bail!("The conversation has only {} turns", starts.len());
+        }
+        let keep = starts.get(turn).copied().unwrap_or(self.messages.len());
+        self.sync_branch();
+        self.branches.push(Branch {
+            parent: Some(self.branch),
+            forked_at_turn: turn,
+            messages: self.messages[..keep].to_vec(),
+        });
+        self.branch = self.branches.len() - 1;
+        self.messages.truncate(keep);
+        Ok(self.branch)
+    }
+
+    /// 別の分岐に切り替える（現在の会話は分岐に残す）
+    pub fn checkout(&mut self, branch: usize) -> Result<()> {
+        if branch >= self.branches.len().max(1) {
+            bail!("No branch {}", branch + 1);
+        }
+        self.sync_branch();
+        self.branch = branch;
+        self.messages = self.branches[branch].messages.clone();
+        Ok(())
+    }
+
+    /// 現在の会話を分岐の木に書き戻す（最初の分岐では木を作る）
+    fn sync_branch(&mut self) {
+        if self.branches.is_empty() {
+            self.branches.push(Branch {
+                parent: None,
+                forked_at_turn: 0,
+                messages: Vec::new(),
+            });
+        }
+        self.branches[self.branch].messages = self.messages.clone();
+    }
+
+    /// 会話のターン数（ユーザーが入力したメッセージの数）
+    pub fn turns(messages: &[Message]) -> usize {
+        turn_starts(messages).len()
+    }
+
+    /// Get the sessions directory (~/.codex/sessions)
+    pub fn sessions_dir() -> Result<PathBuf> {
+        Ok(Config::codex_home()?.join("sessions"))
+    }
+
+    fn path(id: &str) -> Result<PathBuf> {
+        Ok(Self::sessions_dir()?.join(format!("{}.json", id)))
+    }
+
+    /// 会話を保存する
+    pub fn save(&mut self) -> Result<()> {
+        let dir = Self::sessions_dir()?;
+        std::fs::create_dir_all(&dir).context("Failed to create sessions directory")?;
+
+        self.updated_at = Utc::now();
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize session")?;
+        let path = Self::path(&self.id)?;
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write session {:?}", path))?;
+
+        tracing::debug!("Saved session to {:?}", path);
+        Ok(())
+    }
+
+    /// 保存されたセッションを読み込む
+    pub fn load(id: &str) -> Result<Self> {
+        let path = Self::path(id)?;
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Session '{}' not found", id))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse session {:?}", path))
+    }
+
+    /// 保存されたセッションを新しい順に列挙する（読めないファイルは無視）
+    pub fn list() -> Result<Vec<Self>> {
+        let dir = Self::sessions_dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut sessions = Vec::new();
+        for entry in std::fs::read_dir(&dir).context("Failed to read sessions directory")? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            match std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<Self>(&content)?))
+            {
+                Ok(session) => sessions.push(session),
+                Err(e) => tracing::warn!("Skipping unreadable session {:?}: {}", path, e),
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+        Ok(sessions)
+    }
+
+    /// 保存されたセッションを削除する
+    pub fn delete(id: &str) -> Result<()> {
+        let path = Self::path(id)?;
+        std::fs::remove_file(&path).with_context(|| format!("Session '{}' not found", id))
+    }
+
+    /// 付けたタイトル、なければ最初のユーザーメッセージの 1 行目（一覧表示用）
+    pub fn title(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        let first = self
+            .messages
+            .iter()
+            .filter(|m| m.role == "user")
+            .find_map(text_of)
+            .unwrap_or("");
+        summary_line(first)
+    }
+
+    /// 最後にユーザーが入力したメッセージの位置とテキスト（`/retry` と `/edit` 用）
+    pub fn last_turn(messages: &[Message]) -> Option<(usize, String)> {
+        let index = *turn_starts(messages).last()?;
+        Some((index, text_of(&messages[index])?.to_string()))
+    }
+
+    /// 最後にユーザーが入力したメッセージの 1 行目（分岐の一覧表示用）
+    pub fn latest_prompt(messages: &[Message]) -> String {
+        let latest = turn_starts(messages)
+            .last()
+            .and_then(|&index| text_of(&messages[index]))
+            .unwrap_or("");
+        summary_line(latest)
+    }
+}
+
+/// 会話の最初のやり取りから短いタイトルを `model` に付けさせる
+pub async fn generate_title(
+    client: &AnthropicClient,
+    model: &str,
+    messages: &[Message],
+) -> Result<String> {
+    let mut transcript = String::new();
+    for message in messages.iter().take(2) {
+        if let Some(text) = text_of(message) {
+            let text: String = text.chars().take(MAX_TITLE_CONTEXT_CHARS).collect();
+            transcript.push_str(&format!("[{}]\n{}\n\n", message.role, text));
+        }
+    }
+    if transcript.is_empty() {
+        bail!("The conversation has no text to title");
+    }
+
+    let prompt = tr!(
+        "Write a title of at most six words for the conversation below, in the language of the conversation. Reply with the title only, without quotes or a trailing period.\n\n{}",
+        "次の会話に、会話と同じ言語で 6 語（日本語なら 20 文字）以内のタイトルを付けてください。かぎ括弧や句点を付けず、タイトルだけを返してください。\n\n{}",
+        transcript.trim_end()
+    );
+    let params = GenerationParams {
+        max_tokens: 32,
+        temperature: None,
+        top_p: None,
+    };
+    let response = client
+        .create_message_quietly(model, &params, &[Message::user_text(prompt)])
+        .await?;
+    let text = response
+        .content
+        .iter()
+        .find_map(|block| match block {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .unwrap_or("");
+    let title = text
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_matches(['"', '\'', '「', '」', '。', '.'])
+                .trim()
+        })
+        .find(|line| !line.is_empty())
+        .context("The model returned an empty title")?;
+    Ok(summary_line(title))
+}
+
+/// メッセージの最初のテキスト
+fn text_of(message: &Message) -> Option<&str> {
+    match &message.content {
+        MessageContent::Text(text) => Some(text.as_str()),
+        MessageContent::Blocks(blocks) => blocks.iter().find_map(|b| match b {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        }),
+    }
+}
+
+/// 1 行目を 60 文字までに切り詰める
+fn summary_line(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("");
+    if line.chars().count() > 60 {
+        format!("{}…", line.chars().take(60).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// ユーザーが入力したメッセージ（ツールの結果以外）の位置
+fn turn_starts(messages: &[Message]) -> Vec<usize> {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.role == "user")
+        .filter(|(_, message)| match &message.content {
+            MessageContent::Text(_) => true,
+            MessageContent::Blocks(blocks) => !blocks
+                .iter()
+                .any(|block| matches!(block, ContentBlock::ToolResult { .. })),
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeAnthropic, Reply};
+
+    #[test]
+    fn test_title_uses_first_user_line() {
+        let mut session = Session::new(Path::new("/tmp"), "claude-sonnet-4-5");
+        session
+            .messages
+            .push(Message::user_text("fix the bug\nin main.rs"));
+        session.messages.push(Message::assistant_text("done"));
+        assert_eq!(session.title(), "fix the bug");
+
+        let long = "a".repeat(80);
+        session.messages[0] = Message::user_text(long);
+        assert_eq!(session.title().chars().count(), 61);
+    }
+
+    #[test]
+    fn test_fork_and_checkout_keep_every_branch() {
+        let mut session = Session::new(Path::new("/tmp"), "claude-sonnet-4-5");
+        for (question, answer) in [("one", "1"), ("two", "2"), ("three", "3")] {
+            session.messages.push(Message::user_text(question));
+            session.messages.push(Message::assistant_text(answer));
+        }
+
+        // 2 ターン目の後で分岐して別の質問をする
+        assert_eq!(session.fork(Some(2)).unwrap(), 1);
+        assert_eq!(Session::turns(&session.messages), 2);
+        session.messages.push(Message::user_text("four"));
+        assert!(session.fork(Some(5)).is_err());
+        assert_eq!(Session::latest_prompt(&session.messages), "four");
+        assert_eq!(
+            Session::last_turn(&session.messages),
+            Some((4, "four".to_string()))
+        );
+
+        session.checkout(0).unwrap();
+        assert_eq!(Session::turns(&session.messages), 3);
+        session.checkout(1).unwrap();
+        assert_eq!(Session::turns(&session.messages), 3);
+        assert_eq!(session.branches[1].parent, Some(0));
+        assert_eq!(session.branches[1].forked_at_turn, 2);
+        assert!(session.checkout(2).is_err());
+
+        let saved: Session =
+            serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert_eq!(saved.branch, 1);
+        assert_eq!(saved.branches.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_generated_title_replaces_first_line() {
+        let fake = FakeAnthropic::start(vec![Reply::text("\"Fix flaky login test.\"\n")]).await;
+        let mut session = Session::new(Path::new("/tmp"), "claude-sonnet-4-5");
+        session
+            .messages
+            .push(Message::user_text("the login test fails sometimes"));
+        session
+            .messages
+            .push(Message::assistant_text("Fixed the race."));
+
+        let title = generate_title(&fake.client(), "claude-haiku-4-5", &session.messages)
+            .await
+            .unwrap();
+        assert_eq!(title, "Fix flaky login test");
+        let requests = fake.requests().await;
+        assert_eq!(requests[0]["model"], "claude-haiku-4-5");
+        assert!(requests[0]["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("[user]\nthe login test fails sometimes\n\n[assistant]\nFixed the race."));
+
+        session.title = Some(title);
+        assert_eq!(session.title(), "Fix flaky login test");
+    }
+}
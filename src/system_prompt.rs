@@ -1,6 +1,271 @@
-/// Build the system prompt for the coding agent
-pub fn build_system_prompt() -> String {
-    r#"You are a Rust coding assistant with access to file system tools.
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::anthropic::Tool;
+use crate::config::{
+    expand_tilde, AgentConfig, Config, CustomInstructions, Mode, SystemPromptMode,
+};
+use crate::i18n::{self, tr};
+use crate::tools::IgnoreMatcher;
+
+mod mode;
+mod repo_map;
+pub use mode::mode_allows_tool;
+
+/// Project instruction file names, checked in order in each directory
+const INSTRUCTION_FILES: [&str; 2] = ["AGENTS.md", "CLAUDE.md"];
+
+/// Runtime facts appended to the built-in prompt
+///
+/// The same `{{name}}` placeholders can be used in `agent.system_prompt_file`.
+const ENVIRONMENT_TEMPLATE_EN: &str = "## Environment
+- Operating system: {{os}}
+- Working directory: {{cwd}}
+- Git: {{git}}
+- Date: {{date}}
+- Toolchain: {{toolchain}}";
+
+const ENVIRONMENT_TEMPLATE_JA: &str = "## 実行環境
+- OS: {{os}}
+- 作業ディレクトリ: {{cwd}}
+- Git: {{git}}
+- 日付: {{date}}
+- ツールチェーン: {{toolchain}}";
+
+/// Marker files used to detect the project language and toolchain
+const TOOLCHAIN_MARKERS: [(&str, &str); 8] = [
+    ("Cargo.toml", "Rust (cargo)"),
+    ("package.json", "JavaScript/TypeScript (npm)"),
+    ("go.mod", "Go (go modules)"),
+    ("pyproject.toml", "Python (pyproject)"),
+    ("requirements.txt", "Python (pip)"),
+    ("pom.xml", "Java (Maven)"),
+    ("build.gradle", "Java/Kotlin (Gradle)"),
+    ("Gemfile", "Ruby (bundler)"),
+];
+
+/// Build the system prompt, applying `agent.system_prompt_file` if configured
+/// and appending the project instructions found from `workspace`
+///
+/// `mode` selects the built-in prompt and `tools` are the schemas of the
+/// registered tools, listed in the built-in prompt.
+pub fn load_system_prompt(
+    config: &Config,
+    mode: Mode,
+    workspace: &Path,
+    tools: &[Tool],
+) -> Result<String> {
+    let agent = &config.agent;
+    let vars = environment_vars(workspace);
+    let mut prompt = base_prompt(
+        agent,
+        mode::build_mode_prompt(mode, tools, workspace),
+        &vars,
+    )?;
+    if let Some(instructions) = &agent.custom_instructions {
+        let content = render_template(&load_custom_instructions(instructions)?, &vars);
+        prompt.push_str(&tr!(
+            "\n\n## User Instructions\n{}",
+            "\n\n## ユーザーの指示\n{}",
+            content.trim_end()
+        ));
+    }
+    if agent.project_instructions {
+        for path in project_instruction_files(workspace) {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read project instructions {:?}", path))?;
+            tracing::info!("Using project instructions from {:?}", path);
+            let name = path.strip_prefix(workspace).unwrap_or(&path);
+            prompt.push_str(&tr!(
+                "\n\n## Project Instructions ({})\n{}",
+                "\n\n## プロジェクトの指示 ({})\n{}",
+                name.display(),
+                content.trim_end()
+            ));
+        }
+    }
+    if agent.repo_map {
+        let ignore = IgnoreMatcher::new(&config.ignore, workspace)?;
+        prompt.push_str("\n\n");
+        prompt.push_str(&repo_map::build_repo_map(
+            workspace,
+            &ignore,
+            agent.repo_map_max_bytes,
+            agent.task.as_deref(),
+        ));
+    }
+    Ok(prompt)
+}
+
+fn base_prompt(
+    agent: &AgentConfig,
+    mode_prompt: String,
+    vars: &[(&str, String)],
+) -> Result<String> {
+    let built_in = format!(
+        "{}\n\n{}",
+        mode_prompt,
+        render_template(
+            i18n::pick(ENVIRONMENT_TEMPLATE_EN, ENVIRONMENT_TEMPLATE_JA),
+            vars
+        )
+    );
+    let Some(path) = &agent.system_prompt_file else {
+        return Ok(built_in);
+    };
+
+    let path = expand_tilde(path);
+    let custom = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read system prompt file {:?}", path))?;
+    let custom = render_template(&custom, vars);
+    tracing::info!("Using custom system prompt from {:?}", path);
+
+    Ok(match agent.system_prompt_mode {
+        SystemPromptMode::Replace => custom,
+        SystemPromptMode::Append => tr!(
+            "{}\n\n## Additional Instructions\n{}",
+            "{}\n\n## 追加の指示\n{}",
+            built_in,
+            custom.trim_end()
+        ),
+    })
+}
+
+/// `agent.custom_instructions` の本文（ファイル指定の場合は読み込む）
+fn load_custom_instructions(instructions: &CustomInstructions) -> Result<String> {
+    match instructions {
+        CustomInstructions::Text(text) => Ok(text.clone()),
+        CustomInstructions::File { file } => {
+            let path = expand_tilde(file);
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read custom instructions file {:?}", path))
+        }
+    }
+}
+
+/// `{{name}}` を値に置き換える（未知の名前はそのまま残す）
+fn render_template(template: &str, vars: &[(&str, String)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{{{}}}}}", name), value)
+        })
+}
+
+/// テンプレートに埋め込む実行環境の情報
+fn environment_vars(workspace: &Path) -> Vec<(&'static str, String)> {
+    let toolchains = detect_toolchains(workspace);
+    vec![
+        (
+            "os",
+            format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+        ),
+        ("cwd", workspace.display().to_string()),
+        ("git", git_status(workspace)),
+        ("date", chrono::Local::now().format("%Y-%m-%d").to_string()),
+        (
+            "toolchain",
+            if toolchains.is_empty() {
+                tr!("unknown", "不明")
+            } else {
+                toolchains.join(", ")
+            },
+        ),
+    ]
+}
+
+/// 現在のブランチと未コミットの変更の有無（git リポジトリでなければその旨）
+fn git_status(workspace: &Path) -> String {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .current_dir(workspace)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let Some(branch) = git(&["rev-parse", "--abbrev-ref", "HEAD"]) else {
+        return tr!("not a git repository", "git リポジトリではない");
+    };
+    match git(&["status", "--porcelain"]) {
+        Some(changes) if changes.is_empty() => {
+            tr!("branch {}, clean", "ブランチ {}、変更なし", branch)
+        }
+        Some(_) => tr!(
+            "branch {}, uncommitted changes",
+            "ブランチ {}、未コミットの変更あり",
+            branch
+        ),
+        None => tr!("branch {}", "ブランチ {}", branch),
+    }
+}
+
+/// workspace からリポジトリのルートまでのマーカーファイルで言語とツールチェーンを推定する
+fn detect_toolchains(workspace: &Path) -> Vec<&'static str> {
+    let mut toolchains = Vec::new();
+    for dir in workspace.ancestors() {
+        for (marker, toolchain) in TOOLCHAIN_MARKERS {
+            if dir.join(marker).is_file() && !toolchains.contains(&toolchain) {
+                toolchains.push(toolchain);
+            }
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    toolchains
+}
+
+/// AGENTS.md（なければ CLAUDE.md）を workspace からリポジトリのルート（.git のあるディレクトリ）まで探す
+///
+/// 外側のディレクトリのものが先になるよう、ルートに近い順に返す
+fn project_instruction_files(workspace: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for dir in workspace.ancestors() {
+        if let Some(file) = INSTRUCTION_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+        {
+            files.push(file);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    files.reverse();
+    files
+}
+
+/// 登録されているツールの一覧（名前、必須の引数、説明の最初の文）
+fn tool_list(tools: &[Tool]) -> String {
+    tools
+        .iter()
+        .map(|tool| {
+            let required: Vec<&str> = tool.input_schema["required"]
+                .as_array()
+                .map(|names| names.iter().filter_map(|name| name.as_str()).collect())
+                .unwrap_or_default();
+            // 説明の最初の文（日本語は「。」、英語は ". " まで）
+            let description = &tool.description;
+            let end = ["。", ". ", "\n"]
+                .iter()
+                .filter_map(|sep| description.find(sep).map(|i| i + sep.len()))
+                .min()
+                .unwrap_or(description.len());
+            let summary = description[..end].trim();
+            format!("- {}({}): {}", tool.name, required.join(", "), summary)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the system prompt for the coding agent (code mode)
+pub fn build_system_prompt(tools: &[Tool]) -> String {
+    i18n::pick(CODE_PROMPT_EN, CODE_PROMPT_JA).replace("{{tools}}", &tool_list(tools))
+}
+
+const CODE_PROMPT_EN: &str = r#"You are a Rust coding assistant with access to file system tools.
 
 ## Critical Rules (Non-Negotiable)
 1. NEVER assume or guess file contents, names, or locations - You must explore to understand them
@@ -22,6 +287,7 @@ When you receive a request, follow this mandatory sequence and proceed automatic
 - Discover project structure: Use 'listFiles' to understand what files exist
 - Use 'readFile': Read ALL reference files mentioned in the request
 - Use 'searchInDirectory': Find related files when unsure about locations
+- Use 'searchInFile': Find the lines to read in a file you already know
 - Verify reality: What you discover often differs from assumptions
 
 **Internal Verification (check silently, do not ask user):**
@@ -46,14 +312,111 @@ When you receive a request, follow this mandatory sequence and proceed automatic
 - FORBIDDEN: Asking "Should I proceed with implementation?" after information gathering
 
 ## Available Tools
-- readFile: Read file contents by path
-- writeFile: Create new files (requires user confirmation)
-- editFile: Modify existing files (requires reading first)
-- listFiles: List directory contents
-- searchInDirectory: Search for text patterns in files
+{{tools}}
 
 ## Your Responsibility
 Complete the entire task following this protocol in one continuous flow.
-No shortcuts, no assumptions, no guessing, and no asking for permission between steps."#
-        .to_string()
+No shortcuts, no assumptions, no guessing, and no asking for permission between steps."#;
+
+const CODE_PROMPT_JA: &str = r#"あなたはファイルシステムのツールを使える Rust のコーディングアシスタントです。
+
+## 重要なルール（例外なし）
+1. ファイルの内容・名前・場所を決して推測しない - 必ず調べて把握すること
+2. 実装の前の情報収集は必須 - 推測は即座に失敗につながる
+3. writeFile や editFile を使う前に、参照ファイルを必ず readFile で読むこと
+4. ステップの間で許可を求めない - ワークフロー全体を自動的に進めること
+5. タスク全体を一続きの流れで完了する - 確認のために止まらないこと
+
+## 情報収集が重要な理由
+- ファイル構成はさまざま: 想定と実際はしばしば異なる
+- 拡張子が重要: .rs か .ts か .go かで実装が変わる
+- ディレクトリ構成が重要: プロジェクトごとに構成は異なる
+- 推測の代償: 推測が外れると作業がすべてやり直しになる
+
+## 実行手順
+依頼を受けたら、次の必須の手順に従って自動的に進めること:
+
+### ステップ 1: 情報収集（必須、ただし自動的に進める）
+- プロジェクト構成を把握する: 'listFiles' でどのファイルがあるかを調べる
+- 'readFile' を使う: 依頼で言及された参照ファイルをすべて読む
+- 'searchInDirectory' を使う: 場所が不明なときは関連するファイルを探す
+- 'searchInFile' を使う: 分かっているファイルの中で読むべき行を探す
+- 実際を確認する: 調べた結果は想定と異なることが多い
+
+**内部チェック（黙って確認し、ユーザーには尋ねない）:**
+□ 必要なときにプロジェクト構成を調べたか？
+□ 参照ファイルの内容を readFile で読んだか？
+□ 既存のコード構造を理解しているか？
+
+### ステップ 2: 実装（ステップ 1 の後に自動的に進める）
+- 新しいファイルの作成には 'writeFile' を使う
+- 既存ファイルの変更には 'editFile' を使う
+- 関連する変更をすべて完了させる
+
+**重要: 許可を求めずにステップ 1 からステップ 2 へ自動的に進むこと。**
+
+## 避けるべきよくある間違い
+- 禁止: ファイル名の推測（例: 確認せずに "todo.rs" があると決めつける）
+- 禁止: 拡張子の推測（例: .ts かもしれないのに .js と決めつける）
+- 禁止: ディレクトリ構成の推測（例: 確認せずにファイルが "src/" にあると決めつける）
+- 禁止: 「X ファイルを参照」とあるのに X を読まずに実装する
+- 禁止: 知識に頼ってファイルの内容を推測する
+- 禁止: タスクが簡単そうだからと readFile を省略する
+- 禁止: 情報収集の後に「実装を進めてよいですか？」と尋ねる
+
+## 使えるツール
+{{tools}}
+
+## あなたの責任
+この手順に従い、タスク全体を一続きの流れで完了すること。
+近道も決めつけも推測もせず、ステップの間で許可を求めないこと。"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let vars = [
+            ("os", "linux".to_string()),
+            ("date", "2025-01-02".to_string()),
+        ];
+        assert_eq!(
+            render_template("{{os}} on {{date}}, {{unknown}}", &vars),
+            "linux on 2025-01-02, {{unknown}}"
+        );
+    }
+
+    #[test]
+    fn test_tool_list_from_schemas() {
+        let list = tool_list(&crate::tools::builtin_schemas());
+        assert_eq!(list.lines().count(), 9);
+        assert!(list.contains(
+            "- editFile(path, new_content): Completely overwrites the content of an existing file.\n"
+        ));
+        assert!(list.ends_with(
+            "- createPullRequest(title): Pushes the current git branch to origin and opens a pull request on GitHub."
+        ));
+    }
+
+    #[test]
+    fn test_project_instruction_files_stop_at_repo_root() {
+        let root = std::env::temp_dir().join(format!("system_prompt_test_{}", std::process::id()));
+        let repo = root.join("repo");
+        let crate_dir = repo.join("crates").join("core");
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::write(root.join("AGENTS.md"), "outside").unwrap();
+        std::fs::write(repo.join("AGENTS.md"), "repo").unwrap();
+        std::fs::write(repo.join("CLAUDE.md"), "ignored").unwrap();
+        std::fs::write(crate_dir.join("CLAUDE.md"), "crate").unwrap();
+
+        let files = project_instruction_files(&crate_dir);
+        assert_eq!(files, [repo.join("AGENTS.md"), crate_dir.join("CLAUDE.md")]);
+
+        let prompt = load_system_prompt(&Config::default(), Mode::Code, &crate_dir, &[]).unwrap();
+        assert!(prompt.ends_with("## Project Instructions (CLAUDE.md)\ncrate"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
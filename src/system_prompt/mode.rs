@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use crate::anthropic::Tool;
+use crate::config::Mode;
+use crate::i18n::{self, tr};
+
+/// plan / review で使える読み取り専用のツール
+const READ_ONLY_TOOLS: [&str; 6] = [
+    "readFile",
+    "listFiles",
+    "searchInDirectory",
+    "searchInFile",
+    "getDiagnostics",
+    "getGitHubIssue",
+];
+
+/// review でシステムプロンプトに含める差分の上限（文字数）
+const MAX_DIFF_CHARS: usize = 100_000;
+
+const PLAN_PROMPT_EN: &str = r#"You are a Rust coding assistant in planning mode with read-only access to the file system.
+
+## Rules
+1. Do NOT modify files - writing tools are not available in this mode
+2. NEVER guess file contents, names, or locations - Explore with the tools before planning
+3. Base every step of the plan on code you have actually read
+
+## Output
+Produce an implementation plan:
+- The current state of the code relevant to the request
+- Numbered steps, each naming the files to change and what to change in them
+- Risks, open questions, and how to verify the result
+
+## Available Tools
+{{tools}}"#;
+
+const REVIEW_PROMPT_EN: &str = r#"You are a Rust coding assistant reviewing the uncommitted changes shown below.
+
+## Rules
+1. Focus on the diff - Read the surrounding code with the tools when a change depends on it
+2. Do NOT modify files - writing tools are not available in this mode
+3. Only report problems you can point to in the code; do not speculate
+
+## Output
+- Findings ordered by severity (bugs, then risks, then style), each with the file and line
+- A short overall verdict
+
+## Available Tools
+{{tools}}"#;
+
+const ASK_PROMPT_EN: &str = r#"You are a Rust coding assistant answering questions.
+
+No tools are available in this mode: answer from the conversation and your own knowledge,
+say so when you would need to look at the project files to be sure, and keep answers concise."#;
+
+const PLAN_PROMPT_JA: &str = r#"あなたは計画モードの Rust のコーディングアシスタントで、ファイルシステムを読み取ることだけができます。
+
+## ルール
+1. ファイルを変更しない - このモードでは書き込みのツールは使えない
+2. ファイルの内容・名前・場所を決して推測しない - 計画の前にツールで調べること
+3. 計画の各ステップは実際に読んだコードに基づけること
+
+## 出力
+実装計画を作成する:
+- 依頼に関係するコードの現状
+- 番号付きのステップ（それぞれ変更するファイルと変更内容を示す）
+- リスク、未解決の疑問、結果の確認方法
+
+## 使えるツール
+{{tools}}"#;
+
+const REVIEW_PROMPT_JA: &str = r#"あなたは以下に示すコミットされていない変更をレビューする Rust のコーディングアシスタントです。
+
+## ルール
+1. 差分に集中する - 変更が依存する周辺のコードは必要に応じてツールで読む
+2. ファイルを変更しない - このモードでは書き込みのツールは使えない
+3. コードの中で示せる問題だけを指摘し、憶測はしない
+
+## 出力
+- 重要度順の指摘（バグ、リスク、スタイルの順）。それぞれファイルと行を示す
+- 全体の短い評価
+
+## 使えるツール
+{{tools}}"#;
+
+const ASK_PROMPT_JA: &str = r#"あなたは質問に答える Rust のコーディングアシスタントです。
+
+このモードではツールは使えません。会話の内容と自分の知識から答え、
+確かめるためにプロジェクトのファイルを見る必要がある場合はそう伝え、簡潔に答えてください。"#;
+
+/// モードで使えるツールか（code はすべて、plan / review は読み取りのみ、ask はなし）
+pub fn mode_allows_tool(mode: Mode, name: &str) -> bool {
+    match mode {
+        Mode::Code => true,
+        Mode::Plan | Mode::Review => READ_ONLY_TOOLS.contains(&name),
+        Mode::Ask => false,
+    }
+}
+
+/// モードごとの組み込みプロンプト
+pub(super) fn build_mode_prompt(mode: Mode, tools: &[Tool], workspace: &Path) -> String {
+    match mode {
+        Mode::Code => super::build_system_prompt(tools),
+        Mode::Plan => i18n::pick(PLAN_PROMPT_EN, PLAN_PROMPT_JA)
+            .replace("{{tools}}", &super::tool_list(tools)),
+        Mode::Review => tr!(
+            "{}\n\n## Changes to Review\n{}",
+            "{}\n\n## レビューする変更\n{}",
+            i18n::pick(REVIEW_PROMPT_EN, REVIEW_PROMPT_JA)
+                .replace("{{tools}}", &super::tool_list(tools)),
+            changes_to_review(workspace)
+        ),
+        Mode::Ask => i18n::pick(ASK_PROMPT_EN, ASK_PROMPT_JA).to_string(),
+    }
+}
+
+/// コミットされていない変更（git diff HEAD）を上限まで返す
+fn changes_to_review(workspace: &Path) -> String {
+    let output = std::process::Command::new("git")
+        .args(["diff", "HEAD", "--no-color"])
+        .current_dir(workspace)
+        .output()
+        .ok()
+        .filter(|output| output.status.success());
+    let Some(output) = output else {
+        return tr!(
+            "The diff is unavailable (not a git repository or no commits yet).",
+            "差分を取得できません（git リポジトリでないか、まだコミットがありません）。"
+        );
+    };
+
+    let diff = String::from_utf8_lossy(&output.stdout);
+    if diff.trim().is_empty() {
+        return tr!(
+            "There are no uncommitted changes.",
+            "コミットされていない変更はありません。"
+        );
+    }
+    match diff.char_indices().nth(MAX_DIFF_CHARS) {
+        Some((end, _)) => tr!(
+            "```diff\n{}\n```\n(The diff was truncated after {} characters.)",
+            "```diff\n{}\n```\n（差分は {} 文字で切り詰めました。）",
+            &diff[..end],
+            MAX_DIFF_CHARS
+        ),
+        None => format!("```diff\n{}```", diff),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_tool_policies() {
+        assert!(mode_allows_tool(Mode::Code, "editFile"));
+        assert!(mode_allows_tool(Mode::Plan, "readFile"));
+        assert!(!mode_allows_tool(Mode::Plan, "writeFile"));
+        assert!(!mode_allows_tool(Mode::Review, "editFile"));
+        assert!(!mode_allows_tool(Mode::Ask, "readFile"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use crate::i18n::tr;
+use crate::outline;
+use crate::relevance::Relevance;
+use crate::tools::IgnoreMatcher;
+
+/// 1 ファイルあたりに載せるシンボルの上限
+const MAX_SYMBOLS_PER_FILE: usize = 20;
+
+/// シンボルを探すファイルのサイズの上限（バイト）
+const MAX_SOURCE_BYTES: u64 = 512 * 1024;
+
+/// マップの 1 行（ディレクトリまたはファイル）
+struct Entry {
+    depth: usize,
+    line: String,
+    /// ファイルなら関連度の点（ディレクトリは None）
+    score: Option<f64>,
+}
+
+/// ワークスペースのツリーとファイルごとのトップレベルのシンボル（`max_bytes` まで）
+///
+/// 最初の listFiles / readFile による探索を省けるようシステムプロンプトに載せる。
+/// `max_bytes` を超える場合は `task` との関連度が高いファイル（とその親ディレクトリ）だけを載せる
+pub(super) fn build_repo_map(
+    workspace: &Path,
+    ignore: &IgnoreMatcher,
+    max_bytes: usize,
+    task: Option<&str>,
+) -> String {
+    let walker = walkdir::WalkDir::new(workspace)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0
+                || !(e.file_name().to_string_lossy().starts_with('.')
+                    || ignore.is_ignored(e.path()))
+        });
+
+    let relevance = Relevance::new(task.unwrap_or(""));
+    let entries: Vec<Entry> = walker
+        .flatten()
+        .filter(|e| e.depth() > 0)
+        .map(|entry| {
+            let indent = "  ".repeat(entry.depth() - 1);
+            let name = entry.file_name().to_string_lossy();
+            if entry.file_type().is_dir() {
+                return Entry {
+                    depth: entry.depth(),
+                    line: format!("{}{}/\n", indent, name),
+                    score: None,
+                };
+            }
+            let symbols = file_symbols(entry.path());
+            let relative = entry.path().strip_prefix(workspace).unwrap_or(entry.path());
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            let score = relevance.score(relative, &symbols.join(" "), modified);
+            let line = if symbols.is_empty() {
+                format!("{}{}\n", indent, name)
+            } else {
+                format!("{}{}: {}\n", indent, name, symbols.join(", "))
+            };
+            Entry {
+                depth: entry.depth(),
+                line,
+                score: Some(score),
+            }
+        })
+        .collect();
+
+    let total: usize = entries.iter().map(|entry| entry.line.len()).sum();
+    let included = if total <= max_bytes {
+        vec![true; entries.len()]
+    } else {
+        select_entries(&entries, max_bytes)
+    };
+    let map: String = entries
+        .iter()
+        .zip(&included)
+        .filter(|(_, &included)| included)
+        .map(|(entry, _)| entry.line.as_str())
+        .collect();
+    let omitted = entries
+        .iter()
+        .zip(&included)
+        .filter(|(entry, &included)| entry.score.is_some() && !included)
+        .count();
+
+    let mut section = tr!(
+        "## Repository Map\nFiles under the working directory and their top-level symbols. Use it instead of exploring with listFiles, and read a file before relying on its details.\n\n```\n{}```",
+        "## リポジトリの構成\n作業ディレクトリ配下のファイルとトップレベルのシンボルです。listFiles で探索する代わりに使い、詳細に依存する前にファイルを読んでください。\n\n```\n{}```",
+        map
+    );
+    if omitted > 0 {
+        section.push_str(&tr!(
+            "\n(The map is limited to {} bytes; {} files less relevant to the task are not shown.)",
+            "\n（{} バイトに収めるため、タスクとの関連が薄い {} 件のファイルは載せていません。）",
+            max_bytes,
+            omitted
+        ));
+    }
+    section
+}
+
+/// 関連度の高いファイルから、親ディレクトリの行も含めて `max_bytes` に収まるだけ選ぶ
+fn select_entries(entries: &[Entry], max_bytes: usize) -> Vec<bool> {
+    // ファイルごとに、まだ載せていない親ディレクトリの行も合わせた大きさを費用にする
+    let parents: Vec<Vec<usize>> = (0..entries.len())
+        .map(|i| {
+            let mut parents = Vec::new();
+            let mut depth = entries[i].depth;
+            for j in (0..i).rev() {
+                if entries[j].depth < depth {
+                    parents.push(j);
+                    depth = entries[j].depth;
+                }
+            }
+            parents
+        })
+        .collect();
+
+    let mut files: Vec<usize> = (0..entries.len())
+        .filter(|&i| entries[i].score.is_some())
+        .collect();
+    let score = |i: usize| entries[i].score.unwrap_or(0.0);
+    files.sort_by(|&a, &b| score(b).total_cmp(&score(a)));
+
+    let mut included = vec![false; entries.len()];
+    let mut used = 0;
+    for i in files {
+        let cost = entries[i].line.len()
+            + parents[i]
+                .iter()
+                .filter(|&&j| !included[j])
+                .map(|&j| entries[j].line.len())
+                .sum::<usize>();
+        if used + cost > max_bytes {
+            continue;
+        }
+        used += cost;
+        included[i] = true;
+        for &j in &parents[i] {
+            included[j] = true;
+        }
+    }
+    included
+}
+
+/// ファイルのトップレベルのシンボル（対応していない言語は空）
+fn file_symbols(path: &Path) -> Vec<String> {
+    if !outline::is_supported(path) {
+        return Vec::new();
+    }
+    let too_large = std::fs::metadata(path).map_or(true, |m| m.len() > MAX_SOURCE_BYTES);
+    let Some(content) = (!too_large)
+        .then(|| std::fs::read_to_string(path).ok())
+        .flatten()
+    else {
+        return Vec::new();
+    };
+
+    let mut symbols: Vec<String> = outline::top_level_symbols(path, &content)
+        .into_iter()
+        .map(|(_, symbol)| symbol)
+        .collect();
+    if symbols.len() > MAX_SYMBOLS_PER_FILE {
+        symbols.truncate(MAX_SYMBOLS_PER_FILE);
+        symbols.push("…".to_string());
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempWorkspace;
+
+    #[test]
+    fn test_keeps_relevant_files_over_the_budget() {
+        let workspace = TempWorkspace::new()
+            .file("src/auth/login.rs", "pub fn check_password() {}\n")
+            .file("src/render.rs", "pub fn draw_frame() {}\n")
+            .file("docs/guide.md", "");
+        let ignore = IgnoreMatcher::new(&[], workspace.root()).unwrap();
+
+        let full = build_repo_map(workspace.root(), &ignore, 1024, None);
+        assert!(full.contains("render.rs") && !full.contains("not shown"));
+
+        let map = build_repo_map(workspace.root(), &ignore, 60, Some("Fix check_password"));
+        assert!(map.contains("src/\n  auth/\n    login.rs: fn check_password\n"));
+        assert!(!map.contains("render.rs"));
+        assert!(map.contains("2 files less relevant to the task are not shown"));
+    }
+}
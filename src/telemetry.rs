@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracerProvider, Tracer};
+use opentelemetry_sdk::Resource;
+use std::fmt::Display;
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::anthropic::Usage;
+use crate::config::TelemetryConfig;
+
+/// エクスポートの有効化に使う標準の環境変数
+const ENDPOINT_VARS: [&str; 2] = [
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+];
+
+/// トレースのエクスポーター（drop 時に残りのスパンを送ってから止める）
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Warning: Failed to export traces: {}", e);
+        }
+    }
+}
+
+/// OTLP へスパンを送るレイヤーを作る（エンドポイントが設定されていなければ None）
+pub fn layer<S>(
+    config: &TelemetryConfig,
+) -> Result<Option<(OpenTelemetryLayer<S, Tracer>, Telemetry)>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let from_env = ENDPOINT_VARS
+        .iter()
+        .any(|var| std::env::var(var).is_ok_and(|v| !v.is_empty()));
+    if config.otlp_endpoint.is_none() && !from_env {
+        return Ok(None);
+    }
+
+    let mut exporter = SpanExporter::builder().with_http();
+    if let Some(endpoint) = &config.otlp_endpoint {
+        exporter = exporter.with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')));
+    }
+    let exporter = exporter
+        .build()
+        .context("Failed to create the OTLP trace exporter")?;
+
+    // OTEL_SERVICE_NAME があればそちらを使う
+    let mut resource = Resource::builder()
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")));
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    Ok(Some((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        Telemetry { provider },
+    )))
+}
+
+/// トークン数をスパンに記録する
+pub fn record_usage(span: &Span, usage: &Usage) {
+    span.record("gen_ai.usage.input_tokens", usage.input_tokens);
+    span.record("gen_ai.usage.output_tokens", usage.output_tokens);
+    span.record(
+        "gen_ai.usage.cache_read_input_tokens",
+        usage.cache_read_input_tokens,
+    );
+    span.record(
+        "gen_ai.usage.cache_creation_input_tokens",
+        usage.cache_creation_input_tokens,
+    );
+}
+
+/// スパンを失敗として記録する
+pub fn record_error(span: &Span, error: impl Display) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_description", error.to_string());
+}
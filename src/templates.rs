@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// Directory holding the prompt templates (`~/.codex/templates`)
+pub fn templates_dir() -> Result<PathBuf> {
+    Ok(Config::codex_home()?.join("templates"))
+}
+
+/// `--var KEY=VALUE` を分解する
+pub fn parse_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got {:?}", arg)),
+    }
+}
+
+/// 名前付きテンプレート（`~/.codex/templates/<name>.md`）を読み込んで変数を埋める
+pub fn load(name: &str, vars: &[(String, String)]) -> Result<String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("Invalid template name {:?}", name);
+    }
+    let dir = templates_dir()?;
+    let path = dir.join(format!("{}.md", name));
+    if !path.is_file() {
+        let available = list(&dir);
+        if available.is_empty() {
+            bail!("Template {:?} not found; add it as {:?}", name, path);
+        }
+        bail!(
+            "Template {:?} not found in {:?} (available: {})",
+            name,
+            dir,
+            available.join(", ")
+        );
+    }
+    let template = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read template {:?}", path))?;
+    render(&template, vars).with_context(|| format!("Failed to render template {:?}", name))
+}
+
+/// テンプレート名の一覧（拡張子 .md を除いたファイル名）
+fn list(dir: &std::path::Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// `{{var}}` を置き換える（値のない変数が残る場合はエラー）
+pub fn render(template: &str, vars: &[(String, String)]) -> Result<String> {
+    let mut rendered = String::new();
+    let mut missing = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        rendered.push_str(&rest[..start]);
+        // 後から指定した値を優先する
+        match vars.iter().rev().find(|(key, _)| key == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => {
+                if !missing.iter().any(|m| m == name) {
+                    missing.push(name.to_string());
+                }
+            }
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    rendered.push_str(rest);
+
+    if !missing.is_empty() {
+        bail!(
+            "Missing template variables: {} (pass them with --var KEY=VALUE)",
+            missing.join(", ")
+        );
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_vars() {
+        let vars = vec![("crate".to_string(), "mylib".to_string())];
+        assert_eq!(
+            render("Fix the tests in {{crate}} ({{ crate }})", &vars).unwrap(),
+            "Fix the tests in mylib (mylib)"
+        );
+        let error = render("{{crate}} {{version}}", &vars).unwrap_err();
+        assert!(error.to_string().contains("version"));
+        assert_eq!(parse_var("a=b=c").unwrap(), ("a".into(), "b=c".into()));
+        assert!(parse_var("novalue").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! 結合テスト用の偽の Anthropic API と一時ワークスペース（`test-support` フィーチャー）
+//!
+//! 台本どおりの応答を順に返す wiremock のサーバーを立て、エージェントループ全体
+//! （複数回の反復、API のエラー、max_tokens での打ち切りなど）を外部に接続せずに試せるようにする
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use crate::anthropic::AnthropicClient;
+use crate::config::ApiConfig;
+
+/// 偽の API が返す 1 回分の応答
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// content ブロックと stop_reason を持つメッセージ
+    Message {
+        content: Vec<Value>,
+        stop_reason: String,
+    },
+    /// エラーのステータスと本文
+    Error { status: u16, body: String },
+}
+
+impl Reply {
+    /// テキストだけの最終応答
+    pub fn text(text: &str) -> Self {
+        Reply::Message {
+            content: vec![json!({ "type": "text", "text": text })],
+            stop_reason: "end_turn".to_string(),
+        }
+    }
+
+    /// ツールを 1 つ呼び出す応答
+    pub fn tool_use(name: &str, input: Value) -> Self {
+        Self::tool_uses(&[(name, input)])
+    }
+
+    /// 複数のツールを呼び出す応答（ID は `toolu_<番号>`）
+    pub fn tool_uses(calls: &[(&str, Value)]) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        let content = calls
+            .iter()
+            .map(|(name, input)| {
+                json!({
+                    "type": "tool_use",
+                    "id": format!("toolu_{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+                    "name": name,
+                    "input": input,
+                })
+            })
+            .collect();
+        Reply::Message {
+            content,
+            stop_reason: "tool_use".to_string(),
+        }
+    }
+
+    /// max_tokens で途中まで生成された応答
+    pub fn truncated(text: &str) -> Self {
+        Reply::Message {
+            content: vec![json!({ "type": "text", "text": text })],
+            stop_reason: "max_tokens".to_string(),
+        }
+    }
+
+    /// API のエラー（429 / 529 / 5xx は再試行の対象）
+    pub fn error(status: u16, message: &str) -> Self {
+        Reply::Error {
+            status,
+            body: json!({
+                "type": "error",
+                "error": { "type": "api_error", "message": message },
+            })
+            .to_string(),
+        }
+    }
+
+    fn respond(&self, stream: bool) -> ResponseTemplate {
+        let (content, stop_reason) = match self {
+            Reply::Error { status, body } => {
+                return ResponseTemplate::new(*status)
+                    .set_body_raw(body.clone(), "application/json");
+            }
+            Reply::Message {
+                content,
+                stop_reason,
+            } => (content, stop_reason),
+        };
+        if stream {
+            return ResponseTemplate::new(200)
+                .set_body_raw(sse_body(content, stop_reason), "text/event-stream");
+        }
+        ResponseTemplate::new(200).set_body_json(json!({
+            "id": "msg_fake",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-fake",
+            "content": content,
+            "stop_reason": stop_reason,
+            "stop_sequence": null,
+            "usage": { "input_tokens": 10, "output_tokens": 5 },
+        }))
+    }
+}
+
+/// ストリーミング API の応答（テキストは数文字ずつの差分に分ける）
+fn sse_body(content: &[Value], stop_reason: &str) -> String {
+    fn event(data: Value) -> String {
+        format!(
+            "event: {}\ndata: {}\n\n",
+            data["type"].as_str().unwrap(),
+            data
+        )
+    }
+
+    let mut body = event(json!({
+        "type": "message_start",
+        "message": { "id": "msg_fake", "usage": { "input_tokens": 10, "output_tokens": 1 } },
+    }));
+    for (index, block) in content.iter().enumerate() {
+        if block["type"] == "text" {
+            body.push_str(&event(json!({
+                "type": "content_block_start",
+                "index": index,
+                "content_block": { "type": "text", "text": "" },
+            })));
+            let chars: Vec<char> = block["text"].as_str().unwrap_or_default().chars().collect();
+            for chunk in chars.chunks(5) {
+                body.push_str(&event(json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": { "type": "text_delta", "text": chunk.iter().collect::<String>() },
+                })));
+            }
+        } else {
+            body.push_str(&event(json!({
+                "type": "content_block_start",
+                "index": index,
+                "content_block": { "type": "tool_use", "id": block["id"], "name": block["name"], "input": {} },
+            })));
+            body.push_str(&event(json!({
+                "type": "content_block_delta",
+                "index": index,
+                "delta": { "type": "input_json_delta", "partial_json": block["input"].to_string() },
+            })));
+        }
+        body.push_str(&event(
+            json!({ "type": "content_block_stop", "index": index }),
+        ));
+    }
+    body.push_str(&event(json!({
+        "type": "message_delta",
+        "delta": { "stop_reason": stop_reason },
+        "usage": { "output_tokens": 5 },
+    })));
+    body.push_str(&event(json!({ "type": "message_stop" })));
+    body
+}
+
+/// 台本の応答を先頭から 1 つずつ返す（使い切った後は 500）
+struct Script(Mutex<VecDeque<Reply>>);
+
+impl Respond for Script {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let stream =
+            serde_json::from_slice::<Value>(&request.body).is_ok_and(|body| body["stream"] == true);
+        match self.0.lock().unwrap().pop_front() {
+            Some(reply) => reply.respond(stream),
+            None => Reply::error(500, "no scripted reply left").respond(false),
+        }
+    }
+}
+
+/// 台本どおりに応答する偽の Messages API
+pub struct FakeAnthropic {
+    server: MockServer,
+}
+
+impl FakeAnthropic {
+    /// `replies` を順に返すサーバーを起動する
+    pub async fn start(replies: Vec<Reply>) -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(Script(Mutex::new(replies.into())))
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    /// このサーバーに接続する `[api]` の設定（再試行なし）
+    pub fn api_config(&self) -> ApiConfig {
+        ApiConfig {
+            base_url: format!("{}/v1", self.server.uri()),
+            max_retries: 0,
+            ..ApiConfig::default()
+        }
+    }
+
+    pub fn client(&self) -> AnthropicClient {
+        AnthropicClient::new("test-key".to_string(), &self.api_config())
+            .expect("failed to build the client")
+    }
+
+    /// 受け取った Messages API のリクエストの本文（受け取った順）
+    pub async fn requests(&self) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|request| serde_json::from_slice(&request.body).ok())
+            .collect()
+    }
+}
+
+/// テストごとの一時ワークスペース（drop 時に削除する）
+pub struct TempWorkspace {
+    root: PathBuf,
+}
+
+impl TempWorkspace {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "agent_workspace_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&root).expect("failed to create the workspace");
+        Self {
+            root: root.canonicalize().unwrap_or(root),
+        }
+    }
+
+    /// ファイルを作る（親ディレクトリも作る）
+    pub fn file(self, relative: &str, content: impl AsRef<[u8]>) -> Self {
+        let path = self.path(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("failed to create the directory");
+        }
+        std::fs::write(&path, content).expect("failed to write the file");
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// ワークスペース内のパス
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// ワークスペース内のパス（ツールの入力に使う文字列）
+    pub fn path_str(&self, relative: &str) -> String {
+        self.path(relative).to_string_lossy().into_owned()
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        std::fs::read_to_string(self.path(relative)).expect("failed to read the file")
+    }
+}
+
+impl Default for TempWorkspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TempWorkspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
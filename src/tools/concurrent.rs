@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use std::future::Future;
+use tokio::task::JoinSet;
+
+/// 最大 `limit` 件ずつ並行に `task` を実行し、結果を入力の順に `consume` へ渡す
+///
+/// 先に終わった結果は手前の結果がそろうまで保持する。
+/// `consume` が false を返した時点で残りのタスクは中止する
+pub async fn for_each_ordered<I, T, F, Fut>(
+    items: Vec<I>,
+    limit: usize,
+    task: F,
+    mut consume: impl FnMut(T) -> bool,
+) -> Result<()>
+where
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut results: Vec<Option<T>> = items.iter().map(|_| None).collect();
+    let mut pending = items.into_iter().enumerate();
+    let mut tasks = JoinSet::new();
+    let mut next = 0;
+
+    loop {
+        while tasks.len() < limit.max(1) {
+            let Some((index, item)) = pending.next() else {
+                break;
+            };
+            let future = task(item);
+            tasks.spawn(async move { (index, future.await) });
+        }
+        let Some(joined) = tasks.join_next().await else {
+            return Ok(());
+        };
+        let (index, result) = joined.context("File task failed")?;
+        results[index] = Some(result);
+
+        while let Some(result) = results.get_mut(next).and_then(Option::take) {
+            next += 1;
+            if !consume(result) {
+                // JoinSet を破棄すると実行中のタスクも中止される
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_for_each_ordered_keeps_order_and_stops_early() {
+        let mut seen = Vec::new();
+        for_each_ordered(
+            vec![30u64, 10, 20, 0, 5],
+            3,
+            |delay| async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                delay
+            },
+            |delay| {
+                seen.push(delay);
+                seen.len() < 4
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(seen, [30, 10, 20, 0]);
+    }
+}
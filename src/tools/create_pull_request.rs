@@ -0,0 +1,191 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::github::GitHubClient;
+use crate::i18n::tr;
+use crate::ui::Confirmer;
+
+/// createPullRequest ツールの引数
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreatePullRequestArgs {
+    title: String,
+    #[serde(default)]
+    body: String,
+    /// 省略時はリポジトリの既定のブランチ
+    base: Option<String>,
+    #[serde(default)]
+    draft: bool,
+}
+
+/// createPullRequest ツールの実装
+///
+/// 現在のブランチを origin へ push し、プルリクエストを作成する（確認を求める）
+pub struct CreatePullRequestTool {
+    client: GitHubClient,
+    confirmer: Arc<Confirmer>,
+    workspace: PathBuf,
+}
+
+impl CreatePullRequestTool {
+    pub fn new(client: GitHubClient, confirmer: Arc<Confirmer>, workspace: &Path) -> Self {
+        Self {
+            client,
+            confirmer,
+            workspace: workspace.to_path_buf(),
+        }
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        let output = tokio::process::Command::new("git")
+            .args(args)
+            .current_dir(&self.workspace)
+            .output()
+            .await
+            .context("Failed to run git")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("git {} failed: {}", args.join(" "), stderr.trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// ブランチを push してプルリクエストを作る（ユーザーが拒否した場合は None）
+    async fn create(&self, args: &CreatePullRequestArgs) -> Result<Option<String>> {
+        if !self.client.has_token() {
+            bail!(
+                "No GitHub token; set GITHUB_TOKEN, github.token in the config, or run `login --github`"
+            );
+        }
+        let branch = self.git(&["rev-parse", "--abbrev-ref", "HEAD"]).await?;
+        if branch == "HEAD" {
+            bail!("HEAD is detached; check out a branch first");
+        }
+        let base = match &args.base {
+            Some(base) => base.clone(),
+            None => self.client.default_branch().await?,
+        };
+        if branch == base {
+            bail!(
+                "The current branch is the base branch {}; commit the changes on a new branch first",
+                base
+            );
+        }
+
+        // origin の base がなければコミット一覧は省く
+        let commits = self
+            .git(&["log", "--oneline", &format!("origin/{}..HEAD", base)])
+            .await
+            .unwrap_or_default();
+        let mut preview = format!("{}\n\n{}\n", args.title, args.body.trim());
+        if !commits.is_empty() {
+            preview.push_str(&format!("\nCommits:\n{}\n", commits));
+        }
+        let message = tr!(
+            "Push {} to origin and open a pull request into {} on {}?",
+            "{} を origin へ push し、{2} の {1} へのプルリクエストを作成しますか？",
+            branch,
+            base,
+            self.client.repo
+        );
+        if !self
+            .confirmer
+            .confirm("createPullRequest", &branch, &message, &preview)
+            .await?
+        {
+            return Ok(None);
+        }
+
+        self.git(&["push", "--set-upstream", "origin", &branch])
+            .await?;
+        let pull = self
+            .client
+            .create_pull_request(&json!({
+                "title": args.title,
+                "body": args.body,
+                "head": branch,
+                "base": base,
+                "draft": args.draft,
+            }))
+            .await?;
+        Ok(Some(tr!(
+            "Created pull request #{}: {}",
+            "プルリクエスト #{} を作成しました: {}",
+            pull["number"],
+            pull["html_url"].as_str().unwrap_or_default()
+        )))
+    }
+}
+
+#[async_trait]
+impl ToolHandler for CreatePullRequestTool {
+    type Args = CreatePullRequestArgs;
+
+    const NAME: &'static str = "createPullRequest";
+
+    fn description() -> String {
+        tr!(
+            "Pushes the current git branch to origin and opens a pull request on GitHub. Commit the changes on a new branch first. Asks for confirmation.",
+            "現在の git ブランチを origin へ push し、GitHub にプルリクエストを作成します。先に新しいブランチに変更をコミットしてください。確認を求めます。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "title",
+                tr!("Title of the pull request", "プルリクエストのタイトル"),
+            ),
+            (
+                "body",
+                tr!(
+                    "Description in markdown (e.g. what changed and \"Fixes #42\")",
+                    "markdown の説明（例: 変更内容と \"Fixes #42\"）"
+                ),
+            ),
+            (
+                "base",
+                tr!(
+                    "Branch to merge into; defaults to the repository's default branch",
+                    "マージ先のブランチ。省略時はリポジトリの既定のブランチ"
+                ),
+            ),
+            ("draft", tr!("Open as a draft", "ドラフトとして作成する")),
+        ]
+    }
+
+    async fn execute(&self, args: CreatePullRequestArgs) -> Result<ToolResult> {
+        debug!("Executing createPullRequest tool with input: {:?}", args);
+
+        match self.create(&args).await {
+            Ok(Some(content)) => Ok(ToolResult {
+                content,
+                error: None,
+            }),
+            Ok(None) => {
+                debug!("User cancelled");
+                Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "Cancelled by the user",
+                        "ユーザーによりキャンセルされました"
+                    )),
+                })
+            }
+            Err(e) => Ok(ToolResult {
+                content: String::new(),
+                error: Some(tr!(
+                    "Failed to create the pull request: {:#}",
+                    "プルリクエストを作成できませんでした: {:#}",
+                    e
+                )),
+            }),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tracing::debug;
+
+use crate::anthropic::{DynamicTool, Progress, Tool, ToolResult};
+use crate::config::CustomToolConfig;
+use crate::i18n::tr;
+use crate::sandbox::Sandbox;
+
+/// 結果に含める標準出力・標準エラーの上限（バイト）
+const MAX_OUTPUT_BYTES: usize = 100_000;
+
+/// `[[tools.custom]]` で定義した外部コマンドのツール
+///
+/// 入力の JSON を標準入力に渡し、標準出力をそのまま結果にする。
+/// 標準エラーの各行は途中経過として通知する（ビルドなどの長いコマンド向け）。
+/// コマンドは `[sandbox]` の設定どおりに実行する
+pub struct CustomCommandTool {
+    config: CustomToolConfig,
+    sandbox: Sandbox,
+}
+
+impl CustomCommandTool {
+    pub fn new(config: CustomToolConfig, sandbox: Sandbox) -> Self {
+        Self { config, sandbox }
+    }
+
+    /// コマンドを実行して終了を待つ（時間切れなら None）
+    async fn run(&self, input: &Value, progress: &Progress) -> Result<Option<Output>> {
+        let (program, args) =
+            self.config.command.split_first().with_context(|| {
+                format!("tools.custom {} has an empty command", self.config.name)
+            })?;
+        let mut child = self
+            .sandbox
+            .command(program, args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+
+        let mut stdin = child.stdin.take().context("Failed to open stdin")?;
+        let mut stdout = child.stdout.take().context("Failed to open stdout")?;
+        let stderr = child.stderr.take().context("Failed to open stderr")?;
+        let input = input.to_string();
+        let write = async move {
+            // 入力を読まずに終了するコマンドもあるので書き込みの失敗は無視する
+            let _ = stdin.write_all(input.as_bytes()).await;
+        };
+        let read_stdout = async move {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).await.map(|_| buf)
+        };
+        let read_stderr = async move {
+            let mut lines = BufReader::new(stderr).lines();
+            let mut text = String::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if !line.trim().is_empty() {
+                    progress.report(None, line.trim());
+                }
+                text.push_str(&line);
+                text.push('\n');
+            }
+            text
+        };
+
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let finished = tokio::time::timeout(timeout, async {
+            let (_, stdout, stderr, status) =
+                tokio::join!(write, read_stdout, read_stderr, child.wait());
+            anyhow::Ok(Output {
+                status: status.context("Failed to wait for the command")?,
+                stdout: stdout.context("Failed to read the output")?,
+                stderr,
+            })
+        })
+        .await;
+        match finished {
+            Ok(output) => output.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// 終了したコマンドの出力
+struct Output {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: String,
+}
+
+#[async_trait]
+impl DynamicTool for CustomCommandTool {
+    fn schema(&self) -> Tool {
+        Tool {
+            name: self.config.name.clone(),
+            description: self.config.description.clone(),
+            input_schema: self.config.schema.clone(),
+        }
+    }
+
+    async fn execute(&self, input: Value, progress: Progress) -> Result<ToolResult> {
+        debug!(
+            "Running custom tool {}: {:?}",
+            self.config.name, self.config.command
+        );
+        let output = match self.run(&input, &progress).await {
+            Ok(Some(output)) => output,
+            Ok(None) => {
+                return Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "The command did not finish within {} seconds",
+                        "コマンドが {} 秒以内に終了しませんでした",
+                        self.config.timeout_secs
+                    )),
+                })
+            }
+            Err(e) => {
+                return Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(format!("{:#}", e)),
+                })
+            }
+        };
+
+        let stdout = truncate(&output.stdout);
+        if output.status.success() {
+            return Ok(ToolResult {
+                content: stdout,
+                error: None,
+            });
+        }
+        Ok(ToolResult {
+            content: stdout,
+            error: Some(tr!(
+                "The command failed ({}): {}",
+                "コマンドが失敗しました（{}）: {}",
+                output.status,
+                truncate(output.stderr.as_bytes()).trim_end()
+            )),
+        })
+    }
+}
+
+/// 出力を UTF-8 にして上限で切り詰める
+fn truncate(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text.into_owned();
+    }
+    let mut end = MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n{}",
+        &text[..end],
+        tr!(
+            "[output truncated at {} bytes]",
+            "[出力は {} バイトで切り詰めました]",
+            MAX_OUTPUT_BYTES
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SandboxConfig;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    fn tool(command: &str) -> CustomCommandTool {
+        CustomCommandTool::new(
+            CustomToolConfig {
+                name: "shout".to_string(),
+                description: "Upper-cases the input.".to_string(),
+                schema: json!({ "type": "object", "properties": {} }),
+                command: vec!["sh".to_string(), "-c".to_string(), command.to_string()],
+                timeout_secs: 5,
+            },
+            Sandbox::new(&SandboxConfig::default(), &std::env::temp_dir()),
+        )
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_passes_input_on_stdin_and_reports_failures() {
+        let result = tool("tr a-z A-Z")
+            .execute(json!({ "text": "hi" }), Progress::default())
+            .await
+            .unwrap();
+        assert_eq!(result.content, r#"{"TEXT":"HI"}"#);
+        assert!(result.error.is_none());
+
+        let result = tool("echo partial; echo broken >&2; exit 3")
+            .execute(json!({}), Progress::default())
+            .await
+            .unwrap();
+        assert_eq!(result.content, "partial\n");
+        let error = result.error.unwrap();
+        assert!(error.contains("broken"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reports_stderr_lines_as_progress() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let progress = Progress::new({
+            let messages = messages.clone();
+            move |percent, message| messages.lock().unwrap().push((percent, message))
+        });
+        let result = tool("echo 'Compiling foo' >&2; echo done")
+            .execute(json!({}), progress)
+            .await
+            .unwrap();
+        assert_eq!(result.content, "done\n");
+        assert_eq!(
+            *messages.lock().unwrap(),
+            [(None, "Compiling foo".to_string())]
+        );
+    }
+}
@@ -1,15 +1,21 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use serde_json::{json, Value};
-use std::io::{self, Write};
 use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
 use tracing::{debug, warn};
 
-use crate::anthropic::{Tool, ToolHandler, ToolResult};
+use super::encoding::{self, TextEncoding};
+use super::file_tracker::{self, FileTracker, Freshness};
+use super::symlinks::SymlinkGuard;
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::i18n::tr;
+use crate::platform::LineEnding;
+use crate::ui::{diff, Confirmer};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct EditFileArgs {
     /// 編集する既存ファイルのパス
     pub path: String,
@@ -17,41 +23,56 @@ pub struct EditFileArgs {
     pub new_content: String,
 }
 
+/// 既存ファイルのテキスト形式（改行コードと末尾改行の有無）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TextFormat {
+    line_ending: LineEnding,
+    trailing_newline: bool,
+}
+
+impl TextFormat {
+    /// 既存の内容から形式を検出する
+    ///
+    /// 改行を含まないファイルは LF とみなす
+    fn detect(content: &str) -> Self {
+        Self {
+            line_ending: LineEnding::detect(content),
+            trailing_newline: content.ends_with('\n'),
+        }
+    }
+
+    /// 新しい内容を既存ファイルの形式に合わせる
+    fn apply(&self, content: &str) -> String {
+        let mut normalized = content.replace("\r\n", "\n");
+
+        if self.trailing_newline {
+            if !normalized.is_empty() && !normalized.ends_with('\n') {
+                normalized.push('\n');
+            }
+        } else {
+            while normalized.ends_with('\n') {
+                normalized.pop();
+            }
+        }
+
+        self.line_ending.apply(&normalized)
+    }
+}
+
 /// editFile ツール
-pub struct EditFileTool;
+pub struct EditFileTool {
+    tracker: FileTracker,
+    confirmer: Arc<Confirmer>,
+    symlinks: SymlinkGuard,
+}
 
 impl EditFileTool {
     /// 新しいインスタンスを作成
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// ツールのスキーマ定義を返す
-    pub fn schema() -> Tool {
-        Tool {
-            name: "editFile".to_string(),
-            description: "既存ファイルの内容を完全に上書きします。\
-                          重要: ファイルを破壊しないために、必ず以下のワークフローに従ってください:\n\
-                          1. 'readFile'を使用して現在の完全な内容を取得する\n\
-                          2. 思考プロセスで、読み取った内容を基に新しいファイルの完全版を構築する\n\
-                          3. このツールを使用して完全な新しい内容を書き込む\n\
-                          部分的な編集には使用しないでください。常にファイル全体の内容を提供してください。\
-                          実行前にユーザーの許可を求めます。"
-                .to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "編集する既存ファイルのパス"
-                    },
-                    "new_content": {
-                        "type": "string",
-                        "description": "ファイル全体を上書きする新しい完全な内容"
-                    }
-                },
-                "required": ["path", "new_content"]
-            }),
+    pub fn new(tracker: FileTracker, confirmer: Arc<Confirmer>, symlinks: SymlinkGuard) -> Self {
+        Self {
+            tracker,
+            confirmer,
+            symlinks,
         }
     }
 }
@@ -62,62 +83,85 @@ impl EditFileTool {
     /// ファイルが存在するかチェック
     fn check_file_exists(path: &str) -> Result<(), String> {
         if !Path::new(path).exists() {
-            return Err(
+            return Err(tr!(
+                "The file does not exist. Use writeFile to create a new file.",
                 "ファイルが存在しません。新しいファイルの作成にはwriteFileを使用してください。"
-                    .to_string(),
-            );
+            ));
         }
 
         if !Path::new(path).is_file() {
-            return Err(format!("{} はファイルではありません。", path));
+            return Err(tr!(
+                "{} is not a file.",
+                "{} はファイルではありません。",
+                path
+            ));
         }
 
         Ok(())
     }
+}
 
-    /// ユーザーに確認を求める
-    fn prompt_user_confirmation(path: &str) -> Result<bool> {
-        // 1. プロンプトを表示
-        print!(
-            "\n既存ファイルを編集します: {}\n実行してもよろしいですか？ [y/N]: ",
-            path
-        );
+#[async_trait]
+impl ToolHandler for EditFileTool {
+    type Args = EditFileArgs;
 
-        // 2. バッファをフラッシュ（即座に表示）
-        io::stdout()
-            .flush()
-            .context("標準出力のフラッシュに失敗しました")?;
+    const NAME: &'static str = "editFile";
 
-        // 3. ユーザー入力を読み取り
-        let mut input = String::new();
-        io::stdin()
-            .read_line(&mut input)
-            .context("ユーザー入力の読み取りに失敗しました")?;
+    const MODIFIES_FILES: bool = true;
 
-        // 4. 入力を検証（"y" または "Y" のみ許可）
-        let confirmed = input.trim().to_lowercase() == "y";
+    fn description() -> String {
+        tr!(
+            "Completely overwrites the content of an existing file. \
+             IMPORTANT: to avoid corrupting the file, always follow this workflow:\n\
+             1. Use 'readFile' to get the current complete content\n\
+             2. In your reasoning, build the complete new version of the file from what you read\n\
+             3. Use this tool to write the complete new content\n\
+             Do not use it for partial edits; always provide the whole file content. \
+             The edit is refused if the file was not read in full with readFile or changed after it. \
+             Asks the user for permission before running.",
+            "既存ファイルの内容を完全に上書きします。\
+             重要: ファイルを破壊しないために、必ず以下のワークフローに従ってください:\n\
+             1. 'readFile'を使用して現在の完全な内容を取得する\n\
+             2. 思考プロセスで、読み取った内容を基に新しいファイルの完全版を構築する\n\
+             3. このツールを使用して完全な新しい内容を書き込む\n\
+             部分的な編集には使用しないでください。常にファイル全体の内容を提供してください。\
+             readFile でファイル全体を読んでいない場合や、その後にファイルが変更されていた場合は編集を拒否します。\
+             実行前にユーザーの許可を求めます。"
+        )
+    }
 
-        Ok(confirmed)
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "path",
+                tr!(
+                    "Path of the existing file to edit",
+                    "編集する既存ファイルのパス"
+                ),
+            ),
+            (
+                "new_content",
+                tr!(
+                    "The complete new content that replaces the whole file",
+                    "ファイル全体を上書きする新しい完全な内容"
+                ),
+            ),
+        ]
     }
-}
 
-#[async_trait]
-impl ToolHandler for EditFileTool {
-    async fn execute(&self, input: Value) -> Result<ToolResult> {
+    async fn execute(&self, args: EditFileArgs) -> Result<ToolResult> {
         debug!("Executing editFile tool");
 
-        // 1. 入力をパース
-        let args: EditFileArgs =
-            serde_json::from_value(input).context("editFile: 引数のパースに失敗しました")?;
-
         debug!(
             "editFile args: path={}, content_length={}",
             args.path,
             args.new_content.len()
         );
 
-        // 2. ファイルが存在するかチェック
-        if let Err(error_msg) = Self::check_file_exists(&args.path) {
+        // 2. ファイルが存在するか、辿ってよいシンボリックリンクかをチェック
+        if let Err(error_msg) = Self::check_file_exists(&args.path)
+            .and_then(|()| self.symlinks.check(Path::new(&args.path)))
+        {
             warn!("editFile: ファイル存在チェック失敗: {}", error_msg);
             return Ok(ToolResult {
                 content: String::new(),
@@ -125,8 +169,98 @@ impl ToolHandler for EditFileTool {
             });
         }
 
-        // 3. ユーザーに確認
-        match Self::prompt_user_confirmation(&args.path) {
+        // 3. readFile でファイル全体を読み、その後に変更されていないかチェック
+        //    （全体を上書きするので、読んでいない内容を消さないよう未読のファイルも拒否する）
+        match self.tracker.check(Path::new(&args.path)) {
+            Freshness::Fresh => {}
+            Freshness::NotRead => {
+                warn!("editFile: {} は readFile されていません", args.path);
+                return Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "File {} has not been read in full with readFile. \
+                         editFile overwrites the whole file, so read its complete current content with readFile first.",
+                        "ファイル {} は readFile で全体を読み込んでいません。\
+                         editFile はファイル全体を上書きするため、先に readFile で現在の内容を全て読んでください。",
+                        args.path
+                    )),
+                });
+            }
+            Freshness::Stale => {
+                warn!(
+                    "editFile: 読み込み後にファイルが変更されています: {}",
+                    args.path
+                );
+                return Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "File {} was modified on disk after it was read with readFile. \
+                         To avoid overwriting those changes, read the latest content with readFile before editing.",
+                        "ファイル {} は readFile で読み込んだ後にディスク上で変更されています。\
+                         変更を上書きしないよう、readFile で最新の内容を読み直してから編集してください。",
+                        args.path
+                    )),
+                });
+            }
+        }
+
+        // 4. 既存ファイルのパーミッション・改行形式・文字コードを取得
+        let permissions = match fs::metadata(&args.path).await {
+            Ok(metadata) => Some(metadata.permissions()),
+            Err(e) => {
+                warn!("editFile: パーミッションの取得に失敗: {}", e);
+                None
+            }
+        };
+        let known_encoding = self.tracker.encoding(Path::new(&args.path));
+        let original_bytes = fs::read(&args.path).await;
+        let (original, text_encoding) = match &original_bytes {
+            Ok(bytes) => match encoding::decode(bytes, known_encoding) {
+                Some((original, text_encoding)) => (Some(original), text_encoding),
+                None => (None, TextEncoding::UTF_8),
+            },
+            Err(e) => {
+                debug!(
+                    "editFile: 既存内容を読み込めないため形式を保持しません: {}",
+                    e
+                );
+                (None, known_encoding.unwrap_or(TextEncoding::UTF_8))
+            }
+        };
+        let new_content = match &original {
+            Some(original) => TextFormat::detect(original).apply(&args.new_content),
+            None => args.new_content.clone(),
+        };
+        let bytes = match text_encoding.encode(&new_content) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("editFile: 元の文字コードに変換できません: {}", e);
+                return Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "Cannot keep the file's encoding: {}",
+                        "ファイルの文字コードを保てません: {}",
+                        e
+                    )),
+                });
+            }
+        };
+
+        // 5. 差分を表示してユーザーに確認
+        let preview = match &original {
+            Some(original) => diff::render_diff(&args.path, original, &new_content),
+            None => diff::render_preview(&args.path, &new_content),
+        };
+        let message = tr!(
+            "\nEditing existing file: {}\nProceed?",
+            "\n既存ファイルを編集します: {}\n実行してもよろしいですか？",
+            args.path
+        );
+        match self
+            .confirmer
+            .confirm("editFile", &args.path, &message, &preview)
+            .await
+        {
             Ok(true) => {
                 debug!("editFile: ユーザーが承認しました");
             }
@@ -134,24 +268,56 @@ impl ToolHandler for EditFileTool {
                 warn!("editFile: ユーザーによってキャンセルされました");
                 return Ok(ToolResult {
                     content: String::new(),
-                    error: Some("ユーザーによってキャンセルされました".to_string()),
+                    error: Some(tr!(
+                        "Cancelled by the user",
+                        "ユーザーによってキャンセルされました"
+                    )),
                 });
             }
             Err(e) => {
                 warn!("editFile: ユーザー確認中にエラー: {}", e);
                 return Ok(ToolResult {
                     content: String::new(),
-                    error: Some(format!("ユーザー確認中にエラーが発生しました: {}", e)),
+                    error: Some(tr!(
+                        "Failed to get the user's confirmation: {}",
+                        "ユーザー確認中にエラーが発生しました: {}",
+                        e
+                    )),
                 });
             }
         }
 
-        // 4. ファイルを完全に上書き
-        match fs::write(&args.path, &args.new_content).await {
+        // 6. 確認を待つ間に人が保存した変更を上書きしないよう、書き込む直前にもう一度調べる
+        let current_bytes = fs::read(&args.path).await;
+        if self.tracker.check(Path::new(&args.path)) == Freshness::Stale
+            || current_bytes.as_ref().ok() != original_bytes.as_ref().ok()
+        {
+            warn!(
+                "editFile: 確認中にファイルが変更されています: {}",
+                args.path
+            );
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(file_tracker::modified_during_confirmation(&args.path)),
+            });
+        }
+
+        // 7. ファイルを完全に上書き
+        match fs::write(&args.path, &bytes).await {
             Ok(_) => {
                 debug!("editFile: ファイルを正常に更新しました: {}", args.path);
+                if let Some(permissions) = permissions {
+                    if let Err(e) = fs::set_permissions(&args.path, permissions).await {
+                        warn!("editFile: パーミッションの復元に失敗: {}", e);
+                    }
+                }
+                self.tracker.record_write(Path::new(&args.path), &bytes);
                 Ok(ToolResult {
-                    content: format!("ファイル {} を正常に更新しました", args.path),
+                    content: tr!(
+                        "Updated file {}",
+                        "ファイル {} を正常に更新しました",
+                        args.path
+                    ),
                     error: None,
                 })
             }
@@ -159,9 +325,112 @@ impl ToolHandler for EditFileTool {
                 warn!("editFile: ファイルの書き込みに失敗: {}", e);
                 Ok(ToolResult {
                     content: String::new(),
-                    error: Some(format!("ファイルの書き込みに失敗しました: {}", e)),
+                    error: Some(tr!(
+                        "Failed to write the file: {}",
+                        "ファイルの書き込みに失敗しました: {}",
+                        e
+                    )),
                 })
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApprovalConfig, ApprovalPolicy, SymlinkPolicy};
+    use crate::policy::ApprovalEngine;
+    use crate::test_support::TempWorkspace;
+    use crate::ui::confirm::{Answer, ConfirmRequest};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_text_format_preserves_crlf_and_trailing_newline() {
+        let format = TextFormat::detect("fn main() {\r\n}\r\n");
+        assert_eq!(format.line_ending, LineEnding::CrLf);
+        assert!(format.trailing_newline);
+        assert_eq!(format.apply("a\nb"), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_text_format_strips_added_trailing_newline() {
+        let format = TextFormat::detect("a\nb");
+        assert_eq!(format.line_ending, LineEnding::Lf);
+        assert!(!format.trailing_newline);
+        assert_eq!(format.apply("a\r\nb\n"), "a\nb");
+    }
+
+    #[tokio::test]
+    async fn test_refuses_files_not_read() {
+        let workspace = TempWorkspace::new().file("a.txt", "before\n");
+        let tool = EditFileTool::new(
+            FileTracker::new(),
+            Arc::new(Confirmer::new(
+                true,
+                ApprovalEngine::new(
+                    &ApprovalConfig::default(),
+                    ApprovalPolicy::Allow,
+                    workspace.root(),
+                )
+                .unwrap(),
+                None,
+            )),
+            SymlinkGuard::new(SymlinkPolicy::Follow, workspace.root()),
+        );
+
+        let result = tool
+            .execute(EditFileArgs {
+                path: workspace.path_str("a.txt"),
+                new_content: "from the model\n".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(result.error.unwrap().contains("has not been read in full"));
+        assert_eq!(workspace.read("a.txt"), "before\n");
+    }
+
+    #[tokio::test]
+    async fn test_refuses_edits_saved_during_confirmation() {
+        let workspace = TempWorkspace::new().file("a.txt", "before\n");
+        let path = workspace.path_str("a.txt");
+        let tracker = FileTracker::new();
+        tracker.record(Path::new(&path), b"before\n");
+
+        // 確認の表示中に人がファイルを保存する
+        let human_edit = PathBuf::from(&path);
+        let confirmer = Confirmer::new(
+            false,
+            ApprovalEngine::new(
+                &ApprovalConfig::default(),
+                ApprovalPolicy::Ask,
+                workspace.root(),
+            )
+            .unwrap(),
+            Some(Arc::new(move |_: ConfirmRequest| {
+                std::fs::write(&human_edit, "saved by a human\n").unwrap();
+                let (tx, rx) = tokio::sync::oneshot::channel();
+                tx.send(Answer::Yes).unwrap();
+                rx
+            })),
+        );
+        let tool = EditFileTool::new(
+            tracker,
+            Arc::new(confirmer),
+            SymlinkGuard::new(SymlinkPolicy::Follow, workspace.root()),
+        );
+
+        let result = tool
+            .execute(EditFileArgs {
+                path,
+                new_content: "from the model\n".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(result
+            .error
+            .unwrap()
+            .contains("while waiting for confirmation"));
+        assert_eq!(workspace.read("a.txt"), "saved by a human\n");
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{bail, Result};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// バイナリとみなすかを判定するために先頭から調べるバイト数
+const BINARY_CHECK_BYTES: usize = 8192;
+
+/// ファイルの文字コード（BOM の有無を含む）
+///
+/// readFile で UTF-8 に変換して返したファイルを、editFile / writeFile で元の文字コードに戻して書く
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding {
+    encoding: &'static Encoding,
+    bom: bool,
+}
+
+impl TextEncoding {
+    pub const UTF_8: Self = Self {
+        encoding: UTF_8,
+        bom: false,
+    };
+
+    /// 文字コード名（例: Shift_JIS, windows-1252）
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    /// BOM なしの UTF-8 か（変換せずにそのまま扱える）
+    pub fn is_plain_utf8(&self) -> bool {
+        *self == Self::UTF_8
+    }
+
+    /// この文字コードとして UTF-8 に変換する（変換できないバイトは置換文字になる）
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let bytes = if self.bom {
+            Encoding::for_bom(bytes).map_or(bytes, |(_, len)| &bytes[len..])
+        } else {
+            bytes
+        };
+        self.encoding
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned()
+    }
+
+    /// この文字コードに変換する（表現できない文字があればエラー）
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(text.len());
+        // encoding_rs は UTF-16 への変換に対応していないので自前で行う
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            let little_endian = self.encoding == UTF_16LE;
+            let units = std::iter::once('\u{feff}')
+                .filter(|_| self.bom)
+                .chain(text.chars())
+                .flat_map(|c| {
+                    let mut buf = [0u16; 2];
+                    c.encode_utf16(&mut buf).to_vec()
+                });
+            for unit in units {
+                bytes.extend(if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                });
+            }
+            return Ok(bytes);
+        }
+
+        if self.bom {
+            bytes.extend_from_slice(b"\xef\xbb\xbf");
+        }
+        let (encoded, _, had_errors) = self.encoding.encode(text);
+        if had_errors {
+            let unmappable: String = text
+                .chars()
+                .filter(|c| self.encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+                .take(5)
+                .collect();
+            bail!(
+                "the content has characters that cannot be written in {}: {}",
+                self.name(),
+                unmappable
+            );
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+}
+
+/// ファイルの内容を UTF-8 に変換する（バイナリと思われる場合は None）
+///
+/// `known` があればその文字コードとして扱い、なければ BOM、UTF-8 としての妥当性、
+/// chardetng の推定の順に判定する
+pub fn decode(bytes: &[u8], known: Option<TextEncoding>) -> Option<(String, TextEncoding)> {
+    if let Some(encoding) = known {
+        return Some((encoding.decode(bytes), encoding));
+    }
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        let encoding = TextEncoding {
+            encoding,
+            bom: true,
+        };
+        return Some((encoding.decode(bytes), encoding));
+    }
+    if bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0) {
+        return None;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => return Some((text.to_string(), TextEncoding::UTF_8)),
+        // 末尾で切れたマルチバイト文字だけなら UTF-8（先頭部分だけを読んだ場合）
+        Err(e) if e.error_len().is_none() => {
+            return Some((
+                String::from_utf8_lossy(bytes).into_owned(),
+                TextEncoding::UTF_8,
+            ))
+        }
+        Err(_) => {}
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = TextEncoding {
+        encoding: detector.guess(None, false),
+        bom: false,
+    };
+    Some((encoding.decode(bytes), encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_shift_jis_and_round_trips() {
+        let (original, _, _) =
+            encoding_rs::SHIFT_JIS.encode("こんにちは、世界。日本語のテキストです。\n");
+        let (text, encoding) = decode(&original, None).unwrap();
+        assert_eq!(encoding.name(), "Shift_JIS");
+        assert_eq!(text, "こんにちは、世界。日本語のテキストです。\n");
+        assert_eq!(encoding.encode(&text).unwrap(), original.as_ref());
+        assert!(encoding.encode("emoji 😀").is_err());
+    }
+
+    #[test]
+    fn test_keeps_bom_and_rejects_binary() {
+        let utf16 = [0xff, 0xfe, b'h', 0, b'i', 0];
+        let (text, encoding) = decode(&utf16, None).unwrap();
+        assert_eq!(text, "hi");
+        assert_eq!(encoding.encode("hi").unwrap(), utf16);
+
+        let (text, encoding) = decode("é".as_bytes(), None).unwrap();
+        assert!(encoding.is_plain_utf8());
+        assert_eq!(text, "é");
+
+        assert!(decode(b"\x7fELF\x02\x01\x00\x00", None).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::encoding::TextEncoding;
+use super::search_index::SearchIndex;
+use crate::i18n::tr;
+
+/// readFile 時点のファイルの状態
+///
+/// mtime は書き込みが短時間に連続すると同じ値になり得るため、
+/// 内容のサイズとハッシュで比較する
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    len: usize,
+    hash: u64,
+}
+
+impl FileStamp {
+    fn capture(content: &[u8]) -> Self {
+        Self {
+            len: content.len(),
+            hash: hash_content(content),
+        }
+    }
+}
+
+/// readFile で内容を返した時点の mtime とサイズ
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ReadMark {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl ReadMark {
+    fn current(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// 鮮度チェックの結果
+#[derive(Debug, PartialEq, Eq)]
+pub enum Freshness {
+    /// 読み込み以降、変更されていない
+    Fresh,
+    /// 一度も readFile されていない
+    NotRead,
+    /// 読み込み以降にディスク上で変更された
+    Stale,
+}
+
+/// readFile で読み込んだファイルの内容ハッシュを記録し、
+/// editFile 実行前に他者による変更がないかを検出する
+///
+/// 実行中に readFile で返した内容も mtime とサイズで記録し、変更のないファイルを
+/// 再び読んだ場合は会話内の以前の結果を参照させる（内容を何度も送らない）
+///
+/// 対話セッションでは searchInDirectory の索引も持ち、書き込みを反映する
+#[derive(Debug, Clone, Default)]
+pub struct FileTracker {
+    stamps: Arc<Mutex<HashMap<PathBuf, FileStamp>>>,
+    reads: Arc<Mutex<HashMap<PathBuf, ReadMark>>>,
+    /// writeFile / editFile で書き込んだファイル（書き込んだ順）
+    writes: Arc<Mutex<Vec<PathBuf>>>,
+    /// readFile で UTF-8 以外から変換したファイルの元の文字コード
+    encodings: Arc<Mutex<HashMap<PathBuf, TextEncoding>>>,
+    search_index: Option<SearchIndex>,
+}
+
+impl FileTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 対話セッション用: ワークスペースの検索索引を持つ
+    pub fn with_search_index(workspace: &Path) -> Self {
+        Self {
+            search_index: Some(SearchIndex::new(workspace)),
+            ..Self::default()
+        }
+    }
+
+    pub fn search_index(&self) -> Option<&SearchIndex> {
+        self.search_index.as_ref()
+    }
+
+    /// 読み込み（または書き込み）時点の内容を記録する
+    ///
+    /// 書き込みの後は会話内の readFile の結果が古くなるため、その記録は消す
+    pub fn record(&self, path: &Path, content: &[u8]) {
+        let path = normalize(path);
+        let stamp = FileStamp::capture(content);
+        self.reads.lock().unwrap().remove(&path);
+        self.stamps.lock().unwrap().insert(path, stamp);
+    }
+
+    /// writeFile / editFile で書き込んだ内容を記録し、検索索引にも反映する
+    pub fn record_write(&self, path: &Path, content: &[u8]) {
+        self.record(path, content);
+        {
+            let written = normalize(path);
+            let mut writes = self.writes.lock().unwrap();
+            if !writes.contains(&written) {
+                writes.push(written);
+            }
+        }
+        if let Some(index) = &self.search_index {
+            index.update(path, content);
+        }
+    }
+
+    /// これまでに書き込んだファイル（getDiagnostics の既定の対象）
+    pub fn written_files(&self) -> Vec<PathBuf> {
+        self.writes.lock().unwrap().clone()
+    }
+
+    /// readFile でファイル全体の内容を会話に返したことを記録する
+    pub fn record_read(&self, path: &Path, content: &[u8]) {
+        self.record(path, content);
+        if let Some(mark) = ReadMark::current(path) {
+            self.reads.lock().unwrap().insert(normalize(path), mark);
+        }
+    }
+
+    /// readFile で検出した文字コードを記録する（書き込み時に元の文字コードへ戻すため）
+    pub fn record_encoding(&self, path: &Path, encoding: TextEncoding) {
+        let mut encodings = self.encodings.lock().unwrap();
+        if encoding.is_plain_utf8() {
+            encodings.remove(&normalize(path));
+        } else {
+            encodings.insert(normalize(path), encoding);
+        }
+    }
+
+    /// readFile で検出した文字コード（UTF-8 のファイルや未読のファイルは None）
+    pub fn encoding(&self, path: &Path) -> Option<TextEncoding> {
+        self.encodings
+            .lock()
+            .unwrap()
+            .get(&normalize(path))
+            .copied()
+    }
+
+    /// 以前の readFile の結果から mtime とサイズが変わっていないか
+    pub fn is_unchanged_since_read(&self, path: &Path) -> bool {
+        let reads = self.reads.lock().unwrap();
+        reads
+            .get(&normalize(path))
+            .is_some_and(|mark| ReadMark::current(path).as_ref() == Some(mark))
+    }
+
+    /// 以前の readFile の結果が会話から消えた場合（/clear、/compact など）に記録を捨てる
+    pub fn forget_reads(&self) {
+        self.reads.lock().unwrap().clear();
+    }
+
+    /// 記録時点からファイルが変更されていないかを確認する
+    pub fn check(&self, path: &Path) -> Freshness {
+        let stamps = self.stamps.lock().unwrap();
+        let Some(recorded) = stamps.get(&normalize(path)) else {
+            return Freshness::NotRead;
+        };
+
+        match std::fs::read(path) {
+            Ok(current) if FileStamp::capture(&current) == *recorded => Freshness::Fresh,
+            _ => Freshness::Stale,
+        }
+    }
+}
+
+/// 確認を待つ間にディスク上で変更されたファイルについてモデルに返すエラー
+pub fn modified_during_confirmation(path: &str) -> String {
+    tr!(
+        "File {} was modified on disk while waiting for confirmation, so it was not written. \
+         To avoid overwriting those changes, read the latest content with readFile and try again.",
+        "ファイル {} は確認を待つ間にディスク上で変更されたため、書き込みませんでした。\
+         変更を上書きしないよう、readFile で最新の内容を読み直してからやり直してください。",
+        path
+    )
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn hash_content(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_external_modification() {
+        let dir = std::env::temp_dir().join(format!("file_tracker_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        std::fs::write(&path, "before").unwrap();
+
+        let tracker = FileTracker::new();
+        assert_eq!(tracker.check(&path), Freshness::NotRead);
+
+        tracker.record(&path, b"before");
+        assert_eq!(tracker.check(&path), Freshness::Fresh);
+
+        std::fs::write(&path, "after").unwrap();
+        assert_eq!(tracker.check(&path), Freshness::Stale);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_marks_invalidated_by_writes() {
+        let dir = std::env::temp_dir().join(format!("file_tracker_read_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        std::fs::write(&path, "before").unwrap();
+
+        let tracker = FileTracker::new();
+        assert!(!tracker.is_unchanged_since_read(&path));
+        tracker.record_read(&path, b"before");
+        assert!(tracker.is_unchanged_since_read(&path));
+
+        std::fs::write(&path, "after!!").unwrap();
+        assert!(!tracker.is_unchanged_since_read(&path));
+
+        tracker.record_read(&path, b"after!!");
+        tracker.record(&path, b"after!!");
+        assert!(!tracker.is_unchanged_since_read(&path));
+
+        tracker.record_read(&path, b"after!!");
+        tracker.forget_reads();
+        assert!(!tracker.is_unchanged_since_read(&path));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
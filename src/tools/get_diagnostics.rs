@@ -0,0 +1,239 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::file_tracker::FileTracker;
+use super::lsp::{Diagnostic, LanguageServer};
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::config::GetDiagnosticsConfig;
+use crate::i18n::tr;
+use crate::platform;
+
+/// getDiagnostics ツールの引数
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetDiagnosticsArgs {
+    #[serde(default)]
+    paths: Vec<String>,
+}
+
+/// getDiagnostics ツールの実装
+///
+/// 最初の呼び出しで言語サーバーを起動し、エージェントが終わるまで使い続ける
+pub struct GetDiagnosticsTool {
+    tracker: FileTracker,
+    config: GetDiagnosticsConfig,
+    workspace: PathBuf,
+    server: Mutex<Option<LanguageServer>>,
+}
+
+impl GetDiagnosticsTool {
+    pub fn new(tracker: FileTracker, config: GetDiagnosticsConfig, workspace: &Path) -> Self {
+        Self {
+            tracker,
+            config,
+            workspace: workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+            server: Mutex::new(None),
+        }
+    }
+
+    fn display_path(&self, path: &Path) -> String {
+        platform::display_path(path.strip_prefix(&self.workspace).unwrap_or(path))
+    }
+
+    fn format_report(&self, report: Vec<(PathBuf, Option<Vec<Diagnostic>>)>) -> String {
+        let mut lines = Vec::new();
+        let mut shown = 0;
+        let mut omitted = 0;
+        for (path, diagnostics) in report {
+            let name = self.display_path(&path);
+            let Some(mut diagnostics) = diagnostics else {
+                lines.push(tr!(
+                    "{}: the language server did not report diagnostics within {} seconds",
+                    "{}: 言語サーバーから {} 秒以内に診断が届きませんでした",
+                    name,
+                    self.config.timeout_secs
+                ));
+                continue;
+            };
+            if diagnostics.is_empty() {
+                lines.push(tr!("{}: no diagnostics", "{}: 診断はありません", name));
+                continue;
+            }
+
+            // 上限で切る場合にエラーが残るよう重大なものから並べる
+            diagnostics.sort_by_key(|d| (d.severity, d.line, d.column));
+            for d in diagnostics {
+                if shown >= self.config.max_diagnostics {
+                    omitted += 1;
+                    continue;
+                }
+                shown += 1;
+                lines.push(format_diagnostic(&name, &d));
+            }
+        }
+        if omitted > 0 {
+            lines.push(tr!(
+                "[{} more diagnostics are not shown]",
+                "[ほかに {} 件の診断があります]",
+                omitted
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+fn format_diagnostic(path: &str, d: &Diagnostic) -> String {
+    let severity = match d.severity {
+        1 => "error",
+        2 => "warning",
+        3 => "info",
+        _ => "hint",
+    };
+    // 複数行のメッセージは続きの行を字下げする
+    let message = d.message.trim_end().replace('\n', "\n    ");
+    let mut line = format!(
+        "{}:{}:{}: {}: {}",
+        path, d.line, d.column, severity, message
+    );
+    let origin: Vec<&str> = [d.source.as_deref(), d.code.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+    if !origin.is_empty() {
+        line.push_str(&format!(" [{}]", origin.join(" ")));
+    }
+    line
+}
+
+#[async_trait]
+impl ToolHandler for GetDiagnosticsTool {
+    type Args = GetDiagnosticsArgs;
+
+    const NAME: &'static str = "getDiagnostics";
+
+    fn description() -> String {
+        tr!(
+            "Returns compiler diagnostics (errors and warnings) for files from the language server. Use it after editing to check the changes without running a full build. Without paths, checks every file edited with writeFile or editFile so far.",
+            "言語サーバーからファイルのコンパイラ診断（エラーと警告）を取得します。編集後にビルド全体を実行せずに変更を確認するために使います。paths を省略すると、これまでに writeFile / editFile で編集したファイルをすべて確認します。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![(
+            "paths",
+            tr!(
+                "Files to check (e.g. [\"src/main.rs\"]); defaults to the edited files",
+                "確認するファイル（例: [\"src/main.rs\"]）。省略時は編集したファイル"
+            ),
+        )]
+    }
+
+    async fn execute(&self, args: GetDiagnosticsArgs) -> Result<ToolResult> {
+        debug!("Executing getDiagnostics tool with input: {:?}", args);
+
+        let paths = if args.paths.is_empty() {
+            self.tracker.written_files()
+        } else {
+            let mut paths = Vec::new();
+            for path in &args.paths {
+                match Path::new(path).canonicalize() {
+                    Ok(path) if path.is_file() => paths.push(path),
+                    _ => {
+                        return Ok(ToolResult {
+                            content: String::new(),
+                            error: Some(tr!(
+                                "File not found: {}",
+                                "ファイルが見つかりません: {}",
+                                path
+                            )),
+                        })
+                    }
+                }
+            }
+            paths
+        };
+        if paths.is_empty() {
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(tr!(
+                    "No files have been edited yet; pass the files to check in paths",
+                    "まだ編集したファイルがありません。確認するファイルを paths で指定してください"
+                )),
+            });
+        }
+
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let mut server = self.server.lock().await;
+        let mut running = match server.take() {
+            Some(running) => running,
+            None => {
+                debug!("Starting language server {:?}", self.config.command);
+                match LanguageServer::start(&self.config.command, &self.workspace, timeout).await {
+                    Ok(started) => started,
+                    Err(e) => {
+                        warn!("Failed to start the language server: {:#}", e);
+                        return Ok(ToolResult {
+                            content: String::new(),
+                            error: Some(tr!(
+                                "Failed to start the language server ({}): {:#}",
+                                "言語サーバー（{}）を起動できませんでした: {:#}",
+                                self.config.command.join(" "),
+                                e
+                            )),
+                        });
+                    }
+                }
+            }
+        };
+
+        match running.diagnostics(&paths, timeout).await {
+            Ok(report) => {
+                *server = Some(running);
+                Ok(ToolResult {
+                    content: self.format_report(report),
+                    error: None,
+                })
+            }
+            // サーバーは捨てて次の呼び出しで起動し直す
+            Err(e) => {
+                warn!("Language server failed: {:#}", e);
+                Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "The language server failed: {:#}",
+                        "言語サーバーでエラーが発生しました: {:#}",
+                        e
+                    )),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_diagnostic() {
+        let diagnostic = Diagnostic {
+            line: 12,
+            column: 5,
+            severity: 1,
+            message: "mismatched types\nexpected `u32`".to_string(),
+            source: Some("rustc".to_string()),
+            code: Some("E0308".to_string()),
+        };
+        assert_eq!(
+            format_diagnostic("src/main.rs", &diagnostic),
+            "src/main.rs:12:5: error: mismatched types\n    expected `u32` [rustc E0308]"
+        );
+    }
+}
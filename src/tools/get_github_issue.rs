@@ -0,0 +1,147 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::github::GitHubClient;
+use crate::i18n::tr;
+
+/// 結果に含めるコメントの上限
+const MAX_COMMENTS: usize = 50;
+
+/// getGitHubIssue ツールの引数
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetGitHubIssueArgs {
+    number: u64,
+}
+
+/// getGitHubIssue ツールの実装
+pub struct GetGitHubIssueTool {
+    client: GitHubClient,
+}
+
+impl GetGitHubIssueTool {
+    pub fn new(client: GitHubClient) -> Self {
+        Self { client }
+    }
+
+    async fn describe(&self, number: u64) -> Result<String> {
+        let issue = self.client.issue(number).await?;
+        let is_pull_request = issue.get("pull_request").is_some();
+        let mut text = format!(
+            "{} #{}: {} ({})\n",
+            if is_pull_request {
+                "Pull request"
+            } else {
+                "Issue"
+            },
+            number,
+            str_field(&issue, "title"),
+            str_field(&issue, "state"),
+        );
+        text.push_str(&format!(
+            "Author: @{}\nURL: {}\n",
+            str_field(&issue["user"], "login"),
+            str_field(&issue, "html_url")
+        ));
+        let labels: Vec<&str> = issue["labels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|label| label["name"].as_str())
+            .collect();
+        if !labels.is_empty() {
+            text.push_str(&format!("Labels: {}\n", labels.join(", ")));
+        }
+
+        if is_pull_request {
+            let pull = self.client.pull_request(number).await?;
+            text.push_str(&format!(
+                "Branches: {} -> {}\nChanges: {} files, +{} -{}\n",
+                str_field(&pull["head"], "label"),
+                str_field(&pull["base"], "ref"),
+                pull["changed_files"],
+                pull["additions"],
+                pull["deletions"]
+            ));
+        }
+
+        let body = issue["body"].as_str().unwrap_or_default().trim();
+        text.push_str(&format!(
+            "\n{}\n",
+            if body.is_empty() {
+                "(no description)"
+            } else {
+                body
+            }
+        ));
+
+        let comments = self.client.issue_comments(number, MAX_COMMENTS).await?;
+        if !comments.is_empty() {
+            text.push_str(&format!("\n--- Comments ({}) ---\n", comments.len()));
+            for comment in &comments {
+                text.push_str(&format!(
+                    "\n@{} ({}):\n{}\n",
+                    str_field(&comment["user"], "login"),
+                    str_field(comment, "created_at"),
+                    str_field(comment, "body").trim()
+                ));
+            }
+        }
+        Ok(text)
+    }
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value[key].as_str().unwrap_or_default()
+}
+
+#[async_trait]
+impl ToolHandler for GetGitHubIssueTool {
+    type Args = GetGitHubIssueArgs;
+
+    const NAME: &'static str = "getGitHubIssue";
+
+    const CACHEABLE: bool = true;
+
+    fn description() -> String {
+        tr!(
+            "Reads an issue or pull request of the GitHub repository of the origin remote, with its comments. For pull requests, the branches and the size of the change are included.",
+            "origin リモートの GitHub リポジトリの issue またはプルリクエストをコメントとともに読み込みます。プルリクエストの場合はブランチと変更の規模も含みます。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![(
+            "number",
+            tr!(
+                "Issue or pull request number (e.g. 42 for #42)",
+                "issue またはプルリクエストの番号（例: #42 なら 42）"
+            ),
+        )]
+    }
+
+    async fn execute(&self, args: GetGitHubIssueArgs) -> Result<ToolResult> {
+        debug!("Executing getGitHubIssue tool with input: {:?}", args);
+
+        match self.describe(args.number).await {
+            Ok(content) => Ok(ToolResult {
+                content,
+                error: None,
+            }),
+            Err(e) => Ok(ToolResult {
+                content: String::new(),
+                error: Some(tr!(
+                    "Failed to read #{} from {}: {:#}",
+                    "{1} の #{0} を読み込めませんでした: {2:#}",
+                    args.number,
+                    self.client.repo,
+                    e
+                )),
+            }),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// 設定の `ignore` パターンに一致するパスを listFiles / searchInDirectory から除外する
+#[derive(Debug, Clone)]
+pub struct IgnoreMatcher {
+    set: GlobSet,
+    workspace: PathBuf,
+}
+
+impl IgnoreMatcher {
+    /// ワークスペースルートからの相対パスに対するパターンを構築する
+    pub fn new(patterns: &[String], workspace: &Path) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(
+                Glob::new(pattern)
+                    .with_context(|| format!("Invalid ignore pattern: {}", pattern))?,
+            );
+            // "target/**" はディレクトリ "target" 自体も除外して走査を省く
+            if let Some(dir) = pattern.strip_suffix("/**") {
+                builder.add(
+                    Glob::new(dir)
+                        .with_context(|| format!("Invalid ignore pattern: {}", pattern))?,
+                );
+            }
+        }
+
+        Ok(Self {
+            set: builder.build().context("Failed to build ignore patterns")?,
+            workspace: workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+        })
+    }
+
+    /// パスが除外対象かどうか
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        if self.set.is_empty() {
+            return false;
+        }
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let relative = if path.is_absolute() {
+            path.strip_prefix(&self.workspace).unwrap_or(path)
+        } else {
+            path
+        };
+        self.set.is_match(relative)
+    }
+}
+
+/// 隠しファイル・隠しディレクトリの名前（`.` で始まる）かどうか
+///
+/// listFiles / searchInDirectory は `include_hidden` を指定しない限りこれらを走査しない
+pub fn is_hidden(name: &OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|name| name.starts_with('.') && name != "." && name != "..")
+}
+
+/// `base` からの相対パスに隠しファイル・隠しディレクトリを含むか
+pub fn has_hidden_component(path: &Path, base: &Path) -> bool {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .any(|component| is_hidden(component.as_os_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let patterns = vec![
+            "target/**".to_string(),
+            "*.min.js".to_string(),
+            "vendor/**".to_string(),
+        ];
+        let matcher = IgnoreMatcher::new(&patterns, Path::new("/work")).unwrap();
+
+        assert!(matcher.is_ignored(Path::new("target")));
+        assert!(matcher.is_ignored(Path::new("./target/debug/app")));
+        assert!(matcher.is_ignored(Path::new("static/js/app.min.js")));
+        assert!(matcher.is_ignored(Path::new("/work/vendor/lib.rs")));
+        assert!(!matcher.is_ignored(Path::new("src/main.rs")));
+
+        assert!(is_hidden(OsStr::new(".git")));
+        assert!(!is_hidden(OsStr::new(".")) && !is_hidden(OsStr::new("src")));
+        assert!(has_hidden_component(
+            Path::new("/work/.github/workflows/ci.yml"),
+            Path::new("/work")
+        ));
+        assert!(!has_hidden_component(
+            Path::new("/work/src/main.rs"),
+            Path::new("/work")
+        ));
+    }
+}
@@ -1,18 +1,27 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use std::path::Path;
 use tracing::{debug, warn};
 
-use crate::anthropic::{Tool, ToolHandler, ToolResult};
+use super::concurrent::for_each_ordered;
+use super::ignore::{self, IgnoreMatcher};
+use super::symlinks::SymlinkGuard;
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::config::ListFilesConfig;
+use crate::i18n::tr;
+use crate::platform;
 
 /// listFiles ツールの引数
-#[derive(Debug, Deserialize)]
-struct ListFilesArgs {
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListFilesArgs {
     path: String,
     #[serde(default)]
     recursive: bool,
+    /// 隠しファイル・隠しディレクトリ（`.` で始まる名前）も含める
+    #[serde(default)]
+    include_hidden: bool,
 }
 
 /// ファイル情報
@@ -24,58 +33,87 @@ struct FileInfo {
 }
 
 /// listFiles ツールの実装
-pub struct ListFilesTool;
+pub struct ListFilesTool {
+    config: ListFilesConfig,
+    ignore: IgnoreMatcher,
+    symlinks: SymlinkGuard,
+}
 
 impl ListFilesTool {
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// ツールのスキーマ定義を返す
-    pub fn schema() -> Tool {
-        Tool {
-            name: "listFiles".to_string(),
-            description: "指定されたディレクトリ内のファイルとディレクトリの一覧を取得します。recursiveがtrueの場合、サブディレクトリも含めます。".to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "一覧を取得するディレクトリのパス（例: src, ., ./docs）"
-                    },
-                    "recursive": {
-                        "type": "boolean",
-                        "description": "サブディレクトリも含めて再帰的に一覧を取得するか（デフォルト: false）"
-                    }
-                },
-                "required": ["path"]
-            }),
+    pub fn new(config: ListFilesConfig, ignore: IgnoreMatcher, symlinks: SymlinkGuard) -> Self {
+        Self {
+            config,
+            ignore,
+            symlinks,
         }
     }
 }
 
 #[async_trait]
 impl ToolHandler for ListFilesTool {
-    async fn execute(&self, input: serde_json::Value) -> Result<ToolResult> {
-        debug!("Executing listFiles tool with input: {:?}", input);
+    type Args = ListFilesArgs;
+
+    const NAME: &'static str = "listFiles";
+
+    const CACHEABLE: bool = true;
 
-        // 引数をパース
-        let args: ListFilesArgs =
-            serde_json::from_value(input).context("Failed to parse listFiles arguments")?;
+    fn description() -> String {
+        tr!(
+            "Lists the files and directories in the given directory. Subdirectories are included when recursive is true.",
+            "指定されたディレクトリ内のファイルとディレクトリの一覧を取得します。recursiveがtrueの場合、サブディレクトリも含めます。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "path",
+                tr!(
+                    "Path of the directory to list (e.g. src, ., ./docs)",
+                    "一覧を取得するディレクトリのパス（例: src, ., ./docs）"
+                ),
+            ),
+            (
+                "recursive",
+                tr!(
+                    "Whether to include subdirectories recursively (default: false)",
+                    "サブディレクトリも含めて再帰的に一覧を取得するか（デフォルト: false）"
+                ),
+            ),
+            (
+                "include_hidden",
+                tr!(
+                    "Whether to include hidden files and directories whose names start with a dot, such as .github (default: false; .git internals are rarely useful)",
+                    "名前が . で始まる隠しファイル・隠しディレクトリ（.github など）も含めるか（デフォルト: false。.git の中身はほとんど役に立ちません）"
+                ),
+            ),
+        ]
+    }
+
+    async fn execute(&self, args: ListFilesArgs) -> Result<ToolResult> {
+        debug!("Executing listFiles tool with input: {:?}", args);
 
         debug!(
-            "Listing files in: {} (recursive: {})",
-            args.path, args.recursive
+            "Listing files in: {} (recursive: {}, include_hidden: {})",
+            args.path, args.recursive, args.include_hidden
         );
 
         let path = Path::new(&args.path);
+        if let Err(error) = self.symlinks.check(path) {
+            warn!("listFiles: {}", error);
+            return Ok(error_result(error));
+        }
 
         // ディレクトリが存在しない場合
         if !path.exists() {
             warn!("Directory not found: {}", args.path);
             return Ok(ToolResult {
                 content: String::new(),
-                error: Some(format!("ディレクトリが見つかりません: {}", args.path)),
+                error: Some(tr!(
+                    "Directory not found: {}",
+                    "ディレクトリが見つかりません: {}",
+                    args.path
+                )),
             });
         }
 
@@ -84,40 +122,47 @@ impl ToolHandler for ListFilesTool {
             warn!("Path is not a directory: {}", args.path);
             return Ok(ToolResult {
                 content: String::new(),
-                error: Some(format!(
+                error: Some(tr!(
+                    "The path is not a directory: {}",
                     "指定されたパスはディレクトリではありません: {}",
                     args.path
                 )),
             });
         }
 
-        // ファイル一覧を取得（今は非再帰のみ）
+        // ファイル一覧を取得
         let mut files = Vec::new();
+        // 上限を超えたためメタデータを読まなかった件数
+        let mut skipped = 0;
 
         if args.recursive {
-            // 再帰モード: walkdir を使用
-            use walkdir::WalkDir;
-
-            for entry_result in WalkDir::new(path) {
-                match entry_result {
-                    Ok(entry) => {
-                        let entry_path = entry.path();
-                        let metadata = match entry.metadata() {
-                            Ok(m) => m,
-                            Err(e) => {
-                                warn!("Failed to get metadata for {:?}: {}", entry_path, e);
-                                continue;
-                            }
-                        };
+            // 再帰モード: 除外パターンに一致するディレクトリと辿らないリンクは配下ごと走査しない
+            let mut paths: Vec<_> =
+                match self.symlinks.walk(path, &self.ignore, args.include_hidden) {
+                    Ok(entries) => entries.into_iter().map(|e| e.into_path()).collect(),
+                    Err(error) => return Ok(error_result(error)),
+                };
 
-                        files.push(process_entry(entry_path, &metadata))
-                    }
-                    Err(e) => {
-                        warn!("Failed to read entry: {}", e);
-                        continue;
+            // 表示する分のメタデータだけを並行して読み込む
+            skipped = paths.len().saturating_sub(self.config.max_entries);
+            paths.truncate(self.config.max_entries);
+            for_each_ordered(
+                paths,
+                self.config.concurrency,
+                |entry_path| async move {
+                    // 辿るリンクはリンク先の種類とサイズを表示する
+                    let metadata = tokio::fs::metadata(&entry_path).await;
+                    (entry_path, metadata)
+                },
+                |(entry_path, metadata)| {
+                    match metadata {
+                        Ok(metadata) => files.push(process_entry(&entry_path, &metadata)),
+                        Err(e) => warn!("Failed to get metadata for {:?}: {}", entry_path, e),
                     }
-                }
-            }
+                    true
+                },
+            )
+            .await?;
         } else {
             // 非再帰モード: std::fs::read_dir を使用
             match std::fs::read_dir(path) {
@@ -126,7 +171,21 @@ impl ToolHandler for ListFilesTool {
                         match entry_result {
                             Ok(entry) => {
                                 let entry_path = entry.path();
-                                let metadata = match entry.metadata() {
+                                if self.ignore.is_ignored(&entry_path)
+                                    || (!args.include_hidden
+                                        && ignore::is_hidden(&entry.file_name()))
+                                {
+                                    continue;
+                                }
+                                match self.symlinks.check_entry(&entry_path) {
+                                    Ok(true) => {}
+                                    Ok(false) => {
+                                        debug!("Skipping symbolic link {:?}", entry_path);
+                                        continue;
+                                    }
+                                    Err(error) => return Ok(error_result(error)),
+                                }
+                                let metadata = match std::fs::metadata(&entry_path) {
                                     Ok(m) => m,
                                     Err(e) => {
                                         warn!("Failed to get metadata for {:?}: {}", entry_path, e);
@@ -146,17 +205,33 @@ impl ToolHandler for ListFilesTool {
                 Err(e) => {
                     return Ok(ToolResult {
                         content: String::new(),
-                        error: Some(format!("ディレクトリの読み込みに失敗しました: {}", e)),
+                        error: Some(tr!(
+                            "Failed to read the directory: {}",
+                            "ディレクトリの読み込みに失敗しました: {}",
+                            e
+                        )),
                     });
                 }
             }
         }
 
+        debug!("Found {} files/directories", files.len() + skipped);
+
+        // 上限を超えた分は切り捨てる
+        let total = files.len() + skipped;
+        files.truncate(self.config.max_entries);
+
         // 結果をJSON形式で返す
-        let result_json =
+        let mut result_json =
             serde_json::to_string_pretty(&files).context("Failed to serialize file list")?;
-
-        debug!("Found {} files/directories", files.len());
+        if total > files.len() {
+            result_json.push_str(&tr!(
+                "\n\n[Showing only the first {1} of {0} entries]",
+                "\n\n[{} 件中、先頭 {} 件のみ表示しています]",
+                total,
+                files.len()
+            ));
+        }
 
         Ok(ToolResult {
             content: result_json,
@@ -165,9 +240,16 @@ impl ToolHandler for ListFilesTool {
     }
 }
 
+fn error_result(error: String) -> ToolResult {
+    ToolResult {
+        content: String::new(),
+        error: Some(error),
+    }
+}
+
 fn process_entry(entry_path: &Path, metadata: &std::fs::Metadata) -> FileInfo {
     FileInfo {
-        path: entry_path.display().to_string(),
+        path: platform::display_path(entry_path),
         is_dir: metadata.is_dir(),
         size: metadata.len(),
     }
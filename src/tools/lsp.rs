@@ -0,0 +1,441 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+/// 最後の報告（診断・進捗）からこの間なにも届かなければ診断が出そろったとみなす
+const SETTLE: Duration = Duration::from_millis(500);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// サーバーが報告した診断（行・列は 1 始まり）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: u64,
+    pub column: u64,
+    /// 1: error, 2: warning, 3: information, 4: hint
+    pub severity: u8,
+    pub message: String,
+    pub source: Option<String>,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LspDiagnostic {
+    range: LspRange,
+    severity: Option<u8>,
+    code: Option<Value>,
+    source: Option<String>,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LspRange {
+    start: LspPosition,
+}
+
+#[derive(Debug, Deserialize)]
+struct LspPosition {
+    line: u64,
+    character: u64,
+}
+
+impl From<LspDiagnostic> for Diagnostic {
+    fn from(d: LspDiagnostic) -> Self {
+        Self {
+            line: d.range.start.line + 1,
+            column: d.range.start.character + 1,
+            // 省略時の扱いはクライアント次第なので error とする
+            severity: d.severity.unwrap_or(1),
+            message: d.message,
+            source: d.source,
+            code: d.code.map(|code| match code {
+                Value::String(code) => code,
+                other => other.to_string(),
+            }),
+        }
+    }
+}
+
+/// サーバーから届いた診断と進捗
+#[derive(Debug, Default)]
+struct ServerState {
+    diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
+    /// 実行中の作業（`$/progress` の begin から end まで）
+    progress: HashSet<String>,
+    last_activity: Option<Instant>,
+    closed: bool,
+}
+
+/// 開いているファイルのバージョンと、最後に送った内容
+struct OpenFile {
+    version: i32,
+    text: String,
+}
+
+/// 標準入出力で LSP を話す言語サーバーのプロセス（drop で終了する）
+pub struct LanguageServer {
+    _child: Child,
+    outgoing: mpsc::UnboundedSender<Value>,
+    pending: Arc<Mutex<HashMap<i64, oneshot::Sender<Value>>>>,
+    next_id: i64,
+    state: Arc<Mutex<ServerState>>,
+    open_files: HashMap<PathBuf, OpenFile>,
+}
+
+impl LanguageServer {
+    /// サーバーを起動して initialize を済ませる
+    pub async fn start(command: &[String], workspace: &Path, timeout: Duration) -> Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .context("The language server command is empty")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .current_dir(workspace)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start language server {:?}", program))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("No stdin for the language server")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("No stdout for the language server")?;
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Value>();
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                let body = message.to_string();
+                let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+                if stdin.write_all(frame.as_bytes()).await.is_err() || stdin.flush().await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let pending: Arc<Mutex<HashMap<i64, oneshot::Sender<Value>>>> = Arc::default();
+        let state: Arc<Mutex<ServerState>> = Arc::default();
+        {
+            let pending = Arc::clone(&pending);
+            let state = Arc::clone(&state);
+            let outgoing = outgoing.clone();
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(stdout);
+                loop {
+                    match read_message(&mut reader).await {
+                        Ok(Some(message)) => handle_message(message, &pending, &state, &outgoing),
+                        Ok(None) => break,
+                        Err(e) => {
+                            debug!("Failed to read from the language server: {:#}", e);
+                            break;
+                        }
+                    }
+                }
+                state.lock().unwrap().closed = true;
+                // 応答を待っている要求は送信側の drop で失敗させる
+                pending.lock().unwrap().clear();
+            });
+        }
+
+        let mut server = Self {
+            _child: child,
+            outgoing,
+            pending,
+            next_id: 1,
+            state,
+            open_files: HashMap::new(),
+        };
+        let root = file_uri(workspace);
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": root,
+            "workspaceFolders": [{
+                "uri": root,
+                "name": workspace.file_name().map(|name| name.to_string_lossy()).unwrap_or_default(),
+            }],
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": { "didSave": false },
+                    "publishDiagnostics": { "relatedInformation": false },
+                },
+                "window": { "workDoneProgress": true },
+                "workspace": { "configuration": true, "workspaceFolders": true },
+            },
+        });
+        tokio::time::timeout(timeout, server.request("initialize", params))
+            .await
+            .context("The language server did not answer initialize in time")??;
+        server.notify("initialized", json!({}));
+        Ok(server)
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let _ = self
+            .outgoing
+            .send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+
+        let response = rx.await.context("The language server exited")?;
+        if let Some(error) = response.get("error") {
+            bail!("{} failed: {}", method, error);
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// ファイルを開き（開いていれば内容を送り直し）、報告された診断を返す
+    ///
+    /// すべてのファイルの診断が届き、サーバーの作業が終わって `SETTLE` の間なにも
+    /// 届かなくなるまで待つ。`timeout` までに報告がなかったファイルは None
+    pub async fn diagnostics(
+        &mut self,
+        paths: &[PathBuf],
+        timeout: Duration,
+    ) -> Result<Vec<(PathBuf, Option<Vec<Diagnostic>>)>> {
+        for path in paths {
+            let text = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read {:?}", path))?;
+            let uri = file_uri(path);
+            let (method, params) = match self.open_files.get_mut(path) {
+                // 前回から変わっていなければ前回の診断をそのまま使う
+                Some(open) if open.text == text => continue,
+                Some(open) => {
+                    open.version += 1;
+                    let params = json!({
+                        "textDocument": { "uri": uri, "version": open.version },
+                        "contentChanges": [{ "text": text }],
+                    });
+                    open.text = text;
+                    ("textDocument/didChange", params)
+                }
+                None => {
+                    let params = json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id(path),
+                            "version": 1,
+                            "text": text,
+                        },
+                    });
+                    self.open_files
+                        .insert(path.clone(), OpenFile { version: 1, text });
+                    ("textDocument/didOpen", params)
+                }
+            };
+            self.notify(method, params);
+            let mut state = self.state.lock().unwrap();
+            state.diagnostics.remove(path);
+            state.last_activity = Some(Instant::now());
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            {
+                let state = self.state.lock().unwrap();
+                if state.closed {
+                    bail!("The language server exited");
+                }
+                let reported = paths.iter().all(|p| state.diagnostics.contains_key(p));
+                let settled = state.progress.is_empty()
+                    && state.last_activity.is_none_or(|t| t.elapsed() >= SETTLE);
+                if (reported && settled) || Instant::now() >= deadline {
+                    return Ok(paths
+                        .iter()
+                        .map(|p| (p.clone(), state.diagnostics.get(p).cloned()))
+                        .collect());
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// サーバーからのメッセージ（応答・要求・通知）を処理する
+fn handle_message(
+    message: Value,
+    pending: &Mutex<HashMap<i64, oneshot::Sender<Value>>>,
+    state: &Mutex<ServerState>,
+    outgoing: &mpsc::UnboundedSender<Value>,
+) {
+    let method = message["method"].as_str();
+    match (message.get("id"), method) {
+        (Some(id), None) => {
+            if let Some(tx) = id
+                .as_i64()
+                .and_then(|id| pending.lock().unwrap().remove(&id))
+            {
+                let _ = tx.send(message);
+            }
+        }
+        // サーバーからの要求には最小限の応答を返す（設定はすべて既定値）
+        (Some(id), Some(method)) => {
+            let result = match method {
+                "workspace/configuration" => {
+                    let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                    Value::Array(vec![Value::Null; items])
+                }
+                _ => Value::Null,
+            };
+            let _ = outgoing.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+        (None, Some("textDocument/publishDiagnostics")) => {
+            let params = &message["params"];
+            let Some(path) = params["uri"].as_str().and_then(uri_path) else {
+                return;
+            };
+            let diagnostics: Vec<LspDiagnostic> =
+                serde_json::from_value(params["diagnostics"].clone()).unwrap_or_default();
+            let mut state = state.lock().unwrap();
+            state.diagnostics.insert(
+                path,
+                diagnostics.into_iter().map(Diagnostic::from).collect(),
+            );
+            state.last_activity = Some(Instant::now());
+        }
+        (None, Some("$/progress")) => {
+            let params = &message["params"];
+            let token = params["token"].to_string();
+            let mut state = state.lock().unwrap();
+            match params["value"]["kind"].as_str() {
+                Some("begin") => {
+                    state.progress.insert(token);
+                }
+                Some("end") => {
+                    state.progress.remove(&token);
+                }
+                _ => {}
+            }
+            state.last_activity = Some(Instant::now());
+        }
+        _ => {}
+    }
+}
+
+/// `Content-Length` ヘッダー付きのメッセージを 1 つ読む（入力の終わりでは None）
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let length = length.context("Missing Content-Length header")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn language_id(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+    {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "hpp" => "cpp",
+        _ => "plaintext",
+    }
+}
+
+/// 絶対パスを file URI にする（英数字と一部の記号以外はパーセントエンコード）
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let decoded = (encoded[i] == b'%')
+            .then(|| std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            None => {
+                bytes.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_framing_and_uris() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut reader = input.as_bytes();
+        let message = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(message["id"], 1);
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+
+        let path = Path::new("/work/my crate/src/main.rs");
+        assert_eq!(file_uri(path), "file:///work/my%20crate/src/main.rs");
+        assert_eq!(uri_path(&file_uri(path)).unwrap(), path);
+    }
+}
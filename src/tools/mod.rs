@@ -1,11 +1,76 @@
+mod concurrent;
+mod create_pull_request;
+mod custom_command;
 mod edit_file;
+mod encoding;
+pub mod file_tracker;
+mod get_diagnostics;
+mod get_github_issue;
+pub mod ignore;
 pub mod list_files;
+mod lsp;
 pub mod read_file;
+mod registry;
 pub mod search_in_directory;
+mod search_in_file;
+pub mod search_index;
+pub mod symlinks;
 pub mod write_file;
 
+pub use create_pull_request::CreatePullRequestTool;
+pub use custom_command::CustomCommandTool;
 pub use edit_file::EditFileTool;
+pub use file_tracker::FileTracker;
+pub use get_diagnostics::GetDiagnosticsTool;
+pub use get_github_issue::GetGitHubIssueTool;
+pub use ignore::IgnoreMatcher;
 pub use list_files::ListFilesTool;
 pub use read_file::ReadFileTool;
+pub use registry::ToolRegistryBuilder;
 pub use search_in_directory::SearchInDirectoryTool;
+pub use search_in_file::SearchInFileTool;
+pub use symlinks::SymlinkGuard;
 pub use write_file::WriteFileTool;
+
+use crate::anthropic::{Tool, ToolHandler};
+
+/// 組み込みツールのスキーマ一覧（登録順）
+pub fn builtin_schemas() -> Vec<Tool> {
+    vec![
+        ReadFileTool::schema(),
+        ListFilesTool::schema(),
+        SearchInDirectoryTool::schema(),
+        SearchInFileTool::schema(),
+        WriteFileTool::schema(),
+        EditFileTool::schema(),
+        GetDiagnosticsTool::schema(),
+        GetGitHubIssueTool::schema(),
+        CreatePullRequestTool::schema(),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_schemas_describe_every_argument() {
+        for tool in builtin_schemas() {
+            let schema = &tool.input_schema;
+            assert_eq!(schema["type"], "object", "{}", tool.name);
+            let properties = schema["properties"].as_object().unwrap();
+            for (name, property) in properties {
+                assert!(
+                    property["description"].is_string(),
+                    "{}.{} has no description",
+                    tool.name,
+                    name
+                );
+            }
+        }
+        let edit = EditFileTool::schema();
+        assert_eq!(
+            edit.input_schema["required"],
+            serde_json::json!(["path", "new_content"])
+        );
+    }
+}
@@ -1,79 +1,314 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use serde_json::json;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use tracing::{debug, warn};
 
-use crate::anthropic::{Tool, ToolHandler, ToolResult};
+use super::encoding::{self, TextEncoding};
+use super::file_tracker::FileTracker;
+use super::symlinks::SymlinkGuard;
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::config::ReadFileConfig;
+use crate::i18n::tr;
+use crate::outline;
 
 /// readFile ツールの引数
-#[derive(Debug, Deserialize)]
-struct ReadFileArgs {
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReadFileArgs {
     path: String,
+    /// 読み込む最初の行（1 から）
+    start_line: Option<usize>,
+    /// 読み込む最後の行（この行を含む）
+    end_line: Option<usize>,
 }
 
+impl ReadFileArgs {
+    fn is_range(&self) -> bool {
+        self.start_line.is_some() || self.end_line.is_some()
+    }
+}
+
+/// 概要と行範囲を作るために全体を読み込むファイルのサイズの上限（超える場合は先頭だけを返す）
+const MAX_OUTLINE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// 大きなファイルの概要に載せるシンボルの上限
+const MAX_OUTLINE_SYMBOLS: usize = 200;
+
 /// readFile ツールの実装
-pub struct ReadFileTool;
+pub struct ReadFileTool {
+    tracker: FileTracker,
+    config: ReadFileConfig,
+    symlinks: SymlinkGuard,
+}
 
 impl ReadFileTool {
-    pub fn new() -> Self {
-        Self
+    pub fn new(tracker: FileTracker, config: ReadFileConfig, symlinks: SymlinkGuard) -> Self {
+        Self {
+            tracker,
+            config,
+            symlinks,
+        }
     }
+}
+
+impl ReadFileTool {
+    /// 上限を超えるファイルの代わりに、トップレベルのシンボルと 1 回で読める行範囲を返す
+    ///
+    /// モデルには start_line / end_line で範囲を指定して読み直させる
+    fn outline(&self, path: &Path, bytes: &[u8]) -> ToolResult {
+        let Some((content, _)) = encoding::decode(bytes, None) else {
+            return binary_file_error();
+        };
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
 
-    /// ツールのスキーマ定義を返す
-    pub fn schema() -> Tool {
-        Tool {
-            name: "readFile".to_string(),
-            description:
-                "指定されたパスのファイル内容を読み込みます。相対パスまたは絶対パスを指定できます。"
-                    .to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "読み込むファイルのパス（例: README.md, src/main.rs）"
-                    }
-                },
-                "required": ["path"]
-            }),
+        let mut result = tr!(
+            "[The file is {} bytes ({} lines), more than the {} bytes readFile returns at once. Read the part you need by calling readFile again with start_line and end_line.]\n",
+            "[ファイルが {} バイト（{} 行）あり、readFile が一度に返せる {} バイトを超えています。必要な部分を start_line と end_line を指定した readFile で読み直してください。]\n",
+            bytes.len(),
+            lines.len(),
+            self.config.max_bytes
+        );
+
+        let symbols = outline::top_level_symbols(path, &content);
+        if !symbols.is_empty() {
+            result.push_str(&tr!(
+                "\nTop-level symbols (line: symbol):\n",
+                "\nトップレベルのシンボル（行: シンボル）:\n"
+            ));
+            for (line, symbol) in symbols.iter().take(MAX_OUTLINE_SYMBOLS) {
+                result.push_str(&format!("{}: {}\n", line, symbol));
+            }
+            if symbols.len() > MAX_OUTLINE_SYMBOLS {
+                result.push_str(&tr!(
+                    "({} more not shown)\n",
+                    "（ほか {} 件）\n",
+                    symbols.len() - MAX_OUTLINE_SYMBOLS
+                ));
+            }
+        }
+
+        result.push_str(&tr!(
+            "\nLine ranges that fit in one read:\n",
+            "\n1 回で読める行範囲:\n"
+        ));
+        for (start, end) in line_ranges(&lines, self.config.max_bytes) {
+            result.push_str(&tr!("- lines {}-{}\n", "- {}〜{} 行\n", start, end));
+        }
+        ToolResult {
+            content: result,
+            error: None,
+        }
+    }
+
+    /// start_line〜end_line の行を返す（max_bytes を超える分は続きの読み方を添えて切る）
+    fn read_range(&self, args: &ReadFileArgs, bytes: &[u8]) -> ToolResult {
+        let Some((content, encoding)) = encoding::decode(bytes, None) else {
+            return binary_file_error();
+        };
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let start = args.start_line.unwrap_or(1);
+        let end = args.end_line.unwrap_or(lines.len()).min(lines.len());
+        if start == 0 || start > end {
+            return ToolResult {
+                content: String::new(),
+                error: Some(tr!(
+                    "Invalid line range {}-{}: the file has {} lines (lines start at 1)",
+                    "行範囲 {}〜{} が不正です。ファイルは {} 行です（行は 1 から数えます）",
+                    start,
+                    args.end_line.unwrap_or(lines.len()),
+                    lines.len()
+                )),
+            };
+        }
+
+        let mut text = String::new();
+        let mut last = start - 1;
+        for line in &lines[start - 1..end] {
+            // 1 行だけでも上限を超える場合はその行を返す
+            if last >= start && (text.len() + line.len()) as u64 > self.config.max_bytes {
+                break;
+            }
+            text.push_str(line);
+            last += 1;
+        }
+        push_encoding_note(&mut text, encoding);
+        if last < end {
+            text.push_str(&tr!(
+                "\n\n[Lines {}-{} of {}; the range was cut at {} bytes. Continue with start_line {}.]",
+                "\n\n[{}〜{} 行目（全 {} 行）。{} バイトで打ち切ったため、続きは start_line {} から読んでください]",
+                start,
+                last,
+                lines.len(),
+                self.config.max_bytes,
+                last + 1
+            ));
+        } else {
+            text.push_str(&tr!(
+                "\n\n[Lines {}-{} of {}]",
+                "\n\n[{}〜{} 行目（全 {} 行）]",
+                start,
+                last,
+                lines.len()
+            ));
+        }
+        ToolResult {
+            content: text,
+            error: None,
+        }
+    }
+
+    /// 先頭 max_bytes バイトだけを読み込む
+    async fn read_truncated(&self, path: &Path, size: u64) -> ToolResult {
+        use tokio::io::AsyncReadExt;
+
+        let mut buf = Vec::new();
+        let result = match fs::File::open(path).await {
+            Ok(file) => file.take(self.config.max_bytes).read_to_end(&mut buf).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            return read_error(e);
+        }
+
+        // 途中で切れたマルチバイト文字は置換文字になる
+        let Some((mut content, encoding)) = encoding::decode(&buf, None) else {
+            return binary_file_error();
+        };
+        push_encoding_note(&mut content, encoding);
+        content.push_str(&tr!(
+            "\n\n[The file is large; showing only the first {} bytes ({} bytes in total)]",
+            "\n\n[ファイルが大きいため先頭 {} バイトのみ表示しています（全体: {} バイト）]",
+            self.config.max_bytes,
+            size
+        ));
+        ToolResult {
+            content,
+            error: None,
         }
     }
 }
 
 #[async_trait]
 impl ToolHandler for ReadFileTool {
-    async fn execute(&self, input: serde_json::Value) -> Result<ToolResult> {
-        debug!("Executing readFile tool with input: {:?}", input);
+    type Args = ReadFileArgs;
+
+    const NAME: &'static str = "readFile";
 
-        // 引数をパース
-        let args: ReadFileArgs =
-            serde_json::from_value(input).context("Failed to parse readFile arguments")?;
+    fn description() -> String {
+        tr!(
+            "Reads the contents of the file at the given path. Relative and absolute paths are accepted. A file too large to return at once is answered with its outline and line ranges; read those with start_line and end_line.",
+            "指定されたパスのファイル内容を読み込みます。相対パスまたは絶対パスを指定できます。一度に返せない大きなファイルには概要と行範囲を返すので、start_line と end_line で範囲を指定して読んでください。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "path",
+                tr!(
+                    "Path of the file to read (e.g. README.md, src/main.rs)",
+                    "読み込むファイルのパス（例: README.md, src/main.rs）"
+                ),
+            ),
+            (
+                "start_line",
+                tr!(
+                    "First line to read, counting from 1 (omit to read from the start)",
+                    "読み込む最初の行（1 から数える。省略すると先頭から）"
+                ),
+            ),
+            (
+                "end_line",
+                tr!(
+                    "Last line to read, inclusive (omit to read to the end)",
+                    "読み込む最後の行（この行を含む。省略すると末尾まで）"
+                ),
+            ),
+        ]
+    }
+
+    async fn execute(&self, args: ReadFileArgs) -> Result<ToolResult> {
+        debug!("Executing readFile tool with input: {:?}", args);
 
         debug!("Reading file: {}", args.path);
 
         // パスのバリデーション
         let path = PathBuf::from(&args.path);
+        if let Err(error) = self.symlinks.check(&path) {
+            warn!("readFile: {}", error);
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(error),
+            });
+        }
 
         // ファイルが存在しない場合
         if !path.exists() {
             warn!("File not found: {}", args.path);
             return Ok(ToolResult {
                 content: String::new(),
-                error: Some(format!("ファイルが見つかりません: {}", args.path)),
+                error: Some(tr!(
+                    "File not found: {}",
+                    "ファイルが見つかりません: {}",
+                    args.path
+                )),
             });
         }
 
-        // ファイル読み込み
-        match fs::read_to_string(&path).await {
-            Ok(content) => {
-                debug!(
-                    "Successfully read {} bytes from {}",
-                    content.len(),
+        // 変更のないファイルは会話内の以前の結果を参照させる（範囲の指定がない場合だけ）
+        if self.config.dedupe && !args.is_range() && self.tracker.is_unchanged_since_read(&path) {
+            debug!("{} is unchanged since the last read", args.path);
+            return Ok(ToolResult {
+                content: tr!(
+                    "[File {} is unchanged since it was last read with readFile; refer to that earlier result]",
+                    "[ファイル {} は前回 readFile で読み込んでから変更されていません。以前の結果を参照してください]",
                     args.path
+                ),
+                error: None,
+            });
+        }
+
+        // 範囲の指定とサイズ上限を超えるファイルは、全体を読んだことにしない（editFile は全体を書き換えるため）
+        let size = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+        if args.is_range() || size > self.config.max_bytes {
+            if size > MAX_OUTLINE_BYTES {
+                warn!(
+                    "File {} is {} bytes, truncating to {}",
+                    args.path, size, self.config.max_bytes
                 );
+                return Ok(self.read_truncated(&path, size).await);
+            }
+            let bytes = match fs::read(&path).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!("Failed to read file {}: {}", args.path, e);
+                    return Ok(read_error(e));
+                }
+            };
+            if args.is_range() {
+                return Ok(self.read_range(&args, &bytes));
+            }
+            warn!(
+                "File {} is {} bytes, returning its outline and line ranges",
+                args.path, size
+            );
+            return Ok(self.outline(&path, &bytes));
+        }
+
+        // ファイル読み込み
+        match fs::read(&path).await {
+            Ok(bytes) => {
+                debug!("Successfully read {} bytes from {}", bytes.len(), args.path);
+                let Some((mut content, encoding)) = encoding::decode(&bytes, None) else {
+                    warn!("File {} looks binary", args.path);
+                    return Ok(binary_file_error());
+                };
+                // editFile 時の鮮度チェックと再読み込みの省略、元の文字コードでの書き戻し用に記録
+                self.tracker.record_read(&path, &bytes);
+                self.tracker.record_encoding(&path, encoding);
+                push_encoding_note(&mut content, encoding);
                 Ok(ToolResult {
                     content,
                     error: None,
@@ -81,11 +316,152 @@ impl ToolHandler for ReadFileTool {
             }
             Err(e) => {
                 warn!("Failed to read file {}: {}", args.path, e);
-                Ok(ToolResult {
-                    content: String::new(),
-                    error: Some(format!("ファイルの読み込みに失敗しました: {}", e)),
-                })
+                Ok(read_error(e))
             }
         }
     }
 }
+
+fn read_error(e: std::io::Error) -> ToolResult {
+    ToolResult {
+        content: String::new(),
+        error: Some(tr!(
+            "Failed to read the file: {}",
+            "ファイルの読み込みに失敗しました: {}",
+            e
+        )),
+    }
+}
+
+fn binary_file_error() -> ToolResult {
+    ToolResult {
+        content: String::new(),
+        error: Some(tr!(
+            "The file looks binary and cannot be read as text",
+            "バイナリファイルのようなのでテキストとして読み込めません"
+        )),
+    }
+}
+
+/// 各範囲が `max_bytes` に収まるよう行を分ける（1 から数えた両端を含む行番号）
+fn line_ranges(lines: &[&str], max_bytes: u64) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 1;
+    let mut bytes = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        if number > start && bytes + line.len() as u64 > max_bytes {
+            ranges.push((start, number - 1));
+            start = number;
+            bytes = 0;
+        }
+        bytes += line.len() as u64;
+    }
+    if !lines.is_empty() {
+        ranges.push((start, lines.len()));
+    }
+    ranges
+}
+
+/// UTF-8 以外から変換した場合はその旨を添える
+fn push_encoding_note(content: &mut String, encoding: TextEncoding) {
+    if encoding.name() != "UTF-8" {
+        content.push_str(&tr!(
+            "\n\n[Converted from {0} to UTF-8; editFile and writeFile write the file back in {0}]",
+            "\n\n[{0} から UTF-8 に変換して表示しています。editFile と writeFile は {0} で書き戻します]",
+            encoding.name()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApprovalConfig, ApprovalPolicy, SymlinkPolicy};
+    use crate::policy::ApprovalEngine;
+    use crate::test_support::TempWorkspace;
+    use crate::tools::edit_file::{EditFileArgs, EditFileTool};
+    use crate::ui::Confirmer;
+    use std::sync::Arc;
+
+    fn args(path: String, start_line: Option<usize>, end_line: Option<usize>) -> ReadFileArgs {
+        ReadFileArgs {
+            path,
+            start_line,
+            end_line,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_file_returns_outline_and_ranges() {
+        let source = (1..=6)
+            .map(|i| format!("pub fn step{}() {{\n    run({});\n}}\n", i, i))
+            .collect::<String>();
+        let workspace = TempWorkspace::new().file("steps.rs", &source);
+        let path = workspace.path_str("steps.rs");
+        let tracker = FileTracker::new();
+        let tool = ReadFileTool::new(
+            tracker.clone(),
+            ReadFileConfig {
+                max_bytes: 100,
+                dedupe: true,
+            },
+            SymlinkGuard::new(SymlinkPolicy::Follow, workspace.root()),
+        );
+
+        let result = tool.execute(args(path.clone(), None, None)).await.unwrap();
+        assert!(result.content.contains("1: fn step1\n4: fn step2\n"));
+        assert!(result.content.contains("- lines 1-9\n- lines 10-18\n"));
+
+        let result = tool
+            .execute(args(path.clone(), Some(4), Some(5)))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.content,
+            "pub fn step2() {\n    run(2);\n\n\n[Lines 4-5 of 18]"
+        );
+        // 範囲の続きは start_line で指定させる
+        let result = tool
+            .execute(args(path.clone(), Some(4), None))
+            .await
+            .unwrap();
+        assert!(result
+            .content
+            .ends_with("cut at 100 bytes. Continue with start_line 13.]"));
+        let result = tool
+            .execute(args(path.clone(), Some(19), None))
+            .await
+            .unwrap();
+        assert!(result.error.is_some());
+
+        // 一部だけ読んだファイルは editFile できない（全体を上書きすると読んでいない部分が消える）
+        assert_eq!(
+            tracker.check(Path::new(&path)),
+            super::super::file_tracker::Freshness::NotRead
+        );
+        let edit = EditFileTool::new(
+            tracker,
+            Arc::new(Confirmer::new(
+                true,
+                ApprovalEngine::new(
+                    &ApprovalConfig::default(),
+                    ApprovalPolicy::Allow,
+                    workspace.root(),
+                )
+                .unwrap(),
+                None,
+            )),
+            SymlinkGuard::new(SymlinkPolicy::Follow, workspace.root()),
+        );
+        let result = edit
+            .execute(EditFileArgs {
+                path,
+                new_content: "pub fn step4() {}\n".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(result.error.is_some());
+        assert_eq!(workspace.read("steps.rs"), source);
+    }
+}
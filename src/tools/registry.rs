@@ -0,0 +1,254 @@
+use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    CreatePullRequestTool, CustomCommandTool, EditFileTool, FileTracker, GetDiagnosticsTool,
+    GetGitHubIssueTool, IgnoreMatcher, ListFilesTool, ReadFileTool, SearchInDirectoryTool,
+    SearchInFileTool, SymlinkGuard, WriteFileTool,
+};
+use crate::anthropic::{ToolHandler, ToolRegistry};
+use crate::config::Config;
+use crate::github::{self, GitHubClient};
+use crate::policy::ApprovalEngine;
+use crate::sandbox::Sandbox;
+use crate::ui::Confirmer;
+
+/// 標準のツールをまとめて登録する `ToolRegistry` のビルダー（`ToolRegistry::builder`）
+///
+/// ツールの作成に失敗した場合（除外パターンや GitHub の設定の誤りなど）は `build` でエラーを返す
+pub struct ToolRegistryBuilder<'a> {
+    config: &'a Config,
+    workspace: &'a Path,
+    file_tracker: FileTracker,
+    confirmer: Option<Arc<Confirmer>>,
+    registry: ToolRegistry,
+    error: Option<anyhow::Error>,
+}
+
+impl<'a> ToolRegistryBuilder<'a> {
+    pub fn new(config: &'a Config, workspace: &'a Path) -> Self {
+        Self {
+            config,
+            workspace,
+            file_tracker: FileTracker::new(),
+            confirmer: None,
+            registry: ToolRegistry::new(),
+            error: None,
+        }
+    }
+
+    /// ツール間で共有するファイルの記録（省略時は新しく作る）
+    pub fn file_tracker(mut self, file_tracker: FileTracker) -> Self {
+        self.file_tracker = file_tracker;
+        self
+    }
+
+    /// 書き込みと GitHub のツールが使うユーザー確認
+    ///
+    /// 省略時は `[approvals]` の設定どおりに端末で確認する
+    pub fn confirmer(mut self, confirmer: Arc<Confirmer>) -> Self {
+        self.confirmer = Some(confirmer);
+        self
+    }
+
+    /// 読み込み専用のツール（readFile / listFiles / searchInDirectory / searchInFile）
+    pub fn with_default_read_tools(mut self) -> Self {
+        let tools = &self.config.tools;
+        let ignore = match IgnoreMatcher::new(&self.config.ignore, self.workspace) {
+            Ok(ignore) => ignore,
+            Err(e) => return self.fail(e),
+        };
+        let search_index = self
+            .file_tracker
+            .search_index()
+            .filter(|_| tools.search_in_directory.index)
+            .cloned();
+        let symlinks = SymlinkGuard::new(tools.symlinks, self.workspace);
+        self.add(ReadFileTool::new(
+            self.file_tracker.clone(),
+            tools.read_file.clone(),
+            symlinks.clone(),
+        ));
+        self.add(ListFilesTool::new(
+            tools.list_files.clone(),
+            ignore.clone(),
+            symlinks.clone(),
+        ));
+        self.add(SearchInDirectoryTool::new(
+            tools.search_in_directory.clone(),
+            ignore,
+            symlinks.clone(),
+            search_index,
+        ));
+        self.add(SearchInFileTool::new(
+            tools.search_in_directory.clone(),
+            symlinks,
+        ));
+        self
+    }
+
+    /// ファイルを書き込むツール（writeFile / editFile）
+    pub fn with_write_tools(mut self) -> Self {
+        let Some(confirmer) = self.shared_confirmer() else {
+            return self;
+        };
+        let symlinks = SymlinkGuard::new(self.config.tools.symlinks, self.workspace);
+        self.add(WriteFileTool::new(
+            self.file_tracker.clone(),
+            confirmer.clone(),
+            symlinks.clone(),
+        ));
+        self.add(EditFileTool::new(
+            self.file_tracker.clone(),
+            confirmer,
+            symlinks,
+        ));
+        self
+    }
+
+    /// 言語サーバーの診断を返すツール（getDiagnostics）
+    pub fn with_diagnostics_tool(mut self) -> Self {
+        self.add(GetDiagnosticsTool::new(
+            self.file_tracker.clone(),
+            self.config.tools.get_diagnostics.clone(),
+            self.workspace,
+        ));
+        self
+    }
+
+    /// GitHub のツール（getGitHubIssue / createPullRequest）
+    ///
+    /// origin リモートが GitHub にある場合だけ登録する
+    pub fn with_github_tools(mut self) -> Self {
+        let Some(repo) = github::origin_repo(self.workspace, &self.config.github.host) else {
+            return self;
+        };
+        let client = match GitHubClient::new(&self.config.github, repo) {
+            Ok(client) => client,
+            Err(e) => return self.fail(e),
+        };
+        let Some(confirmer) = self.shared_confirmer() else {
+            return self;
+        };
+        self.add(GetGitHubIssueTool::new(client.clone()));
+        self.add(CreatePullRequestTool::new(
+            client,
+            confirmer,
+            self.workspace,
+        ));
+        self
+    }
+
+    /// `[[tools.custom]]` で定義した外部コマンドのツール
+    pub fn with_custom_tools(mut self) -> Self {
+        let sandbox = Sandbox::new(&self.config.sandbox, self.workspace);
+        for custom in &self.config.tools.custom {
+            let tool = CustomCommandTool::new(custom.clone(), sandbox.clone());
+            if let Err(e) = self.registry.register_dynamic(tool) {
+                return self.fail(e.context(format!("Invalid custom tool {}", custom.name)));
+            }
+        }
+        self
+    }
+
+    /// 任意のツールを登録する
+    #[allow(dead_code)] // CLI からはまだ使っていない
+    pub fn with<T: ToolHandler + 'static>(mut self, tool: T) -> Self {
+        self.add(tool);
+        self
+    }
+
+    pub fn build(self) -> Result<ToolRegistry> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.registry),
+        }
+    }
+
+    /// 設定されたユーザー確認（なければ `[approvals]` から作る）
+    fn shared_confirmer(&mut self) -> Option<Arc<Confirmer>> {
+        if self.confirmer.is_none() {
+            let approvals = &self.config.approvals;
+            match ApprovalEngine::new(approvals, approvals.policy, self.workspace) {
+                Ok(engine) => {
+                    self.confirmer = Some(Arc::new(Confirmer::new(
+                        approvals.approve_when_non_interactive,
+                        engine,
+                        None,
+                    )))
+                }
+                Err(e) => {
+                    self.error.get_or_insert(e);
+                }
+            }
+        }
+        self.confirmer.clone()
+    }
+
+    /// ツールを登録する（重複などのエラーは `build` まで持ち越す）
+    fn add<T: ToolHandler + 'static>(&mut self, tool: T) {
+        if let Err(e) = self.registry.register(tool) {
+            self.error.get_or_insert(e);
+        }
+    }
+
+    /// 最初のエラーを `build` まで持ち越す
+    fn fail(mut self, error: anyhow::Error) -> Self {
+        self.error.get_or_insert(error);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempWorkspace;
+
+    #[test]
+    fn test_builder_registers_tools_in_order() {
+        let workspace = TempWorkspace::new();
+        let config = Config::default();
+        let registry = ToolRegistry::builder(&config, workspace.root())
+            .with_default_read_tools()
+            .with_write_tools()
+            .with(GetDiagnosticsTool::new(
+                FileTracker::new(),
+                config.tools.get_diagnostics.clone(),
+                workspace.root(),
+            ))
+            .build()
+            .unwrap();
+        let names: Vec<&str> = registry
+            .get_schemas()
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "readFile",
+                "listFiles",
+                "searchInDirectory",
+                "searchInFile",
+                "writeFile",
+                "editFile",
+                "getDiagnostics"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builder_reports_invalid_settings() {
+        let workspace = TempWorkspace::new();
+        let config = Config {
+            ignore: vec!["[".to_string()],
+            ..Config::default()
+        };
+        let result = ToolRegistry::builder(&config, workspace.root())
+            .with_default_read_tools()
+            .with_write_tools()
+            .build();
+        assert!(result.is_err());
+    }
+}
@@ -1,17 +1,30 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tracing::{debug, warn};
 
-use crate::anthropic::{Tool, ToolHandler, ToolResult};
+use super::concurrent::for_each_ordered;
+use super::ignore::IgnoreMatcher;
+use super::search_index::SearchIndex;
+use super::symlinks::SymlinkGuard;
+use crate::anthropic::{Progress, ToolHandler, ToolResult};
+use crate::config::SearchInDirectoryConfig;
+use crate::i18n::tr;
+use crate::platform;
 
 /// searchInDirectory ツールの引数
-#[derive(Debug, Deserialize)]
-struct SearchInDirectoryArgs {
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchInDirectoryArgs {
     path: String,
     keyword: String,
+    /// 隠しファイル・隠しディレクトリ（`.` で始まる名前）も検索する
+    #[serde(default)]
+    include_hidden: bool,
 }
 
 /// 検索結果の1件
@@ -23,106 +36,374 @@ struct SearchMatch {
 }
 
 /// searchInDirectory ツールの実装
-pub struct SearchInDirectoryTool;
+pub struct SearchInDirectoryTool {
+    config: SearchInDirectoryConfig,
+    ignore: IgnoreMatcher,
+    symlinks: SymlinkGuard,
+    /// 対話セッションでのみ使う索引
+    index: Option<SearchIndex>,
+}
 
-impl SearchInDirectoryTool {
-    pub fn new() -> Self {
-        Self
-    }
+/// 検索で見つかった行
+struct Found {
+    matches: Vec<SearchMatch>,
+    truncated: bool,
+    capped_files: usize,
+}
 
-    /// ツールのスキーマ定義を返す
-    pub fn schema() -> Tool {
-        Tool {
-            name: "searchInDirectory".to_string(),
-            description: "指定されたディレクトリ配下のファイルをキーワード検索し、マッチした行を返します。大文字小文字は区別しません。".to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "検索を開始するディレクトリのパス"
-                    },
-                    "keyword": {
-                        "type": "string",
-                        "description": "検索するキーワード"
-                    }
-                },
-                "required": ["path", "keyword"]
-            }),
+impl SearchInDirectoryTool {
+    pub fn new(
+        config: SearchInDirectoryConfig,
+        ignore: IgnoreMatcher,
+        symlinks: SymlinkGuard,
+        index: Option<SearchIndex>,
+    ) -> Self {
+        Self {
+            config,
+            ignore,
+            symlinks,
+            index,
         }
     }
 }
 
 #[async_trait]
 impl ToolHandler for SearchInDirectoryTool {
-    async fn execute(&self, input: serde_json::Value) -> Result<ToolResult> {
-        debug!("Executing searchInDirectory tool with input: {:?}", input);
+    type Args = SearchInDirectoryArgs;
+
+    const NAME: &'static str = "searchInDirectory";
+
+    const CACHEABLE: bool = true;
+
+    fn description() -> String {
+        tr!(
+            "Searches the files under the given directory for a keyword and returns the matching lines. The search is case-insensitive.",
+            "指定されたディレクトリ配下のファイルをキーワード検索し、マッチした行を返します。大文字小文字は区別しません。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "path",
+                tr!(
+                    "Path of the directory to search from",
+                    "検索を開始するディレクトリのパス"
+                ),
+            ),
+            (
+                "keyword",
+                tr!("Keyword to search for", "検索するキーワード"),
+            ),
+            (
+                "include_hidden",
+                tr!(
+                    "Whether to also search hidden files and directories whose names start with a dot, such as .github (default: false)",
+                    "名前が . で始まる隠しファイル・隠しディレクトリ（.github など）も検索するか（デフォルト: false）"
+                ),
+            ),
+        ]
+    }
+
+    async fn execute(&self, args: SearchInDirectoryArgs) -> Result<ToolResult> {
+        self.search(args, &Progress::default()).await
+    }
 
-        // 引数をパース
-        let args: SearchInDirectoryArgs =
-            serde_json::from_value(input).context("Failed to parse searchInDirectory arguments")?;
+    async fn execute_with_progress(
+        &self,
+        args: SearchInDirectoryArgs,
+        progress: Progress,
+    ) -> Result<ToolResult> {
+        self.search(args, &progress).await
+    }
+}
+
+impl SearchInDirectoryTool {
+    /// 検索して結果を返す（走査したファイルの割合を途中経過として通知する）
+    async fn search(&self, args: SearchInDirectoryArgs, progress: &Progress) -> Result<ToolResult> {
+        debug!("Executing searchInDirectory tool with input: {:?}", args);
 
         debug!("Searching for '{}' in: {}", args.keyword, args.path);
 
         let path = Path::new(&args.path);
+        if let Err(error) = self.symlinks.check(path) {
+            warn!("searchInDirectory: {}", error);
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(error),
+            });
+        }
 
         // ディレクトリが存在しない場合
         if !path.exists() {
             warn!("Directory not found: {}", args.path);
             return Ok(ToolResult {
                 content: String::new(),
-                error: Some(format!("ディレクトリが見つかりません: {}", args.path)),
+                error: Some(tr!(
+                    "Directory not found: {}",
+                    "ディレクトリが見つかりません: {}",
+                    args.path
+                )),
             });
         }
 
-        // TODO: タスク5で実装
-        let mut matches = Vec::new();
+        // 索引には隠しファイルを含めないので、含める場合は走査する
         let keyword_lower = args.keyword.to_lowercase();
+        let indexed = if args.include_hidden {
+            None
+        } else {
+            self.search_indexed(&args.path, &keyword_lower, progress)
+                .await?
+        };
+        let found = match indexed {
+            Some(found) => found,
+            None => {
+                let files = match self.symlinks.walk(path, &self.ignore, args.include_hidden) {
+                    Ok(entries) => entries
+                        .into_iter()
+                        .filter(|e| !e.file_type().is_dir())
+                        .map(|e| e.into_path())
+                        .collect(),
+                    Err(error) => {
+                        return Ok(ToolResult {
+                            content: String::new(),
+                            error: Some(error),
+                        })
+                    }
+                };
+                self.search_walk(files, keyword_lower, progress).await?
+            }
+        };
+        let Found {
+            matches,
+            truncated,
+            capped_files,
+        } = found;
 
-        use walkdir::WalkDir;
+        let mut result_json =
+            serde_json::to_string_pretty(&matches).context("Failed to serialize serach results")?;
 
-        for entry_result in WalkDir::new(path) {
-            let entry = match entry_result {
-                Ok(e) => e,
-                Err(e) => {
-                    warn!("Failed to read entry: {}", e);
-                    continue;
-                }
-            };
-            if entry.file_type().is_dir() {
-                continue;
-            }
+        debug!("Found {} matches", matches.len());
+        if truncated {
+            result_json.push_str(&tr!(
+                "\n\n[Too many matches; showing only the first {}. Narrow down the keyword or path]",
+                "\n\n[マッチが多すぎるため先頭 {} 件のみ表示しています。キーワードやパスを絞り込んでください]",
+                self.config.max_matches
+            ));
+        }
+        if capped_files > 0 {
+            result_json.push_str(&tr!(
+                "\n\n[{} files are larger than {} bytes and were searched only up to that size]",
+                "\n\n[{} 件のファイルは {} バイトを超えるため、そこまでのみ検索しました]",
+                capped_files,
+                self.config.max_file_bytes
+            ));
+        }
 
-            let file_path = entry.path();
+        Ok(ToolResult {
+            content: result_json,
+            error: None,
+        })
+    }
 
-            let content = match tokio::fs::read_to_string(file_path).await {
-                Ok(c) => c,
-                Err(_) => {
-                    // バイナリファイルや権限エラーは静かにスキップ
-                    debug!("Skipping file: {:?}", file_path);
-                    continue;
+    /// 走査したファイルを複数並行して読み込んで探す（結果は走査順）
+    async fn search_walk(
+        &self,
+        files: Vec<PathBuf>,
+        keyword_lower: String,
+        progress: &Progress,
+    ) -> Result<Found> {
+        let mut found = Found {
+            matches: Vec::new(),
+            truncated: false,
+            capped_files: 0,
+        };
+        let keyword_lower = Arc::new(keyword_lower);
+        let max_matches = self.config.max_matches;
+        let max_file_bytes = self.config.max_file_bytes;
+        let total = files.len();
+        let mut searched = 0;
+        for_each_ordered(
+            files,
+            self.config.concurrency,
+            |file_path| search_file(file_path, keyword_lower.clone(), max_file_bytes),
+            |file| {
+                searched += 1;
+                progress.report(
+                    Some((searched * 100 / total) as u8),
+                    format!("{}/{} files", searched, total),
+                );
+                if file.capped {
+                    found.capped_files += 1;
                 }
-            };
+                for file_match in file.matches {
+                    if found.matches.len() >= max_matches {
+                        found.truncated = true;
+                        return false;
+                    }
+                    found.matches.push(file_match);
+                }
+                true
+            },
+        )
+        .await?;
+        Ok(found)
+    }
+
+    /// 対話セッションの索引から探す（索引を使えない場合は None）
+    ///
+    /// 索引は最初の検索で作る
+    async fn search_indexed(
+        &self,
+        path: &str,
+        keyword_lower: &str,
+        progress: &Progress,
+    ) -> Result<Option<Found>> {
+        let Some(index) = &self.index else {
+            return Ok(None);
+        };
+        if !index.is_built() {
+            progress.report(None, "indexing the workspace");
+            index
+                .build(
+                    &self.ignore,
+                    &self.symlinks,
+                    self.config.max_file_bytes,
+                    self.config.concurrency,
+                )
+                .await?;
+        }
+
+        let (index, scope, keyword) = (
+            index.clone(),
+            PathBuf::from(path),
+            keyword_lower.to_string(),
+        );
+        let result = tokio::task::spawn_blocking(move || {
+            let result = index.search(&scope, &keyword);
+            result.map(|result| (result, scope.canonicalize().ok()))
+        })
+        .await
+        .context("Search task failed")?;
+        let Some((result, Some(root))) = result else {
+            return Ok(None);
+        };
 
-            for (line_num, line) in content.lines().enumerate() {
-                if line.to_lowercase().contains(&keyword_lower) {
-                    matches.push(SearchMatch {
-                        path: file_path.display().to_string(),
-                        line_number: line_num + 1,
-                        line: line.to_string(),
-                    });
+        // 走査した場合と同じく、指定されたパスを基準に表示する
+        let mut found = Found {
+            matches: Vec::new(),
+            truncated: false,
+            capped_files: result.capped_files,
+        };
+        'files: for (file_path, lines) in result.files {
+            let relative = file_path.strip_prefix(&root).unwrap_or(&file_path);
+            let display = if relative.as_os_str().is_empty() {
+                PathBuf::from(path)
+            } else {
+                Path::new(path).join(relative)
+            };
+            for (line_number, line) in lines {
+                if found.matches.len() >= self.config.max_matches {
+                    found.truncated = true;
+                    break 'files;
                 }
+                found.matches.push(SearchMatch {
+                    path: platform::display_path(&display),
+                    line_number,
+                    line,
+                });
             }
         }
+        Ok(Some(found))
+    }
+}
 
-        let result_json =
-            serde_json::to_string_pretty(&matches).context("Failed to serialize serach results")?;
+/// 1 つのファイルの検索結果
+#[derive(Default)]
+struct FileMatches {
+    matches: Vec<SearchMatch>,
+    /// 上限のサイズまでしか読まなかった
+    capped: bool,
+}
 
-        debug!("Found {} matches", matches.len());
+/// 1 つのファイルからキーワードを含む行を探す
+async fn search_file(
+    file_path: PathBuf,
+    keyword_lower: Arc<String>,
+    max_bytes: u64,
+) -> FileMatches {
+    match scan_file(&file_path, &keyword_lower, max_bytes).await {
+        Ok(found) => found,
+        Err(e) => {
+            // バイナリファイルや権限エラーは静かにスキップ
+            debug!("Skipping file {:?}: {}", file_path, e);
+            FileMatches::default()
+        }
+    }
+}
 
-        Ok(ToolResult {
-            content: result_json,
-            error: None,
-        })
+/// ファイル全体を読み込まず、先頭から `max_bytes` までを 1 行ずつ調べる
+async fn scan_file(
+    file_path: &Path,
+    keyword_lower: &str,
+    max_bytes: u64,
+) -> io::Result<FileMatches> {
+    let file = tokio::fs::File::open(file_path).await?;
+    let capped = file.metadata().await?.len() > max_bytes;
+    let mut reader = BufReader::new(file.take(max_bytes));
+
+    let mut found = FileMatches {
+        matches: Vec::new(),
+        capped,
+    };
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf).await? == 0 {
+            break;
+        }
+        // 上限で途中から切れた最後の行は調べない
+        if capped && buf.last() != Some(&b'\n') {
+            break;
+        }
+        line_number += 1;
+        let line =
+            std::str::from_utf8(&buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.to_lowercase().contains(keyword_lower) {
+            found.matches.push(SearchMatch {
+                path: platform::display_path(file_path),
+                line_number,
+                line: line.to_string(),
+            });
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scan_file_stops_at_byte_cap() {
+        let dir = std::env::temp_dir().join(format!("search_scan_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.log");
+        std::fs::write(&path, "Needle one\r\nhay\nneedle two\nneedle three\n").unwrap();
+
+        let found = scan_file(&path, "needle", 1024).await.unwrap();
+        assert!(!found.capped);
+        assert_eq!(found.matches.len(), 3);
+        assert_eq!(found.matches[0].line, "Needle one");
+
+        // 3 行目の途中で切れるので 2 行目までしか調べない
+        let found = scan_file(&path, "needle", 20).await.unwrap();
+        assert!(found.capped);
+        assert_eq!(found.matches.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
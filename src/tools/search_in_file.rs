@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use regex::RegexBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use tracing::{debug, warn};
+
+use super::encoding;
+use super::symlinks::SymlinkGuard;
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::config::SearchInDirectoryConfig;
+use crate::i18n::tr;
+
+/// searchInFile ツールの引数
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchInFileArgs {
+    path: String,
+    /// 正規表現（Rust の regex の構文）
+    pattern: String,
+    /// 大文字小文字を区別する（省略時は区別しない）
+    case_sensitive: Option<bool>,
+}
+
+/// 検索結果の 1 件（行と列は 1 から、バイト位置は 0 から数える）
+#[derive(Debug, Serialize, PartialEq)]
+struct FileMatch {
+    line_number: usize,
+    column: usize,
+    end_line_number: usize,
+    end_column: usize,
+    byte_offset: usize,
+    byte_end: usize,
+    /// マッチが始まる行
+    line: String,
+}
+
+/// 正規表現の大きさの上限（巨大なパターンでメモリを使い切らないように）
+const MAX_REGEX_SIZE: usize = 10 * 1024 * 1024;
+
+/// searchInFile ツールの実装
+///
+/// 対象のファイルが分かっている場合にディレクトリを走査せずに探す。
+/// マッチ数とファイルサイズの上限は `[tools.searchInDirectory]` の設定を使う
+pub struct SearchInFileTool {
+    config: SearchInDirectoryConfig,
+    symlinks: SymlinkGuard,
+}
+
+impl SearchInFileTool {
+    pub fn new(config: SearchInDirectoryConfig, symlinks: SymlinkGuard) -> Self {
+        Self { config, symlinks }
+    }
+}
+
+#[async_trait]
+impl ToolHandler for SearchInFileTool {
+    type Args = SearchInFileArgs;
+
+    const NAME: &'static str = "searchInFile";
+
+    const CACHEABLE: bool = true;
+
+    fn description() -> String {
+        tr!(
+            "Searches a single file for a regular expression and returns each match with its line, column and byte offsets. Use it instead of searchInDirectory when you already know the file; pass line_number to readFile as start_line to read around a match. The search is case-insensitive unless case_sensitive is true.",
+            "1 つのファイルを正規表現で検索し、マッチごとに行・列・バイト位置を返します。対象のファイルが分かっている場合は searchInDirectory の代わりに使ってください。マッチの周辺は line_number を readFile の start_line に指定して読めます。case_sensitive が true でなければ大文字小文字は区別しません。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "path",
+                tr!("Path of the file to search", "検索するファイルのパス"),
+            ),
+            (
+                "pattern",
+                tr!(
+                    "Regular expression to search for (Rust regex syntax; ^ and $ match at line boundaries)",
+                    "検索する正規表現（Rust の regex の構文。^ と $ は行の先頭と末尾にマッチ）"
+                ),
+            ),
+            (
+                "case_sensitive",
+                tr!(
+                    "Match case exactly (default false)",
+                    "大文字小文字を区別する（既定は false）"
+                ),
+            ),
+        ]
+    }
+
+    async fn execute(&self, args: SearchInFileArgs) -> Result<ToolResult> {
+        debug!("Executing searchInFile tool with input: {:?}", args);
+
+        let path = Path::new(&args.path);
+        if let Err(message) = self.symlinks.check(path) {
+            warn!("searchInFile: {}", message);
+            return Ok(error(message));
+        }
+        if !path.is_file() {
+            warn!("File not found: {}", args.path);
+            return Ok(error(tr!(
+                "File not found: {}",
+                "ファイルが見つかりません: {}",
+                args.path
+            )));
+        }
+
+        let regex = match RegexBuilder::new(&args.pattern)
+            .case_insensitive(!args.case_sensitive.unwrap_or(false))
+            .multi_line(true)
+            .size_limit(MAX_REGEX_SIZE)
+            .build()
+        {
+            Ok(regex) => regex,
+            Err(e) => {
+                return Ok(error(tr!(
+                    "Invalid regular expression: {}",
+                    "正規表現が不正です: {}",
+                    e
+                )))
+            }
+        };
+
+        // 上限のサイズまでを読む
+        let mut bytes = Vec::new();
+        let result = match tokio::fs::File::open(path).await {
+            Ok(file) => {
+                file.take(self.config.max_file_bytes)
+                    .read_to_end(&mut bytes)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Failed to read file {}: {}", args.path, e);
+            return Ok(error(tr!(
+                "Failed to read the file: {}",
+                "ファイルの読み込みに失敗しました: {}",
+                e
+            )));
+        }
+        let size = tokio::fs::metadata(path).await.map_or(0, |m| m.len());
+        let capped = size > self.config.max_file_bytes;
+        let Some((content, _)) = encoding::decode(&bytes, None) else {
+            return Ok(error(tr!(
+                "The file looks binary and cannot be searched as text",
+                "バイナリファイルのようなのでテキストとして検索できません"
+            )));
+        };
+
+        let (matches, truncated) = find_matches(&regex, &content, self.config.max_matches);
+        debug!("Found {} matches in {}", matches.len(), args.path);
+
+        let mut result =
+            serde_json::to_string_pretty(&matches).context("Failed to serialize search results")?;
+        if truncated {
+            result.push_str(&tr!(
+                "\n\n[Too many matches; showing only the first {}. Narrow down the pattern]",
+                "\n\n[マッチが多すぎるため先頭 {} 件のみ表示しています。パターンを絞り込んでください]",
+                self.config.max_matches
+            ));
+        }
+        if capped {
+            result.push_str(&tr!(
+                "\n\n[The file is {} bytes; only the first {} bytes were searched]",
+                "\n\n[ファイルが {} バイトあるため、先頭 {} バイトのみ検索しました]",
+                size,
+                self.config.max_file_bytes
+            ));
+        }
+        Ok(ToolResult {
+            content: result,
+            error: None,
+        })
+    }
+}
+
+/// マッチを位置付きで集める（上限を超えた場合は true を返す）
+///
+/// バイト位置は UTF-8 にした内容での位置（UTF-8 のファイルではファイル上の位置と同じ）
+fn find_matches(regex: &regex::Regex, content: &str, max_matches: usize) -> (Vec<FileMatch>, bool) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    // 位置を (行, 列) にする
+    let position = |offset: usize| {
+        let line = line_starts.partition_point(|&start| start <= offset);
+        let start = line_starts[line - 1];
+        (line, content[start..offset].chars().count() + 1)
+    };
+
+    let mut matches = Vec::new();
+    for found in regex.find_iter(content) {
+        if matches.len() >= max_matches {
+            return (matches, true);
+        }
+        let (line_number, column) = position(found.start());
+        let (end_line_number, end_column) = position(found.end());
+        let line_start = line_starts[line_number - 1];
+        let line_end = content[line_start..]
+            .find('\n')
+            .map_or(content.len(), |i| line_start + i);
+        let line = &content[line_start..line_end];
+        matches.push(FileMatch {
+            line_number,
+            column,
+            end_line_number,
+            end_column,
+            byte_offset: found.start(),
+            byte_end: found.end(),
+            line: line.strip_suffix('\r').unwrap_or(line).to_string(),
+        });
+    }
+    (matches, false)
+}
+
+fn error(message: String) -> ToolResult {
+    ToolResult {
+        content: String::new(),
+        error: Some(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_matches_with_positions() {
+        let regex = RegexBuilder::new(r"^fn (\w+)")
+            .case_insensitive(true)
+            .multi_line(true)
+            .build()
+            .unwrap();
+        let content = "// é\nfn main() {}\r\n\nFN helper() {}\n";
+        let (matches, truncated) = find_matches(&regex, content, 10);
+        assert!(!truncated);
+        assert_eq!(
+            matches[0],
+            FileMatch {
+                line_number: 2,
+                column: 1,
+                end_line_number: 2,
+                end_column: 8,
+                byte_offset: 6,
+                byte_end: 13,
+                line: "fn main() {}".to_string(),
+            }
+        );
+        assert_eq!(matches[1].line_number, 4);
+        assert_eq!(
+            &content[matches[1].byte_offset..matches[1].byte_end],
+            "FN helper"
+        );
+
+        // 行をまたぐマッチと上限
+        let regex = regex::Regex::new(r"\{\}\r?\n\n").unwrap();
+        let (matches, _) = find_matches(&regex, content, 10);
+        assert_eq!((matches[0].line_number, matches[0].end_line_number), (2, 4));
+        let regex = regex::Regex::new("e").unwrap();
+        assert!(find_matches(&regex, content, 1).1);
+    }
+}
@@ -0,0 +1,352 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::debug;
+
+use super::concurrent::for_each_ordered;
+use super::ignore::{self, IgnoreMatcher};
+use super::symlinks::SymlinkGuard;
+
+/// 小文字化した連続する 3 文字
+type Trigram = [char; 3];
+
+/// 索引に載せたファイル
+#[derive(Debug)]
+struct IndexedFile {
+    path: PathBuf,
+    content: String,
+    /// 上限のサイズまでしか読まなかった
+    capped: bool,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+struct IndexState {
+    /// 走査順に並べる（削除されたファイルは None）
+    files: Vec<Option<IndexedFile>>,
+    ids: HashMap<PathBuf, usize>,
+    trigrams: HashMap<Trigram, HashSet<usize>>,
+    ignore: IgnoreMatcher,
+    max_bytes: u64,
+}
+
+/// 索引を使った検索の結果
+pub struct IndexSearch {
+    /// キーワードを含むファイルと、その行（行番号と内容）
+    pub files: Vec<(PathBuf, Vec<(usize, String)>)>,
+    /// 上限のサイズまでしか索引に載せていないファイルの数
+    pub capped_files: usize,
+}
+
+/// 対話セッション中の searchInDirectory 用のワークスペースのトライグラム索引
+///
+/// 最初の検索でワークスペースを走査して内容をメモリに持ち、以降の検索はディスクを
+/// 走査せずに答える。writeFile / editFile の書き込みはその場で反映し、それ以外で
+/// 変更されたファイルは候補になった時点で mtime を見て読み直す
+#[derive(Debug, Clone)]
+pub struct SearchIndex {
+    root: PathBuf,
+    state: Arc<Mutex<Option<IndexState>>>,
+}
+
+impl SearchIndex {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            root: workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+            state: Arc::default(),
+        }
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.state.lock().unwrap().is_some()
+    }
+
+    /// 索引を捨てる（設定の再読み込みで除外パターンなどが変わった場合）
+    pub fn clear(&self) {
+        *self.state.lock().unwrap() = None;
+    }
+
+    /// ワークスペースを走査して索引を作る
+    pub async fn build(
+        &self,
+        ignore: &IgnoreMatcher,
+        symlinks: &SymlinkGuard,
+        max_bytes: u64,
+        concurrency: usize,
+    ) -> Result<()> {
+        let paths: Vec<PathBuf> = symlinks
+            .walk(&self.root, ignore, false)
+            .map_err(anyhow::Error::msg)?
+            .into_iter()
+            .filter(|e| !e.file_type().is_dir())
+            .map(|e| e.into_path())
+            .collect();
+
+        let mut state = IndexState {
+            files: Vec::new(),
+            ids: HashMap::new(),
+            trigrams: HashMap::new(),
+            ignore: ignore.clone(),
+            max_bytes,
+        };
+        for_each_ordered(
+            paths,
+            concurrency,
+            |path| async move {
+                tokio::task::spawn_blocking(move || load_file(path, max_bytes)).await
+            },
+            |loaded| {
+                if let Ok(Some(file)) = loaded {
+                    state.insert(file);
+                }
+                true
+            },
+        )
+        .await
+        .context("Failed to build the search index")?;
+
+        debug!("Indexed {} files under {:?}", state.files.len(), self.root);
+        *self.state.lock().unwrap() = Some(state);
+        Ok(())
+    }
+
+    /// `scope` 配下のファイルからキーワード（小文字化済み）を含む行を探す
+    ///
+    /// 索引がない場合や `scope` がワークスペースの外・除外対象・隠しディレクトリの場合は None を返す。
+    /// 索引には隠しファイルを含めない
+    /// ファイルの読み直しを伴うので非同期ランタイムの外で呼ぶ
+    pub fn search(&self, scope: &Path, keyword_lower: &str) -> Option<IndexSearch> {
+        let scope = scope.canonicalize().ok()?;
+        let mut guard = self.state.lock().unwrap();
+        let state = guard.as_mut()?;
+        if !scope.starts_with(&self.root)
+            || (scope != self.root && state.ignore.is_ignored(&scope))
+            || ignore::has_hidden_component(&scope, &self.root)
+        {
+            return None;
+        }
+
+        let mut result = IndexSearch {
+            files: Vec::new(),
+            capped_files: 0,
+        };
+        for id in state.candidates(keyword_lower) {
+            let Some(file) = &state.files[id] else {
+                continue;
+            };
+            if !file.path.starts_with(&scope) {
+                continue;
+            }
+            // 索引の外で変更・削除されたファイルは読み直す
+            if modified(&file.path) != file.modified {
+                let path = file.path.clone();
+                state.remove(&path);
+                if let Some(file) = load_file(path, state.max_bytes) {
+                    state.insert(file);
+                }
+            }
+            let Some(file) = &state.files[id] else {
+                continue;
+            };
+            let lines = matching_lines(&file.content, keyword_lower);
+            if !lines.is_empty() {
+                result.files.push((file.path.clone(), lines));
+            }
+        }
+        result.capped_files = state
+            .files
+            .iter()
+            .flatten()
+            .filter(|file| file.capped && file.path.starts_with(&scope))
+            .count();
+        Some(result)
+    }
+
+    /// 書き込まれた内容を索引に反映する（索引の作成前は何もしない）
+    pub fn update(&self, path: &Path, content: &[u8]) {
+        let mut guard = self.state.lock().unwrap();
+        let Some(state) = guard.as_mut() else {
+            return;
+        };
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if !path.starts_with(&self.root)
+            || state.ignore.is_ignored(&path)
+            || ignore::has_hidden_component(&path, &self.root)
+        {
+            return;
+        }
+
+        state.remove(&path);
+        if let Some((content, capped)) = cap_content(content.to_vec(), state.max_bytes) {
+            let modified = modified(&path);
+            state.insert(IndexedFile {
+                path,
+                content,
+                capped,
+                modified,
+            });
+        }
+    }
+}
+
+impl IndexState {
+    fn insert(&mut self, file: IndexedFile) {
+        let id = match self.ids.get(&file.path) {
+            Some(&id) => id,
+            None => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+        };
+        for trigram in trigrams(&file.content.to_lowercase()) {
+            self.trigrams.entry(trigram).or_default().insert(id);
+        }
+        self.ids.insert(file.path.clone(), id);
+        self.files[id] = Some(file);
+    }
+
+    /// ファイルを索引から外す（位置は残して再び追加された場合に使う）
+    fn remove(&mut self, path: &Path) {
+        let Some(&id) = self.ids.get(path) else {
+            return;
+        };
+        let Some(file) = self.files[id].take() else {
+            return;
+        };
+        for trigram in trigrams(&file.content.to_lowercase()) {
+            if let Some(ids) = self.trigrams.get_mut(&trigram) {
+                ids.remove(&id);
+            }
+        }
+    }
+
+    /// キーワードのトライグラムをすべて含むファイル（走査順）
+    fn candidates(&self, keyword_lower: &str) -> Vec<usize> {
+        let keyword = trigrams(keyword_lower);
+        // 3 文字未満のキーワードは絞り込めないのですべてのファイルを調べる
+        if keyword.is_empty() {
+            return (0..self.files.len()).collect();
+        }
+
+        let mut sets: Vec<&HashSet<usize>> = Vec::new();
+        for trigram in &keyword {
+            match self.trigrams.get(trigram) {
+                Some(ids) => sets.push(ids),
+                None => return Vec::new(),
+            }
+        }
+        sets.sort_by_key(|ids| ids.len());
+        let mut ids: Vec<usize> = sets[0]
+            .iter()
+            .copied()
+            .filter(|id| sets[1..].iter().all(|ids| ids.contains(id)))
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// ファイルを先頭から `max_bytes` まで読み込む（テキストでない場合は None）
+fn load_file(path: PathBuf, max_bytes: u64) -> Option<IndexedFile> {
+    let read = || -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        std::fs::File::open(&path)?
+            .take(max_bytes + 1)
+            .read_to_end(&mut buf)?;
+        Ok(buf)
+    };
+    let (content, capped) = match read() {
+        Ok(buf) => cap_content(buf, max_bytes)?,
+        Err(e) => {
+            debug!("Skipping file {:?}: {}", path, e);
+            return None;
+        }
+    };
+    let modified = modified(&path);
+    Some(IndexedFile {
+        path,
+        content,
+        capped,
+        modified,
+    })
+}
+
+/// 上限を超える内容は最後の完全な行までに切り詰める（UTF-8 でない場合は None）
+fn cap_content(mut content: Vec<u8>, max_bytes: u64) -> Option<(String, bool)> {
+    let capped = content.len() as u64 > max_bytes;
+    if capped {
+        content.truncate(max_bytes as usize);
+        let end = content
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        content.truncate(end);
+    }
+    String::from_utf8(content)
+        .ok()
+        .map(|content| (content, capped))
+}
+
+fn matching_lines(content: &str, keyword_lower: &str) -> Vec<(usize, String)> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.to_lowercase().contains(keyword_lower))
+        .map(|(i, line)| (i + 1, line.to_string()))
+        .collect()
+}
+
+fn trigrams(text: &str) -> HashSet<Trigram> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SymlinkPolicy;
+
+    #[tokio::test]
+    async fn test_index_search_and_update() {
+        let root = std::env::temp_dir().join(format!("search_index_test_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("src/a.rs"), "fn main() {\n    Needle();\n}\n").unwrap();
+        std::fs::write(root.join("src/b.rs"), "fn other() {}\n").unwrap();
+        std::fs::write(root.join("target/c.rs"), "needle\n").unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join(".git/config"), "needle\n").unwrap();
+
+        let ignore = IgnoreMatcher::new(&["target/**".to_string()], &root).unwrap();
+        let index = SearchIndex::new(&root);
+        assert!(index.search(&root, "needle").is_none());
+        let symlinks = SymlinkGuard::new(SymlinkPolicy::Follow, &root);
+        index.build(&ignore, &symlinks, 1024, 4).await.unwrap();
+
+        let found = index.search(&root, "needle").unwrap();
+        assert_eq!(found.files.len(), 1);
+        assert_eq!(found.files[0].1, [(2, "    Needle();".to_string())]);
+
+        // 書き込みはその場で反映する
+        let b = root.join("src/b.rs");
+        std::fs::write(&b, "needle here\n").unwrap();
+        index.update(&b, b"needle here\n");
+        let found = index.search(&root.join("src"), "needle").unwrap();
+        assert_eq!(found.files.len(), 2);
+
+        // 除外したディレクトリと隠しディレクトリは索引を使わない
+        assert!(index.search(&root.join("target"), "needle").is_none());
+        assert!(index.search(&root.join(".git"), "needle").is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+use std::path::{Path, PathBuf};
+use tracing::warn;
+use walkdir::DirEntry;
+
+use super::ignore::{self, IgnoreMatcher};
+use crate::config::SymlinkPolicy;
+use crate::i18n::tr;
+
+/// 辿るシンボリックリンクの段数の上限（ループを避ける）
+const MAX_LINK_DEPTH: usize = 40;
+
+/// 走査の失敗として一度に挙げるリンクの数
+const MAX_LISTED_LINKS: usize = 10;
+
+/// ファイルツールのシンボリックリンクの扱い（`tools.symlinks`）
+///
+/// どの方針でも、ワークスペースの中からワークスペースの外を指すリンクは辿らない。
+/// 辿るとワークスペースの外のファイルを読み書きできてしまうため
+#[derive(Debug, Clone)]
+pub struct SymlinkGuard {
+    policy: SymlinkPolicy,
+    /// ワークスペース（指定されたままのパスと、リンクを解決したパス）
+    workspace: PathBuf,
+    root: PathBuf,
+}
+
+impl SymlinkGuard {
+    pub fn new(policy: SymlinkPolicy, workspace: &Path) -> Self {
+        let workspace = std::path::absolute(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+        Self {
+            policy,
+            root: workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.clone()),
+            workspace,
+        }
+    }
+
+    /// ツールに渡されたパスを使ってよいか調べる（だめな場合はモデルに返すエラー）
+    ///
+    /// ワークスペースの中のパスは途中のディレクトリも、外のパスは最後の要素だけを調べる
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        let Some(link) = self.first_link(path) else {
+            return Ok(());
+        };
+        match self.policy {
+            // 途中のリンクの先にさらにリンクがあってもよいよう、パス全体を解決して調べる
+            SymlinkPolicy::Follow => self.check_target(path),
+            SymlinkPolicy::Skip | SymlinkPolicy::Error => Err(tr!(
+                "{} is a symbolic link, and symbolic links are not followed (tools.symlinks = \"{}\")",
+                "{} はシンボリックリンクで、シンボリックリンクは辿らない設定です（tools.symlinks = \"{}\"）",
+                link.display(),
+                self.policy_name()
+            )),
+        }
+    }
+
+    /// `root` 配下を走査する（除外パターンに一致するもの、`include_hidden` でなければ隠しファイル、
+    /// リンクの扱いに従って飛ばすものは配下ごと除く）
+    ///
+    /// `error` の方針でリンクがあった場合はモデルに返すエラーになる
+    pub fn walk(
+        &self,
+        root: &Path,
+        ignore: &IgnoreMatcher,
+        include_hidden: bool,
+    ) -> Result<Vec<DirEntry>, String> {
+        let mut refused = Vec::new();
+        let walker = walkdir::WalkDir::new(root)
+            .follow_links(self.policy == SymlinkPolicy::Follow)
+            .into_iter()
+            .filter_entry(|e| {
+                if e.depth() == 0 {
+                    return true;
+                }
+                if ignore.is_ignored(e.path())
+                    || (!include_hidden && ignore::is_hidden(e.file_name()))
+                {
+                    return false;
+                }
+                if e.path_is_symlink() && !matches!(self.check_entry(e.path()), Ok(true)) {
+                    refused.push(e.path().to_path_buf());
+                    return false;
+                }
+                true
+            });
+        let mut entries = Vec::new();
+        for entry_result in walker {
+            match entry_result {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Failed to read entry: {}", e),
+            }
+        }
+
+        if self.policy == SymlinkPolicy::Error && !refused.is_empty() {
+            let links: Vec<String> = refused
+                .iter()
+                .take(MAX_LISTED_LINKS)
+                .map(|link| link.display().to_string())
+                .collect();
+            return Err(tr!(
+                "Found {} symbolic links, which are not allowed (tools.symlinks = \"error\"): {}",
+                "シンボリックリンクが {} 件あり、許可されていません（tools.symlinks = \"error\"）: {}",
+                refused.len(),
+                links.join(", ")
+            ));
+        }
+        for link in &refused {
+            warn!("Skipping symbolic link {:?}", link);
+        }
+        Ok(entries)
+    }
+
+    /// ディレクトリの一覧で見つけたパスを含めるか（リンクでなければ常に含める）
+    ///
+    /// `skip` の方針とワークスペースの外を指すリンクは Ok(false)
+    pub fn check_entry(&self, path: &Path) -> Result<bool, String> {
+        let is_link = path.symlink_metadata().is_ok_and(|m| m.is_symlink());
+        if !is_link {
+            return Ok(true);
+        }
+        match self.policy {
+            SymlinkPolicy::Follow => Ok(self.check_target(path).is_ok()),
+            SymlinkPolicy::Skip => Ok(false),
+            SymlinkPolicy::Error => Err(tr!(
+                "{} is a symbolic link, which is not allowed (tools.symlinks = \"error\")",
+                "{} はシンボリックリンクで、許可されていません（tools.symlinks = \"error\"）",
+                path.display()
+            )),
+        }
+    }
+
+    /// ワークスペースの中のパスが、リンクを全て解決してもワークスペースの中にあるか
+    fn check_target(&self, path: &Path) -> Result<(), String> {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        if !self.in_workspace(&path) {
+            return Ok(());
+        }
+        let target = resolve(&path, 0);
+        if target.starts_with(&self.root) {
+            Ok(())
+        } else {
+            Err(tr!(
+                "{} leads through a symbolic link to {}, outside the workspace; links out of the workspace are not followed",
+                "{} はシンボリックリンクを通じてワークスペースの外の {} を指しています。ワークスペースの外へのリンクは辿りません",
+                path.display(),
+                target.display()
+            ))
+        }
+    }
+
+    /// パスの中で最初のシンボリックリンク（途中のディレクトリを含む）
+    fn first_link(&self, path: &Path) -> Option<PathBuf> {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let is_link = |path: &Path| path.symlink_metadata().is_ok_and(|m| m.is_symlink());
+        let base = [&self.workspace, &self.root]
+            .into_iter()
+            .find(|base| path.starts_with(base));
+        let Some(base) = base else {
+            return is_link(&path).then_some(path);
+        };
+        let mut current = base.clone();
+        for component in path.strip_prefix(base).ok()?.components() {
+            current.push(component);
+            if is_link(&current) {
+                return Some(current);
+            }
+        }
+        None
+    }
+
+    fn in_workspace(&self, path: &Path) -> bool {
+        path.starts_with(&self.workspace) || path.starts_with(&self.root)
+    }
+
+    fn policy_name(&self) -> &'static str {
+        match self.policy {
+            SymlinkPolicy::Follow => "follow",
+            SymlinkPolicy::Skip => "skip",
+            SymlinkPolicy::Error => "error",
+        }
+    }
+}
+
+/// リンクを解決した実際のパス（まだ存在しないファイルやリンク先のないリンクも解決する）
+fn resolve(path: &Path, depth: usize) -> PathBuf {
+    if let Ok(resolved) = path.canonicalize() {
+        return resolved;
+    }
+    if depth < MAX_LINK_DEPTH {
+        if let Ok(target) = std::fs::read_link(path) {
+            let parent = path.parent().unwrap_or(Path::new("/"));
+            return resolve(&parent.join(target), depth + 1);
+        }
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if depth < MAX_LINK_DEPTH => {
+            resolve(parent, depth + 1).join(name)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempWorkspace;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_refuses_links_out_of_the_workspace() {
+        let outside = TempWorkspace::new().file("secret.txt", "secret");
+        let workspace = TempWorkspace::new()
+            .file("src/lib.rs", "")
+            .file("notes.txt", "");
+        let root = workspace.root();
+        symlink(outside.path("secret.txt"), root.join("secret.txt")).unwrap();
+        symlink(outside.root(), root.join("outside")).unwrap();
+        symlink(root.join("notes.txt"), root.join("src/notes.txt")).unwrap();
+        symlink(root.join("missing"), root.join("dangling")).unwrap();
+        let ignore = IgnoreMatcher::new(&[], root).unwrap();
+
+        let follow = SymlinkGuard::new(SymlinkPolicy::Follow, root);
+        assert!(follow.check(&root.join("src/lib.rs")).is_ok());
+        assert!(follow.check(&root.join("src/notes.txt")).is_ok());
+        assert!(follow.check(&root.join("secret.txt")).is_err());
+        assert!(follow.check(&root.join("outside/new.txt")).is_err());
+        assert!(follow.check(&root.join("dangling")).is_ok());
+        // ワークスペースの外のパスはそのまま使える
+        assert!(follow.check(&outside.path("secret.txt")).is_ok());
+        let names = |entries: Vec<DirEntry>| {
+            let mut names: Vec<String> = entries
+                .iter()
+                .map(|e| e.path().strip_prefix(root).unwrap().display().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(follow.walk(root, &ignore, false).unwrap()),
+            ["", "notes.txt", "src", "src/lib.rs", "src/notes.txt"]
+        );
+
+        let skip = SymlinkGuard::new(SymlinkPolicy::Skip, root);
+        assert!(skip.check(&root.join("src/notes.txt")).is_err());
+        assert_eq!(
+            names(skip.walk(root, &ignore, false).unwrap()),
+            ["", "notes.txt", "src", "src/lib.rs"]
+        );
+
+        let error = SymlinkGuard::new(SymlinkPolicy::Error, root);
+        assert!(error.check(&root.join("src/lib.rs")).is_ok());
+        assert!(error
+            .walk(root, &ignore, false)
+            .unwrap_err()
+            .contains("4 symbolic links"));
+
+        // ワークスペースの中のリンクの先にある、外へのリンク
+        std::fs::create_dir(root.join("b")).unwrap();
+        symlink(root.join("b"), root.join("a")).unwrap();
+        symlink(outside.root(), root.join("b/c")).unwrap();
+        assert!(follow.check(&root.join("a/c/secret.txt")).is_err());
+        assert!(follow.check(&root.join("a/c/new.txt")).is_err());
+        assert!(follow.check(&root.join("a/d.txt")).is_ok());
+    }
+}
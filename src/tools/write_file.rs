@@ -1,95 +1,136 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use serde_json::json;
-use std::io::{self, Write as IoWrite};
 use std::path::Path;
+use std::sync::Arc;
 use tracing::{debug, warn};
 
-use crate::anthropic::{Tool, ToolHandler, ToolResult};
-
-/// ユーザーに確認を求める
-///
-/// # Returns
-/// - `Ok(true)` - ユーザーが 'y' または 'Y' を入力
-/// - `Ok(false)` - ユーザーがそれ以外を入力
-/// - `Err(_)` - 入力の読み取りに失敗
-fn prompt_user_confirmation(message: &str) -> Result<bool> {
-    // 1. プロンプトを表示
-    print!("{} [y/N]: ", message);
-
-    // 2. バッファをフラッシュ（即座に表示）
-    io::stdout().flush().context("Failed to flush stdout")?;
-
-    // 3. ユーザー入力を読み取り
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .context("Failed to read user input")?;
-
-    // 4. 入力を検証（'y' または 'Y' のみ許可）
-    Ok(input.trim().to_lowercase() == "y")
-}
+use super::encoding::{self, TextEncoding};
+use super::file_tracker::{self, FileTracker};
+use super::symlinks::SymlinkGuard;
+use crate::anthropic::{ToolHandler, ToolResult};
+use crate::i18n::tr;
+use crate::platform::LineEnding;
+use crate::ui::{diff, Confirmer};
 
 /// writeFile ツールの引数
-#[derive(Debug, Deserialize)]
-struct WriteFileArgs {
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct WriteFileArgs {
     path: String,
     content: String,
 }
 
 /// writeFile ツールの実装
-pub struct WriteFileTool;
+pub struct WriteFileTool {
+    tracker: FileTracker,
+    confirmer: Arc<Confirmer>,
+    symlinks: SymlinkGuard,
+}
 
 impl WriteFileTool {
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// ツールのスキーマ定義を返す
-    pub fn schema() -> Tool {
-        Tool {
-            name: "writeFile".to_string(),
-            description: "指定されたパスに新しいファイルを作成し、内容を書き込みます。親ディレクトリが存在しない場合は自動で作成します。既存ファイルが存在する場合は確認を求めます。".to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "path": {
-                        "type": "string",
-                        "description": "作成するファイルの完全なパス（例: test.txt, src/new_file.rs）"
-                    },
-                    "content": {
-                        "type": "string",
-                        "description": "ファイルに書き込む内容"
-                    }
-                },
-                "required": ["path", "content"]
-            }),
+    pub fn new(tracker: FileTracker, confirmer: Arc<Confirmer>, symlinks: SymlinkGuard) -> Self {
+        Self {
+            tracker,
+            confirmer,
+            symlinks,
         }
     }
 }
 
 #[async_trait]
 impl ToolHandler for WriteFileTool {
-    async fn execute(&self, input: serde_json::Value) -> Result<ToolResult> {
-        debug!("Executing writeFile tool with input: {:?}", input);
+    type Args = WriteFileArgs;
+
+    const NAME: &'static str = "writeFile";
 
-        // 引数をパース
-        let args: WriteFileArgs =
-            serde_json::from_value(input).context("Failed to parse writeFile arguments")?;
+    const MODIFIES_FILES: bool = true;
+
+    fn description() -> String {
+        tr!(
+            "Creates a new file at the given path and writes the content. Missing parent directories are created. Asks for confirmation when the file already exists.",
+            "指定されたパスに新しいファイルを作成し、内容を書き込みます。親ディレクトリが存在しない場合は自動で作成します。既存ファイルが存在する場合は確認を求めます。"
+        )
+    }
+
+    fn field_descriptions() -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "path",
+                tr!(
+                    "Full path of the file to create (e.g. test.txt, src/new_file.rs)",
+                    "作成するファイルの完全なパス（例: test.txt, src/new_file.rs）"
+                ),
+            ),
+            (
+                "content",
+                tr!("Content to write to the file", "ファイルに書き込む内容"),
+            ),
+        ]
+    }
+
+    async fn execute(&self, args: WriteFileArgs) -> Result<ToolResult> {
+        debug!("Executing writeFile tool with input: {:?}", args);
 
         debug!("Writing to file: {}", args.path);
 
         let path = Path::new(&args.path);
+        if let Err(error) = self.symlinks.check(path) {
+            warn!("writeFile: {}", error);
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(error),
+            });
+        }
+        let mut content = args.content.clone();
+        let mut text_encoding = TextEncoding::UTF_8;
+        // 確認の前に読んだ既存の内容（確認中に変更されていないかを書き込み前に調べる）
+        let mut existing = None;
 
         if path.exists() {
             warn!("File already exists: {}", args.path);
 
-            let message = format!(
+            // 既存の内容との差分を表示（上書きでも既存ファイルの改行コードと文字コードは保つ）
+            let current = match tokio::fs::read(path).await {
+                Ok(bytes) => {
+                    let current = encoding::decode(&bytes, self.tracker.encoding(path));
+                    existing = Some(bytes);
+                    current
+                }
+                Err(e) => {
+                    debug!("Failed to read existing file for diff: {}", e);
+                    None
+                }
+            };
+            let preview = match current {
+                Some((current, current_encoding)) => {
+                    content = LineEnding::detect(&current).apply(&args.content);
+                    text_encoding = current_encoding;
+                    diff::render_diff(&args.path, &current, &content)
+                }
+                None => String::new(),
+            };
+            if let Err(e) = text_encoding.encode(&content) {
+                return Ok(ToolResult {
+                    content: String::new(),
+                    error: Some(tr!(
+                        "Cannot keep the file's encoding: {}",
+                        "ファイルの文字コードを保てません: {}",
+                        e
+                    )),
+                });
+            }
+
+            let message = tr!(
+                "File '{}' already exists. Overwrite it?",
                 "ファイル '{}' は既に存在します。上書きしますか？",
                 args.path
             );
-            match prompt_user_confirmation(&message) {
+            match self
+                .confirmer
+                .confirm("writeFile", &args.path, &message, &preview)
+                .await
+            {
                 Ok(true) => {
                     debug!("User confirmed overwrite");
                 }
@@ -97,20 +138,36 @@ impl ToolHandler for WriteFileTool {
                     debug!("User cancelled");
                     return Ok(ToolResult {
                         content: String::new(),
-                        error: Some("ユーザーによりキャンセルされました".to_string()),
+                        error: Some(tr!(
+                            "Cancelled by the user",
+                            "ユーザーによりキャンセルされました"
+                        )),
                     });
                 }
                 Err(e) => {
                     return Ok(ToolResult {
                         content: String::new(),
-                        error: Some(format!("ユーザー入力の読み取りに失敗しました: {}", e)),
+                        error: Some(tr!(
+                            "Failed to read the user's answer: {}",
+                            "ユーザー入力の読み取りに失敗しました: {}",
+                            e
+                        )),
                     });
                 }
             }
         } else {
-            // 新規ファイルの場合も確認
-            let message = format!("ファイル '{}' を作成しますか？", args.path);
-            match prompt_user_confirmation(&message) {
+            // 新規ファイルの場合も内容を表示して確認
+            let preview = diff::render_preview(&args.path, &args.content);
+            let message = tr!(
+                "Create file '{}'?",
+                "ファイル '{}' を作成しますか？",
+                args.path
+            );
+            match self
+                .confirmer
+                .confirm("writeFile", &args.path, &message, &preview)
+                .await
+            {
                 Ok(true) => {
                     debug!("User confirmed file creation");
                 }
@@ -118,18 +175,34 @@ impl ToolHandler for WriteFileTool {
                     debug!("User cancelled");
                     return Ok(ToolResult {
                         content: String::new(),
-                        error: Some("ユーザーによりキャンセルされました".to_string()),
+                        error: Some(tr!(
+                            "Cancelled by the user",
+                            "ユーザーによりキャンセルされました"
+                        )),
                     });
                 }
                 Err(e) => {
                     return Ok(ToolResult {
                         content: String::new(),
-                        error: Some(format!("ユーザー入力の読み取りに失敗しました: {}", e)),
+                        error: Some(tr!(
+                            "Failed to read the user's answer: {}",
+                            "ユーザー入力の読み取りに失敗しました: {}",
+                            e
+                        )),
                     });
                 }
             }
         }
 
+        // 確認を待つ間に作られたり変更されたりしたファイルは上書きしない
+        if tokio::fs::read(path).await.ok() != existing {
+            warn!("File changed while waiting for confirmation: {}", args.path);
+            return Ok(ToolResult {
+                content: String::new(),
+                error: Some(file_tracker::modified_during_confirmation(&args.path)),
+            });
+        }
+
         // 親ディレクトリの作成
         if let Some(parent) = path.parent() {
             if !parent.exists() {
@@ -141,21 +214,28 @@ impl ToolHandler for WriteFileTool {
                     Err(e) => {
                         return Ok(ToolResult {
                             content: String::new(),
-                            error: Some(format!("ディレクトリの作成に失敗しました: {}", e)),
+                            error: Some(tr!(
+                                "Failed to create the directory: {}",
+                                "ディレクトリの作成に失敗しました: {}",
+                                e
+                            )),
                         });
                     }
                 }
             }
         }
         // ファイル書き込み
-        match tokio::fs::write(&path, &args.content).await {
+        let bytes = text_encoding.encode(&content)?;
+        match tokio::fs::write(&path, &bytes).await {
             Ok(_) => {
                 debug!("File written successfully: {}", args.path);
+                self.tracker.record_write(path, &bytes);
                 Ok(ToolResult {
-                    content: format!(
+                    content: tr!(
+                        "Created file '{}' ({} bytes)",
                         "ファイル '{}' を作成しました（{}バイト）",
                         args.path,
-                        args.content.len()
+                        bytes.len()
                     ),
                     error: None,
                 })
@@ -164,7 +244,11 @@ impl ToolHandler for WriteFileTool {
                 warn!("Failed to write file {}: {}", args.path, e);
                 Ok(ToolResult {
                     content: String::new(),
-                    error: Some(format!("ファイルの書き込みに失敗しました: {}", e)),
+                    error: Some(tr!(
+                        "Failed to write the file: {}",
+                        "ファイルの書き込みに失敗しました: {}",
+                        e
+                    )),
                 })
             }
         }
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+use super::{notify, progress};
+
+use crate::config::ApprovalPolicy;
+use crate::i18n::tr;
+use crate::platform;
+use crate::policy::ApprovalEngine;
+use crate::webhooks;
+
+/// ユーザー確認を一元管理する
+///
+/// 標準入出力のどれかが端末でない場合（パイプや CI など）は入力待ちでブロックせず、
+/// 設定されたデフォルト値を返す。
+/// 'a'（常に許可）/ 'd'（常に拒否）の回答はツールごとに実行中ずっと記憶する。
+/// 承認ポリシーが allow / deny の場合は確認せずに決定する
+pub struct Confirmer {
+    interactive: bool,
+    policy: ApprovalEngine,
+    non_interactive_default: bool,
+    /// ツール名ごとのセッション中の決定
+    session_decisions: Mutex<HashMap<String, bool>>,
+    /// 端末以外の UI（TUI など）で確認する場合の問い合わせ先
+    prompt_handler: Option<PromptHandler>,
+}
+
+/// 確認の問い合わせ内容
+#[derive(Debug, Clone)]
+pub struct ConfirmRequest {
+    pub tool: String,
+    pub path: String,
+    pub message: String,
+    /// 差分または新規ファイルのプレビュー
+    pub preview: String,
+}
+
+/// 確認を UI に問い合わせ、回答を受け取るチャネルを返す
+pub type PromptHandler = Arc<dyn Fn(ConfirmRequest) -> oneshot::Receiver<Answer> + Send + Sync>;
+
+/// 確認プロンプトへの回答
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    Always,
+    DenyAll,
+}
+
+impl Answer {
+    pub fn parse(input: &str) -> Self {
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => Answer::Yes,
+            "a" | "always" => Answer::Always,
+            "d" | "deny" => Answer::DenyAll,
+            _ => Answer::No,
+        }
+    }
+}
+
+impl Confirmer {
+    /// 新しい Confirmer を作成
+    ///
+    /// `non_interactive_default` は非対話の実行（`ui::is_interactive`）で返す値、
+    /// `policy` はツールとパスごとの確認の要否、
+    /// `prompt_handler` は標準入力の代わりに確認を問い合わせる先
+    pub fn new(
+        non_interactive_default: bool,
+        policy: ApprovalEngine,
+        prompt_handler: Option<PromptHandler>,
+    ) -> Self {
+        Self {
+            interactive: super::is_interactive(),
+            policy,
+            non_interactive_default,
+            session_decisions: Mutex::new(HashMap::new()),
+            prompt_handler,
+        }
+    }
+
+    /// ユーザーに確認を求める
+    ///
+    /// # Returns
+    /// - `Ok(true)` - ユーザーが 'y' / 'a' を入力、または以前 `tool` に 'a' と回答済み
+    /// - `Ok(false)` - ユーザーがそれ以外を入力、または以前 `tool` に 'd' と回答済み
+    /// - `Err(_)` - 入力の読み取りに失敗
+    pub async fn confirm(
+        &self,
+        tool: &str,
+        path: &str,
+        message: &str,
+        preview: &str,
+    ) -> Result<bool> {
+        // 端末ではポリシーで自動決定される場合も変更内容を表示する
+        if self.prompt_handler.is_none() {
+            progress::hide();
+            eprint!("\n{}", preview);
+        }
+
+        match self.policy.decide(tool, path) {
+            ApprovalPolicy::Allow => {
+                info!("Approval policy allows {} on {} without prompt", tool, path);
+                return Ok(true);
+            }
+            ApprovalPolicy::Deny => {
+                warn!("Approval policy denies {} on {}", tool, path);
+                return Ok(false);
+            }
+            ApprovalPolicy::Ask => {}
+        }
+
+        if let Some(&decision) = self.session_decisions.lock().unwrap().get(tool) {
+            debug!("Using session decision for {}: {}", tool, decision);
+            return Ok(decision);
+        }
+
+        if self.prompt_handler.is_some() || self.interactive {
+            notify::notify("Confirmation needed", message);
+            webhooks::confirmation_needed(tool, path, message).await;
+        }
+
+        if let Some(handler) = &self.prompt_handler {
+            let request = ConfirmRequest {
+                tool: tool.to_string(),
+                path: path.to_string(),
+                message: message.to_string(),
+                preview: preview.to_string(),
+            };
+            let answer = handler(request)
+                .await
+                .context("Confirmation was abandoned")?;
+            return Ok(self.apply_answer(tool, answer));
+        }
+
+        if !self.interactive {
+            warn!(
+                "Not running in a terminal; answering {} to: {}",
+                if self.non_interactive_default {
+                    "yes"
+                } else {
+                    "no"
+                },
+                message
+            );
+            return Ok(self.non_interactive_default);
+        }
+
+        let message = message.to_string();
+        // 標準入力の読み取りは非同期ランタイムをブロックしないよう専用スレッドで行う
+        let input = tokio::task::spawn_blocking(move || read_answer(&message))
+            .await
+            .context("Confirmation task panicked")??;
+
+        Ok(self.apply_answer(tool, Answer::parse(&input)))
+    }
+
+    /// 回答を決定に変換する（'a' / 'd' はセッション中記憶する）
+    fn apply_answer(&self, tool: &str, answer: Answer) -> bool {
+        debug!("User answered {:?} for {}", answer, tool);
+
+        match answer {
+            Answer::Yes => true,
+            Answer::No => false,
+            Answer::Always | Answer::DenyAll => {
+                let decision = answer == Answer::Always;
+                self.session_decisions
+                    .lock()
+                    .unwrap()
+                    .insert(tool.to_string(), decision);
+                decision
+            }
+        }
+    }
+}
+
+fn read_answer(message: &str) -> Result<String> {
+    platform::prompt_line(&format!(
+        "{} {}: ",
+        message,
+        tr!(
+            "[y/N/a(always allow this session)/d(always deny)]",
+            "[y/N/a(このセッション中は常に許可)/d(常に拒否)]"
+        )
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answer_parse() {
+        assert_eq!(Answer::parse("Y\n"), Answer::Yes);
+        assert_eq!(Answer::parse("a"), Answer::Always);
+        assert_eq!(Answer::parse("D"), Answer::DenyAll);
+        assert_eq!(Answer::parse(""), Answer::No);
+        assert_eq!(Answer::parse("nope"), Answer::No);
+    }
+}
@@ -0,0 +1,94 @@
+use similar::{ChangeTag, TextDiff};
+
+use super::style;
+use crate::i18n::tr;
+
+/// 新規ファイルのプレビューで表示する最大行数
+const PREVIEW_LINES: usize = 20;
+
+/// 既存の内容と新しい内容の unified diff を色付きで生成する
+pub fn render_diff(path: &str, old: &str, new: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let mut out = String::new();
+
+    out.push_str(&style::bold(&format!("--- a/{}\n+++ b/{}", path, path)));
+    out.push('\n');
+
+    let mut has_changes = false;
+    for group in diff.grouped_ops(3) {
+        has_changes = true;
+        let first = &group[0];
+        let last = &group[group.len() - 1];
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        out.push_str(&style::cyan(&format!(
+            "@@ -{},{} +{},{} @@",
+            old_range.start + 1,
+            old_range.len(),
+            new_range.start + 1,
+            new_range.len()
+        )));
+        out.push('\n');
+
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let line = change.to_string_lossy();
+                let line = line.trim_end_matches(['\n', '\r']);
+                let rendered = match change.tag() {
+                    ChangeTag::Delete => style::red(&format!("-{}", line)),
+                    ChangeTag::Insert => style::green(&format!("+{}", line)),
+                    ChangeTag::Equal => format!(" {}", line),
+                };
+                out.push_str(&rendered);
+                out.push('\n');
+            }
+        }
+    }
+
+    if !has_changes {
+        out.push_str(&style::dim(&tr!("(no changes)", "(変更なし)")));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// 新規ファイルの先頭部分を色付きで生成する
+pub fn render_preview(path: &str, content: &str) -> String {
+    let mut out = String::new();
+    out.push_str(&style::bold(&tr!(
+        "+++ b/{} (new file)",
+        "+++ b/{} (新規ファイル)",
+        path
+    )));
+    out.push('\n');
+
+    let total = content.lines().count();
+    for line in content.lines().take(PREVIEW_LINES) {
+        out.push_str(&style::green(&format!("+{}", line)));
+        out.push('\n');
+    }
+    if total > PREVIEW_LINES {
+        out.push_str(&style::dim(&tr!(
+            "... {} more lines",
+            "... 他 {} 行",
+            total - PREVIEW_LINES
+        )));
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_diff_marks_changed_lines() {
+        let rendered = render_diff("a.rs", "one\ntwo\nthree\n", "one\n2\nthree\n");
+        assert!(rendered.contains("@@ -1,3 +1,3 @@"));
+        assert!(rendered.contains("-two"));
+        assert!(rendered.contains("+2"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+use super::style;
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes
+            .remove("base16-ocean.dark")
+            .expect("bundled syntect theme")
+    })
+}
+
+/// コードを言語（コードブロックの言語名または拡張子）に応じてハイライトする
+///
+/// 色が無効な場合や言語が不明な場合はそのまま返す
+pub fn highlight(code: &str, lang: &str) -> String {
+    if !style::color_enabled() {
+        return code.to_string();
+    }
+    let syntaxes = syntax_set();
+    let Some(syntax) = syntaxes
+        .find_syntax_by_token(lang)
+        .or_else(|| syntaxes.find_syntax_by_extension(lang))
+    else {
+        return code.to_string();
+    };
+
+    let mut highlighter = HighlightLines::new(syntax, theme());
+    let mut highlighted = String::new();
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, syntaxes) {
+            Ok(ranges) => highlighted.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => return code.to_string(),
+        }
+    }
+    highlighted.push_str("\x1b[0m");
+    highlighted
+}
+
+/// コードを言語に応じてハイライトした HTML の `<pre>` にする（言語が不明ならエスケープだけ）
+///
+/// 端末の色の設定とは関係なく、書き出した HTML で使う
+pub fn highlight_html(code: &str, lang: &str) -> String {
+    let syntaxes = syntax_set();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .or_else(|| syntaxes.find_syntax_by_extension(lang));
+    if let Some(html) =
+        syntax.and_then(|syntax| highlighted_html_for_string(code, syntaxes, syntax, theme()).ok())
+    {
+        return html;
+    }
+    format!("<pre>{}</pre>\n", escape_html(code))
+}
+
+/// HTML の特殊文字をエスケープする
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Markdown の断片（通常のテキストかコードブロック）
+#[derive(Debug, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(String),
+    Code { lang: &'a str, code: String },
+}
+
+/// Markdown をコードブロックとそれ以外に分割する（閉じられていないブロックは末尾まで）
+pub fn split_code_blocks(markdown: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut code: Option<(&str, &str, String)> = None;
+
+    for line in markdown.lines() {
+        match &mut code {
+            Some((fence, lang, body)) => {
+                if line.trim_start().starts_with(*fence) && line.trim().trim_matches('`').is_empty()
+                {
+                    segments.push(Segment::Code {
+                        lang,
+                        code: std::mem::take(body),
+                    });
+                    code = None;
+                } else {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+            }
+            None => {
+                let trimmed = line.trim_start();
+                let ticks = trimmed.chars().take_while(|&c| c == '`').count();
+                if ticks >= 3 {
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    code = Some((&trimmed[..ticks], trimmed[ticks..].trim(), String::new()));
+                } else {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+            }
+        }
+    }
+
+    if let Some((_, lang, body)) = code {
+        segments.push(Segment::Code { lang, code: body });
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_code_blocks() {
+        let segments = split_code_blocks("Intro\n```rust\nfn main() {}\n```\nOutro");
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("Intro\n".to_string()),
+                Segment::Code {
+                    lang: "rust",
+                    code: "fn main() {}\n".to_string()
+                },
+                Segment::Text("Outro\n".to_string()),
+            ]
+        );
+    }
+}
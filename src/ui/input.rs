@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use rustyline::error::ReadlineError;
+use rustyline::{Cmd, DefaultEditor, KeyCode, KeyEvent, Modifiers};
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// 入力の読み取り結果
+pub enum Input {
+    Line(String),
+    /// Ctrl+C（入力中の行だけを取り消す）
+    Interrupted,
+    /// Ctrl+D または入力の終端
+    Eof,
+}
+
+/// 複数行の入力を囲む区切り
+const TRIPLE_QUOTE: &str = "\"\"\"";
+
+/// 対話モードの行入力（矢印キーでの履歴、Ctrl+R の履歴検索）
+///
+/// 複数行の入力は `"""` で囲むか、Esc+Enter（Alt+Enter）で改行する。
+/// 履歴は ~/.codex/history に保存し、次回の起動時に読み込む
+pub struct LineEditor {
+    /// 読み取り中は専用スレッドへ移すため Option で持つ
+    editor: Option<DefaultEditor>,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> Result<Self> {
+        let mut editor = DefaultEditor::new().context("Failed to initialize line editor")?;
+        editor.bind_sequence(KeyEvent(KeyCode::Enter, Modifiers::ALT), Cmd::Newline);
+        let history = Config::codex_home().ok().map(|home| home.join("history"));
+        if let Some(path) = &history {
+            // 初回は履歴ファイルがないので失敗は無視する
+            let _ = editor.load_history(path);
+        }
+        Ok(Self {
+            editor: Some(editor),
+            history,
+        })
+    }
+
+    /// プロンプトを表示して 1 つのメッセージを読み込む（`"""` で始まる場合は閉じるまで続ける）
+    pub async fn read_line(&mut self, prompt: &str) -> Result<Input> {
+        let first = match self.read_raw(prompt).await? {
+            Input::Line(line) => line,
+            other => return Ok(other),
+        };
+        let Some(rest) = first.trim_start().strip_prefix(TRIPLE_QUOTE) else {
+            self.add_history(&first);
+            return Ok(Input::Line(first));
+        };
+
+        let mut lines = Vec::new();
+        let mut next = rest.to_string();
+        loop {
+            if let Some(last) = next.trim_end().strip_suffix(TRIPLE_QUOTE) {
+                lines.push(last.to_string());
+                break;
+            }
+            lines.push(next);
+            next = match self.read_raw("... ").await? {
+                Input::Line(line) => line,
+                Input::Interrupted => return Ok(Input::Interrupted),
+                // 閉じる前に入力が終わった場合はそこまでを送る
+                Input::Eof => break,
+            };
+        }
+
+        let message = lines.join("\n").trim_matches('\n').to_string();
+        self.add_history(&format!("{0}{1}{0}", TRIPLE_QUOTE, message));
+        Ok(Input::Line(message))
+    }
+
+    /// `initial` を入力済みの状態から編集したメッセージを読み込む（`/edit`）
+    pub async fn edit_line(&mut self, prompt: &str, initial: &str) -> Result<Input> {
+        let input = self.read_raw_with(prompt, initial).await?;
+        if let Input::Line(line) = &input {
+            self.add_history(line);
+        }
+        Ok(input)
+    }
+
+    /// 1 行（Esc+Enter で改行した行を含む）を読み込む
+    async fn read_raw(&mut self, prompt: &str) -> Result<Input> {
+        self.read_raw_with(prompt, "").await
+    }
+
+    async fn read_raw_with(&mut self, prompt: &str, initial: &str) -> Result<Input> {
+        let mut editor = self.editor.take().context("Line editor is busy")?;
+        let prompt = prompt.to_string();
+        let initial = initial.to_string();
+        // 入力待ちで非同期ランタイムをブロックしないよう専用スレッドで読み取る
+        let (editor, result) = tokio::task::spawn_blocking(move || {
+            let result = editor.readline_with_initial(&prompt, (&initial, ""));
+            (editor, result)
+        })
+        .await
+        .context("Input task failed")?;
+        self.editor = Some(editor);
+
+        match result {
+            Ok(line) => Ok(Input::Line(line)),
+            Err(ReadlineError::Interrupted) => Ok(Input::Interrupted),
+            Err(ReadlineError::Eof) => Ok(Input::Eof),
+            Err(e) => Err(e).context("Failed to read input"),
+        }
+    }
+
+    fn add_history(&mut self, line: &str) {
+        let (Some(editor), Some(path)) = (&mut self.editor, &self.history) else {
+            return;
+        };
+        if line.trim().is_empty() || !editor.add_history_entry(line).unwrap_or(false) {
+            return;
+        }
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(e) = editor.save_history(path) {
+            tracing::debug!("Failed to save history to {:?}: {}", path, e);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+pub mod confirm;
+pub mod diff;
+pub mod highlight;
+pub mod input;
+pub mod notify;
+pub mod progress;
+pub mod style;
+pub mod tui;
+
+pub use confirm::Confirmer;
+
+use std::io::{self, IsTerminal};
+
+/// 端末に接続された対話的な実行かを判定する
+///
+/// 標準入出力のどれかがパイプやファイルの場合（CI、`| tee` など）は
+/// 確認プロンプトが見えないか回答できないため、非対話として扱う
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal() && io::stderr().is_terminal()
+}
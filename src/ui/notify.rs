@@ -0,0 +1,34 @@
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `--notify` / `output.notify` の設定値
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 通知の有効・無効を設定する
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 端末のベルを鳴らし、デスクトップ通知を送る（無効な場合は何もしない）
+///
+/// 通知サーバーがない環境でも実行は続けられるよう、送信の失敗はログに残すだけにする
+pub fn notify(summary: &str, body: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut stderr = std::io::stderr();
+    if stderr.is_terminal() {
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+
+    if let Err(e) = notify_rust::Notification::new()
+        .appname("coding-agent-example")
+        .summary(summary)
+        .body(body)
+        .show()
+    {
+        tracing::debug!("Failed to show desktop notification: {}", e);
+    }
+}
@@ -0,0 +1,53 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 表示中のスピナー（対話的な実行の場合のみ作成する）
+static SPINNER: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// スピナーを表示し、状態メッセージを更新する
+pub fn show(message: String) {
+    if !super::is_interactive() {
+        return;
+    }
+    let mut spinner = SPINNER.lock().unwrap();
+    let bar = spinner.get_or_insert_with(|| {
+        let bar = ProgressBar::new_spinner();
+        if let Ok(style) = ProgressStyle::with_template("{spinner:.cyan} {msg} {elapsed:.dim}") {
+            bar.set_style(style);
+        }
+        bar.enable_steady_tick(Duration::from_millis(100));
+        bar
+    });
+    bar.set_message(message);
+}
+
+/// スピナーを消去する（出力の表示やユーザーへの確認の前に呼ぶ）
+pub fn hide() {
+    if let Some(bar) = SPINNER.lock().unwrap().take() {
+        bar.finish_and_clear();
+    }
+}
+
+/// スピナーを一時的に消して `f` を実行する（標準エラーへの短い出力用）
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    let bar = SPINNER.lock().unwrap().clone();
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
+
+/// スピナーと行が混ざらないようにするログ出力先
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
@@ -0,0 +1,64 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::config::ColorChoice;
+use crate::platform;
+
+const RESET: &str = "\x1b[0m";
+
+/// `--color` / `output.color` の設定値
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(0);
+
+/// 色付き出力の方針を設定する
+pub fn set_color_choice(choice: ColorChoice) {
+    let value = match choice {
+        ColorChoice::Auto => 0,
+        ColorChoice::Always => 1,
+        ColorChoice::Never => 2,
+    };
+    COLOR_CHOICE.store(value, Ordering::Relaxed);
+}
+
+/// 端末に色付き出力をしてよいかを判定する
+///
+/// auto の場合、`NO_COLOR` が設定されているか標準出力が端末でなければ無効
+/// （Windows の古いコンソールのようにエスケープシーケンスを解釈できない場合も無効）
+pub fn color_enabled() -> bool {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        1 => true,
+        2 => false,
+        _ => {
+            std::env::var_os("NO_COLOR").is_none()
+                && std::io::stdout().is_terminal()
+                && platform::ansi_supported()
+        }
+    }
+}
+
+fn paint(code: &str, text: &str) -> String {
+    if color_enabled() {
+        format!("\x1b[{}m{}{}", code, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn red(text: &str) -> String {
+    paint("31", text)
+}
+
+pub fn green(text: &str) -> String {
+    paint("32", text)
+}
+
+pub fn cyan(text: &str) -> String {
+    paint("36", text)
+}
+
+pub fn bold(text: &str) -> String {
+    paint("1", text)
+}
+
+pub fn dim(text: &str) -> String {
+    paint("2", text)
+}
@@ -0,0 +1,303 @@
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::Frame;
+use tokio::sync::oneshot;
+
+use super::confirm::{Answer, ConfirmRequest};
+use crate::anthropic::{progress_text, AgentEvent, Usage};
+use crate::pricing;
+use crate::tokens;
+
+/// 会話ペインに表示する発言
+enum Entry {
+    User(String),
+    Assistant(String),
+    Error(String),
+}
+
+/// 回答待ちの確認
+struct PendingApproval {
+    request: ConfirmRequest,
+    reply: oneshot::Sender<Answer>,
+}
+
+/// TUI の表示状態
+pub struct App {
+    model: String,
+    entries: Vec<Entry>,
+    /// ストリーミング中のアシスタントの応答
+    streaming: String,
+    activity: Vec<String>,
+    pending: Option<PendingApproval>,
+    usage: Usage,
+    status: String,
+    pub input: String,
+    pub busy: bool,
+    /// 会話ペインを末尾から何行さかのぼって表示するか
+    scroll_back: u16,
+}
+
+impl App {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            entries: Vec::new(),
+            streaming: String::new(),
+            activity: Vec::new(),
+            pending: None,
+            usage: Usage::default(),
+            status: "Ready".to_string(),
+            input: String::new(),
+            busy: false,
+            scroll_back: 0,
+        }
+    }
+
+    /// ユーザーのメッセージを追加して応答待ちにする
+    pub fn push_user(&mut self, text: &str) {
+        self.entries.push(Entry::User(text.to_string()));
+        self.busy = true;
+        self.scroll_back = 0;
+    }
+
+    /// 最終応答を確定する
+    pub fn finish(&mut self, text: String) {
+        self.streaming.clear();
+        self.entries.push(Entry::Assistant(text));
+        self.busy = false;
+        self.status = "Ready".to_string();
+    }
+
+    pub fn fail(&mut self, error: String) {
+        self.streaming.clear();
+        self.entries.push(Entry::Error(error));
+        self.busy = false;
+        self.status = "Error".to_string();
+    }
+
+    /// エージェントの進行イベントを反映する
+    pub fn handle_event(&mut self, event: AgentEvent) {
+        match event {
+            AgentEvent::IterationStart {
+                iteration,
+                max_iterations,
+            } => {
+                self.streaming.clear();
+                self.status = format!(
+                    "Iteration {}/{} — waiting for Claude…",
+                    iteration, max_iterations
+                );
+            }
+            AgentEvent::TextDelta { text } => self.streaming.push_str(&text),
+            AgentEvent::ToolCall { name, input, .. } => {
+                self.status = format!("Running {}…", name);
+                self.activity.push(format!("▶ {} {}", name, input));
+            }
+            AgentEvent::ToolProgress {
+                name,
+                percent,
+                message,
+                ..
+            } => {
+                self.status = format!("Running {}: {}", name, progress_text(percent, &message));
+            }
+            AgentEvent::ToolResult {
+                name,
+                is_error,
+                duration_ms,
+                tokens,
+                ..
+            } => {
+                let status = if is_error { "error" } else { "ok" };
+                self.activity.push(format!(
+                    "◀ {} ({}, {} ms, ~{} tokens)",
+                    name,
+                    status,
+                    duration_ms,
+                    tokens::format(tokens)
+                ));
+            }
+            AgentEvent::Usage { usage, .. } => self.usage.add(&usage),
+        }
+    }
+
+    /// 確認の問い合わせを受け付ける
+    pub fn request_approval(&mut self, request: ConfirmRequest, reply: oneshot::Sender<Answer>) {
+        self.activity
+            .push(format!("? {} {}", request.tool, request.path));
+        self.pending = Some(PendingApproval { request, reply });
+    }
+
+    pub fn awaiting_approval(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// 確認に回答する
+    pub fn answer(&mut self, answer: Answer) {
+        if let Some(pending) = self.pending.take() {
+            self.activity.push(format!(
+                "  {:?}: {} {}",
+                answer, pending.request.tool, pending.request.path
+            ));
+            let _ = pending.reply.send(answer);
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll_back = self.scroll_back.saturating_add(10);
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll_back = self.scroll_back.saturating_sub(10);
+    }
+
+    /// 画面全体を描画する
+    pub fn draw(&self, frame: &mut Frame) {
+        let [main, input] =
+            Layout::vertical([Constraint::Min(5), Constraint::Length(3)]).areas(frame.area());
+        let [conversation, side] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main);
+        let [activity, approval, usage] = Layout::vertical([
+            Constraint::Percentage(35),
+            Constraint::Min(5),
+            Constraint::Length(5),
+        ])
+        .areas(side);
+
+        self.draw_conversation(frame, conversation);
+        self.draw_activity(frame, activity);
+        self.draw_approval(frame, approval);
+        self.draw_usage(frame, usage);
+        self.draw_input(frame, input);
+    }
+
+    fn draw_conversation(&self, frame: &mut Frame, area: Rect) {
+        let mut text = Text::default();
+        let label = |name: &str, color: Color| {
+            Line::from(Span::styled(
+                name.to_string(),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ))
+        };
+        for entry in &self.entries {
+            let (name, color, body) = match entry {
+                Entry::User(body) => ("You", Color::Cyan, body),
+                Entry::Assistant(body) => ("Claude", Color::Green, body),
+                Entry::Error(body) => ("Error", Color::Red, body),
+            };
+            text.lines.push(label(name, color));
+            text.extend(Text::raw(body.clone()));
+            text.lines.push(Line::default());
+        }
+        if !self.streaming.is_empty() {
+            text.lines.push(label("Claude", Color::Green));
+            text.extend(Text::raw(self.streaming.clone()));
+        }
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Conversation ");
+        let paragraph = Paragraph::new(text).wrap(Wrap { trim: false });
+        // 末尾が見えるようにスクロール位置を計算する
+        let height = area.height.saturating_sub(2);
+        let total = paragraph.line_count(area.width.saturating_sub(2)) as u16;
+        let offset = total
+            .saturating_sub(height)
+            .saturating_sub(self.scroll_back);
+        frame.render_widget(paragraph.block(block).scroll((offset, 0)), area);
+    }
+
+    fn draw_activity(&self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        let start = self.activity.len().saturating_sub(height);
+        let lines: Vec<Line> = self.activity[start..]
+            .iter()
+            .map(|line| Line::raw(line.clone()))
+            .collect();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Tool activity ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_approval(&self, frame: &mut Frame, area: Rect) {
+        let (title, lines) = match &self.pending {
+            Some(pending) => {
+                let mut lines = vec![
+                    Line::styled(
+                        pending.request.message.trim().to_string(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Line::default(),
+                ];
+                lines.extend(pending.request.preview.lines().map(diff_line));
+                (" Approval: y / n / a(always) / d(deny all) ", lines)
+            }
+            None => (" Approval ", vec![Line::raw("No pending changes")]),
+        };
+        let style = if self.pending.is_some() {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(style)
+            .title(title);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(block)
+                .wrap(Wrap { trim: false }),
+            area,
+        );
+    }
+
+    fn draw_usage(&self, frame: &mut Frame, area: Rect) {
+        let cost = pricing::estimate_cost(&self.model, &self.usage)
+            .map(|cost| format!("${:.4}", cost))
+            .unwrap_or_else(|| "unknown".to_string());
+        let lines = vec![
+            Line::raw(format!("Model: {}", self.model)),
+            Line::raw(format!(
+                "Tokens: {} in / {} out   Cost: {}",
+                self.usage.input_tokens, self.usage.output_tokens, cost
+            )),
+            Line::raw(self.status.clone()),
+        ];
+        let block = Block::default().borders(Borders::ALL).title(" Usage ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let title = if self.busy {
+            " Message (waiting for Claude…) "
+        } else {
+            " Message (Enter: send, PgUp/PgDn: scroll, Esc: quit) "
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.input)).block(block),
+            area,
+        );
+    }
+}
+
+/// 差分の行を色分けする
+fn diff_line(line: &str) -> Line<'static> {
+    let color = if line.starts_with("@@") {
+        Some(Color::Cyan)
+    } else if line.starts_with('+') {
+        Some(Color::Green)
+    } else if line.starts_with('-') {
+        Some(Color::Red)
+    } else {
+        None
+    };
+    match color {
+        Some(color) => Line::styled(line.to_string(), Style::default().fg(color)),
+        None => Line::raw(line.to_string()),
+    }
+}
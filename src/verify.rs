@@ -0,0 +1,133 @@
+//! ファイルを変更した反復の後に実行する検査（`[verify]` / `--verify`）
+//!
+//! 失敗した場合は出力をツールの結果と一緒にモデルへ返し、編集・ビルド・修正を
+//! ユーザーの手を介さずに繰り返せるようにする
+
+use anyhow::{bail, Context, Result};
+use std::process::Stdio;
+use std::time::Duration;
+
+use crate::config::{VerifyCheck, VerifyConfig};
+use crate::i18n::tr;
+use crate::sandbox::Sandbox;
+
+/// 検査のコマンド
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    command: Vec<String>,
+    timeout: Duration,
+    max_output_bytes: usize,
+    sandbox: Sandbox,
+}
+
+impl Verifier {
+    pub fn new(check: VerifyCheck, config: &VerifyConfig, sandbox: Sandbox) -> Result<Self> {
+        let command = match check {
+            VerifyCheck::CargoCheck => ["cargo", "check", "--message-format", "short"]
+                .map(String::from)
+                .to_vec(),
+            VerifyCheck::Command if config.command.is_empty() => {
+                bail!("verify.command must be set to use the \"command\" check")
+            }
+            VerifyCheck::Command => config.command.clone(),
+        };
+        Ok(Self {
+            command,
+            timeout: Duration::from_secs(config.timeout_secs),
+            max_output_bytes: config.max_output_bytes,
+            sandbox,
+        })
+    }
+
+    /// 表示用のコマンド（例: "cargo check --message-format short"）
+    pub fn command_line(&self) -> String {
+        self.command.join(" ")
+    }
+
+    /// 検査を実行する（成功なら None、失敗ならモデルに返す報告）
+    pub async fn run(&self) -> Result<Option<String>> {
+        let (program, args) = self.command.split_first().context("Empty verify command")?;
+        let child = self
+            .sandbox
+            .command(program, args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+
+        let output = match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(output) => output.context("Failed to wait for the verify command")?,
+            Err(_) => {
+                return Ok(Some(tr!(
+                    "[Automatic check `{0}` did not finish within {1} seconds]",
+                    "[自動チェック `{0}` が {1} 秒以内に終わりませんでした]",
+                    self.command_line(),
+                    self.timeout.as_secs()
+                )))
+            }
+        };
+        if output.status.success() {
+            return Ok(None);
+        }
+
+        let mut text = String::from_utf8_lossy(&output.stderr).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stdout));
+        Ok(Some(tr!(
+            "[Automatic check `{0}` failed after your changes ({1}); fix these problems]\n{2}",
+            "[変更後の自動チェック `{0}` が失敗しました（{1}）。次の問題を修正してください]\n{2}",
+            self.command_line(),
+            output.status,
+            truncate(text.trim_end(), self.max_output_bytes)
+        )))
+    }
+}
+
+/// 先頭の `max_bytes` バイトまでに切り詰める（文字の途中では切らない）
+fn truncate(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n…", &text[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SandboxConfig;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reports_failures_with_output() {
+        let verifier = |script: &str| {
+            let config = VerifyConfig {
+                command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                max_output_bytes: 12,
+                ..VerifyConfig::default()
+            };
+            let sandbox = Sandbox::new(&SandboxConfig::default(), &std::env::temp_dir());
+            Verifier::new(VerifyCheck::Command, &config, sandbox).unwrap()
+        };
+
+        assert_eq!(verifier("true").run().await.unwrap(), None);
+        let report = verifier("echo 'error[E0308]: mismatched types' >&2; exit 101")
+            .run()
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(report.contains("failed"), "{}", report);
+        assert!(report.ends_with("error[E0308]\n…"), "{}", report);
+
+        assert!(Verifier::new(
+            VerifyCheck::Command,
+            &VerifyConfig::default(),
+            Sandbox::new(&SandboxConfig::default(), &std::env::temp_dir())
+        )
+        .is_err());
+    }
+}
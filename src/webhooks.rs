@@ -0,0 +1,191 @@
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::anthropic::{ContentBlock, ConversationResult, Message, MessageContent};
+use crate::config::{WebhookConfig, WebhookEvent};
+use crate::output;
+
+/// webhook へのリクエストのタイムアウト
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// ペイロードに含める要約の最大文字数
+const MAX_SUMMARY_CHARS: usize = 2000;
+
+struct Webhooks {
+    hooks: Vec<WebhookConfig>,
+    workspace: PathBuf,
+    client: reqwest::Client,
+}
+
+/// 設定された webhook（`Agent::new` で設定する）
+static WEBHOOKS: Mutex<Option<Webhooks>> = Mutex::new(None);
+
+/// 実行の開始・完了・失敗と確認待ちで呼び出す webhook を設定する
+pub fn configure(hooks: &[WebhookConfig], workspace: &Path) {
+    let webhooks = (!hooks.is_empty()).then(|| Webhooks {
+        hooks: hooks.to_vec(),
+        workspace: workspace.to_path_buf(),
+        client: reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default(),
+    });
+    *WEBHOOKS.lock().unwrap() = webhooks;
+}
+
+/// 実行の開始
+pub async fn run_started(model: &str, conversation: &[Message]) {
+    let prompt = conversation
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(message_text)
+        .unwrap_or_default();
+    let prompt = truncate(&prompt);
+    send(
+        WebhookEvent::Started,
+        format!("Run started ({}): {}", model, first_line(&prompt)),
+        json!({ "model": model, "prompt": prompt }),
+    )
+    .await;
+}
+
+/// 実行の完了または失敗
+pub async fn run_finished(model: &str, result: &anyhow::Result<ConversationResult>) {
+    match result {
+        Ok(result) => {
+            let summary = truncate(&output::final_text(result));
+            let cost = result.cost_usd();
+            let mut text = format!(
+                "Run completed in {} iterations ({}{})",
+                result.iterations,
+                model,
+                cost.map(|cost| format!(", ${:.4}", cost))
+                    .unwrap_or_default()
+            );
+            if let Some(limit) = result.timed_out {
+                text.push_str(&format!("; stopped early: {}", limit));
+            }
+            if !summary.is_empty() {
+                text.push_str(&format!("\n{}", summary));
+            }
+            send(
+                WebhookEvent::Completed,
+                text,
+                json!({
+                    "model": model,
+                    "summary": summary,
+                    "iterations": result.iterations,
+                    "usage": result.usage,
+                    "cost_usd": cost,
+                    "timed_out": result.timed_out,
+                }),
+            )
+            .await;
+        }
+        Err(e) => {
+            let error = format!("{:#}", e);
+            send(
+                WebhookEvent::Failed,
+                format!("Run failed ({}): {}", model, error),
+                json!({ "model": model, "error": error }),
+            )
+            .await;
+        }
+    }
+}
+
+/// ユーザーの確認待ち
+pub async fn confirmation_needed(tool: &str, path: &str, message: &str) {
+    send(
+        WebhookEvent::Confirmation,
+        format!("Confirmation needed: {}", message),
+        json!({ "tool": tool, "path": path, "message": message }),
+    )
+    .await;
+}
+
+/// `event` を購読している webhook へ送る（失敗は実行を止めずにログに残す）
+///
+/// ペイロードは共通の項目（event / text / workspace / timestamp）に `fields` を加えたもの。
+/// `text` があるので Slack の Incoming Webhook にもそのまま送れる
+async fn send(event: WebhookEvent, text: String, fields: Value) {
+    let (targets, workspace, client) = {
+        let webhooks = WEBHOOKS.lock().unwrap();
+        let Some(webhooks) = webhooks.as_ref() else {
+            return;
+        };
+        let targets: Vec<String> = webhooks
+            .hooks
+            .iter()
+            .filter(|hook| hook.events.contains(&event))
+            .map(|hook| hook.url.clone())
+            .collect();
+        (targets, webhooks.workspace.clone(), webhooks.client.clone())
+    };
+    if targets.is_empty() {
+        return;
+    }
+
+    let mut payload = json!({
+        "event": event,
+        "text": text,
+        "workspace": workspace,
+        "timestamp": chrono::Utc::now(),
+    });
+    if let (Some(payload), Value::Object(fields)) = (payload.as_object_mut(), fields) {
+        payload.extend(fields);
+    }
+    for url in targets {
+        let result = client
+            .post(&url)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            tracing::warn!("Failed to call webhook: {}", e.without_url());
+        }
+    }
+}
+
+fn message_text(message: &Message) -> String {
+    match &message.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(MAX_SUMMARY_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_keeps_char_boundaries() {
+        assert_eq!(truncate("  done \n"), "done");
+        let long = "あ".repeat(MAX_SUMMARY_CHARS + 1);
+        let truncated = truncate(&long);
+        assert_eq!(truncated.chars().count(), MAX_SUMMARY_CHARS + 1);
+        assert!(truncated.ends_with('…'));
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use crate::platform;
+use crate::ui::{self, style};
+
+/// `--isolated` の実行で作る git worktree とブランチ
+struct Worktree {
+    /// 元のリポジトリの作業ツリーのルート
+    repo_root: PathBuf,
+    /// worktree のルート（`.git/agent-worktrees/<name>`）
+    path: PathBuf,
+    /// エージェントの作業ディレクトリ（元のディレクトリに対応する worktree 内の位置）
+    workspace: PathBuf,
+    branch: String,
+    /// worktree を作った時点の HEAD
+    base: String,
+}
+
+/// 実行の最後に選ぶ変更の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Merge,
+    Keep,
+    Discard,
+}
+
+impl Outcome {
+    fn parse(input: &str) -> Self {
+        match input.trim().to_lowercase().as_str() {
+            "m" | "merge" => Outcome::Merge,
+            "d" | "discard" => Outcome::Discard,
+            _ => Outcome::Keep,
+        }
+    }
+}
+
+/// `--isolated`: 新しい worktree とブランチで `task` を実行し、終わったら差分を表示して
+/// 元のブランチへマージするか、ブランチに残すか、捨てるかを決める
+///
+/// 利用者の作業ツリーはマージを選ぶまで変更されない
+pub async fn run_isolated<F, Fut>(workspace: &Path, task: F) -> Result<()>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let worktree = Worktree::create(workspace)?;
+    eprintln!(
+        "Working in an isolated worktree on branch {} ({})",
+        worktree.branch,
+        worktree.path.display()
+    );
+
+    // ツールの相対パスは worktree 内で解決させる（追跡されていない空のディレクトリは作る）
+    std::fs::create_dir_all(&worktree.workspace)
+        .and_then(|_| std::env::set_current_dir(&worktree.workspace))
+        .with_context(|| format!("Failed to change directory to {:?}", worktree.workspace))?;
+    let result = task(worktree.workspace.clone()).await;
+    std::env::set_current_dir(workspace)
+        .with_context(|| format!("Failed to change directory to {:?}", workspace))?;
+
+    // 失敗や中断で終わった場合も、それまでの変更を確認できるようにする
+    ui::progress::hide();
+    if let Err(e) = worktree.finish() {
+        eprintln!("Error: {:#}", e);
+    }
+    result
+}
+
+impl Worktree {
+    fn create(workspace: &Path) -> Result<Self> {
+        let repo_root = PathBuf::from(
+            git(workspace, &["rev-parse", "--show-toplevel"])
+                .context("--isolated needs a git repository")?,
+        );
+        let base = git(workspace, &["rev-parse", "HEAD"])
+            .context("--isolated needs a repository with at least one commit")?;
+        let git_dir = PathBuf::from(git(
+            workspace,
+            &["rev-parse", "--path-format=absolute", "--git-common-dir"],
+        )?);
+
+        // 同じ秒に起動した複数の --isolated 実行が衝突しないよう PID を付ける
+        let name = format!(
+            "agent-{}-{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id()
+        );
+        let branch = format!("agent/{}", name.trim_start_matches("agent-"));
+        let path = git_dir.join("agent-worktrees").join(&name);
+        let path_str = path.to_string_lossy();
+        git(
+            &repo_root,
+            &[
+                "worktree", "add", "--quiet", "-b", &branch, &path_str, &base,
+            ],
+        )?;
+
+        if !git(&repo_root, &["status", "--porcelain"])?.is_empty() {
+            tracing::warn!(
+                "The worktree starts from HEAD; uncommitted changes in {:?} are not included",
+                repo_root
+            );
+        }
+
+        // サブディレクトリで起動した場合は worktree 内の同じ位置で作業する
+        let relative = workspace
+            .canonicalize()
+            .ok()
+            .and_then(|dir| {
+                let root = repo_root.canonicalize().ok()?;
+                dir.strip_prefix(root).ok().map(Path::to_path_buf)
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            workspace: path.join(relative),
+            repo_root,
+            path,
+            branch,
+            base,
+        })
+    }
+
+    /// 変更をブランチにコミットし、差分を表示して扱いを決める
+    fn finish(self) -> Result<()> {
+        git(&self.path, &["add", "--all"])?;
+        let color = if style::color_enabled() {
+            "--color=always"
+        } else {
+            "--color=never"
+        };
+        let diff = git_raw(&self.path, &["diff", "--cached", color, &self.base])?;
+        if diff.trim().is_empty() {
+            self.remove(true)?;
+            eprintln!("No changes were made; removed the isolated worktree");
+            return Ok(());
+        }
+        eprintln!("\n{}", diff.trim_end());
+        git(
+            &self.path,
+            &[
+                "commit",
+                "--quiet",
+                "--no-verify",
+                "-m",
+                "Changes from an isolated agent run",
+            ],
+        )
+        .with_context(|| {
+            format!(
+                "Failed to commit the changes; they are left in {}",
+                self.path.display()
+            )
+        })?;
+
+        let outcome = if ui::is_interactive() {
+            ask_outcome(&self.branch)?
+        } else {
+            Outcome::Keep
+        };
+        match outcome {
+            Outcome::Merge => {
+                if let Err(e) = git(&self.repo_root, &["merge", "--no-edit", &self.branch]) {
+                    self.remove(false)?;
+                    bail!(
+                        "{:#}\nThe changes are kept on branch {}; merge it manually",
+                        e,
+                        self.branch
+                    );
+                }
+                self.remove(true)?;
+                eprintln!("Merged the changes into the working tree");
+            }
+            Outcome::Keep => {
+                self.remove(false)?;
+                eprintln!("The changes are on branch {}", self.branch);
+            }
+            Outcome::Discard => {
+                self.remove(true)?;
+                eprintln!("Discarded the changes");
+            }
+        }
+        Ok(())
+    }
+
+    /// worktree を消す（`delete_branch` ならブランチも消す）
+    fn remove(&self, delete_branch: bool) -> Result<()> {
+        let path = self.path.to_string_lossy();
+        git(&self.repo_root, &["worktree", "remove", "--force", &path])?;
+        if delete_branch {
+            git(&self.repo_root, &["branch", "--quiet", "-D", &self.branch])?;
+        }
+        Ok(())
+    }
+}
+
+fn ask_outcome(branch: &str) -> Result<Outcome> {
+    let input = platform::prompt_line(&format!(
+        "[m]erge into the current branch, [k]eep on branch {}, or [d]iscard? [m/K/d]: ",
+        branch
+    ))?;
+    Ok(Outcome::parse(&input))
+}
+
+/// git を実行して標準出力（前後の空白を除く）を返す
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    Ok(git_raw(dir, args)?.trim().to_string())
+}
+
+fn git_raw(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome_defaults_to_keep() {
+        assert_eq!(Outcome::parse("m\n"), Outcome::Merge);
+        assert_eq!(Outcome::parse("Discard"), Outcome::Discard);
+        assert_eq!(Outcome::parse(""), Outcome::Keep);
+        assert_eq!(Outcome::parse("x"), Outcome::Keep);
+    }
+}